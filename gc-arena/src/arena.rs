@@ -2,7 +2,7 @@ use std::{f64, usize};
 
 use crate::context::{Context, MutationContext};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct ArenaParameters {
     pub(crate) pause_factor: f64,
     pub(crate) timing_factor: f64,
@@ -53,6 +53,18 @@ impl ArenaParameters {
         self.min_sleep = min_sleep;
         self
     }
+
+    pub fn pause_factor(&self) -> f64 {
+        self.pause_factor
+    }
+
+    pub fn timing_factor(&self) -> f64 {
+        self.timing_factor
+    }
+
+    pub fn min_sleep(&self) -> usize {
+        self.min_sleep
+    }
 }
 
 /// Creates a new "garbage collected arena" type.  The macro takes two parameters, the name you
@@ -172,6 +184,21 @@ macro_rules! make_arena {
                 self.context.total_allocated()
             }
 
+            /// Returns the current garbage collector tuning parameters.
+            #[allow(unused)]
+            #[inline]
+            pub fn parameters(&self) -> $crate::ArenaParameters {
+                self.context.parameters()
+            }
+
+            /// Changes the garbage collector tuning parameters, which take effect from the next
+            /// allocation or unit of collection work.
+            #[allow(unused)]
+            #[inline]
+            pub fn set_parameters(&mut self, parameters: $crate::ArenaParameters) {
+                self.context.set_parameters(parameters)
+            }
+
             /// When the garbage collector is not sleeping, all allocated objects cause the arena to
             /// accumulate "allocation debt".  This debt is then be used to time incremental garbage
             /// collection based on the tuning parameters set in `ArenaParameters`.  The allocation
@@ -197,6 +224,19 @@ macro_rules! make_arena {
                 }
             }
 
+            /// Run the garbage collector for the given amount of work, measured in bytes in the same
+            /// way as allocation debt, regardless of the current debt.  If the garbage collector is
+            /// sleeping, starts a new cycle first.  Returns true if this finished a cycle, leaving
+            /// the garbage collector in the sleeping phase.
+            #[allow(unused)]
+            pub fn collect_step(&mut self, work: f64) -> bool {
+                self.context.wake();
+                unsafe {
+                    self.context.do_collection(&*self.root, work);
+                }
+                self.context.is_sleeping()
+            }
+
             /// Run the current garbage collection cycle to completion, stopping once the garbage
             /// collector has entered the sleeping phase.  If the garbage collector is currently
             /// sleeping, starts a new cycle and runs that cycle to completion.
@@ -208,6 +248,20 @@ macro_rules! make_arena {
                         .do_collection(&*self.root, ::std::f64::INFINITY);
                 }
             }
+
+            /// Run a complete garbage collection cycle, first finishing any cycle which is already
+            /// in progress, so that every object which is unreachable at the time of the call is
+            /// freed.
+            #[allow(unused)]
+            pub fn collect_full(&mut self) {
+                if !self.context.is_sleeping() {
+                    unsafe {
+                        self.context
+                            .do_collection(&*self.root, ::std::f64::INFINITY);
+                    }
+                }
+                self.collect_all();
+            }
         }
 
         impl Drop for $arena {
@@ -5,7 +5,10 @@ use std::{f64, mem, usize};
 
 use crate::arena::ArenaParameters;
 use crate::collect::Collect;
+use crate::finalize::Resurrect;
+use crate::gc_ptr::GcPtr;
 use crate::types::{GcBox, GcColor, GcFlags, Invariant};
+use crate::weak::Weak;
 
 /// Handle value given by arena callbacks during construction and mutation.  Allows allocating new
 /// `Gc` pointers and internally mutating values held by `Gc` pointers.
@@ -23,6 +26,30 @@ impl<'gc, 'context> MutationContext<'gc, 'context> {
     pub(crate) unsafe fn write_barrier<T: 'gc + Collect>(self, ptr: NonNull<GcBox<T>>) {
         self.context.write_barrier(ptr)
     }
+
+    pub(crate) unsafe fn add_finalizer_queue<T: 'gc + Resurrect>(self, ptr: NonNull<GcBox<T>>) {
+        self.context.add_finalizer_queue(ptr)
+    }
+
+    pub(crate) unsafe fn register_weak<T: 'gc + Weak>(self, ptr: NonNull<GcBox<T>>) {
+        self.context.register_weak(ptr)
+    }
+
+    /// Returns the current garbage collector tuning parameters.
+    pub fn parameters(self) -> ArenaParameters {
+        self.context.parameters()
+    }
+
+    /// Changes the garbage collector tuning parameters, which take effect from the next allocation
+    /// or unit of collection work.
+    pub fn set_parameters(self, parameters: ArenaParameters) {
+        self.context.set_parameters(parameters)
+    }
+
+    /// Returns the total memory currently used by the arena.
+    pub fn total_allocated(self) -> usize {
+        self.context.total_allocated()
+    }
 }
 
 /// Handle value given by arena callbacks during garbage collection, which must be passed through
@@ -36,12 +63,18 @@ impl<'context> CollectionContext<'context> {
     pub(crate) unsafe fn trace<T: Collect>(self, ptr: NonNull<GcBox<T>>) {
         self.context.trace(ptr)
     }
+
+    /// Returns whether the given object has been reached so far during this collection.  Once
+    /// marking is complete, objects which are not marked are about to be freed.
+    pub fn is_marked(self, ptr: GcPtr) -> bool {
+        !ptr.is_white()
+    }
 }
 
 // Main gc context type, public because it must be accessible from the `make_arena!` macro.
 #[doc(hidden)]
 pub struct Context {
-    parameters: ArenaParameters,
+    parameters: Cell<ArenaParameters>,
 
     phase: Cell<Phase>,
     total_allocated: Cell<usize>,
@@ -55,6 +88,13 @@ pub struct Context {
 
     gray: RefCell<Vec<NonNull<GcBox<Collect>>>>,
     gray_again: RefCell<Vec<NonNull<GcBox<Collect>>>>,
+
+    // Every live `FinalizerQueue`, which are checked for unreachable registered objects once
+    // marking is otherwise finished.
+    finalizer_queues: RefCell<Vec<NonNull<GcBox<dyn Resurrect>>>>,
+
+    // Every live object registered with `GcCell::register_weak`.
+    weak: RefCell<Vec<NonNull<GcBox<dyn Weak>>>>,
 }
 
 impl Drop for Context {
@@ -83,7 +123,7 @@ impl Drop for Context {
 impl Context {
     pub unsafe fn new(parameters: ArenaParameters) -> Context {
         Context {
-            parameters,
+            parameters: Cell::new(parameters),
             phase: Cell::new(Phase::Wake),
             total_allocated: Cell::new(0),
             remembered_size: Cell::new(0),
@@ -94,6 +134,8 @@ impl Context {
             sweep_prev: Cell::new(None),
             gray: RefCell::new(Vec::new()),
             gray_again: RefCell::new(Vec::new()),
+            finalizer_queues: RefCell::new(Vec::new()),
+            weak: RefCell::new(Vec::new()),
         }
     }
 
@@ -118,6 +160,21 @@ impl Context {
         self.total_allocated.get()
     }
 
+    #[inline]
+    pub fn parameters(&self) -> ArenaParameters {
+        self.parameters.get()
+    }
+
+    #[inline]
+    pub fn set_parameters(&self, parameters: ArenaParameters) {
+        self.parameters.set(parameters)
+    }
+
+    #[inline]
+    pub fn is_sleeping(&self) -> bool {
+        self.phase.get() == Phase::Sleep
+    }
+
     // If the garbage collector is currently in the sleep phase, transition to the wake phase.
     pub fn wake(&self) {
         if self.phase.get() == Phase::Sleep {
@@ -175,9 +232,17 @@ impl Context {
                         let gc_box = ptr.as_ref();
                         (*gc_box.value.get()).trace(cc);
                         gc_box.flags.set_color(GcColor::Black);
+                    } else if self.trace_ephemerons(cc) {
+                        // If weak objects have traced pointers which are now known to be
+                        // reachable, those must be propagated first.
+                    } else if self.resurrect_finalizable(cc) {
+                        // If any objects registered for finalization were unreachable, they have
+                        // been resurrected and must be propagated before we can sweep.
                     } else {
-                        // If we have no objects left in the normal gray queue, we enter the sweep
-                        // phase.
+                        // If we have no objects left in the normal gray queue, marking is
+                        // complete.  Weak objects forget about any unmarked objects, and then we
+                        // enter the sweep phase.
+                        self.clear_weak(cc);
                         self.phase.set(Phase::Sweep);
                         self.sweep.set(self.all.get());
                     }
@@ -233,11 +298,11 @@ impl Context {
                         self.wakeup_total.set(
                             self.total_allocated.get()
                                 + ((self.remembered_size.get() as f64
-                                    * self.parameters.pause_factor)
+                                    * self.parameters.get().pause_factor)
                                     .round()
                                     .min(usize::MAX as f64)
                                     as usize)
-                                    .max(self.parameters.min_sleep),
+                                    .max(self.parameters.get().min_sleep),
                         );
                     }
                 }
@@ -261,7 +326,7 @@ impl Context {
             self.allocation_debt.set(
                 self.allocation_debt.get()
                     + alloc_size as f64
-                    + alloc_size as f64 / self.parameters.timing_factor,
+                    + alloc_size as f64 / self.parameters.get().timing_factor,
             );
         }
 
@@ -291,6 +356,73 @@ impl Context {
         }
     }
 
+    unsafe fn add_finalizer_queue<T: Resurrect>(&self, ptr: NonNull<GcBox<T>>) {
+        self.finalizer_queues
+            .borrow_mut()
+            .push(static_resurrect_box(ptr));
+    }
+
+    // Called when there are no gray objects left.  Every reachable finalizer queue moves its
+    // unreachable registered objects into its pending queue and traces them, and if there were
+    // none, the queues which are themselves unreachable are forgotten before they are swept.
+    // Returns true if any objects were resurrected, in which case we must continue propagating.
+    unsafe fn resurrect_finalizable(&self, cc: CollectionContext) -> bool {
+        let mut finalizer_queues = self.finalizer_queues.borrow_mut();
+
+        let mut resurrected = false;
+        for queue in finalizer_queues.iter() {
+            let queue = queue.as_ref();
+            if queue.flags.color() != GcColor::White {
+                resurrected |= (*queue.value.get()).resurrect(cc);
+            }
+        }
+
+        if !resurrected {
+            finalizer_queues.retain(|queue| queue.as_ref().flags.color() != GcColor::White);
+        }
+        resurrected
+    }
+
+    unsafe fn register_weak<T: Weak>(&self, ptr: NonNull<GcBox<T>>) {
+        let gc_box = ptr.as_ref();
+        if !gc_box.flags.is_weak() {
+            gc_box.flags.set_weak(true);
+            self.weak.borrow_mut().push(static_weak_box(ptr));
+        }
+    }
+
+    // Called when there are no gray objects left, returns true if any reachable weak object traced
+    // anything new.
+    unsafe fn trace_ephemerons(&self, cc: CollectionContext) -> bool {
+        let mut traced = false;
+        for weak in self.weak.borrow().iter() {
+            let weak = weak.as_ref();
+            if weak.flags.color() != GcColor::White {
+                traced |= (*weak.value.get()).trace_ephemerons(cc);
+            }
+        }
+        traced
+    }
+
+    // Called once marking is complete.  Every reachable weak object removes its pointers to
+    // unmarked objects, and the unreachable weak objects are forgotten before they are swept.
+    // Reachable objects which no longer hold anything weakly are unregistered.
+    unsafe fn clear_weak(&self, cc: CollectionContext) {
+        let mut weak = self.weak.borrow_mut();
+        weak.retain(|weak| {
+            let weak = weak.as_ref();
+            if weak.flags.color() != GcColor::White {
+                let still_weak = (*weak.value.get()).clear_unmarked(cc);
+                if !still_weak {
+                    weak.flags.set_weak(false);
+                }
+                still_weak
+            } else {
+                false
+            }
+        });
+    }
+
     unsafe fn trace<T: Collect>(&self, ptr: NonNull<GcBox<T>>) {
         let gc_box = ptr.as_ref();
         match gc_box.flags.color() {
@@ -321,3 +453,13 @@ enum Phase {
 unsafe fn static_gc_box<'gc>(ptr: NonNull<GcBox<Collect + 'gc>>) -> NonNull<GcBox<Collect>> {
     mem::transmute(ptr)
 }
+
+unsafe fn static_resurrect_box<'gc>(
+    ptr: NonNull<GcBox<dyn Resurrect + 'gc>>,
+) -> NonNull<GcBox<dyn Resurrect>> {
+    mem::transmute(ptr)
+}
+
+unsafe fn static_weak_box<'gc>(ptr: NonNull<GcBox<dyn Weak + 'gc>>) -> NonNull<GcBox<dyn Weak>> {
+    mem::transmute(ptr)
+}
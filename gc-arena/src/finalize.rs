@@ -0,0 +1,131 @@
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::marker::PhantomData;
+
+use crate::collect::Collect;
+use crate::context::{CollectionContext, MutationContext};
+use crate::gc::Gc;
+use crate::gc_ptr::GcPointer;
+use crate::types::Invariant;
+
+/// A set of objects registered for finalization, along with the queue of those objects which have
+/// become unreachable.
+///
+/// Registered objects are not kept alive by the queue.  Once the collector finishes marking, every
+/// registered object which was not reached is removed from the registered set and "resurrected":
+/// it is moved into the pending queue, where it and everything it points to are kept alive until
+/// the mutator takes it with `FinalizerQueue::pop`.  Objects are queued in the reverse order of
+/// their registration.  After being taken from the queue, an object is collected normally once it
+/// is unreachable again, unless it is registered again.
+///
+/// If the queue itself becomes unreachable, its registered objects are simply collected.
+pub struct FinalizerQueue<'gc, T: GcPointer<'gc>>(Gc<'gc, QueueState<'gc, T>>);
+
+impl<'gc, T: GcPointer<'gc>> Copy for FinalizerQueue<'gc, T> {}
+
+impl<'gc, T: GcPointer<'gc>> Clone for FinalizerQueue<'gc, T> {
+    fn clone(&self) -> FinalizerQueue<'gc, T> {
+        *self
+    }
+}
+
+unsafe impl<'gc, T: GcPointer<'gc>> Collect for FinalizerQueue<'gc, T> {
+    fn trace(&self, cc: CollectionContext) {
+        self.0.trace(cc)
+    }
+}
+
+impl<'gc, T: GcPointer<'gc>> FinalizerQueue<'gc, T> {
+    pub fn new(mc: MutationContext<'gc, '_>) -> FinalizerQueue<'gc, T> {
+        let queue = Gc::allocate(
+            mc,
+            QueueState {
+                registered: RefCell::new(Vec::new()),
+                registered_set: RefCell::new(HashSet::new()),
+                pending: RefCell::new(VecDeque::new()),
+                _invariant: PhantomData,
+            },
+        );
+        unsafe {
+            mc.add_finalizer_queue(queue.ptr);
+        }
+        FinalizerQueue(queue)
+    }
+
+    /// Registers the given value to be queued once it becomes unreachable.  Returns false and does
+    /// nothing if the value is already registered.
+    pub fn register(&self, value: T) -> bool {
+        if self
+            .0
+            .registered_set
+            .borrow_mut()
+            .insert(value.gc_ptr().address())
+        {
+            self.0.registered.borrow_mut().push(value);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns whether any unreachable objects are waiting to be finalized.
+    pub fn is_pending(&self) -> bool {
+        !self.0.pending.borrow().is_empty()
+    }
+
+    /// Takes the next unreachable object waiting to be finalized.
+    pub fn pop(&self) -> Option<T> {
+        self.0.pending.borrow_mut().pop_front()
+    }
+}
+
+pub(crate) struct QueueState<'gc, T: GcPointer<'gc>> {
+    // Registered objects are not traced, they only keep the addresses of objects which are
+    // guaranteed to be alive until the end of the next marking phase.
+    registered: RefCell<Vec<T>>,
+    registered_set: RefCell<HashSet<*const ()>>,
+    pending: RefCell<VecDeque<T>>,
+    _invariant: Invariant<'gc>,
+}
+
+unsafe impl<'gc, T: GcPointer<'gc>> Collect for QueueState<'gc, T> {
+    fn trace(&self, cc: CollectionContext) {
+        for value in self.pending.borrow().iter() {
+            value.trace(cc);
+        }
+    }
+}
+
+// Implemented by the state of every `FinalizerQueue`, so that the collector may hold them
+// type-erased.
+pub(crate) trait Resurrect: Collect {
+    // Called once marking is otherwise finished.  Moves every registered object which is still
+    // white into the pending queue and traces it, returning whether any objects were moved.
+    unsafe fn resurrect(&self, cc: CollectionContext) -> bool;
+}
+
+impl<'gc, T: GcPointer<'gc>> Resurrect for QueueState<'gc, T> {
+    unsafe fn resurrect(&self, cc: CollectionContext) -> bool {
+        let mut registered = self.registered.borrow_mut();
+        let mut registered_set = self.registered_set.borrow_mut();
+        let mut pending = self.pending.borrow_mut();
+
+        let mut unreachable = Vec::new();
+        registered.retain(|value| {
+            if value.gc_ptr().is_white() {
+                unreachable.push(*value);
+                false
+            } else {
+                true
+            }
+        });
+
+        let resurrected = !unreachable.is_empty();
+        for value in unreachable.into_iter().rev() {
+            registered_set.remove(&value.gc_ptr().address());
+            value.trace(cc);
+            pending.push_back(value);
+        }
+        resurrected
+    }
+}
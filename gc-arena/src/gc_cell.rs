@@ -4,6 +4,8 @@ use std::fmt::{self, Debug};
 use crate::collect::Collect;
 use crate::context::{CollectionContext, MutationContext};
 use crate::gc::Gc;
+use crate::gc_ptr::{GcPointer, GcPtr};
+use crate::weak::{Weak, WeakCollect};
 
 /// A garbage collected pointer to a type T that may be safely mutated.  When a type that may hold
 /// `Gc` pointers is mutated, it may adopt new `Gc` pointers, and in order for this to be safe this
@@ -74,6 +76,23 @@ impl<'gc, T: 'gc + Collect> GcCell<'gc, T> {
     }
 }
 
+impl<'gc, T: 'gc + WeakCollect> GcCell<'gc, T> {
+    /// Registers this object with the collector so that its `WeakCollect` methods are called during
+    /// every collection for as long as it is alive.  Registering an object more than once has no
+    /// effect.
+    pub fn register_weak(mc: MutationContext<'gc, '_>, this: GcCell<'gc, T>) {
+        unsafe {
+            mc.register_weak(this.0.ptr);
+        }
+    }
+}
+
+impl<'gc, T: Collect + 'gc> GcPointer<'gc> for GcCell<'gc, T> {
+    fn gc_ptr(&self) -> GcPtr<'gc> {
+        self.0.gc_ptr()
+    }
+}
+
 struct GcRefCell<T: Collect> {
     cell: RefCell<T>,
 }
@@ -83,3 +102,15 @@ unsafe impl<'gc, T: Collect + 'gc> Collect for GcRefCell<T> {
         self.cell.borrow().trace(cc);
     }
 }
+
+impl<'gc, T: WeakCollect + 'gc> Weak for GcRefCell<T> {
+    unsafe fn trace_ephemerons(&self, cc: CollectionContext) -> bool {
+        self.cell.borrow().trace_ephemerons(cc)
+    }
+
+    unsafe fn clear_unmarked(&self, cc: CollectionContext) -> bool {
+        let mut cell = self.cell.borrow_mut();
+        cell.clear_unmarked(cc);
+        cell.is_weak()
+    }
+}
@@ -0,0 +1,42 @@
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use crate::collect::Collect;
+use crate::gc::Gc;
+use crate::types::{GcBox, GcColor, Invariant};
+
+/// A type-erased pointer to a garbage collected object, used to ask the collector whether that
+/// object is still reachable.
+#[derive(Copy, Clone)]
+pub struct GcPtr<'gc> {
+    ptr: NonNull<GcBox<dyn Collect + 'gc>>,
+    _invariant: Invariant<'gc>,
+}
+
+impl<'gc> GcPtr<'gc> {
+    pub(crate) fn new<T: Collect + 'gc>(ptr: NonNull<GcBox<T>>) -> GcPtr<'gc> {
+        GcPtr {
+            ptr,
+            _invariant: PhantomData,
+        }
+    }
+
+    pub(crate) fn address(self) -> *const () {
+        self.ptr.as_ptr() as *const ()
+    }
+
+    pub(crate) fn is_white(self) -> bool {
+        unsafe { self.ptr.as_ref().flags.color() == GcColor::White }
+    }
+}
+
+/// Types which are, or wrap, a single `Gc` allocation.
+pub trait GcPointer<'gc>: Collect + Copy + 'gc {
+    fn gc_ptr(&self) -> GcPtr<'gc>;
+}
+
+impl<'gc, T: Collect + 'gc> GcPointer<'gc> for Gc<'gc, T> {
+    fn gc_ptr(&self) -> GcPtr<'gc> {
+        GcPtr::new(self.ptr)
+    }
+}
@@ -52,6 +52,15 @@ impl GcFlags {
         self.0
             .set((self.0.get() & !0x4) | if needs_trace { 0x4 } else { 0x0 });
     }
+
+    pub(crate) fn is_weak(&self) -> bool {
+        self.0.get() & 0x8 != 0x0
+    }
+
+    pub(crate) fn set_weak(&self, weak: bool) {
+        self.0
+            .set((self.0.get() & !0x8) | if weak { 0x8 } else { 0x0 });
+    }
 }
 
 // Phantom type that holds a lifetime and ensures that it is invariant.
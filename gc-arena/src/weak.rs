@@ -0,0 +1,89 @@
+use crate::collect::Collect;
+use crate::context::{CollectionContext, MutationContext};
+use crate::gc::Gc;
+use crate::gc_cell::GcCell;
+use crate::gc_ptr::GcPointer;
+
+/// A type which holds pointers that should not, by themselves, keep their targets alive.
+///
+/// Objects of such a type are registered with the collector by `GcCell::register_weak`, after
+/// which their `Collect::trace` implementation may skip any pointers that they hold weakly.  Once
+/// there are no gray objects left, the collector first calls `trace_ephemerons` on every reachable
+/// registered object until none of them trace anything new.  Once marking is complete, and just
+/// before any unreachable objects are freed, it calls `clear_unmarked` on every reachable
+/// registered object.
+///
+/// This trait is unsafe because `clear_unmarked` *must* remove every held pointer to an object
+/// which `CollectionContext::is_marked` reports as unmarked, as such pointers would otherwise
+/// dangle.
+pub unsafe trait WeakCollect: Collect {
+    /// Traces any weakly held pointers which have become reachable because of other marked
+    /// objects, such as the values of an ephemeron table whose keys are marked.  Returns true if
+    /// anything new was traced.
+    fn trace_ephemerons(&self, _cc: CollectionContext) -> bool {
+        false
+    }
+
+    /// Removes every held pointer to an object which is not marked.
+    fn clear_unmarked(&mut self, cc: CollectionContext);
+
+    /// Returns whether this object still holds any pointers weakly, checked after every call to
+    /// `clear_unmarked`.  Once this returns false the object is unregistered, and its `Collect`
+    /// implementation must trace everything it holds until it is registered again.
+    fn is_weak(&self) -> bool {
+        true
+    }
+}
+
+// Implemented for the `GcCell` wrapper of every `WeakCollect` type, so that the collector may hold
+// them type-erased.
+pub(crate) trait Weak: Collect {
+    unsafe fn trace_ephemerons(&self, cc: CollectionContext) -> bool;
+    // Returns false if the object should be unregistered.
+    unsafe fn clear_unmarked(&self, cc: CollectionContext) -> bool;
+}
+
+/// A pointer to a `Gc` object which does not keep it alive, and is cleared once the object is
+/// collected.
+pub struct GcWeak<'gc, T: 'gc + Collect>(GcCell<'gc, WeakTarget<'gc, T>>);
+
+impl<'gc, T: Collect + 'gc> Copy for GcWeak<'gc, T> {}
+
+impl<'gc, T: Collect + 'gc> Clone for GcWeak<'gc, T> {
+    fn clone(&self) -> GcWeak<'gc, T> {
+        *self
+    }
+}
+
+unsafe impl<'gc, T: 'gc + Collect> Collect for GcWeak<'gc, T> {
+    fn trace(&self, cc: CollectionContext) {
+        self.0.trace(cc)
+    }
+}
+
+impl<'gc, T: 'gc + Collect> GcWeak<'gc, T> {
+    pub fn new(mc: MutationContext<'gc, '_>, gc: Gc<'gc, T>) -> GcWeak<'gc, T> {
+        let weak = GcCell::allocate(mc, WeakTarget(Some(gc)));
+        GcCell::register_weak(mc, weak);
+        GcWeak(weak)
+    }
+
+    /// Returns the object, unless it has been collected.
+    pub fn upgrade(&self) -> Option<Gc<'gc, T>> {
+        self.0.read().0
+    }
+}
+
+struct WeakTarget<'gc, T: 'gc + Collect>(Option<Gc<'gc, T>>);
+
+unsafe impl<'gc, T: 'gc + Collect> Collect for WeakTarget<'gc, T> {}
+
+unsafe impl<'gc, T: 'gc + Collect> WeakCollect for WeakTarget<'gc, T> {
+    fn clear_unmarked(&mut self, cc: CollectionContext) {
+        if let Some(gc) = self.0 {
+            if !cc.is_marked(gc.gc_ptr()) {
+                self.0 = None;
+            }
+        }
+    }
+}
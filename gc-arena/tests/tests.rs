@@ -1,9 +1,13 @@
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
 use rand::distributions::Distribution;
 
-use gc_arena::{make_arena, unsafe_empty_collect, ArenaParameters, Collect, Gc, GcCell};
+use gc_arena::{
+    make_arena, unsafe_empty_collect, ArenaParameters, Collect, CollectionContext, FinalizerQueue,
+    Gc, GcCell, GcPointer, GcWeak, WeakCollect,
+};
 
 #[test]
 fn simple_allocation() {
@@ -180,3 +184,377 @@ fn derive_collect() {
     assert_eq!(Test5::needs_trace(), true);
     assert_eq!(Test6::needs_trace(), false);
 }
+
+#[test]
+fn finalization() {
+    #[derive(Clone)]
+    struct RefCounter(Rc<()>);
+    unsafe_empty_collect!(RefCounter);
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc> {
+        queue: FinalizerQueue<'gc, GcCell<'gc, (i32, Gc<'gc, RefCounter>)>>,
+        live: GcCell<'gc, Vec<GcCell<'gc, (i32, Gc<'gc, RefCounter>)>>>,
+    }
+    make_arena!(TestArena, TestRoot);
+
+    let r = RefCounter(Rc::new(()));
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        let queue = FinalizerQueue::new(mc);
+        let mut live = Vec::new();
+        for i in 0..4 {
+            let object = GcCell::allocate(mc, (i, Gc::allocate(mc, r.clone())));
+            assert!(queue.register(object));
+            assert!(!queue.register(object));
+            live.push(object);
+        }
+        TestRoot {
+            queue,
+            live: GcCell::allocate(mc, live),
+        }
+    });
+
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&r.0), 5);
+    arena.mutate(|_, root| assert!(!root.queue.is_pending()));
+
+    arena.mutate(|mc, root| {
+        root.live
+            .write(mc)
+            .retain(|object| object.read().0 % 2 == 0);
+    });
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&r.0), 5);
+
+    arena.mutate(|_, root| {
+        assert_eq!(root.queue.pop().unwrap().read().0, 3);
+        assert_eq!(root.queue.pop().unwrap().read().0, 1);
+        assert!(root.queue.pop().is_none());
+    });
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&r.0), 3);
+
+    arena.mutate(|mc, root| {
+        let object = root.live.write(mc).pop().unwrap();
+        assert_eq!(object.read().0, 2);
+        root.queue.register(object);
+    });
+    arena.collect_all();
+    arena.mutate(|_, root| {
+        assert_eq!(root.queue.pop().unwrap().read().0, 2);
+    });
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&r.0), 2);
+}
+
+#[test]
+fn unreachable_finalizer_queue() {
+    #[derive(Clone)]
+    struct RefCounter(Rc<()>);
+    unsafe_empty_collect!(RefCounter);
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(GcCell<'gc, Option<FinalizerQueue<'gc, Gc<'gc, RefCounter>>>>);
+    make_arena!(TestArena, TestRoot);
+
+    let r = RefCounter(Rc::new(()));
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        let queue = FinalizerQueue::new(mc);
+        queue.register(Gc::allocate(mc, r.clone()));
+        TestRoot(GcCell::allocate(mc, Some(queue)))
+    });
+
+    arena.mutate(|mc, root| {
+        *root.0.write(mc) = None;
+    });
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&r.0), 1);
+}
+
+#[test]
+fn weak_references() {
+    #[derive(Clone)]
+    struct RefCounter(Rc<()>);
+    unsafe_empty_collect!(RefCounter);
+
+    struct WeakList<'gc>(Vec<Gc<'gc, RefCounter>>);
+
+    unsafe impl<'gc> Collect for WeakList<'gc> {}
+
+    unsafe impl<'gc> WeakCollect for WeakList<'gc> {
+        fn clear_unmarked(&mut self, cc: CollectionContext) {
+            self.0.retain(|gc| cc.is_marked(gc.gc_ptr()));
+        }
+    }
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc> {
+        weak: GcCell<'gc, WeakList<'gc>>,
+        strong: GcCell<'gc, Vec<Gc<'gc, RefCounter>>>,
+    }
+    make_arena!(TestArena, TestRoot);
+
+    let r = RefCounter(Rc::new(()));
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        let weak = GcCell::allocate(mc, WeakList(Vec::new()));
+        GcCell::register_weak(mc, weak);
+        TestRoot {
+            weak,
+            strong: GcCell::allocate(mc, Vec::new()),
+        }
+    });
+
+    arena.mutate(|mc, root| {
+        for i in 0..10 {
+            let gc = Gc::allocate(mc, r.clone());
+            root.weak.write(mc).0.push(gc);
+            if i % 2 == 0 {
+                root.strong.write(mc).push(gc);
+            }
+        }
+    });
+
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&r.0), 6);
+    arena.mutate(|_, root| {
+        let weak = root.weak.read();
+        let strong = root.strong.read();
+        assert_eq!(weak.0.len(), 5);
+        for (w, s) in weak.0.iter().zip(strong.iter()) {
+            assert!(Gc::ptr_eq(*w, *s));
+        }
+    });
+
+    arena.mutate(|mc, root| root.strong.write(mc).clear());
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&r.0), 1);
+    arena.mutate(|_, root| assert!(root.weak.read().0.is_empty()));
+}
+
+#[test]
+fn weak_unregistered() {
+    struct Cleared {
+        count: Rc<Cell<usize>>,
+        weak: bool,
+    }
+
+    unsafe impl Collect for Cleared {}
+
+    unsafe impl WeakCollect for Cleared {
+        fn clear_unmarked(&mut self, _cc: CollectionContext) {
+            self.count.set(self.count.get() + 1);
+        }
+
+        fn is_weak(&self) -> bool {
+            self.weak
+        }
+    }
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(GcCell<'gc, Cleared>);
+    make_arena!(TestArena, TestRoot);
+
+    let count = Rc::new(Cell::new(0));
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        let cleared = GcCell::allocate(
+            mc,
+            Cleared {
+                count: count.clone(),
+                weak: true,
+            },
+        );
+        GcCell::register_weak(mc, cleared);
+        TestRoot(cleared)
+    });
+
+    arena.collect_all();
+    assert_eq!(count.get(), 1);
+
+    arena.mutate(|mc, root| root.0.write(mc).weak = false);
+    arena.collect_all();
+    assert_eq!(count.get(), 2);
+    arena.collect_all();
+    assert_eq!(count.get(), 2);
+
+    arena.mutate(|mc, root| {
+        root.0.write(mc).weak = true;
+        GcCell::register_weak(mc, root.0);
+    });
+    arena.collect_all();
+    assert_eq!(count.get(), 3);
+}
+
+#[test]
+fn ephemerons() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct Value<'gc> {
+        key: Option<Gc<'gc, i32>>,
+        counter: Gc<'gc, RefCounter>,
+    }
+
+    #[derive(Clone)]
+    struct RefCounter(Rc<()>);
+    unsafe_empty_collect!(RefCounter);
+
+    struct Ephemerons<'gc>(Vec<(Gc<'gc, i32>, Gc<'gc, Value<'gc>>)>);
+
+    unsafe impl<'gc> Collect for Ephemerons<'gc> {}
+
+    unsafe impl<'gc> WeakCollect for Ephemerons<'gc> {
+        fn trace_ephemerons(&self, cc: CollectionContext) -> bool {
+            let mut traced = false;
+            for (key, value) in &self.0 {
+                if cc.is_marked(key.gc_ptr()) && !cc.is_marked(value.gc_ptr()) {
+                    value.trace(cc);
+                    traced = true;
+                }
+            }
+            traced
+        }
+
+        fn clear_unmarked(&mut self, cc: CollectionContext) {
+            self.0.retain(|(key, _)| cc.is_marked(key.gc_ptr()));
+        }
+    }
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc> {
+        ephemerons: GcCell<'gc, Ephemerons<'gc>>,
+        keys: GcCell<'gc, Vec<Gc<'gc, i32>>>,
+    }
+    make_arena!(TestArena, TestRoot);
+
+    let r = RefCounter(Rc::new(()));
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        let ephemerons = GcCell::allocate(mc, Ephemerons(Vec::new()));
+        GcCell::register_weak(mc, ephemerons);
+        TestRoot {
+            ephemerons,
+            keys: GcCell::allocate(mc, Vec::new()),
+        }
+    });
+
+    arena.mutate(|mc, root| {
+        // A chain of ephemerons, where each value refers to the key of the next, and only the first
+        // key is held strongly.
+        let keys: Vec<_> = (0..4).map(|i| Gc::allocate(mc, i)).collect();
+        for i in 0..4 {
+            let value = Gc::allocate(
+                mc,
+                Value {
+                    key: keys.get(i + 1).cloned(),
+                    counter: Gc::allocate(mc, r.clone()),
+                },
+            );
+            root.ephemerons.write(mc).0.push((keys[i], value));
+        }
+        root.keys.write(mc).push(keys[0]);
+
+        // A value which refers to its own key does not keep the key alive.
+        let key = Gc::allocate(mc, 4);
+        let value = Gc::allocate(
+            mc,
+            Value {
+                key: Some(key),
+                counter: Gc::allocate(mc, r.clone()),
+            },
+        );
+        root.ephemerons.write(mc).0.push((key, value));
+    });
+
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&r.0), 5);
+    arena.mutate(|_, root| assert_eq!(root.ephemerons.read().0.len(), 4));
+
+    arena.mutate(|mc, root| root.keys.write(mc).clear());
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&r.0), 1);
+    arena.mutate(|_, root| assert!(root.ephemerons.read().0.is_empty()));
+}
+
+#[test]
+fn weak_pointers() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc> {
+        strong: GcCell<'gc, Option<Gc<'gc, i32>>>,
+        weak: GcWeak<'gc, i32>,
+    }
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        let gc = Gc::allocate(mc, 42);
+        TestRoot {
+            strong: GcCell::allocate(mc, Some(gc)),
+            weak: GcWeak::new(mc, gc),
+        }
+    });
+
+    arena.collect_all();
+    arena.mutate(|_, root| assert_eq!(root.weak.upgrade().map(|gc| *gc), Some(42)));
+
+    arena.mutate(|mc, root| *root.strong.write(mc) = None);
+    arena.collect_all();
+    arena.mutate(|_, root| assert!(root.weak.upgrade().is_none()));
+}
+
+#[test]
+fn collection_controls() {
+    #[derive(Clone)]
+    struct RefCounter(Rc<()>);
+    unsafe_empty_collect!(RefCounter);
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(GcCell<'gc, Vec<Gc<'gc, RefCounter>>>);
+    make_arena!(TestArena, TestRoot);
+
+    let r = RefCounter(Rc::new(()));
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(GcCell::allocate(mc, Vec::new()))
+    });
+
+    let parameters = ArenaParameters::default().set_pause_factor(2.0);
+    arena.set_parameters(parameters);
+    assert_eq!(arena.parameters().pause_factor(), 2.0);
+    arena.mutate(|mc, _| {
+        assert_eq!(mc.parameters().pause_factor(), 2.0);
+        mc.set_parameters(parameters.set_timing_factor(3.0));
+    });
+    assert_eq!(arena.parameters().timing_factor(), 3.0);
+
+    arena.mutate(|mc, root| {
+        let mut v = root.0.write(mc);
+        for _ in 0..100 {
+            v.push(Gc::allocate(mc, r.clone()));
+        }
+    });
+
+    // Start a cycle which marks everything as reachable, then drop the references part way
+    // through.  A full collection must still free them.
+    assert!(!arena.collect_step(1.0));
+    arena.mutate(|mc, root| root.0.write(mc).clear());
+    arena.collect_full();
+    assert_eq!(Rc::strong_count(&r.0), 1);
+
+    let mut finished = false;
+    for _ in 0..1000 {
+        if arena.collect_step(64.0) {
+            finished = true;
+            break;
+        }
+    }
+    assert!(finished);
+    assert!(arena.mutate(|mc, _| mc.total_allocated()) > 0);
+}
@@ -166,6 +166,34 @@ macro_rules! make_sequencable_arena {
                 $innervis fn collect_all(&mut self) {
                     self.0.collect_all()
                 }
+
+                /// Run a complete garbage collection cycle, first finishing any cycle which is
+                /// already in progress.
+                #[allow(unused)]
+                $innervis fn collect_full(&mut self) {
+                    self.0.collect_full()
+                }
+
+                /// Run the garbage collector for the given amount of work, measured in bytes,
+                /// starting a new cycle if it is sleeping.  Returns true if this finished a cycle.
+                #[allow(unused)]
+                $innervis fn collect_step(&mut self, work: f64) -> bool {
+                    self.0.collect_step(work)
+                }
+
+                /// Returns the current garbage collector tuning parameters.
+                #[allow(unused)]
+                #[inline]
+                $innervis fn parameters(&self) -> ArenaParameters {
+                    self.0.parameters()
+                }
+
+                /// Changes the garbage collector tuning parameters.
+                #[allow(unused)]
+                #[inline]
+                $innervis fn set_parameters(&mut self, parameters: ArenaParameters) {
+                    self.0.set_parameters(parameters)
+                }
             }
 
             $innervis struct Sequencer<O>(InnerArena, PhantomData<O>);
@@ -225,6 +253,28 @@ macro_rules! make_sequencable_arena {
                 $innervis fn collect_all(&mut self) {
                     self.0.collect_all()
                 }
+
+                #[allow(unused)]
+                $innervis fn collect_full(&mut self) {
+                    self.0.collect_full()
+                }
+
+                #[allow(unused)]
+                $innervis fn collect_step(&mut self, work: f64) -> bool {
+                    self.0.collect_step(work)
+                }
+
+                #[allow(unused)]
+                #[inline]
+                $innervis fn parameters(&self) -> ArenaParameters {
+                    self.0.parameters()
+                }
+
+                #[allow(unused)]
+                #[inline]
+                $innervis fn set_parameters(&mut self, parameters: ArenaParameters) {
+                    self.0.set_parameters(parameters)
+                }
             }
         }
     };
@@ -6,7 +6,7 @@ use std::fs::File;
 
 use clap::{crate_authors, crate_description, crate_name, crate_version, App, Arg};
 
-use luster::{compile, io, parser, FunctionProto, Lua, StaticError};
+use luster::{compile_named, io, parser, FunctionProto, Lua, StaticError};
 
 fn print_function_proto<'gc>(function: &FunctionProto<'gc>) {
     println!("=============");
@@ -25,7 +25,10 @@ fn print_function_proto<'gc>(function: &FunctionProto<'gc>) {
     if function.opcodes.len() > 0 {
         println!("opcodes:");
         for (i, c) in function.opcodes.iter().enumerate() {
-            println!("{}: {:?}", i, c);
+            match function.line_number(i) {
+                Some(line_number) => println!("{}: [{}] {:?}", i, line_number, c),
+                None => println!("{}: {:?}", i, c),
+            }
         }
     }
     if function.upvalues.len() > 0 {
@@ -61,7 +64,8 @@ fn main() -> Result<(), Box<StdError>> {
         )
         .get_matches();
 
-    let file = io::buffered_read(File::open(matches.value_of("file").unwrap())?)?;
+    let file_name = matches.value_of("file").unwrap();
+    let file = io::buffered_read(File::open(file_name)?)?;
 
     if matches.is_present("parse") {
         let chunk = parser::parse_chunk(file, |s| s.as_ref().to_vec().into_boxed_slice())?;
@@ -69,7 +73,13 @@ fn main() -> Result<(), Box<StdError>> {
     } else {
         let mut lua = Lua::new();
         lua.mutate(|mc, root| -> Result<(), StaticError> {
-            let function = compile(mc, root.interned_strings, file).map_err(|e| e.to_static())?;
+            let function = compile_named(
+                mc,
+                root.interned_strings,
+                format!("@{}", file_name).as_bytes(),
+                file,
+            )
+            .map_err(|e| e.to_static())?;
             print_function_proto(&function);
             Ok(())
         })?;
@@ -7,9 +7,26 @@ use rustyline::Editor;
 
 use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
 use luster::{
-    compile, io, Closure, Error, Function, Lua, ParserError, StaticError, ThreadSequence,
+    compile_named, io, Closure, Error, Function, Lua, ParserError, StaticError, ThreadSequence,
 };
 
+// Whether the input ended before the chunk did, in which case the REPL reads another line.
+fn is_incomplete(error: &Error) -> bool {
+    match error {
+        Error::ParserError(ParserError::EndOfStream { .. }) => true,
+        Error::LocatedError(error) => is_incomplete(&error.error),
+        _ => false,
+    }
+}
+
+fn is_static_incomplete(error: &StaticError) -> bool {
+    match error {
+        StaticError::ParserError(ParserError::EndOfStream { .. }) => true,
+        StaticError::LocatedError(error) => is_static_incomplete(&error.error),
+        _ => false,
+    }
+}
+
 fn run_repl(lua: &mut Lua) {
     let mut editor = Editor::<()>::new();
 
@@ -27,15 +44,15 @@ fn run_repl(lua: &mut Lua) {
 
             match lua.sequence(move |root| {
                 sequence::from_fn_with(root, move |mc, root| {
-                    let result = compile(mc, root.interned_strings, line_clone.as_bytes());
+                    let result =
+                        compile_named(mc, root.interned_strings, b"=stdin", line_clone.as_bytes());
                     let result = match result {
                         Ok(res) => Ok(res),
-                        err @ Err(Error::ParserError(ParserError::EndOfStream { expected: _ })) => {
-                            err
-                        }
-                        Err(_) => compile(
+                        Err(err) if is_incomplete(&err) => Err(err),
+                        Err(_) => compile_named(
                             mc,
                             root.interned_strings,
+                            b"=stdin",
                             (String::new() + "return " + &line_clone).as_bytes(),
                         ),
                     };
@@ -62,12 +79,12 @@ fn run_repl(lua: &mut Lua) {
                 })
                 .boxed()
             }) {
-                err @ Err(StaticError::ParserError(ParserError::EndOfStream { expected: _ })) => {
+                Err(ref err) if is_static_incomplete(err) => {
                     match line.chars().last() {
                         Some(c) => {
                             if c == '\n' {
                                 editor.add_history_entry(line);
-                                eprintln!("error: {}", err.err().unwrap());
+                                eprintln!("error: {}", err);
                                 break;
                             }
                             prompt = ">> ";
@@ -112,13 +129,15 @@ fn main() -> Result<(), Box<StdError>> {
         return Ok(());
     }
 
-    let file = io::buffered_read(File::open(matches.value_of("file").unwrap())?)?;
+    let file_name = matches.value_of("file").unwrap();
+    let file = io::buffered_read(File::open(file_name)?)?;
+    let chunk_name = format!("@{}", file_name);
 
     lua.sequence(|root| {
         sequence::from_fn_with(root, |mc, root| {
             Ok(Closure::new(
                 mc,
-                compile(mc, root.interned_strings, file)?,
+                compile_named(mc, root.interned_strings, chunk_name.as_bytes(), file)?,
                 Some(root.globals),
             )?)
         })
@@ -17,6 +17,14 @@ pub enum CallbackResult<'gc> {
         args: Vec<Value<'gc>>,
         continuation: Continuation<'gc>,
     },
+    /// Like `TailCall`, but if the call raises an error, `handler` is called with the error before
+    /// any frames are unwound, and its first result is given to `continuation` as the error value.
+    TailCallWithHandler {
+        function: Function<'gc>,
+        args: Vec<Value<'gc>>,
+        handler: Function<'gc>,
+        continuation: Continuation<'gc>,
+    },
 }
 
 pub enum CallbackReturn<'gc> {
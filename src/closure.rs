@@ -4,7 +4,12 @@ use std::hash::{Hash, Hasher};
 
 use gc_arena::{Collect, Gc, GcCell, MutationContext};
 
-use crate::{Constant, OpCode, RegisterIndex, Table, Thread, UpValueIndex, Value};
+use crate::{
+    Constant, LineNumber, OpCode, RegisterIndex, String, Table, Thread, UpValueIndex, Value,
+};
+
+// The longest chunk name which is used in messages, as in PUC-Rio Lua.
+const MAX_CHUNK_ID_LEN: usize = 59;
 
 #[derive(Debug, Collect, Clone, Copy, PartialEq, Eq)]
 #[collect(require_static)]
@@ -17,15 +22,35 @@ pub enum UpValueDescriptor {
 #[derive(Debug, Collect)]
 #[collect(empty_drop)]
 pub struct FunctionProto<'gc> {
+    /// The name of the chunk that this function was compiled from.
+    pub chunk_name: String<'gc>,
     pub fixed_params: u8,
     pub has_varargs: bool,
     pub stack_size: u16,
     pub constants: Vec<Constant<'gc>>,
     pub opcodes: Vec<OpCode>,
+    /// The index of the first opcode generated from each source line, in opcode order.  Every
+    /// opcode up to the next entry belongs to the same line.
+    pub opcode_line_numbers: Vec<(usize, LineNumber)>,
     pub upvalues: Vec<UpValueDescriptor>,
     pub prototypes: Vec<Gc<'gc, FunctionProto<'gc>>>,
 }
 
+impl<'gc> FunctionProto<'gc> {
+    /// The source line that the opcode at the given index was generated from, if known.
+    pub fn line_number(&self, opcode_index: usize) -> Option<LineNumber> {
+        let i = match self
+            .opcode_line_numbers
+            .binary_search_by_key(&opcode_index, |&(start, _)| start)
+        {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        Some(self.opcode_line_numbers[i].1)
+    }
+}
+
 #[derive(Debug, Collect, Copy, Clone)]
 #[collect(require_copy)]
 pub enum UpValueState<'gc> {
@@ -112,3 +137,38 @@ impl<'gc> Closure<'gc> {
         Ok(Closure(Gc::allocate(mc, ClosureState { proto, upvalues })))
     }
 }
+
+// Formats a chunk name for use in messages as PUC-Rio Lua does: names starting with '=' or '@' are
+// used as they are, and any other name is taken to be the source of the chunk.
+pub(crate) fn chunk_id(chunk_name: &[u8]) -> Vec<u8> {
+    match chunk_name.first() {
+        Some(b'=') => chunk_name[1..]
+            .iter()
+            .take(MAX_CHUNK_ID_LEN)
+            .cloned()
+            .collect(),
+        Some(b'@') if chunk_name.len() - 1 <= MAX_CHUNK_ID_LEN => chunk_name[1..].to_vec(),
+        Some(b'@') => {
+            let mut id = b"...".to_vec();
+            id.extend_from_slice(&chunk_name[chunk_name.len() - (MAX_CHUNK_ID_LEN - 3)..]);
+            id
+        }
+        _ => {
+            // Leave room for the surrounding `[string "..."]`.
+            let max_len = MAX_CHUNK_ID_LEN - 15;
+            let line_end = chunk_name
+                .iter()
+                .position(|&c| c == b'\n')
+                .unwrap_or(chunk_name.len());
+            let mut id = b"[string \"".to_vec();
+            if line_end < chunk_name.len() || line_end > max_len {
+                id.extend_from_slice(&chunk_name[..line_end.min(max_len)]);
+                id.extend_from_slice(b"...");
+            } else {
+                id.extend_from_slice(chunk_name);
+            }
+            id.extend_from_slice(b"\"]");
+            id
+        }
+    }
+}
@@ -15,8 +15,8 @@ use crate::parser::{
     WhileStatement,
 };
 use crate::{
-    Constant, ConstantIndex16, ConstantIndex8, FunctionProto, OpCode, Opt254, PrototypeIndex,
-    RegisterIndex, String, UpValueDescriptor, UpValueIndex, VarCount,
+    Constant, ConstantIndex16, ConstantIndex8, FunctionProto, LineNumber, OpCode, Opt254,
+    PrototypeIndex, RegisterIndex, String, UpValueDescriptor, UpValueIndex, VarCount,
 };
 
 use super::operators::{
@@ -62,19 +62,22 @@ impl fmt::Display for CompilerError {
 
 pub fn compile_chunk<'gc>(
     mc: MutationContext<'gc, '_>,
+    chunk_name: String<'gc>,
     chunk: &Chunk<String<'gc>>,
 ) -> Result<FunctionProto<'gc>, CompilerError> {
     let mut compiler = Compiler {
         mutation_context: mc,
+        chunk_name,
         current_function: CompilerFunction::start(&[], true)?,
         upper_functions: Vec::new(),
     };
     compiler.block(&chunk.block)?;
-    compiler.current_function.finish(mc)
+    compiler.current_function.finish(mc, chunk_name)
 }
 
 struct Compiler<'gc, 'a> {
     mutation_context: MutationContext<'gc, 'a>,
+    chunk_name: String<'gc>,
     current_function: CompilerFunction<'gc>,
     upper_functions: Vec<CompilerFunction<'gc>>,
 }
@@ -99,6 +102,8 @@ struct CompilerFunction<'gc> {
     pending_jumps: Vec<PendingJump<'gc>>,
 
     opcodes: Vec<OpCode>,
+    // The index of the first opcode generated for each line, paired with that line
+    opcode_line_numbers: Vec<(usize, LineNumber)>,
 }
 
 #[derive(Debug)]
@@ -109,16 +114,19 @@ enum ExprDescriptor<'gc> {
     UnaryOperator {
         op: UnaryOperator,
         expr: Box<ExprDescriptor<'gc>>,
+        line_number: LineNumber,
     },
     SimpleBinaryOperator {
         left: Box<ExprDescriptor<'gc>>,
         op: SimpleBinOp,
         right: Box<ExprDescriptor<'gc>>,
+        line_number: LineNumber,
     },
     Comparison {
         left: Box<ExprDescriptor<'gc>>,
         op: ComparisonBinOp,
         right: Box<ExprDescriptor<'gc>>,
+        line_number: LineNumber,
     },
     ShortCircuitBinOp {
         left: Box<ExprDescriptor<'gc>>,
@@ -134,13 +142,15 @@ enum ExprDescriptor<'gc> {
     FunctionCall {
         func: Box<ExprDescriptor<'gc>>,
         args: Vec<ExprDescriptor<'gc>>,
+        line_number: LineNumber,
     },
     MethodCall {
         table: Box<ExprDescriptor<'gc>>,
         method: Box<ExprDescriptor<'gc>>,
         args: Vec<ExprDescriptor<'gc>>,
+        line_number: LineNumber,
     },
-    Concat(VecDeque<ExprDescriptor<'gc>>),
+    Concat(VecDeque<ExprDescriptor<'gc>>, LineNumber),
 }
 
 #[derive(Debug)]
@@ -268,16 +278,16 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     // to the end of the block over local variable scope.  This is logically equivalent to an extra
     // `do end` around the inside of the block not including the trailing labels.
     fn block_statements(&mut self, block: &Block<String<'gc>>) -> Result<(), CompilerError> {
-        if let Some(return_statement) = &block.return_statement {
-            for statement in &block.statements {
-                self.statement(statement)?;
+        if let Some((line_number, return_statement)) = &block.return_statement {
+            for (line_number, statement) in &block.statements {
+                self.statement(*line_number, statement)?;
             }
-            self.return_statement(return_statement)?;
+            self.return_statement(*line_number, return_statement)?;
         } else {
             let mut last = block.statements.len();
             for i in (0..block.statements.len()).rev() {
                 match &block.statements[i] {
-                    Statement::Label(_) => {}
+                    (_, Statement::Label(_)) => {}
                     _ => break,
                 }
                 last = i;
@@ -285,19 +295,26 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             let trailing_labels = &block.statements[last..block.statements.len()];
 
             self.enter_block();
-            for i in 0..block.statements.len() - trailing_labels.len() {
-                self.statement(&block.statements[i])?;
+            for (line_number, statement) in
+                &block.statements[0..block.statements.len() - trailing_labels.len()]
+            {
+                self.statement(*line_number, statement)?;
             }
             self.exit_block()?;
 
-            for label_statement in trailing_labels {
-                self.statement(&label_statement)?;
+            for (line_number, label_statement) in trailing_labels {
+                self.statement(*line_number, label_statement)?;
             }
         }
         Ok(())
     }
 
-    fn statement(&mut self, statement: &Statement<String<'gc>>) -> Result<(), CompilerError> {
+    fn statement(
+        &mut self,
+        line_number: LineNumber,
+        statement: &Statement<String<'gc>>,
+    ) -> Result<(), CompilerError> {
+        self.current_function.set_line_number(line_number);
         match statement {
             Statement::If(if_statement) => self.if_statement(if_statement),
             Statement::While(while_statement) => self.while_statement(while_statement),
@@ -321,8 +338,10 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
 
     fn return_statement(
         &mut self,
+        line_number: LineNumber,
         return_statement: &ReturnStatement<String<'gc>>,
     ) -> Result<(), CompilerError> {
+        self.current_function.set_line_number(line_number);
         let mut returns = return_statement
             .returns
             .iter()
@@ -333,9 +352,14 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         // in Lua that is considered a tail call
         if returns.len() == 1 {
             match returns.pop().unwrap() {
-                ExprDescriptor::FunctionCall { func, args } => {
+                ExprDescriptor::FunctionCall {
+                    func,
+                    args,
+                    line_number,
+                } => {
                     let func = self.expr_discharge(*func, ExprDestination::PushNew)?;
                     let args = self.push_arguments(args)?;
+                    self.current_function.set_line_number(line_number);
                     self.current_function
                         .opcodes
                         .push(OpCode::TailCall { func, args });
@@ -343,6 +367,23 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
 
                     return Ok(());
                 }
+                ExprDescriptor::MethodCall {
+                    table,
+                    method,
+                    args,
+                    line_number,
+                } => {
+                    let (base, args) = self.push_method_call(*table, *method, args, line_number)?;
+                    self.current_function.set_line_number(line_number);
+                    self.current_function
+                        .opcodes
+                        .push(OpCode::TailCall { func: base, args });
+                    self.current_function
+                        .register_allocator
+                        .pop_to(base.0 as u16);
+
+                    return Ok(());
+                }
                 other => {
                     returns.push(other);
                 }
@@ -580,11 +621,11 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
 
         // `repeat` statements do not follow the trailing label rule, because the variables inside
         // the block are in scope for the `until` condition at the end.
-        for statement in &repeat_statement.body.statements {
-            self.statement(statement)?;
+        for (line_number, statement) in &repeat_statement.body.statements {
+            self.statement(*line_number, statement)?;
         }
-        if let Some(return_statement) = &repeat_statement.body.return_statement {
-            self.return_statement(return_statement)?;
+        if let Some((line_number, return_statement)) = &repeat_statement.body.return_statement {
+            self.return_statement(*line_number, return_statement)?;
         }
 
         let condition = self.expression(&repeat_statement.until)?;
@@ -716,7 +757,12 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     .iter()
                     .map(|arg| self.expression(arg))
                     .collect::<Result<_, CompilerError>>()?;
-                self.call_function(head_expr, arg_exprs, VarCount::constant(0))?;
+                self.call_function(
+                    head_expr,
+                    arg_exprs,
+                    VarCount::constant(0),
+                    function_call.head.line_number,
+                )?;
             }
             CallSuffix::Method(method, args) => {
                 let arg_exprs = args
@@ -728,6 +774,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     ExprDescriptor::Constant(Constant::String(*method)),
                     arg_exprs,
                     VarCount::constant(0),
+                    function_call.head.line_number,
                 )?;
             }
         }
@@ -812,9 +859,9 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         expression: &Expression<String<'gc>>,
     ) -> Result<ExprDescriptor<'gc>, CompilerError> {
         let mut expr = self.head_expression(&expression.head)?;
-        for (binop, right) in &expression.tail {
+        for (line_number, binop, right) in &expression.tail {
             let right = self.expression(&right)?;
-            expr = self.binary_operator_expression(expr, *binop, right)?;
+            expr = self.binary_operator_expression(expr, *binop, right, *line_number)?;
         }
         Ok(expr)
     }
@@ -825,9 +872,9 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     ) -> Result<ExprDescriptor<'gc>, CompilerError> {
         match head_expression {
             HeadExpression::Simple(simple_expression) => self.simple_expression(simple_expression),
-            HeadExpression::UnaryOperator(unop, expr) => {
+            HeadExpression::UnaryOperator(line_number, unop, expr) => {
                 let expr = self.expression(expr)?;
-                self.unary_operator_expression(*unop, expr)
+                self.unary_operator_expression(*unop, expr, *line_number)
             }
         }
     }
@@ -916,6 +963,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                         expr = ExprDescriptor::FunctionCall {
                             func: Box::new(expr),
                             args,
+                            line_number: suffixed_expression.line_number,
                         };
                     }
                     CallSuffix::Method(method, args) => {
@@ -927,6 +975,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                             table: Box::new(expr),
                             method: Box::new(ExprDescriptor::Constant(Constant::String(*method))),
                             args,
+                            line_number: suffixed_expression.line_number,
                         };
                     }
                 },
@@ -951,6 +1000,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         &mut self,
         unop: UnaryOperator,
         expr: ExprDescriptor<'gc>,
+        line_number: LineNumber,
     ) -> Result<ExprDescriptor<'gc>, CompilerError> {
         if let ExprDescriptor::Constant(v) = expr {
             if let Some(v) = unop_const_fold(unop, v) {
@@ -961,6 +1011,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         Ok(ExprDescriptor::UnaryOperator {
             op: unop,
             expr: Box::new(expr),
+            line_number,
         })
     }
 
@@ -969,6 +1020,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         left: ExprDescriptor<'gc>,
         binop: BinaryOperator,
         right: ExprDescriptor<'gc>,
+        line_number: LineNumber,
     ) -> Result<ExprDescriptor<'gc>, CompilerError> {
         match categorize_binop(binop) {
             BinOpCategory::Simple(op) => {
@@ -983,6 +1035,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     left: Box::new(left),
                     op,
                     right: Box::new(right),
+                    line_number,
                 })
             }
 
@@ -998,6 +1051,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     left: Box::new(left),
                     op,
                     right: Box::new(right),
+                    line_number,
                 })
             }
 
@@ -1007,24 +1061,26 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 right: Box::new(right),
             }),
 
+            // A chain of concatenations is performed by a single opcode, which is attributed to the
+            // line of the first operator.
             BinOpCategory::Concat => Ok(match (left, right) {
-                (ExprDescriptor::Concat(mut left), ExprDescriptor::Concat(right)) => {
+                (ExprDescriptor::Concat(mut left, _), ExprDescriptor::Concat(right, _)) => {
                     left.extend(right);
-                    ExprDescriptor::Concat(left)
+                    ExprDescriptor::Concat(left, line_number)
                 }
-                (ExprDescriptor::Concat(mut left), right) => {
+                (ExprDescriptor::Concat(mut left, _), right) => {
                     left.push_back(right);
-                    ExprDescriptor::Concat(left)
+                    ExprDescriptor::Concat(left, line_number)
                 }
-                (left, ExprDescriptor::Concat(mut right)) => {
+                (left, ExprDescriptor::Concat(mut right, _)) => {
                     right.push_front(left);
-                    ExprDescriptor::Concat(right)
+                    ExprDescriptor::Concat(right, line_number)
                 }
                 (left, right) => {
                     let mut exprs = VecDeque::new();
                     exprs.push_back(left);
                     exprs.push_back(right);
-                    ExprDescriptor::Concat(exprs)
+                    ExprDescriptor::Concat(exprs, line_number)
                 }
             }),
         }
@@ -1036,17 +1092,19 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         has_varargs: bool,
         body: &Block<String<'gc>>,
     ) -> Result<PrototypeIndex, CompilerError> {
-        let old_current = mem::replace(
-            &mut self.current_function,
-            CompilerFunction::start(parameters, has_varargs)?,
-        );
+        let mut new_function = CompilerFunction::start(parameters, has_varargs)?;
+        // Until its first statement, a function is on the line where it is defined.
+        if let Some(&(_, line_number)) = self.current_function.opcode_line_numbers.last() {
+            new_function.set_line_number(line_number);
+        }
+        let old_current = mem::replace(&mut self.current_function, new_function);
         self.upper_functions.push(old_current);
         self.block(body)?;
         let proto = mem::replace(
             &mut self.current_function,
             self.upper_functions.pop().unwrap(),
         )
-        .finish(self.mutation_context)?;
+        .finish(self.mutation_context, self.chunk_name)?;
         self.current_function.prototypes.push(proto);
         Ok(PrototypeIndex(
             cast(self.current_function.prototypes.len() - 1).ok_or(CompilerError::Functions)?,
@@ -1378,10 +1436,12 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         func: ExprDescriptor<'gc>,
         args: Vec<ExprDescriptor<'gc>>,
         returns: VarCount,
+        line_number: LineNumber,
     ) -> Result<RegisterIndex, CompilerError> {
         let func = self.expr_discharge(func, ExprDestination::PushNew)?;
         let args = self.push_arguments(args)?;
 
+        self.current_function.set_line_number(line_number);
         self.current_function.opcodes.push(OpCode::Call {
             func,
             args,
@@ -1401,7 +1461,33 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         method: ExprDescriptor<'gc>,
         args: Vec<ExprDescriptor<'gc>>,
         returns: VarCount,
+        line_number: LineNumber,
     ) -> Result<RegisterIndex, CompilerError> {
+        let (base, args) = self.push_method_call(table, method, args, line_number)?;
+        self.current_function.set_line_number(line_number);
+        self.current_function.opcodes.push(OpCode::Call {
+            func: base,
+            args,
+            returns,
+        });
+
+        self.current_function
+            .register_allocator
+            .pop_to(base.0 as u16);
+
+        Ok(base)
+    }
+
+    // Places the method and the table followed by the arguments at the top of the stack, ready for a
+    // call.  The method's register is left allocated.  Returns the method's register and the number
+    // of arguments including the table.
+    fn push_method_call(
+        &mut self,
+        table: ExprDescriptor<'gc>,
+        method: ExprDescriptor<'gc>,
+        args: Vec<ExprDescriptor<'gc>>,
+        line_number: LineNumber,
+    ) -> Result<(RegisterIndex, VarCount), CompilerError> {
         let (table, table_is_temp) = self.expr_any_register(table)?;
         let (method, method_to_free) = self.expr_any_register_or_constant(method)?;
 
@@ -1418,6 +1504,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             .push(2)
             .ok_or(CompilerError::Registers)?;
 
+        self.current_function.set_line_number(line_number);
         self.current_function.opcodes.push(match method {
             RegisterOrConstant::Register(key) => OpCode::SelfR { base, table, key },
             RegisterOrConstant::Constant(key) => OpCode::SelfC { base, table, key },
@@ -1431,17 +1518,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 .ok_or(CompilerError::Registers)?,
             None => VarCount::variable(),
         };
-        self.current_function.opcodes.push(OpCode::Call {
-            func: base,
-            args,
-            returns,
-        });
-
-        self.current_function
-            .register_allocator
-            .pop_to(base.0 as u16);
-
-        Ok(base)
+        Ok((base, args))
     }
 
     // Pushes the given arguments to the top of the stack in preparation for a function call or
@@ -1461,8 +1538,21 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             }
 
             let arg_count = match last_arg {
-                ExprDescriptor::FunctionCall { func, args } => {
-                    self.call_function(*func, args, VarCount::variable())?;
+                ExprDescriptor::FunctionCall {
+                    func,
+                    args,
+                    line_number,
+                } => {
+                    self.call_function(*func, args, VarCount::variable(), line_number)?;
+                    VarCount::variable()
+                }
+                ExprDescriptor::MethodCall {
+                    table,
+                    method,
+                    args,
+                    line_number,
+                } => {
+                    self.call_method(*table, *method, args, VarCount::variable(), line_number)?;
                     VarCount::variable()
                 }
                 ExprDescriptor::VarArgs => {
@@ -1656,7 +1746,11 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 dest
             }
 
-            ExprDescriptor::UnaryOperator { op, expr } => {
+            ExprDescriptor::UnaryOperator {
+                op,
+                expr,
+                line_number,
+            } => {
                 let (source, source_is_temp) = self.expr_any_register(*expr)?;
                 if source_is_temp {
                     self.current_function.register_allocator.free(source);
@@ -1664,11 +1758,17 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
 
                 let dest = new_destination(self, dest)?;
                 let unop_opcode = unop_opcode(op, dest, source);
+                self.current_function.set_line_number(line_number);
                 self.current_function.opcodes.push(unop_opcode);
                 dest
             }
 
-            ExprDescriptor::SimpleBinaryOperator { left, op, right } => {
+            ExprDescriptor::SimpleBinaryOperator {
+                left,
+                op,
+                right,
+                line_number,
+            } => {
                 let (left_reg_cons, left_to_free) = self.expr_any_register_or_constant(*left)?;
                 let (right_reg_cons, right_to_free) = self.expr_any_register_or_constant(*right)?;
                 if let Some(to_free) = left_to_free {
@@ -1681,12 +1781,18 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 let dest = new_destination(self, dest)?;
                 let simple_binop_opcode =
                     simple_binop_opcode(op, dest, left_reg_cons, right_reg_cons);
+                self.current_function.set_line_number(line_number);
                 self.current_function.opcodes.push(simple_binop_opcode);
 
                 dest
             }
 
-            ExprDescriptor::Comparison { left, op, right } => {
+            ExprDescriptor::Comparison {
+                left,
+                op,
+                right,
+                line_number,
+            } => {
                 let (left_reg_cons, left_to_free) = self.expr_any_register_or_constant(*left)?;
                 let (right_reg_cons, right_to_free) = self.expr_any_register_or_constant(*right)?;
                 if let Some(to_free) = left_to_free {
@@ -1700,6 +1806,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 let comparison_opcode =
                     comparison_binop_opcode(op, left_reg_cons, right_reg_cons, false);
 
+                self.current_function.set_line_number(line_number);
                 let opcodes = &mut self.current_function.opcodes;
                 opcodes.push(comparison_opcode);
                 opcodes.push(OpCode::Jump {
@@ -1775,8 +1882,12 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 dest
             }
 
-            ExprDescriptor::FunctionCall { func, args } => {
-                let source = self.call_function(*func, args, VarCount::constant(1))?;
+            ExprDescriptor::FunctionCall {
+                func,
+                args,
+                line_number,
+            } => {
+                let source = self.call_function(*func, args, VarCount::constant(1), line_number)?;
                 match dest {
                     ExprDestination::Register(dest) => {
                         assert_ne!(dest, source);
@@ -1802,8 +1913,10 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 table,
                 method,
                 args,
+                line_number,
             } => {
-                let source = self.call_method(*table, *method, args, VarCount::constant(1))?;
+                let source =
+                    self.call_method(*table, *method, args, VarCount::constant(1), line_number)?;
                 match dest {
                     ExprDestination::Register(dest) => {
                         assert_ne!(dest, source);
@@ -1825,7 +1938,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 }
             }
 
-            ExprDescriptor::Concat(mut exprs) => {
+            ExprDescriptor::Concat(mut exprs, line_number) => {
                 assert!(!exprs.is_empty());
                 let dest = new_destination(self, dest)?;
                 let source =
@@ -1837,6 +1950,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                         self.expr_discharge(next, ExprDestination::Register(new))?;
                         count += 1;
                     } else {
+                        self.current_function.set_line_number(line_number);
                         self.current_function.opcodes.push(OpCode::Concat {
                             dest: source,
                             source,
@@ -1848,6 +1962,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                         count = 1;
                     }
                 }
+                self.current_function.set_line_number(line_number);
                 self.current_function.opcodes.push(OpCode::Concat {
                     dest,
                     source,
@@ -1872,11 +1987,35 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     ) -> Result<RegisterIndex, CompilerError> {
         assert!(count != 0);
         Ok(match expr {
-            ExprDescriptor::FunctionCall { func, args } => {
+            ExprDescriptor::FunctionCall {
+                func,
+                args,
+                line_number,
+            } => {
                 let dest = self.call_function(
                     *func,
                     args,
                     VarCount::try_constant(count).ok_or(CompilerError::Registers)?,
+                    line_number,
+                )?;
+                self.current_function
+                    .register_allocator
+                    .push(count)
+                    .ok_or(CompilerError::Registers)?;
+                dest
+            }
+            ExprDescriptor::MethodCall {
+                table,
+                method,
+                args,
+                line_number,
+            } => {
+                let dest = self.call_method(
+                    *table,
+                    *method,
+                    args,
+                    VarCount::try_constant(count).ok_or(CompilerError::Registers)?,
+                    line_number,
                 )?;
                 self.current_function
                     .register_allocator
@@ -1934,6 +2073,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             op: ComparisonBinOp,
             right: ExprDescriptor<'gc>,
             skip_if: bool,
+            line_number: LineNumber,
         ) -> Result<(), CompilerError> {
             let (left_reg_cons, left_to_free) = this.expr_any_register_or_constant(left)?;
             let (right_reg_cons, right_to_free) = this.expr_any_register_or_constant(right)?;
@@ -1946,6 +2086,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
 
             let comparison_opcode =
                 comparison_binop_opcode(op, left_reg_cons, right_reg_cons, skip_if);
+            this.current_function.set_line_number(line_number);
             this.current_function.opcodes.push(comparison_opcode);
 
             Ok(())
@@ -1977,16 +2118,23 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     });
                 }
             }
-            ExprDescriptor::Comparison { left, op, right } => {
-                gen_comparison(self, *left, op, *right, skip_if)?
-            }
+            ExprDescriptor::Comparison {
+                left,
+                op,
+                right,
+                line_number,
+            } => gen_comparison(self, *left, op, *right, skip_if, line_number)?,
             ExprDescriptor::UnaryOperator {
                 op: UnaryOperator::Not,
                 expr,
+                ..
             } => match *expr {
-                ExprDescriptor::Comparison { left, op, right } => {
-                    gen_comparison(self, *left, op, *right, !skip_if)?
-                }
+                ExprDescriptor::Comparison {
+                    left,
+                    op,
+                    right,
+                    line_number,
+                } => gen_comparison(self, *left, op, *right, !skip_if, line_number)?,
                 expr => gen_test(self, expr, !skip_if)?,
             },
             expr => gen_test(self, expr, skip_if)?,
@@ -2016,7 +2164,22 @@ impl<'gc> CompilerFunction<'gc> {
         Ok(function)
     }
 
-    fn finish(mut self, mc: MutationContext<'gc, '_>) -> Result<FunctionProto<'gc>, CompilerError> {
+    // Attributes the opcodes generated from now on to the given line.
+    fn set_line_number(&mut self, line_number: LineNumber) {
+        match self.opcode_line_numbers.last_mut() {
+            Some((_, last)) if *last == line_number => {}
+            Some((start, last)) if *start == self.opcodes.len() => *last = line_number,
+            _ => self
+                .opcode_line_numbers
+                .push((self.opcodes.len(), line_number)),
+        }
+    }
+
+    fn finish(
+        mut self,
+        mc: MutationContext<'gc, '_>,
+        chunk_name: String<'gc>,
+    ) -> Result<FunctionProto<'gc>, CompilerError> {
         self.opcodes.push(OpCode::Return {
             start: RegisterIndex(0),
             count: VarCount::constant(0),
@@ -2036,11 +2199,13 @@ impl<'gc> CompilerFunction<'gc> {
         }
 
         Ok(FunctionProto {
+            chunk_name,
             fixed_params: self.fixed_params,
             has_varargs: self.has_varargs,
             stack_size: self.register_allocator.stack_size(),
             constants: self.constants,
             opcodes: self.opcodes,
+            opcode_line_numbers: self.opcode_line_numbers,
             upvalues: self.upvalues.iter().map(|(_, d)| *d).collect(),
             prototypes: self
                 .prototypes
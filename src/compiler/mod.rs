@@ -2,7 +2,7 @@ use std::io::Read;
 
 use gc_arena::MutationContext;
 
-use crate::{parse_chunk, Error, FunctionProto, InternedStringSet};
+use crate::{parse_chunk_located, Error, FunctionProto, InternedStringSet, LocatedError};
 
 mod compiler;
 mod operators;
@@ -10,13 +10,31 @@ mod register_allocator;
 
 pub use self::compiler::{compile_chunk, CompilerError};
 
+/// Compiles a chunk named "=?", see `compile_named`.
 pub fn compile<'gc, R: Read>(
     mc: MutationContext<'gc, '_>,
     interned_strings: InternedStringSet<'gc>,
     source: R,
 ) -> Result<FunctionProto<'gc>, Error<'gc>> {
-    Ok(compile_chunk(
-        mc,
-        &parse_chunk(source, |s| interned_strings.new_string(mc, s))?,
-    )?)
+    compile_named(mc, interned_strings, b"=?", source)
+}
+
+/// Compiles a chunk with the given name, which is used in error messages and debug information in
+/// the same form as the `chunkname` argument to `load`.  Parser errors are returned as a
+/// `LocatedError` giving the line of the error.
+pub fn compile_named<'gc, R: Read>(
+    mc: MutationContext<'gc, '_>,
+    interned_strings: InternedStringSet<'gc>,
+    chunk_name: &[u8],
+    source: R,
+) -> Result<FunctionProto<'gc>, Error<'gc>> {
+    let chunk_name = interned_strings.new_string(mc, chunk_name);
+    let chunk = parse_chunk_located(source, |s| interned_strings.new_string(mc, s)).map_err(
+        |(error, line_number)| LocatedError {
+            chunk_name,
+            line_number,
+            error: Box::new(error.into()),
+        },
+    )?;
+    Ok(compile_chunk(mc, chunk_name, &chunk)?)
 }
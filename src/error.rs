@@ -5,8 +5,9 @@ use std::{fmt, io};
 use gc_arena::{Collect, MutationContext, StaticCollect};
 
 use crate::{
-    BadThreadMode, BinaryOperatorError, ClosureError, CompilerError, InternedStringSet,
-    InvalidTableKey, ParserError, StringError, ThreadError, Value,
+    closure::chunk_id, BadThreadMode, BinaryOperatorError, ClosureError, CompilerError,
+    InternedStringSet, InvalidNextKey, InvalidTableKey, LineNumber, ParserError, String,
+    StringError, ThreadError, Value,
 };
 
 #[derive(Debug, Clone, Copy, Collect)]
@@ -28,6 +29,23 @@ impl fmt::Display for TypeError {
     }
 }
 
+/// Raised by `os.exit` when the host's exit handler returns rather than ending the process.  Unlike
+/// other errors, it is not caught by `pcall` or `coroutine.resume`, so it unwinds all running Lua
+/// code.
+#[derive(Debug, Clone, Copy, Collect)]
+#[collect(require_static)]
+pub struct ExitError {
+    pub code: i32,
+}
+
+impl StdError for ExitError {}
+
+impl fmt::Display for ExitError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "exit with code {}", self.code)
+    }
+}
+
 #[derive(Debug, Clone, Copy, Collect)]
 #[collect(require_copy)]
 pub struct RuntimeError<'gc>(pub Value<'gc>);
@@ -43,6 +61,48 @@ impl<'gc> fmt::Display for RuntimeError<'gc> {
     }
 }
 
+/// An error raised by the VM while running a Lua function, along with the chunk and line of the
+/// opcode that raised it.  The `"chunk:line: message"` string Lua code sees is only built when the
+/// error is converted to a value.
+// Safe, does not implement drop
+#[derive(Debug, Collect)]
+#[collect(unsafe_drop)]
+pub struct LocatedError<'gc> {
+    pub chunk_name: String<'gc>,
+    pub line_number: LineNumber,
+    pub error: Box<Error<'gc>>,
+}
+
+impl<'gc> StdError for LocatedError<'gc> {}
+
+impl<'gc> fmt::Display for LocatedError<'gc> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "{}:{}: {}",
+            StdString::from_utf8_lossy(&chunk_id(self.chunk_name.as_bytes())),
+            self.line_number,
+            self.error
+        )
+    }
+}
+
+impl<'gc> LocatedError<'gc> {
+    // The message seen by Lua code, where string runtime errors are not prefixed with their kind.
+    fn message(&self) -> Vec<u8> {
+        let mut message = chunk_id(self.chunk_name.as_bytes());
+        message.extend_from_slice(format!(":{}: ", self.line_number).as_bytes());
+        match &*self.error {
+            Error::RuntimeError(RuntimeError(Value::String(s))) => {
+                message.extend_from_slice(s.as_bytes())
+            }
+            Error::LocatedError(error) => message.extend_from_slice(&error.message()),
+            error => message.extend_from_slice(error.to_string().as_bytes()),
+        }
+        message
+    }
+}
+
 // Safe, does not implement drop
 #[derive(Debug, Collect)]
 #[collect(unsafe_drop)]
@@ -52,12 +112,15 @@ pub enum Error<'gc> {
     CompilerError(CompilerError),
     ClosureError(ClosureError),
     InvalidTableKey(InvalidTableKey),
+    InvalidNextKey(InvalidNextKey),
     StringError(StringError),
     ThreadError(ThreadError),
     BadThreadMode(BadThreadMode),
     TypeError(TypeError),
     BinaryOperatorError(BinaryOperatorError),
     RuntimeError(RuntimeError<'gc>),
+    LocatedError(LocatedError<'gc>),
+    ExitError(ExitError),
 }
 
 impl<'gc> StdError for Error<'gc> {}
@@ -70,12 +133,15 @@ impl<'gc> fmt::Display for Error<'gc> {
             Error::CompilerError(error) => write!(fmt, "compiler error: {}", error),
             Error::ClosureError(error) => write!(fmt, "closure error: {}", error),
             Error::InvalidTableKey(error) => write!(fmt, "invalid table key: {}", error),
+            Error::InvalidNextKey(error) => write!(fmt, "{}", error),
             Error::StringError(error) => write!(fmt, "string error: {}", error),
             Error::ThreadError(error) => write!(fmt, "thread error: {}", error),
             Error::BadThreadMode(error) => write!(fmt, "bad thread mode: {}", error),
             Error::TypeError(error) => write!(fmt, "type error: {}", error),
             Error::BinaryOperatorError(error) => write!(fmt, "operator error: {}", error),
             Error::RuntimeError(error) => write!(fmt, "runtime error: {}", error),
+            Error::LocatedError(error) => write!(fmt, "{}", error),
+            Error::ExitError(error) => write!(fmt, "exit: {}", error),
         }
     }
 }
@@ -110,6 +176,12 @@ impl<'gc> From<InvalidTableKey> for Error<'gc> {
     }
 }
 
+impl<'gc> From<InvalidNextKey> for Error<'gc> {
+    fn from(error: InvalidNextKey) -> Error<'gc> {
+        Error::InvalidNextKey(error)
+    }
+}
+
 impl<'gc> From<StringError> for Error<'gc> {
     fn from(error: StringError) -> Error<'gc> {
         Error::StringError(error)
@@ -146,6 +218,18 @@ impl<'gc> From<RuntimeError<'gc>> for Error<'gc> {
     }
 }
 
+impl<'gc> From<LocatedError<'gc>> for Error<'gc> {
+    fn from(error: LocatedError<'gc>) -> Error<'gc> {
+        Error::LocatedError(error)
+    }
+}
+
+impl<'gc> From<ExitError> for Error<'gc> {
+    fn from(error: ExitError) -> Error<'gc> {
+        Error::ExitError(error)
+    }
+}
+
 impl<'gc> Error<'gc> {
     pub fn to_static(self) -> StaticError {
         match self {
@@ -154,6 +238,7 @@ impl<'gc> Error<'gc> {
             Error::CompilerError(error) => StaticError::CompilerError(error),
             Error::ClosureError(error) => StaticError::ClosureError(error),
             Error::InvalidTableKey(error) => StaticError::InvalidTableKey(error),
+            Error::InvalidNextKey(error) => StaticError::InvalidNextKey(error),
             Error::StringError(error) => StaticError::StringError(error),
             Error::ThreadError(error) => StaticError::ThreadError(error),
             Error::BadThreadMode(error) => StaticError::BadThreadMode(error),
@@ -164,6 +249,12 @@ impl<'gc> Error<'gc> {
                 error.0.display(&mut buf).unwrap();
                 StaticError::RuntimeError(StdString::from_utf8_lossy(&buf).to_owned().to_string())
             }
+            Error::LocatedError(error) => StaticError::LocatedError(StaticLocatedError {
+                chunk_name: StdString::from_utf8_lossy(error.chunk_name.as_bytes()).into_owned(),
+                line_number: error.line_number,
+                error: Box::new(error.error.to_static()),
+            }),
+            Error::ExitError(error) => StaticError::ExitError(error),
         }
     }
 
@@ -172,13 +263,25 @@ impl<'gc> Error<'gc> {
         mc: MutationContext<'gc, '_>,
         interned_strings: InternedStringSet<'gc>,
     ) -> Value<'gc> {
-        match self {
-            Error::RuntimeError(error) => error.0,
-            other => {
-                let s = other.to_string();
-                Value::String(interned_strings.new_string(mc, s.as_ref()))
-            }
-        }
+        self.as_value(mc, Some(interned_strings))
+    }
+
+    // Converts this error to a value as `to_value` does, without consuming it.  Strings are only
+    // interned if an interned string set is given.
+    pub(crate) fn as_value(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        interned_strings: Option<InternedStringSet<'gc>>,
+    ) -> Value<'gc> {
+        let message = match self {
+            Error::RuntimeError(error) => return error.0,
+            Error::LocatedError(error) => error.message(),
+            other => other.to_string().into_bytes(),
+        };
+        Value::String(match interned_strings {
+            Some(interned_strings) => interned_strings.new_string(mc, &message),
+            None => String::new(mc, &message),
+        })
     }
 }
 
@@ -190,12 +293,15 @@ pub enum StaticError {
     CompilerError(CompilerError),
     ClosureError(ClosureError),
     InvalidTableKey(InvalidTableKey),
+    InvalidNextKey(InvalidNextKey),
     StringError(StringError),
     ThreadError(ThreadError),
     BadThreadMode(BadThreadMode),
     TypeError(TypeError),
     BinaryOperatorError(BinaryOperatorError),
-    RuntimeError(String),
+    RuntimeError(StdString),
+    LocatedError(StaticLocatedError),
+    ExitError(ExitError),
 }
 
 impl StdError for StaticError {}
@@ -208,12 +314,44 @@ impl fmt::Display for StaticError {
             StaticError::CompilerError(error) => write!(fmt, "compiler error: {}", error),
             StaticError::ClosureError(error) => write!(fmt, "closure error: {}", error),
             StaticError::InvalidTableKey(error) => write!(fmt, "invalid table key: {}", error),
+            StaticError::InvalidNextKey(error) => write!(fmt, "{}", error),
             StaticError::StringError(error) => write!(fmt, "string error: {}", error),
             StaticError::ThreadError(error) => write!(fmt, "thread error: {}", error),
             StaticError::BadThreadMode(error) => write!(fmt, "bad thread mode: {}", error),
             StaticError::TypeError(error) => write!(fmt, "type error: {}", error),
             StaticError::BinaryOperatorError(error) => write!(fmt, "operator error: {}", error),
             StaticError::RuntimeError(error) => write!(fmt, "runtime error: {}", error),
+            StaticError::LocatedError(error) => write!(fmt, "{}", error),
+            StaticError::ExitError(error) => write!(fmt, "exit: {}", error),
         }
     }
 }
+
+impl From<ExitError> for StaticError {
+    fn from(error: ExitError) -> StaticError {
+        StaticError::ExitError(error)
+    }
+}
+
+/// A `LocatedError` which has been converted to a `StaticError`.
+#[derive(Debug, Collect)]
+#[collect(require_static)]
+pub struct StaticLocatedError {
+    pub chunk_name: StdString,
+    pub line_number: LineNumber,
+    pub error: Box<StaticError>,
+}
+
+impl StdError for StaticLocatedError {}
+
+impl fmt::Display for StaticLocatedError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "{}:{}: {}",
+            StdString::from_utf8_lossy(&chunk_id(self.chunk_name.as_bytes())),
+            self.line_number,
+            self.error
+        )
+    }
+}
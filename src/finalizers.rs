@@ -0,0 +1,159 @@
+use std::rc::Rc;
+
+use gc_arena::{Collect, FinalizerQueue, GcPointer, GcPtr, MutationContext, StaticCollect};
+use gc_sequence::Sequence;
+
+use crate::meta_ops::{self, MetaMethod};
+use crate::{Error, ExitError, Root, Table, ThreadSequence, UserData, Value};
+
+/// Called with a description of any error raised by a `__gc` metamethod, since there is no running
+/// code for such errors to propagate to.
+pub type WarnHandler = Rc<dyn Fn(&str)>;
+
+/// The set of tables and userdata which should have their `__gc` metamethod called once they become
+/// unreachable.
+///
+/// As in PUC-Rio Lua, an object is only marked for finalization if its metatable has a `__gc` field
+/// at the time the metatable is set, but the metamethod which is called is looked up at the time of
+/// finalization.  Finalized objects are kept alive until their metamethod has been called, and are
+/// only finalized once unless they are marked again.
+#[derive(Collect, Clone, Copy)]
+#[collect(require_copy)]
+pub struct Finalizers<'gc>(FinalizerQueue<'gc, Object<'gc>>);
+
+#[derive(Collect, Clone, Copy)]
+#[collect(require_copy)]
+enum Object<'gc> {
+    Table(Table<'gc>),
+    UserData(UserData<'gc>),
+}
+
+impl<'gc> GcPointer<'gc> for Object<'gc> {
+    fn gc_ptr(&self) -> GcPtr<'gc> {
+        match self {
+            Object::Table(t) => t.0.gc_ptr(),
+            Object::UserData(u) => u.0.gc_ptr(),
+        }
+    }
+}
+
+impl<'gc> Object<'gc> {
+    fn into_value(self) -> Value<'gc> {
+        match self {
+            Object::Table(t) => Value::Table(t),
+            Object::UserData(u) => Value::UserData(u),
+        }
+    }
+}
+
+impl<'gc> Finalizers<'gc> {
+    pub fn new(mc: MutationContext<'gc, '_>) -> Finalizers<'gc> {
+        Finalizers(FinalizerQueue::new(mc))
+    }
+
+    /// Marks the table for finalization if its current metatable has a `__gc` field.
+    pub fn register_table(&self, table: Table<'gc>) {
+        if meta_ops::get_metamethod(Value::Table(table), MetaMethod::Gc) != Value::Nil {
+            self.0.register(Object::Table(table));
+        }
+    }
+
+    /// Marks the userdata for finalization if its current metatable has a `__gc` field.
+    pub fn register_userdata(&self, userdata: UserData<'gc>) {
+        if meta_ops::get_metamethod(Value::UserData(userdata), MetaMethod::Gc) != Value::Nil {
+            self.0.register(Object::UserData(userdata));
+        }
+    }
+
+    /// Returns whether any unreachable objects are waiting for their `__gc` metamethod to be
+    /// called.
+    pub fn is_pending(&self) -> bool {
+        self.0.is_pending()
+    }
+
+    /// Takes the next unreachable object waiting for its `__gc` metamethod to be called.
+    pub fn pop(&self) -> Option<Value<'gc>> {
+        self.0.pop().map(Object::into_value)
+    }
+}
+
+/// Wraps a sequence so that, in between its steps, the `__gc` metamethods of any unreachable
+/// objects are called.
+///
+/// Each metamethod runs to completion on its own thread before the wrapped sequence is stepped
+/// again.  Errors raised by metamethods are given to the `WarnHandler`, except for the `ExitError`
+/// raised by `os.exit`, which abandons the wrapped sequence and becomes its result.
+#[derive(Collect)]
+#[collect(empty_drop)]
+pub struct RunFinalizers<'gc, S> {
+    root: Root<'gc>,
+    warn: StaticCollect<WarnHandler>,
+    sequence: S,
+    finalizer: Option<ThreadSequence<'gc>>,
+}
+
+impl<'gc, S> RunFinalizers<'gc, S> {
+    pub fn new(root: Root<'gc>, warn: WarnHandler, sequence: S) -> RunFinalizers<'gc, S> {
+        RunFinalizers {
+            root,
+            warn: StaticCollect(warn),
+            sequence,
+            finalizer: None,
+        }
+    }
+
+    fn start_finalizer(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        value: Value<'gc>,
+    ) -> Result<Option<ThreadSequence<'gc>>, Error<'gc>> {
+        let (function, args) = match meta_ops::get_metamethod(value, MetaMethod::Gc) {
+            Value::Nil => return Ok(None),
+            Value::Function(function) => (function, vec![value]),
+            handler => (meta_ops::call(handler)?, vec![handler, value]),
+        };
+        let thread = self.root.new_thread(mc, false);
+        Ok(Some(ThreadSequence::call_function(
+            mc, thread, function, &args,
+        )?))
+    }
+
+    fn warn(&self, error: Error<'gc>) {
+        (self.warn.0)(&format!("error in __gc metamethod ({})", error));
+    }
+}
+
+impl<'gc, S, R, E> Sequence<'gc> for RunFinalizers<'gc, S>
+where
+    S: Sequence<'gc, Output = Result<R, E>>,
+    E: From<ExitError>,
+{
+    type Output = S::Output;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<S::Output> {
+        if let Some(finalizer) = &mut self.finalizer {
+            if let Some(result) = finalizer.step(mc) {
+                self.finalizer = None;
+                match result {
+                    Ok(_) => {}
+                    Err(Error::ExitError(err)) => return Some(Err(err.into())),
+                    Err(error) => self.warn(error),
+                }
+            }
+            None
+        } else if let Some(value) = self.root.finalizers.pop() {
+            match self.start_finalizer(mc, value) {
+                Ok(finalizer) => self.finalizer = finalizer,
+                Err(error) => self.warn(error),
+            }
+            None
+        } else {
+            self.sequence.step(mc)
+        }
+    }
+}
+
+/// The default `WarnHandler`, which prints warnings to stderr.
+pub fn default_warn_handler() -> WarnHandler {
+    Rc::new(|message| eprintln!("Lua warning: {}", message))
+}
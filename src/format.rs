@@ -0,0 +1,340 @@
+//! An implementation of C `printf` style formatting directives, producing the same output as
+//! `sprintf` under the "C" locale.
+
+/// An error caused by a malformed format string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatError(pub &'static str);
+
+/// A single parsed `%` directive, not including the leading '%'.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FormatSpec {
+    pub left_align: bool,
+    pub plus_sign: bool,
+    pub space_sign: bool,
+    pub alternate: bool,
+    pub zero_pad: bool,
+    pub width: usize,
+    pub precision: Option<usize>,
+    pub conversion: u8,
+}
+
+impl FormatSpec {
+    /// Parses a directive from the bytes following a '%', returning the spec and the number of
+    /// bytes consumed.  Like PUC-Rio Lua, flags may not be repeated and the width and precision are
+    /// limited to two digits.
+    pub fn parse(fmt: &[u8]) -> Result<(FormatSpec, usize), FormatError> {
+        let mut spec = FormatSpec::default();
+        let mut i = 0;
+
+        let mut flag_count = 0;
+        while let Some(&c) = fmt.get(i) {
+            match c {
+                b'-' => spec.left_align = true,
+                b'+' => spec.plus_sign = true,
+                b' ' => spec.space_sign = true,
+                b'#' => spec.alternate = true,
+                b'0' => spec.zero_pad = true,
+                _ => break,
+            }
+            flag_count += 1;
+            i += 1;
+        }
+        if flag_count > 5 {
+            return Err(FormatError("invalid format (repeated flags)"));
+        }
+
+        let (width, len) = read_digits(&fmt[i..])?;
+        spec.width = width;
+        i += len;
+
+        if fmt.get(i) == Some(&b'.') {
+            i += 1;
+            let (precision, len) = read_digits(&fmt[i..])?;
+            spec.precision = Some(precision);
+            i += len;
+        }
+
+        match fmt.get(i) {
+            Some(&c) => {
+                spec.conversion = c;
+                Ok((spec, i + 1))
+            }
+            None => Err(FormatError("invalid conversion to format")),
+        }
+    }
+
+    /// Writes an integer using one of the `d`, `i`, `u`, `o`, `x` or `X` conversions.  All but `d`
+    /// and `i` treat the integer as unsigned.
+    pub fn write_integer(&self, out: &mut Vec<u8>, i: i64) {
+        let (negative, mut digits) = match self.conversion {
+            b'o' => (false, format!("{:o}", i as u64)),
+            b'x' => (false, format!("{:x}", i as u64)),
+            b'X' => (false, format!("{:X}", i as u64)),
+            b'u' => (false, format!("{}", i as u64)),
+            _ => (i < 0, format!("{}", (i as i128).abs())),
+        };
+
+        if let Some(precision) = self.precision {
+            if precision == 0 && i == 0 {
+                digits.clear();
+            }
+            while digits.len() < precision {
+                digits.insert(0, '0');
+            }
+        }
+
+        let mut prefix = "";
+        if self.alternate {
+            match self.conversion {
+                b'o' if !digits.starts_with('0') => digits.insert(0, '0'),
+                b'x' if i != 0 => prefix = "0x",
+                b'X' if i != 0 => prefix = "0X",
+                _ => {}
+            }
+        }
+
+        let sign = match self.conversion {
+            b'd' | b'i' => self.sign(negative),
+            _ => "",
+        };
+
+        self.pad(
+            out,
+            sign,
+            prefix.as_bytes(),
+            digits.as_bytes(),
+            self.precision.is_none(),
+        );
+    }
+
+    /// Writes a float using one of the `e`, `E`, `f`, `g`, `G`, `a` or `A` conversions.
+    pub fn write_float(&self, out: &mut Vec<u8>, n: f64) {
+        let upper = self.conversion.is_ascii_uppercase();
+        let sign = self.sign(n.is_sign_negative());
+
+        if !n.is_finite() {
+            let body = match (n.is_nan(), upper) {
+                (true, false) => "nan",
+                (true, true) => "NAN",
+                (false, false) => "inf",
+                (false, true) => "INF",
+            };
+            self.pad(out, sign, b"", body.as_bytes(), false);
+            return;
+        }
+
+        let n = n.abs();
+        let body = match self.conversion.to_ascii_lowercase() {
+            b'a' => hex_float(n, self.precision, self.alternate),
+            b'e' => exponent_float(n, self.precision.unwrap_or(6), self.alternate),
+            b'f' => fixed_float(n, self.precision.unwrap_or(6), self.alternate),
+            _ => general_float(n, self.precision, self.alternate),
+        };
+        let body = if upper {
+            body.to_ascii_uppercase()
+        } else {
+            body
+        };
+
+        // Any zero padding of hex floats goes after the "0x" prefix.
+        let (prefix, body) = if self.conversion.to_ascii_lowercase() == b'a' {
+            body.split_at(2)
+        } else {
+            body.split_at(0)
+        };
+        self.pad(out, sign, prefix.as_bytes(), body.as_bytes(), true);
+    }
+
+    /// Writes a string using the `s` conversion, the precision limits the number of bytes written.
+    pub fn write_string(&self, out: &mut Vec<u8>, s: &[u8]) {
+        let s = match self.precision {
+            Some(precision) if precision < s.len() => &s[..precision],
+            _ => s,
+        };
+        self.pad(out, "", b"", s, false);
+    }
+
+    /// Writes a single byte using the `c` conversion.
+    pub fn write_char(&self, out: &mut Vec<u8>, c: u8) {
+        self.pad(out, "", b"", &[c], false);
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.plus_sign {
+            "+"
+        } else if self.space_sign {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    // Pads the output to the field width.  Zero padding is placed between the sign and prefix and
+    // the body.
+    fn pad(&self, out: &mut Vec<u8>, sign: &str, prefix: &[u8], body: &[u8], allow_zeros: bool) {
+        let len = sign.len() + prefix.len() + body.len();
+        let padding = self.width.saturating_sub(len);
+
+        if self.left_align {
+            out.extend_from_slice(sign.as_bytes());
+            out.extend_from_slice(prefix);
+            out.extend_from_slice(body);
+            out.extend((0..padding).map(|_| b' '));
+        } else if self.zero_pad && allow_zeros {
+            out.extend_from_slice(sign.as_bytes());
+            out.extend_from_slice(prefix);
+            out.extend((0..padding).map(|_| b'0'));
+            out.extend_from_slice(body);
+        } else {
+            out.extend((0..padding).map(|_| b' '));
+            out.extend_from_slice(sign.as_bytes());
+            out.extend_from_slice(prefix);
+            out.extend_from_slice(body);
+        }
+    }
+}
+
+/// Writes a float the way Lua converts numbers to strings: as `%.14g`, with ".0" appended when the
+/// result would otherwise look like an integer.
+pub fn write_number(out: &mut Vec<u8>, n: f64) {
+    let start = out.len();
+    FormatSpec {
+        conversion: b'g',
+        precision: Some(14),
+        ..Default::default()
+    }
+    .write_float(out, n);
+    if out[start..]
+        .iter()
+        .all(|&c| c == b'-' || c.is_ascii_digit())
+    {
+        out.extend_from_slice(b".0");
+    }
+}
+
+fn read_digits(fmt: &[u8]) -> Result<(usize, usize), FormatError> {
+    let len = fmt.iter().take_while(|c| c.is_ascii_digit()).count();
+    if len > 2 {
+        return Err(FormatError("invalid format (width or precision too long)"));
+    }
+    let n = fmt[..len]
+        .iter()
+        .fold(0, |n, &c| n * 10 + (c - b'0') as usize);
+    Ok((n, len))
+}
+
+// All of the float formatting functions below take a finite, non-negative number.
+
+fn fixed_float(n: f64, precision: usize, alternate: bool) -> std::string::String {
+    let mut s = format!("{:.*}", precision, n);
+    if alternate && precision == 0 {
+        s.push('.');
+    }
+    s
+}
+
+fn exponent_float(n: f64, precision: usize, alternate: bool) -> std::string::String {
+    let s = format!("{:.*e}", precision, n);
+    let (mantissa, exponent) = s.split_at(s.find('e').unwrap());
+    let exponent: i32 = exponent[1..].parse().unwrap();
+
+    let mut s = mantissa.to_owned();
+    if alternate && precision == 0 {
+        s.push('.');
+    }
+    s.push('e');
+    s.push(if exponent < 0 { '-' } else { '+' });
+    s.push_str(&format!("{:02}", exponent.abs()));
+    s
+}
+
+fn general_float(n: f64, precision: Option<usize>, alternate: bool) -> std::string::String {
+    let precision = match precision {
+        None => 6,
+        Some(0) => 1,
+        Some(p) => p,
+    };
+
+    // The exponent that `%e` would produce after rounding to the given precision.
+    let e = format!("{:.*e}", precision - 1, n);
+    let exponent: i32 = e[e.find('e').unwrap() + 1..].parse().unwrap();
+
+    let s = if exponent >= -4 && exponent < precision as i32 {
+        fixed_float(n, (precision as i32 - 1 - exponent) as usize, alternate)
+    } else {
+        exponent_float(n, precision - 1, alternate)
+    };
+
+    if alternate {
+        return s;
+    }
+
+    // Without the alternate flag, trailing zeros are removed from the fractional part along with a
+    // trailing decimal point.
+    let (mantissa, exponent) = s.split_at(s.find('e').unwrap_or_else(|| s.len()));
+    let mantissa = if mantissa.contains('.') {
+        mantissa.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        mantissa
+    };
+    format!("{}{}", mantissa, exponent)
+}
+
+// Produces the same output as glibc's `%a`, with a leading digit of 1 for normal numbers and 0 for
+// subnormal numbers.  Without a precision, the exact value is printed with no trailing zeros.
+fn hex_float(n: f64, precision: Option<usize>, alternate: bool) -> std::string::String {
+    let bits = n.to_bits();
+    let biased_exponent = ((bits >> 52) & 0x7ff) as i64;
+    let mantissa = bits & ((1 << 52) - 1);
+
+    let (lead, exponent) = if n == 0.0 {
+        (0, 0)
+    } else if biased_exponent == 0 {
+        (0, -1022)
+    } else {
+        (1, biased_exponent - 1023)
+    };
+
+    let (lead, mut digits) = match precision {
+        None => (
+            lead,
+            format!("{:013x}", mantissa)
+                .trim_end_matches('0')
+                .to_owned(),
+        ),
+        Some(p) if p >= 13 => (lead, format!("{:013x}{}", mantissa, "0".repeat(p - 13))),
+        Some(p) => {
+            // Round the mantissa to the requested number of hex digits, with ties to even.
+            let shift = 52 - 4 * p as u32;
+            let full = (lead << 52) | mantissa;
+            let rem = full & ((1 << shift) - 1);
+            let half = 1 << (shift - 1);
+            let mut rounded = full >> shift;
+            if rem > half || (rem == half && rounded & 1 == 1) {
+                rounded += 1;
+            }
+            let frac = rounded & ((1 << (4 * p as u32)) - 1);
+            (
+                rounded >> (4 * p as u32),
+                if p == 0 {
+                    std::string::String::new()
+                } else {
+                    format!("{:0width$x}", frac, width = p)
+                },
+            )
+        }
+    };
+
+    if !digits.is_empty() || alternate {
+        digits.insert(0, '.');
+    }
+    format!(
+        "0x{}{}p{}{}",
+        lead,
+        digits,
+        if exponent < 0 { '-' } else { '+' },
+        exponent.abs()
+    )
+}
@@ -0,0 +1,69 @@
+use std::cell::Cell;
+
+/// A collection requested by `collectgarbage`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GcRequest {
+    /// Perform a full collection cycle.
+    Collect,
+    /// Perform the given amount of collection work, measured in bytes as allocation debt is.
+    Step(f64),
+}
+
+/// Shared between the `collectgarbage` function and whatever drives the arena, since no collection
+/// can take place while Lua code is running.
+///
+/// Requests are deferred: `collectgarbage` records one and yields back to the driver, and only
+/// returns once the driver has had the chance to carry it out.  `Lua` does so in between the steps
+/// of its sequences, and only collects on its own while the collector is running.  Other drivers
+/// may do the same, and requests are otherwise ignored, in which case `collectgarbage("step")`
+/// always returns false.
+pub struct GcControl {
+    running: Cell<bool>,
+    request: Cell<Option<GcRequest>>,
+    finished_cycle: Cell<bool>,
+}
+
+impl Default for GcControl {
+    fn default() -> GcControl {
+        GcControl::new()
+    }
+}
+
+impl GcControl {
+    pub fn new() -> GcControl {
+        GcControl {
+            running: Cell::new(true),
+            request: Cell::new(None),
+            finished_cycle: Cell::new(false),
+        }
+    }
+
+    /// Whether the collector should run automatically, which `collectgarbage("stop")` turns off.
+    pub fn is_running(&self) -> bool {
+        self.running.get()
+    }
+
+    pub fn set_running(&self, running: bool) {
+        self.running.set(running);
+    }
+
+    /// Requests a collection, replacing any request which has not yet been carried out.
+    pub fn request(&self, request: GcRequest) {
+        self.request.set(Some(request));
+        self.finished_cycle.set(false);
+    }
+
+    /// Takes the pending request, if there is one, to carry it out.
+    pub fn take_request(&self) -> Option<GcRequest> {
+        self.request.take()
+    }
+
+    /// Whether the last requested step finished a collection cycle.
+    pub fn finished_cycle(&self) -> bool {
+        self.finished_cycle.get()
+    }
+
+    pub fn set_finished_cycle(&self, finished_cycle: bool) {
+        self.finished_cycle.set(finished_cycle);
+    }
+}
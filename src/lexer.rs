@@ -116,6 +116,18 @@ impl fmt::Display for LexerError {
     }
 }
 
+/// A line of source code, 0-indexed like `Lexer::line_number`, but displayed 1-indexed as Lua line
+/// numbers are.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Collect)]
+#[collect(require_static)]
+pub struct LineNumber(pub u64);
+
+impl fmt::Display for LineNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0 + 1)
+    }
+}
+
 pub struct Lexer<R, CS> {
     source: Option<R>,
     create_string: CS,
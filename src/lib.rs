@@ -4,16 +4,21 @@ mod closure;
 mod compiler;
 mod constant;
 mod error;
+mod finalizers;
+mod format;
+mod gc_control;
 pub mod io;
 mod lexer;
 #[macro_use]
 mod lua;
+mod meta_ops;
 mod opcode;
 pub mod parser;
 mod string;
 mod table;
 mod thread;
 mod types;
+mod userdata;
 mod value;
 
 mod stdlib;
@@ -22,19 +27,26 @@ pub use callback::{Callback, CallbackResult, CallbackReturn, Continuation};
 pub use closure::{
     Closure, ClosureError, ClosureState, FunctionProto, UpValue, UpValueDescriptor, UpValueState,
 };
-pub use compiler::{compile, compile_chunk, CompilerError};
+pub use compiler::{compile, compile_chunk, compile_named, CompilerError};
 pub use constant::Constant;
-pub use error::{Error, RuntimeError, StaticError, TypeError};
-pub use lexer::{Lexer, LexerError, Token};
+pub use error::{
+    Error, ExitError, LocatedError, RuntimeError, StaticError, StaticLocatedError, TypeError,
+};
+pub use finalizers::{default_warn_handler, Finalizers, RunFinalizers, WarnHandler};
+pub use gc_control::{GcControl, GcRequest};
+pub use lexer::{Lexer, LexerError, LineNumber, Token};
 pub use lua::{Lua, Root};
 pub use opcode::OpCode;
-pub use parser::{parse_chunk, ParserError};
-pub use string::{InternedStringSet, String, StringError};
-pub use table::{InvalidTableKey, Table, TableState};
+pub use parser::{parse_chunk, parse_chunk_located, ParserError};
+pub use stdlib::{OsHost, StdOsHost};
+pub use string::{InternedStringSet, String, StringError, MAX_SHORT_LEN};
+pub use table::{InvalidNextKey, InvalidTableKey, Table, TableIter, TableState};
 pub use thread::{
-    BadThreadMode, BinaryOperatorError, Thread, ThreadError, ThreadMode, ThreadSequence,
+    ActiveThreads, BadThreadMode, BinaryOperatorError, Thread, ThreadError, ThreadMode,
+    ThreadSequence,
 };
 pub use types::{
     ConstantIndex16, ConstantIndex8, Opt254, PrototypeIndex, RegisterIndex, UpValueIndex, VarCount,
 };
+pub use userdata::{UserData, UserDataState, UserDataType};
 pub use value::{Function, Value};
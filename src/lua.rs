@@ -1,9 +1,16 @@
+use std::rc::Rc;
+
 use gc_arena::{ArenaParameters, Collect, MutationContext};
-use gc_sequence::{make_sequencable_arena, Sequence};
+use gc_sequence::{make_sequencable_arena, Sequence, SequenceExt};
 
 use crate::{
-    stdlib::{load_base, load_coroutine, load_math},
-    InternedStringSet, Table, Thread,
+    default_warn_handler,
+    stdlib::{
+        load_base, load_coroutine, load_io, load_math, load_os, load_package, load_string,
+        load_table, load_utf8,
+    },
+    ActiveThreads, Callback, ExitError, Finalizers, GcControl, GcRequest, InternedStringSet,
+    OsHost, RunFinalizers, StdOsHost, String, Table, Thread, WarnHandler,
 };
 
 #[derive(Collect, Clone, Copy)]
@@ -12,22 +19,86 @@ pub struct Root<'gc> {
     pub main_thread: Thread<'gc>,
     pub globals: Table<'gc>,
     pub interned_strings: InternedStringSet<'gc>,
+    /// Loaders for modules provided by Rust, keyed by module name, which `require` searches after
+    /// `package.preload`.
+    pub modules: Table<'gc>,
+    /// The metatable shared by all strings, whose `__index` is the `string` library.
+    pub string_metatable: Table<'gc>,
+    /// Tables and userdata marked for finalization by their `__gc` metamethod.
+    pub finalizers: Finalizers<'gc>,
+    /// The threads currently being stepped, shared by the main thread and all threads created with
+    /// `Root::new_thread`.
+    pub active_threads: ActiveThreads<'gc>,
 }
 
 impl<'gc> Root<'gc> {
     pub fn new(mc: MutationContext<'gc, '_>) -> Root<'gc> {
+        Root::with_os_host(mc, Rc::new(StdOsHost::new()))
+    }
+
+    /// Creates a new root whose `os` library goes through the given host.
+    pub fn with_os_host(mc: MutationContext<'gc, '_>, os_host: Rc<dyn OsHost>) -> Root<'gc> {
+        Root::with_gc_control(mc, os_host, Rc::new(GcControl::new()))
+    }
+
+    /// Creates a new root whose `os` library goes through the given host, and whose
+    /// `collectgarbage` makes its requests through the given `GcControl`.
+    pub fn with_gc_control(
+        mc: MutationContext<'gc, '_>,
+        os_host: Rc<dyn OsHost>,
+        gc_control: Rc<GcControl>,
+    ) -> Root<'gc> {
+        let string_metatable = Table::new(mc);
+        let interned_strings = InternedStringSet::new(mc);
+        let active_threads = ActiveThreads::new(mc);
+        let main_thread = Thread::new(mc, false);
+        main_thread.set_string_metatable(mc, Some(string_metatable));
+        main_thread.set_interned_strings(mc, Some(interned_strings));
+        main_thread.set_active_threads(mc, Some(active_threads));
         let root = Root {
-            main_thread: Thread::new(mc, false),
+            main_thread,
             globals: Table::new(mc),
-            interned_strings: InternedStringSet::new(mc),
+            interned_strings,
+            modules: Table::new(mc),
+            string_metatable,
+            finalizers: Finalizers::new(mc),
+            active_threads,
         };
 
-        load_base(mc, root, root.globals);
+        load_base(mc, root, root.globals, gc_control);
         load_coroutine(mc, root, root.globals);
+        load_io(mc, root, root.globals);
         load_math(mc, root, root.globals);
+        load_os(mc, root, root.globals, os_host.clone());
+        load_string(mc, root, root.globals);
+        load_table(mc, root, root.globals);
+        load_utf8(mc, root, root.globals);
+        load_package(mc, root, root.globals, &*os_host);
 
         root
     }
+
+    /// Creates a new thread which shares this root's string metatable, interned strings and active
+    /// threads.
+    pub fn new_thread(&self, mc: MutationContext<'gc, '_>, allow_yield: bool) -> Thread<'gc> {
+        let thread = Thread::new(mc, allow_yield);
+        thread.set_string_metatable(mc, Some(self.string_metatable));
+        thread.set_interned_strings(mc, Some(self.interned_strings));
+        thread.set_active_threads(mc, Some(self.active_threads));
+        thread
+    }
+
+    /// Registers a Rust module which `require` can find under the given name.  As with any other
+    /// loader, `loader` is called with the module name and its first return value becomes the
+    /// value of the module.
+    pub fn register_module(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        name: &[u8],
+        loader: Callback<'gc>,
+    ) {
+        self.modules.set(mc, String::new(mc, name), loader).unwrap();
+    }
 }
 
 make_sequencable_arena!(pub lua_arena, Root);
@@ -35,16 +106,58 @@ make_sequencable_arena!(pub lua_arena, Root);
 pub use lua_arena::Arena;
 pub use lua_arena::Sequencer;
 
-/// Simpler wrapper for `Arena` that automatically garbage collects at reasonable intervals.
-pub struct Lua(Option<lua_arena::Arena>);
+/// Simpler wrapper for `Arena` that automatically garbage collects at reasonable intervals, and
+/// calls `__gc` metamethods in between the steps of sequences.
+pub struct Lua {
+    arena: Option<lua_arena::Arena>,
+    warn: WarnHandler,
+    gc_control: Rc<GcControl>,
+}
 
 const COLLECTOR_GRANULARITY: f64 = 1024.0;
 
+// Carries out any collection requested by `collectgarbage` on an `Arena` or `Sequencer`, and
+// otherwise collects whenever enough allocation debt has built up while the collector is running.
+macro_rules! collect_garbage {
+    ($gc_control:expr, $arena:expr) => {
+        match $gc_control.take_request() {
+            Some(GcRequest::Collect) => $arena.collect_full(),
+            Some(GcRequest::Step(work)) => {
+                let finished = $arena.collect_step(work.max(COLLECTOR_GRANULARITY));
+                $gc_control.set_finished_cycle(finished);
+            }
+            None => {
+                if $gc_control.is_running() && $arena.allocation_debt() > COLLECTOR_GRANULARITY {
+                    $arena.collect_debt();
+                }
+            }
+        }
+    };
+}
+
 impl Lua {
     pub fn new() -> Lua {
-        Lua(Some(Arena::new(ArenaParameters::default(), |mc| {
-            Root::new(mc)
-        })))
+        Lua::with_os_host(StdOsHost::new())
+    }
+
+    /// Creates a new `Lua` whose `os` library goes through the given host rather than `std`.
+    pub fn with_os_host<H: OsHost + 'static>(os_host: H) -> Lua {
+        let os_host: Rc<dyn OsHost> = Rc::new(os_host);
+        let gc_control = Rc::new(GcControl::new());
+        let root_gc_control = gc_control.clone();
+        Lua {
+            arena: Some(Arena::new(ArenaParameters::default(), move |mc| {
+                Root::with_gc_control(mc, os_host, root_gc_control)
+            })),
+            warn: default_warn_handler(),
+            gc_control,
+        }
+    }
+
+    /// Sets the handler given errors raised by `__gc` metamethods, which by default are printed to
+    /// stderr.
+    pub fn set_warn_handler<F: Fn(&str) + 'static>(&mut self, handler: F) {
+        self.warn = Rc::new(handler);
     }
 
     /// Runs a single action inside the Lua arena, during which no garbage collection may take place.
@@ -53,33 +166,36 @@ impl Lua {
         R: 'static,
         F: for<'gc> FnOnce(MutationContext<'gc, '_>, Root<'gc>) -> R,
     {
-        let arena = self.0.as_mut().unwrap();
+        let arena = self.arena.as_mut().unwrap();
         let r = arena.mutate(move |mc, root| f(mc, *root));
-        if arena.allocation_debt() > COLLECTOR_GRANULARITY {
-            arena.collect_debt();
-        }
+        collect_garbage!(self.gc_control, arena);
         r
     }
 
     /// Runs a sequence of actions inside the Lua arena and return the result.  Garbage collection
-    /// may take place in-between sequence steps.
-    pub fn sequence<F, R>(&mut self, f: F) -> R
+    /// may take place in-between sequence steps.  If a `__gc` metamethod calls `os.exit`, the
+    /// sequence is abandoned and its `ExitError` is returned instead.
+    pub fn sequence<F, R, E>(&mut self, f: F) -> Result<R, E>
     where
         R: 'static,
-        F: for<'gc> FnOnce(Root<'gc>) -> Box<dyn Sequence<'gc, Output = R> + 'gc>,
+        E: From<ExitError> + 'static,
+        F: for<'gc> FnOnce(Root<'gc>) -> Box<dyn Sequence<'gc, Output = Result<R, E>> + 'gc>,
     {
-        let mut sequencer = self.0.take().unwrap().sequence(move |root| f(*root));
+        let warn = self.warn.clone();
+        let mut sequencer = self
+            .arena
+            .take()
+            .unwrap()
+            .sequence(move |root| RunFinalizers::new(*root, warn, f(*root)).boxed());
         loop {
             match sequencer.step() {
                 Ok((arena, output)) => {
-                    self.0 = Some(arena);
+                    self.arena = Some(arena);
                     return output;
                 }
                 Err(s) => {
                     sequencer = s;
-                    if sequencer.allocation_debt() > COLLECTOR_GRANULARITY {
-                        sequencer.collect_debt();
-                    }
+                    collect_garbage!(self.gc_control, sequencer);
                 }
             }
         }
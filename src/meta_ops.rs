@@ -0,0 +1,432 @@
+use gc_arena::MutationContext;
+
+use crate::{
+    BinaryOperatorError, Error, Function, InternedStringSet, RuntimeError, String, StringError,
+    Table, ThreadError, TypeError, Value,
+};
+
+// The maximum length of a chain of `__index` or `__newindex` tables before giving up, to guard
+// against loops.
+const MAX_META_CHAIN: usize = 2000;
+
+/// The metatable fields which have a special meaning to Lua.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MetaMethod {
+    Index,
+    NewIndex,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Unm,
+    IDiv,
+    BAnd,
+    BOr,
+    BXor,
+    Shl,
+    Shr,
+    BNot,
+    Concat,
+    Len,
+    Eq,
+    Lt,
+    Le,
+    Call,
+    ToString,
+    Name,
+    Pairs,
+    Metatable,
+    Gc,
+    Mode,
+}
+
+impl MetaMethod {
+    pub fn name(self) -> &'static [u8] {
+        match self {
+            MetaMethod::Index => b"__index",
+            MetaMethod::NewIndex => b"__newindex",
+            MetaMethod::Add => b"__add",
+            MetaMethod::Sub => b"__sub",
+            MetaMethod::Mul => b"__mul",
+            MetaMethod::Div => b"__div",
+            MetaMethod::Mod => b"__mod",
+            MetaMethod::Pow => b"__pow",
+            MetaMethod::Unm => b"__unm",
+            MetaMethod::IDiv => b"__idiv",
+            MetaMethod::BAnd => b"__band",
+            MetaMethod::BOr => b"__bor",
+            MetaMethod::BXor => b"__bxor",
+            MetaMethod::Shl => b"__shl",
+            MetaMethod::Shr => b"__shr",
+            MetaMethod::BNot => b"__bnot",
+            MetaMethod::Concat => b"__concat",
+            MetaMethod::Len => b"__len",
+            MetaMethod::Eq => b"__eq",
+            MetaMethod::Lt => b"__lt",
+            MetaMethod::Le => b"__le",
+            MetaMethod::Call => b"__call",
+            MetaMethod::ToString => b"__tostring",
+            MetaMethod::Name => b"__name",
+            MetaMethod::Pairs => b"__pairs",
+            MetaMethod::Metatable => b"__metatable",
+            MetaMethod::Gc => b"__gc",
+            MetaMethod::Mode => b"__mode",
+        }
+    }
+}
+
+/// A metamethod which must be called to complete an operation.
+pub struct MetaCall<'gc> {
+    pub function: Function<'gc>,
+    pub args: Vec<Value<'gc>>,
+}
+
+/// The result of an operation which may require calling a metamethod.
+pub enum MetaResult<'gc> {
+    Value(Value<'gc>),
+    Call(MetaCall<'gc>),
+}
+
+/// Returns the metatable for any value which can have one.
+pub fn metatable<'gc>(value: Value<'gc>) -> Option<Table<'gc>> {
+    match value {
+        Value::Table(t) => t.metatable(),
+        Value::UserData(u) => u.metatable(),
+        _ => None,
+    }
+}
+
+/// Returns the given metamethod from a value's metatable, or nil if there is none.
+pub fn get_metamethod<'gc>(value: Value<'gc>, method: MetaMethod) -> Value<'gc> {
+    match metatable(value) {
+        Some(mt) => mt.get(String::new_static(method.name())),
+        None => Value::Nil,
+    }
+}
+
+/// Performs `table[key]`, following `__index` metamethods.  Strings are indexed through the given
+/// string metatable.
+pub fn index<'gc>(
+    string_metatable: Option<Table<'gc>>,
+    table: Value<'gc>,
+    key: Value<'gc>,
+) -> Result<MetaResult<'gc>, Error<'gc>> {
+    let mut table = table;
+    for _ in 0..MAX_META_CHAIN {
+        let handler = match table {
+            Value::Table(t) => {
+                let value = t.get(key);
+                if value != Value::Nil {
+                    return Ok(MetaResult::Value(value));
+                }
+
+                get_metamethod(table, MetaMethod::Index)
+            }
+            Value::String(_) => match string_metatable {
+                Some(mt) => mt.get(String::new_static(MetaMethod::Index.name())),
+                None => Value::Nil,
+            },
+            _ => get_metamethod(table, MetaMethod::Index),
+        };
+
+        match handler {
+            Value::Nil => {
+                return match table {
+                    Value::Table(_) => Ok(MetaResult::Value(Value::Nil)),
+                    _ => Err(TypeError {
+                        expected: "table",
+                        found: table.type_name(),
+                    }
+                    .into()),
+                };
+            }
+            Value::Function(function) => {
+                return Ok(MetaResult::Call(MetaCall {
+                    function,
+                    args: vec![table, key],
+                }));
+            }
+            handler => table = handler,
+        }
+    }
+
+    Err(RuntimeError(Value::String(String::new_static(
+        b"'__index' chain too long; possible loop",
+    )))
+    .into())
+}
+
+/// Performs `table[key] = value`, following `__newindex` metamethods.  Returns the metamethod
+/// call which must be made to complete the assignment, if any.
+pub fn new_index<'gc>(
+    mc: MutationContext<'gc, '_>,
+    table: Value<'gc>,
+    key: Value<'gc>,
+    value: Value<'gc>,
+) -> Result<Option<MetaCall<'gc>>, Error<'gc>> {
+    let mut table = table;
+    for _ in 0..MAX_META_CHAIN {
+        let handler = match table {
+            Value::Table(t) => {
+                let handler = if t.get(key) != Value::Nil {
+                    Value::Nil
+                } else {
+                    get_metamethod(table, MetaMethod::NewIndex)
+                };
+
+                if handler == Value::Nil {
+                    t.set(mc, key, value)?;
+                    return Ok(None);
+                }
+                handler
+            }
+            _ => match get_metamethod(table, MetaMethod::NewIndex) {
+                Value::Nil => {
+                    return Err(TypeError {
+                        expected: "table",
+                        found: table.type_name(),
+                    }
+                    .into());
+                }
+                handler => handler,
+            },
+        };
+
+        match handler {
+            Value::Function(function) => {
+                return Ok(Some(MetaCall {
+                    function,
+                    args: vec![table, key, value],
+                }));
+            }
+            handler => table = handler,
+        }
+    }
+
+    Err(RuntimeError(Value::String(String::new_static(
+        b"'__newindex' chain too long; possible loop",
+    )))
+    .into())
+}
+
+/// Finds the metamethod for an arithmetic or bitwise operation whose operands could not be used
+/// directly, trying the left operand's metatable and then the right's.  Unary operations pass
+/// their operand as both `left` and `right`, as Lua does.
+pub fn arithmetic<'gc>(
+    method: MetaMethod,
+    left: Value<'gc>,
+    right: Value<'gc>,
+) -> Result<MetaCall<'gc>, Error<'gc>> {
+    match binary_handler(method, left, right) {
+        Value::Nil => Err(match method {
+            MetaMethod::Add => BinaryOperatorError::Add,
+            MetaMethod::Sub => BinaryOperatorError::Subtract,
+            MetaMethod::Mul => BinaryOperatorError::Multiply,
+            MetaMethod::Div => BinaryOperatorError::FloatDivide,
+            MetaMethod::Mod => BinaryOperatorError::Modulo,
+            MetaMethod::Pow => BinaryOperatorError::Exponentiate,
+            MetaMethod::Unm => BinaryOperatorError::UnaryNegate,
+            MetaMethod::IDiv => BinaryOperatorError::FloorDivide,
+            MetaMethod::BAnd => BinaryOperatorError::BitAnd,
+            MetaMethod::BOr => BinaryOperatorError::BitOr,
+            MetaMethod::BXor => BinaryOperatorError::BitXor,
+            MetaMethod::Shl => BinaryOperatorError::ShiftLeft,
+            MetaMethod::Shr => BinaryOperatorError::ShiftRight,
+            MetaMethod::BNot => BinaryOperatorError::BitNot,
+            method => panic!("{:?} is not an arithmetic metamethod", method),
+        }
+        .into()),
+        handler => handler_call(handler, vec![left, right]),
+    }
+}
+
+/// Performs `#value`.  Strings always return their length in bytes, other values use their
+/// `__len` metamethod if they have one, and tables otherwise return their border.
+pub fn length<'gc>(value: Value<'gc>) -> Result<MetaResult<'gc>, Error<'gc>> {
+    if let Value::String(s) = value {
+        return Ok(MetaResult::Value(Value::Integer(s.as_bytes().len() as i64)));
+    }
+
+    match get_metamethod(value, MetaMethod::Len) {
+        Value::Nil => match value {
+            Value::Table(t) => Ok(MetaResult::Value(Value::Integer(t.length()))),
+            _ => Err(TypeError {
+                expected: "table or string",
+                found: value.type_name(),
+            }
+            .into()),
+        },
+        handler => Ok(MetaResult::Call(handler_call(handler, vec![value, value])?)),
+    }
+}
+
+/// The result of a comparison which may require calling a metamethod.
+pub enum CompareResult<'gc> {
+    Value(bool),
+    /// The comparison's result is the truthiness of the metamethod's first result, inverted if
+    /// `invert` is true.
+    Call {
+        call: MetaCall<'gc>,
+        invert: bool,
+    },
+}
+
+/// Performs `left == right`.  The `__eq` metamethod is only consulted when both values are tables
+/// or both are userdata, and they are not already the same object.
+pub fn equal<'gc>(left: Value<'gc>, right: Value<'gc>) -> Result<CompareResult<'gc>, Error<'gc>> {
+    match (left, right) {
+        (Value::Table(_), Value::Table(_)) | (Value::UserData(_), Value::UserData(_))
+            if left != right =>
+        {
+            match binary_handler(MetaMethod::Eq, left, right) {
+                Value::Nil => Ok(CompareResult::Value(false)),
+                handler => Ok(CompareResult::Call {
+                    call: handler_call(handler, vec![left, right])?,
+                    invert: false,
+                }),
+            }
+        }
+        _ => Ok(CompareResult::Value(left == right)),
+    }
+}
+
+/// Performs `left < right`, calling the `__lt` metamethod for values which are not both numbers
+/// or both strings.
+pub fn less_than<'gc>(
+    left: Value<'gc>,
+    right: Value<'gc>,
+) -> Result<CompareResult<'gc>, Error<'gc>> {
+    if let Some(less) = left.less_than(right) {
+        return Ok(CompareResult::Value(less));
+    }
+
+    match binary_handler(MetaMethod::Lt, left, right) {
+        Value::Nil => Err(BinaryOperatorError::LessThan.into()),
+        handler => Ok(CompareResult::Call {
+            call: handler_call(handler, vec![left, right])?,
+            invert: false,
+        }),
+    }
+}
+
+/// Performs `left <= right`, calling the `__le` metamethod for values which are not both numbers
+/// or both strings.  As in Lua 5.3, if there is no `__le` metamethod this falls back to
+/// `not (right < left)` using `__lt`.
+pub fn less_equal<'gc>(
+    left: Value<'gc>,
+    right: Value<'gc>,
+) -> Result<CompareResult<'gc>, Error<'gc>> {
+    if let Some(less_equal) = left.less_equal(right) {
+        return Ok(CompareResult::Value(less_equal));
+    }
+
+    match binary_handler(MetaMethod::Le, left, right) {
+        Value::Nil => match binary_handler(MetaMethod::Lt, right, left) {
+            Value::Nil => Err(BinaryOperatorError::LessEqual.into()),
+            handler => Ok(CompareResult::Call {
+                call: handler_call(handler, vec![right, left])?,
+                invert: true,
+            }),
+        },
+        handler => Ok(CompareResult::Call {
+            call: handler_call(handler, vec![left, right])?,
+            invert: false,
+        }),
+    }
+}
+
+/// Returns the `__call` metamethod of a value which is not itself a function.
+pub fn call<'gc>(value: Value<'gc>) -> Result<Function<'gc>, TypeError> {
+    match get_metamethod(value, MetaMethod::Call) {
+        Value::Function(function) => Ok(function),
+        _ => Err(TypeError {
+            expected: "function",
+            found: value.type_name(),
+        }),
+    }
+}
+
+/// The result of a concatenation which may require calling `__concat` metamethods.
+pub enum ConcatResult<'gc> {
+    Value(Value<'gc>),
+    /// A metamethod must be called, after which the concatenation continues with the given number
+    /// of leading values, the last of which must be replaced by the metamethod's result.  If only
+    /// one value remains, the metamethod's result is the result of the concatenation.
+    Call(MetaCall<'gc>, usize),
+}
+
+/// Concatenates values from right to left as Lua does, collapsing them in place.  Runs of strings
+/// and numbers are joined all at once, and the first pair of values which cannot be joined
+/// directly produces a call to their `__concat` metamethod.
+pub fn concat<'gc>(
+    mc: MutationContext<'gc, '_>,
+    interned_strings: Option<InternedStringSet<'gc>>,
+    values: &mut [Value<'gc>],
+) -> Result<ConcatResult<'gc>, Error<'gc>> {
+    fn is_concatable(value: Value) -> bool {
+        match value {
+            Value::String(_) | Value::Integer(_) | Value::Number(_) => true,
+            _ => false,
+        }
+    }
+
+    let mut top = values.len();
+    while top > 1 {
+        let left = values[top - 2];
+        let right = values[top - 1];
+        if is_concatable(left) && is_concatable(right) {
+            let mut start = top - 2;
+            while start > 0 && is_concatable(values[start - 1]) {
+                start -= 1;
+            }
+            let joined = &values[start..top];
+            values[start] = Value::String(match interned_strings {
+                Some(interned_strings) => interned_strings.concat(mc, joined)?,
+                None => String::concat(mc, joined)?,
+            });
+            top = start + 1;
+        } else {
+            return match binary_handler(MetaMethod::Concat, left, right) {
+                Value::Nil => Err(StringError::Concat {
+                    bad_type: if is_concatable(left) {
+                        right.type_name()
+                    } else {
+                        left.type_name()
+                    },
+                }
+                .into()),
+                handler => Ok(ConcatResult::Call(
+                    handler_call(handler, vec![left, right])?,
+                    top - 1,
+                )),
+            };
+        }
+    }
+
+    Ok(ConcatResult::Value(values[0]))
+}
+
+// Looks up a metamethod for a binary operation in the left operand and then the right.
+fn binary_handler<'gc>(method: MetaMethod, left: Value<'gc>, right: Value<'gc>) -> Value<'gc> {
+    match get_metamethod(left, method) {
+        Value::Nil => get_metamethod(right, method),
+        handler => handler,
+    }
+}
+
+// Calls a metamethod handler, which may itself be callable through `__call`.
+fn handler_call<'gc>(
+    handler: Value<'gc>,
+    mut args: Vec<Value<'gc>>,
+) -> Result<MetaCall<'gc>, Error<'gc>> {
+    match handler {
+        Value::Function(function) => Ok(MetaCall { function, args }),
+        handler => {
+            let function = call(handler).map_err(ThreadError::BadCall)?;
+            args.insert(0, handler);
+            Ok(MetaCall { function, args })
+        }
+    }
+}
@@ -5,17 +5,18 @@ use std::rc::Rc;
 
 use gc_arena::Collect;
 
-use crate::{Lexer, LexerError, Token};
+use crate::{Lexer, LexerError, LineNumber, Token};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Chunk<S> {
     pub block: Block<S>,
 }
 
+/// Each statement is paired with the line that it starts on.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Block<S> {
-    pub statements: Vec<Statement<S>>,
-    pub return_statement: Option<ReturnStatement<S>>,
+    pub statements: Vec<(LineNumber, Statement<S>)>,
+    pub return_statement: Option<(LineNumber, ReturnStatement<S>)>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -141,13 +142,13 @@ pub enum UnaryOperator {
 #[derive(Debug, PartialEq, Clone)]
 pub struct Expression<S> {
     pub head: Box<HeadExpression<S>>,
-    pub tail: Vec<(BinaryOperator, Expression<S>)>,
+    pub tail: Vec<(LineNumber, BinaryOperator, Expression<S>)>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum HeadExpression<S> {
     Simple(SimpleExpression<S>),
-    UnaryOperator(UnaryOperator, Expression<S>),
+    UnaryOperator(LineNumber, UnaryOperator, Expression<S>),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -190,6 +191,7 @@ pub enum SuffixPart<S> {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct SuffixedExpression<S> {
+    pub line_number: LineNumber,
     pub primary: PrimaryExpression<S>,
     pub suffixes: Vec<SuffixPart<S>>,
 }
@@ -292,17 +294,37 @@ where
     S: fmt::Debug + PartialEq,
     CS: FnMut(&[u8]) -> S,
 {
-    Parser {
+    parse_chunk_located(source, create_string).map_err(|(error, _)| error)
+}
+
+/// Like `parse_chunk`, but errors are returned along with the line on which they were found.
+pub fn parse_chunk_located<R, S, CS>(
+    source: R,
+    create_string: CS,
+) -> Result<Chunk<S>, (ParserError, LineNumber)>
+where
+    R: Read,
+    S: fmt::Debug + PartialEq,
+    CS: FnMut(&[u8]) -> S,
+{
+    let mut parser = Parser {
         lexer: Lexer::new(source, create_string),
         read_buffer: Vec::new(),
         recursion_guard: Rc::new(()),
-    }
-    .parse_chunk()
+    };
+    parser.parse_chunk().map_err(|error| {
+        let line_number = match parser.read_buffer.get(0) {
+            Some((_, line_number)) => *line_number,
+            None => LineNumber(parser.lexer.line_number()),
+        };
+        (error, line_number)
+    })
 }
 
 struct Parser<R, S, CS> {
     lexer: Lexer<R, CS>,
-    read_buffer: Vec<Token<S>>,
+    // Tokens which have been read ahead, along with the line each token starts on
+    read_buffer: Vec<(Token<S>, LineNumber)>,
     recursion_guard: Rc<()>,
 }
 
@@ -333,12 +355,14 @@ where
                     self.take_next()?;
                 }
                 Some(&Token::Return) => {
-                    return_statement = Some(self.parse_return_statement()?);
+                    let line_number = self.line_number()?;
+                    return_statement = Some((line_number, self.parse_return_statement()?));
                     break;
                 }
                 None => break,
                 _ => {
-                    statements.push(self.parse_statement()?);
+                    let line_number = self.line_number()?;
+                    statements.push((line_number, self.parse_statement()?));
                 }
             }
         }
@@ -648,8 +672,13 @@ where
         let _recursion_guard = self.recursion_guard()?;
 
         let head = if let Some(unary_op) = get_unary_operator(self.get_next()?) {
+            let line_number = self.line_number()?;
             self.take_next()?;
-            HeadExpression::UnaryOperator(unary_op, self.parse_sub_expression(UNARY_PRIORITY)?)
+            HeadExpression::UnaryOperator(
+                line_number,
+                unary_op,
+                self.parse_sub_expression(UNARY_PRIORITY)?,
+            )
         } else {
             HeadExpression::Simple(self.parse_simple_expression()?)
         };
@@ -661,9 +690,10 @@ where
                 break;
             }
 
+            let line_number = self.line_number()?;
             self.take_next()?;
             let right_expression = self.parse_sub_expression(right_priority)?;
-            tail.push((binary_op, right_expression));
+            tail.push((line_number, binary_op, right_expression));
         }
 
         Ok(Expression {
@@ -803,6 +833,7 @@ where
     }
 
     fn parse_suffixed_expression(&mut self) -> Result<SuffixedExpression<S>, ParserError> {
+        let line_number = self.line_number()?;
         let primary = self.parse_primary_expression()?;
         let mut suffixes = Vec::new();
         loop {
@@ -819,7 +850,11 @@ where
             }
         }
 
-        Ok(SuffixedExpression { primary, suffixes })
+        Ok(SuffixedExpression {
+            line_number,
+            primary,
+            suffixes,
+        })
     }
 
     fn parse_function_definition(&mut self) -> Result<FunctionDefinition<S>, ParserError> {
@@ -914,10 +949,19 @@ where
         }
     }
 
+    // Return the line that the next token starts on, or the current line if we are at the end.
+    fn line_number(&mut self) -> Result<LineNumber, ParserError> {
+        self.read_ahead(1)?;
+        Ok(match self.read_buffer.get(0) {
+            Some((_, line_number)) => *line_number,
+            None => LineNumber(self.lexer.line_number()),
+        })
+    }
+
     // Return a reference to the next token in the stream, erroring if we are at the end.
     fn get_next(&mut self) -> Result<&Token<S>, ParserError> {
         self.read_ahead(1)?;
-        if let Some(token) = self.read_buffer.get(0) {
+        if let Some((token, _)) = self.read_buffer.get(0) {
             Ok(token)
         } else {
             Err(ParserError::EndOfStream { expected: None })
@@ -932,7 +976,7 @@ where
                 expected: Some(format!("{:?}", token)),
             })
         } else {
-            let next_token = self.read_buffer.remove(0);
+            let (next_token, _) = self.read_buffer.remove(0);
             if next_token == token {
                 Ok(())
             } else {
@@ -952,7 +996,7 @@ where
                 expected: Some("name".to_owned()),
             })
         } else {
-            match self.read_buffer.remove(0) {
+            match self.read_buffer.remove(0).0 {
                 Token::Name(name) => Ok(name),
                 token => Err(ParserError::Unexpected {
                     unexpected: format!("{:?}", token),
@@ -970,7 +1014,7 @@ where
                 expected: Some("string".to_owned()),
             })
         } else {
-            match self.read_buffer.remove(0) {
+            match self.read_buffer.remove(0).0 {
                 Token::String(string) => Ok(string),
                 token => Err(ParserError::Unexpected {
                     unexpected: format!("{:?}", token),
@@ -986,21 +1030,21 @@ where
         if self.read_buffer.is_empty() {
             Err(ParserError::EndOfStream { expected: None })
         } else {
-            Ok(self.read_buffer.remove(0))
+            Ok(self.read_buffer.remove(0).0)
         }
     }
 
     // Return the nth token ahead in the stream, if it is not past the end.
     fn look_ahead(&mut self, n: usize) -> Result<Option<&Token<S>>, ParserError> {
         self.read_ahead(n + 1)?;
-        Ok(self.read_buffer.get(n))
+        Ok(self.read_buffer.get(n).map(|(token, _)| token))
     }
 
     // Return true if the nth token ahead in the stream matches the given token.  If this would read
     // past the end of the stream, this will simply return false.
     fn check_ahead(&mut self, n: usize, token: Token<S>) -> Result<bool, ParserError> {
         self.read_ahead(n)?;
-        Ok(if let Some(t) = self.read_buffer.get(n) {
+        Ok(if let Some((t, _)) = self.read_buffer.get(n) {
             *t == token
         } else {
             false
@@ -1011,8 +1055,12 @@ where
     // possible).
     fn read_ahead(&mut self, n: usize) -> Result<(), ParserError> {
         while self.read_buffer.len() <= n {
+            self.lexer
+                .skip_whitespace()
+                .map_err(ParserError::LexerError)?;
+            let line_number = LineNumber(self.lexer.line_number());
             if let Some(token) = self.lexer.read_token().map_err(ParserError::LexerError)? {
-                self.read_buffer.push(token);
+                self.read_buffer.push((token, line_number));
             } else {
                 break;
             }
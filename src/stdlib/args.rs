@@ -0,0 +1,59 @@
+use std::borrow::Cow;
+
+use crate::{Error, Table, TypeError, Value};
+
+// Library functions accept numbers wherever a string is expected, converting them the same way that
+// concatenation does.
+pub fn string_arg<'gc, 'a>(args: &'a [Value<'gc>], i: usize) -> Result<Cow<'a, [u8]>, Error<'gc>> {
+    match args.get(i) {
+        Some(Value::String(s)) => Ok(Cow::Borrowed(s.as_bytes())),
+        Some(v @ Value::Integer(_)) | Some(v @ Value::Number(_)) => {
+            let mut buf = Vec::new();
+            v.display(&mut buf)?;
+            Ok(Cow::Owned(buf))
+        }
+        v => Err(TypeError {
+            expected: "string",
+            found: v.cloned().unwrap_or(Value::Nil).type_name(),
+        }
+        .into()),
+    }
+}
+
+// Returns `None` if the argument is missing or nil, and an error if it is present but cannot be
+// represented as an integer.
+pub fn integer_arg<'gc>(args: &[Value<'gc>], i: usize) -> Result<Option<i64>, Error<'gc>> {
+    match args.get(i).cloned().unwrap_or(Value::Nil) {
+        Value::Nil => Ok(None),
+        v => match v.to_integer() {
+            Some(i) => Ok(Some(i)),
+            None => Err(TypeError {
+                expected: "integer",
+                found: v.type_name(),
+            }
+            .into()),
+        },
+    }
+}
+
+pub fn required_integer_arg<'gc>(args: &[Value<'gc>], i: usize) -> Result<i64, Error<'gc>> {
+    match integer_arg(args, i)? {
+        Some(i) => Ok(i),
+        None => Err(TypeError {
+            expected: "integer",
+            found: "nil",
+        }
+        .into()),
+    }
+}
+
+pub fn table_arg<'gc>(args: &[Value<'gc>], i: usize) -> Result<Table<'gc>, Error<'gc>> {
+    match args.get(i).cloned().unwrap_or(Value::Nil) {
+        Value::Table(t) => Ok(t),
+        v => Err(TypeError {
+            expected: "table",
+            found: v.type_name(),
+        }
+        .into()),
+    }
+}
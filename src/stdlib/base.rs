@@ -1,27 +1,53 @@
-use std::io::{self, Write};
+use std::fs::File;
+use std::io::{self, Read, Write};
+#[cfg(unix)]
+use std::path::Path;
+#[cfg(not(unix))]
+use std::path::PathBuf;
+use std::rc::Rc;
 
-use gc_arena::MutationContext;
-use gc_sequence as sequence;
+use gc_arena::{ArenaParameters, Collect, MutationContext};
+use gc_sequence::{self as sequence, SequenceExt};
 
 use crate::{
-    Callback, CallbackResult, Continuation, Root, RuntimeError, String, Table, TypeError, Value,
+    closure::chunk_id,
+    compile_named,
+    io::buffered_read,
+    lexer::{read_float, read_hex_float},
+    meta_ops::{self, MetaMethod, MetaResult},
+    Callback, CallbackResult, Closure, Continuation, Error, Function, GcControl, GcRequest,
+    InternedStringSet, Root, RuntimeError, String, Table, TypeError, Value,
 };
 
-pub fn load_base<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<'gc>) {
+use super::args::table_arg;
+
+// Precompiled chunks start with this byte, and cannot be loaded.
+const BINARY_CHUNK_SIGNATURE: u8 = 0x1b;
+
+pub fn load_base<'gc>(
+    mc: MutationContext<'gc, '_>,
+    root: Root<'gc>,
+    env: Table<'gc>,
+    gc_control: Rc<GcControl>,
+) {
     env.set(
         mc,
         String::new_static(b"print"),
-        Callback::new_immediate(mc, |args| {
-            let mut stdout = io::stdout();
-            for i in 0..args.len() {
-                args[i].display(&mut stdout)?;
-                if i != args.len() - 1 {
-                    stdout.write_all(&b"\t"[..])?;
-                }
-            }
-            stdout.write_all(&b"\n"[..])?;
-            stdout.flush()?;
-            Ok(CallbackResult::Return(vec![]))
+        Callback::new_sequence(mc, |args| {
+            Ok(sequence::from_fn_with(args, |mc, args| {
+                to_strings(mc, args, Vec::new(), (), |_, (), strings| {
+                    let mut stdout = io::stdout();
+                    for i in 0..strings.len() {
+                        stdout.write_all(strings[i].as_bytes())?;
+                        if i != strings.len() - 1 {
+                            stdout.write_all(&b"\t"[..])?;
+                        }
+                    }
+                    stdout.write_all(&b"\n"[..])?;
+                    stdout.flush()?;
+                    Ok(CallbackResult::Return(vec![]))
+                })
+            }))
         }),
     )
     .unwrap();
@@ -55,27 +81,55 @@ pub fn load_base<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<
             Ok(CallbackResult::TailCall {
                 function,
                 args,
-                continuation: Continuation::new_sequence_with(
-                    *interned_strings,
-                    move |interned_strings, res| {
-                        Ok(sequence::from_fn_with(
-                            (res, interned_strings),
-                            |mc, (res, interned_strings)| {
-                                Ok(CallbackResult::Return(match res {
-                                    Ok(mut res) => {
-                                        res.insert(0, Value::Boolean(true));
-                                        res
-                                    }
-                                    Err(err) => vec![
-                                        Value::Boolean(false),
-                                        err.to_value(mc, interned_strings),
-                                    ],
-                                }))
-                            },
-                        ))
-                    },
-                ),
+                continuation: protected_call_continuation(*interned_strings),
+            })
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"xpcall"),
+        Callback::new_immediate_with(mc, root.interned_strings, |interned_strings, mut args| {
+            let (function, handler) = match (
+                args.get(0).cloned().unwrap_or(Value::Nil),
+                args.get(1).cloned().unwrap_or(Value::Nil),
+            ) {
+                (Value::Function(function), Value::Function(handler)) => (function, handler),
+                (Value::Function(_), value) | (value, _) => {
+                    return Err(TypeError {
+                        expected: "function",
+                        found: value.type_name(),
+                    }
+                    .into());
+                }
+            };
+
+            args.drain(0..2);
+            Ok(CallbackResult::TailCallWithHandler {
+                function,
+                args,
+                handler,
+                continuation: protected_call_continuation(*interned_strings),
+            })
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"assert"),
+        Callback::new_immediate(mc, |mut args| {
+            if args.get(0).cloned().unwrap_or(Value::Nil).to_bool() {
+                return Ok(CallbackResult::Return(args));
+            }
+            // The message may be any value, and is raised as it is.
+            Err(RuntimeError(if args.len() > 1 {
+                args.swap_remove(1)
+            } else {
+                Value::String(String::new_static(b"assertion failed!"))
             })
+            .into())
         }),
     )
     .unwrap();
@@ -97,6 +151,183 @@ pub fn load_base<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<
     )
     .unwrap();
 
+    env.set(
+        mc,
+        String::new_static(b"tostring"),
+        Callback::new_sequence(mc, |args| {
+            let value = match args.get(0) {
+                Some(&value) => value,
+                None => {
+                    return Err(RuntimeError(Value::String(String::new_static(
+                        b"Missing argument to tostring",
+                    )))
+                    .into());
+                }
+            };
+
+            Ok(sequence::from_fn_with(value, |mc, value| {
+                to_strings(mc, vec![value], Vec::new(), (), |_, (), strings| {
+                    Ok(CallbackResult::Return(vec![strings[0].into()]))
+                })
+            }))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"tonumber"),
+        Callback::new_immediate(mc, |args| {
+            let value = match args.get(0) {
+                Some(&value) => value,
+                None => {
+                    return Err(RuntimeError(Value::String(String::new_static(
+                        b"Missing argument to tonumber",
+                    )))
+                    .into());
+                }
+            };
+
+            let number = match args.get(1).cloned().unwrap_or(Value::Nil) {
+                Value::Nil => match value {
+                    Value::Integer(_) | Value::Number(_) => Some(value),
+                    Value::String(s) => string_to_number(&s),
+                    _ => None,
+                },
+                base => {
+                    let base = match base.to_integer() {
+                        Some(base) => base,
+                        None => {
+                            return Err(TypeError {
+                                expected: "integer",
+                                found: base.type_name(),
+                            }
+                            .into());
+                        }
+                    };
+                    let s = match value {
+                        Value::String(s) => s,
+                        value => {
+                            return Err(TypeError {
+                                expected: "string",
+                                found: value.type_name(),
+                            }
+                            .into());
+                        }
+                    };
+                    if base < 2 || base > 36 {
+                        return Err(RuntimeError(Value::String(String::new_static(
+                            b"base out of range",
+                        )))
+                        .into());
+                    }
+                    string_to_integer(&s, base as u32).map(Value::Integer)
+                }
+            };
+
+            Ok(CallbackResult::Return(vec![number.unwrap_or(Value::Nil)]))
+        }),
+    )
+    .unwrap();
+
+    let next = Callback::new_immediate(mc, |args| {
+        let table = table_arg(&args, 0)?;
+        Ok(CallbackResult::Return(
+            match table.next(args.get(1).cloned().unwrap_or(Value::Nil))? {
+                Some((key, value)) => vec![key, value],
+                None => vec![Value::Nil],
+            },
+        ))
+    });
+    env.set(mc, String::new_static(b"next"), next).unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"pairs"),
+        Callback::new_immediate_with(mc, next, |&next, args| {
+            let value = args.get(0).cloned().unwrap_or(Value::Nil);
+            match meta_ops::get_metamethod(value, MetaMethod::Pairs) {
+                Value::Nil => {
+                    let table = table_arg(&args, 0)?;
+                    Ok(CallbackResult::Return(vec![
+                        next.into(),
+                        table.into(),
+                        Value::Nil,
+                    ]))
+                }
+                Value::Function(function) => Ok(CallbackResult::TailCall {
+                    function,
+                    args: vec![value],
+                    continuation: Continuation::new_immediate(|res| {
+                        let mut res = res?;
+                        res.resize(3, Value::Nil);
+                        Ok(CallbackResult::Return(res))
+                    }),
+                }),
+                handler => Err(TypeError {
+                    expected: "function",
+                    found: handler.type_name(),
+                }
+                .into()),
+            }
+        }),
+    )
+    .unwrap();
+
+    let ipairs_iter =
+        Callback::new_immediate_with(mc, root.string_metatable, |&string_metatable, args| {
+            let value = args.get(0).cloned().unwrap_or(Value::Nil);
+            let index = match args.get(1).cloned().unwrap_or(Value::Nil).to_integer() {
+                Some(index) => index.wrapping_add(1),
+                None => {
+                    return Err(TypeError {
+                        expected: "integer",
+                        found: args.get(1).cloned().unwrap_or(Value::Nil).type_name(),
+                    }
+                    .into());
+                }
+            };
+
+            fn ipairs_result<'gc>(index: i64, value: Value<'gc>) -> Vec<Value<'gc>> {
+                match value {
+                    Value::Nil => vec![Value::Nil],
+                    value => vec![Value::Integer(index), value],
+                }
+            }
+
+            match meta_ops::index(Some(string_metatable), value, Value::Integer(index))? {
+                MetaResult::Value(value) => Ok(CallbackResult::Return(ipairs_result(index, value))),
+                MetaResult::Call(call) => Ok(CallbackResult::TailCall {
+                    function: call.function,
+                    args: call.args,
+                    continuation: Continuation::new_immediate(move |res| {
+                        let value = res?.into_iter().next().unwrap_or(Value::Nil);
+                        Ok(CallbackResult::Return(ipairs_result(index, value)))
+                    }),
+                }),
+            }
+        });
+    env.set(
+        mc,
+        String::new_static(b"ipairs"),
+        Callback::new_immediate_with(mc, ipairs_iter, |&ipairs_iter, args| {
+            let value = args.get(0).cloned().unwrap_or(Value::Nil);
+            if value == Value::Nil {
+                return Err(TypeError {
+                    expected: "table",
+                    found: value.type_name(),
+                }
+                .into());
+            }
+            Ok(CallbackResult::Return(vec![
+                ipairs_iter.into(),
+                value,
+                Value::Integer(0),
+            ]))
+        }),
+    )
+    .unwrap();
+
     env.set(
         mc,
         String::new_static(b"select"),
@@ -116,4 +347,734 @@ pub fn load_base<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<
         }),
     )
     .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"getmetatable"),
+        Callback::new_immediate_with(mc, root.string_metatable, |&string_metatable, args| {
+            let metatable = match args.get(0).cloned().unwrap_or(Value::Nil) {
+                Value::String(_) => Some(string_metatable),
+                value => meta_ops::metatable(value),
+            };
+            Ok(CallbackResult::Return(vec![match metatable {
+                Some(mt) => match mt.get(String::new_static(MetaMethod::Metatable.name())) {
+                    Value::Nil => mt.into(),
+                    protected => protected,
+                },
+                None => Value::Nil,
+            }]))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"setmetatable"),
+        Callback::new_sequence_with(mc, root, |root, args| {
+            let table = table_arg(&args, 0)?;
+            let metatable = match args.get(1).cloned().unwrap_or(Value::Nil) {
+                Value::Nil => None,
+                Value::Table(mt) => Some(mt),
+                value => {
+                    return Err(TypeError {
+                        expected: "nil or table",
+                        found: value.type_name(),
+                    }
+                    .into());
+                }
+            };
+            if let Some(current) = table.metatable() {
+                if current.get(String::new_static(MetaMethod::Metatable.name())) != Value::Nil {
+                    return Err(RuntimeError(Value::String(String::new_static(
+                        b"cannot change a protected metatable",
+                    )))
+                    .into());
+                }
+            }
+
+            Ok(sequence::from_fn_with(
+                (*root, table, metatable),
+                |mc, (root, table, metatable)| {
+                    table.set_metatable(mc, metatable);
+                    root.finalizers.register_table(table);
+                    Ok(CallbackResult::Return(vec![table.into()]))
+                },
+            ))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"rawget"),
+        Callback::new_immediate(mc, |args| {
+            let table = table_arg(&args, 0)?;
+            Ok(CallbackResult::Return(vec![
+                table.get(args.get(1).cloned().unwrap_or(Value::Nil))
+            ]))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"rawset"),
+        Callback::new_sequence(mc, |args| {
+            let table = table_arg(&args, 0)?;
+            let key = args.get(1).cloned().unwrap_or(Value::Nil);
+            let value = args.get(2).cloned().unwrap_or(Value::Nil);
+            Ok(sequence::from_fn_with(
+                (table, key, value),
+                |mc, (table, key, value)| {
+                    table.set(mc, key, value)?;
+                    Ok(CallbackResult::Return(vec![table.into()]))
+                },
+            ))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"rawequal"),
+        Callback::new_immediate(mc, |args| {
+            let a = args.get(0).cloned().unwrap_or(Value::Nil);
+            let b = args.get(1).cloned().unwrap_or(Value::Nil);
+            Ok(CallbackResult::Return(vec![Value::Boolean(a == b)]))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"rawlen"),
+        Callback::new_immediate(mc, |args| {
+            let length = match args.get(0).cloned().unwrap_or(Value::Nil) {
+                Value::Table(table) => table.length(),
+                Value::String(string) => string.as_bytes().len() as i64,
+                value => {
+                    return Err(TypeError {
+                        expected: "table or string",
+                        found: value.type_name(),
+                    }
+                    .into());
+                }
+            };
+            Ok(CallbackResult::Return(vec![Value::Integer(length)]))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"load"),
+        Callback::new_sequence_with(mc, root, |&root, args| {
+            let chunk = args.get(0).cloned().unwrap_or(Value::Nil);
+            let chunk_name = match (args.get(1).cloned().unwrap_or(Value::Nil), chunk) {
+                (Value::String(name), _) => name.as_bytes().to_vec(),
+                (Value::Nil, Value::String(source)) => source.as_bytes().to_vec(),
+                (Value::Nil, Value::Function(_)) => b"=(load)".to_vec(),
+                (_, Value::String(_)) | (_, Value::Function(_)) => {
+                    return Err(TypeError {
+                        expected: "string",
+                        found: args[1].type_name(),
+                    }
+                    .into());
+                }
+                (_, chunk) => {
+                    return Err(TypeError {
+                        expected: "string or function",
+                        found: chunk.type_name(),
+                    }
+                    .into());
+                }
+            };
+            let load = LoadState {
+                root,
+                chunk_name,
+                mode: mode_arg(&args, 2)?,
+                env: env_arg(root, &args, 3)?,
+                source: Vec::new(),
+            };
+
+            Ok(sequence::from_fn_with(
+                (chunk, load),
+                |mc, (chunk, mut load)| match chunk {
+                    Value::Function(reader) => Ok(read_chunk(reader, load)),
+                    Value::String(source) => {
+                        load.source.extend_from_slice(source.as_bytes());
+                        Ok(finish_load(mc, load))
+                    }
+                    _ => unreachable!(),
+                },
+            ))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"loadfile"),
+        Callback::new_sequence_with(mc, root, |&root, args| {
+            let file_name = optional_file_name(&args, 0)?;
+            let load = LoadState {
+                root,
+                chunk_name: Vec::new(),
+                mode: mode_arg(&args, 1)?,
+                env: env_arg(root, &args, 2)?,
+                source: Vec::new(),
+            };
+
+            Ok(sequence::from_fn_with(
+                (file_name, load),
+                |mc, (file_name, mut load)| match read_file(file_name.as_ref()) {
+                    Ok((chunk_name, source)) => {
+                        load.chunk_name = chunk_name;
+                        load.source = source;
+                        Ok(finish_load(mc, load))
+                    }
+                    Err(message) => Ok(CallbackResult::Return(vec![
+                        Value::Nil,
+                        Value::String(String::new(mc, &message)),
+                    ])),
+                },
+            ))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"dofile"),
+        Callback::new_sequence_with(mc, root, |&root, args| {
+            let file_name = optional_file_name(&args, 0)?;
+
+            Ok(sequence::from_fn_with(
+                (root, file_name),
+                |mc, (root, file_name)| {
+                    let closure = read_file(file_name.as_ref()).and_then(|(chunk_name, source)| {
+                        load_chunk(mc, root, &source, &chunk_name, b"bt", root.globals)
+                    });
+                    match closure {
+                        Ok(closure) => Ok(CallbackResult::TailCall {
+                            function: Function::Closure(closure),
+                            args: Vec::new(),
+                            continuation: Continuation::new_immediate(|res| {
+                                Ok(CallbackResult::Return(res?))
+                            }),
+                        }),
+                        Err(message) => {
+                            Err(RuntimeError(Value::String(String::new(mc, &message))).into())
+                        }
+                    }
+                },
+            ))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"collectgarbage"),
+        Callback::new_sequence(mc, move |args| {
+            let option = gc_option_arg(&args, 0)?;
+            let arg = optional_number_arg(&args, 1)?;
+            let gc_control = gc_control.clone();
+
+            match option.as_slice() {
+                b"collect" | b"step" => {
+                    let step = option == b"step";
+                    gc_control.request(if step {
+                        GcRequest::Step(arg.unwrap_or(0.0) * 1024.0)
+                    } else {
+                        GcRequest::Collect
+                    });
+                    // The request is carried out by the driver in between these steps.
+                    Ok(sequence::from_fn(|_| ())
+                        .then(move |_, ()| {
+                            Ok(CallbackResult::Return(vec![if step {
+                                Value::Boolean(gc_control.finished_cycle())
+                            } else {
+                                Value::Integer(0)
+                            }]))
+                        })
+                        .boxed())
+                }
+                _ => {
+                    let stepmul = optional_number_arg(&args, 2)?;
+                    Ok(sequence::from_fn(move |mc| {
+                        let parameters = mc.parameters();
+                        let ret = match option.as_slice() {
+                            b"count" => Value::Number(mc.total_allocated() as f64 / 1024.0),
+                            b"stop" => {
+                                gc_control.set_running(false);
+                                Value::Integer(0)
+                            }
+                            b"restart" => {
+                                gc_control.set_running(true);
+                                Value::Integer(0)
+                            }
+                            b"isrunning" => Value::Boolean(gc_control.is_running()),
+                            b"incremental" => {
+                                let mut parameters = parameters;
+                                if let Some(pause) = arg.filter(|&p| p != 0.0) {
+                                    parameters = set_gc_pause(parameters, pause);
+                                }
+                                if let Some(stepmul) = stepmul.filter(|&s| s != 0.0) {
+                                    parameters = set_gc_stepmul(parameters, stepmul);
+                                }
+                                mc.set_parameters(parameters);
+                                Value::String(String::new_static(b"incremental"))
+                            }
+                            b"setpause" => {
+                                mc.set_parameters(set_gc_pause(parameters, arg.unwrap_or(0.0)));
+                                Value::Integer(gc_pause(parameters))
+                            }
+                            b"setstepmul" => {
+                                mc.set_parameters(set_gc_stepmul(parameters, arg.unwrap_or(0.0)));
+                                Value::Integer(gc_stepmul(parameters))
+                            }
+                            _ => {
+                                let mut msg =
+                                    b"bad argument #1 to 'collectgarbage' (invalid option '"
+                                        .to_vec();
+                                msg.extend_from_slice(&option);
+                                msg.extend_from_slice(b"')");
+                                return Err(
+                                    RuntimeError(Value::String(String::new(mc, &msg))).into()
+                                );
+                            }
+                        };
+                        Ok(CallbackResult::Return(vec![ret]))
+                    })
+                    .boxed())
+                }
+            }
+        }),
+    )
+    .unwrap();
+}
+
+// Returns the results of a protected call, prefixed by whether the call succeeded.
+fn protected_call_continuation<'gc>(interned_strings: InternedStringSet<'gc>) -> Continuation<'gc> {
+    Continuation::new_sequence_with(interned_strings, move |interned_strings, res| {
+        // `os.exit` unwinds through protected calls.
+        if let Err(Error::ExitError(err)) = res {
+            return Err(err.into());
+        }
+        Ok(sequence::from_fn_with(
+            (res, interned_strings),
+            |mc, (res, interned_strings)| {
+                Ok(CallbackResult::Return(match res {
+                    Ok(mut res) => {
+                        res.insert(0, Value::Boolean(true));
+                        res
+                    }
+                    Err(err) => vec![Value::Boolean(false), err.to_value(mc, interned_strings)],
+                }))
+            },
+        ))
+    })
+}
+
+// The arguments to `load` or `loadfile`, along with the source of the chunk read so far.
+#[derive(Collect)]
+#[collect(empty_drop)]
+struct LoadState<'gc> {
+    root: Root<'gc>,
+    chunk_name: Vec<u8>,
+    mode: Vec<u8>,
+    env: Table<'gc>,
+    source: Vec<u8>,
+}
+
+// Calls the reader function given to `load` for the next piece of the chunk, until it returns nil
+// or an empty string.  As in PUC-Rio Lua, errors raised by the reader are returned by `load` rather
+// than propagated.
+fn read_chunk<'gc>(reader: Function<'gc>, load: LoadState<'gc>) -> CallbackResult<'gc> {
+    CallbackResult::TailCall {
+        function: reader,
+        args: Vec::new(),
+        continuation: Continuation::new_sequence_with((reader, load), |(reader, load), res| {
+            // `os.exit` unwinds through `load`.
+            if let Err(Error::ExitError(err)) = res {
+                return Err(err.into());
+            }
+            Ok(sequence::from_fn_with(
+                (reader, load, res),
+                |mc, (reader, mut load, res)| {
+                    let piece = match res {
+                        Ok(res) => res.get(0).cloned().unwrap_or(Value::Nil),
+                        Err(err) => {
+                            return Ok(CallbackResult::Return(vec![
+                                Value::Nil,
+                                err.to_value(mc, load.root.interned_strings),
+                            ]));
+                        }
+                    };
+                    match piece {
+                        Value::Nil => Ok(finish_load(mc, load)),
+                        Value::String(s) if s.as_bytes().is_empty() => Ok(finish_load(mc, load)),
+                        Value::String(s) => {
+                            load.source.extend_from_slice(s.as_bytes());
+                            Ok(read_chunk(reader, load))
+                        }
+                        _ => Ok(CallbackResult::Return(vec![
+                            Value::Nil,
+                            Value::String(String::new_static(
+                                b"reader function must return a string",
+                            )),
+                        ])),
+                    }
+                },
+            ))
+        }),
+    }
+}
+
+// Compiles the chunk which has been read, returning either the loaded function or nil and an error
+// message.
+fn finish_load<'gc>(mc: MutationContext<'gc, '_>, load: LoadState<'gc>) -> CallbackResult<'gc> {
+    CallbackResult::Return(
+        match load_chunk(
+            mc,
+            load.root,
+            &load.source,
+            &load.chunk_name,
+            &load.mode,
+            load.env,
+        ) {
+            Ok(closure) => vec![Value::Function(Function::Closure(closure))],
+            Err(message) => vec![Value::Nil, Value::String(String::new(mc, &message))],
+        },
+    )
+}
+
+// Compiles a chunk into a function with the given environment, checking that it is of a type
+// allowed by `mode`.  Errors are returned as messages prefixed with the chunk's name, as they are
+// given to Lua code rather than raised.
+fn load_chunk<'gc>(
+    mc: MutationContext<'gc, '_>,
+    root: Root<'gc>,
+    source: &[u8],
+    chunk_name: &[u8],
+    mode: &[u8],
+    env: Table<'gc>,
+) -> Result<Closure<'gc>, Vec<u8>> {
+    let (kind, allowed) = if source.first() == Some(&BINARY_CHUNK_SIGNATURE) {
+        ("binary", mode.contains(&b'b'))
+    } else {
+        ("text", mode.contains(&b't'))
+    };
+    if !allowed {
+        let mut message = format!("attempt to load a {} chunk (mode is '", kind).into_bytes();
+        message.extend_from_slice(mode);
+        message.extend_from_slice(b"')");
+        return Err(message);
+    } else if kind == "binary" {
+        return Err(b"binary chunks are not supported".to_vec());
+    }
+
+    compile_named(mc, root.interned_strings, chunk_name, source)
+        .and_then(|proto| Ok(Closure::new(mc, proto, Some(env))?))
+        .map_err(|err| match err {
+            // Parser errors already carry the chunk name and line.
+            Error::LocatedError(_) => err.to_string().into_bytes(),
+            err => {
+                let mut message = chunk_id(chunk_name);
+                message.extend_from_slice(b": ");
+                message.extend_from_slice(err.to_string().as_bytes());
+                message
+            }
+        })
+}
+
+// Reads the file given to `loadfile` or `dofile`, or stdin if no file is given, returning the name
+// of the chunk along with its source.  Any leading UTF-8 BOM or unix shebang is skipped.
+fn read_file(file_name: Option<&Vec<u8>>) -> Result<(Vec<u8>, Vec<u8>), Vec<u8>> {
+    let mut source = Vec::new();
+    let (chunk_name, read) = match file_name {
+        Some(file_name) => {
+            let mut chunk_name = b"@".to_vec();
+            chunk_name.extend_from_slice(file_name);
+            let read = File::open(bytes_to_path(file_name))
+                .and_then(buffered_read)
+                .and_then(|mut file| file.read_to_end(&mut source));
+            (chunk_name, read)
+        }
+        None => {
+            let stdin = io::stdin();
+            let read = buffered_read(stdin.lock()).and_then(|mut r| r.read_to_end(&mut source));
+            (b"=stdin".to_vec(), read)
+        }
+    };
+
+    match read {
+        Ok(_) => Ok((chunk_name, source)),
+        Err(err) => {
+            let mut message = b"cannot open ".to_vec();
+            message.extend_from_slice(&chunk_name[1..]);
+            message.extend_from_slice(format!(": {}", err).as_bytes());
+            Err(message)
+        }
+    }
+}
+
+fn gc_option_arg<'gc>(args: &[Value<'gc>], i: usize) -> Result<Vec<u8>, Error<'gc>> {
+    match args.get(i).cloned().unwrap_or(Value::Nil) {
+        Value::Nil => Ok(b"collect".to_vec()),
+        Value::String(option) => Ok(option.as_bytes().to_vec()),
+        value => Err(TypeError {
+            expected: "string",
+            found: value.type_name(),
+        }
+        .into()),
+    }
+}
+
+fn optional_number_arg<'gc>(args: &[Value<'gc>], i: usize) -> Result<Option<f64>, Error<'gc>> {
+    match args.get(i).cloned().unwrap_or(Value::Nil) {
+        Value::Nil => Ok(None),
+        value => match value.to_number() {
+            Some(n) => Ok(Some(n)),
+            None => Err(TypeError {
+                expected: "number",
+                found: value.type_name(),
+            }
+            .into()),
+        },
+    }
+}
+
+// The collector pause is given as a percentage like in PUC-Rio Lua, where 200 waits for the memory
+// in use to double before starting a new cycle.  The arena's `pause_factor` is the fraction of the
+// memory remaining after a cycle that must be allocated before the next one, so 200 is a factor of
+// 1.0.
+fn gc_pause(parameters: ArenaParameters) -> i64 {
+    ((1.0 + parameters.pause_factor()) * 100.0).round() as i64
+}
+
+fn set_gc_pause(parameters: ArenaParameters, pause: f64) -> ArenaParameters {
+    parameters.set_pause_factor((pause / 100.0 - 1.0).max(0.0))
+}
+
+// The step multiplier is also a percentage, of the collection work done for each byte allocated
+// beyond that byte itself.  This is the inverse of the arena's `timing_factor`, and a multiplier of
+// 0 never does extra work.
+fn gc_stepmul(parameters: ArenaParameters) -> i64 {
+    (100.0 / parameters.timing_factor()).round() as i64
+}
+
+fn set_gc_stepmul(parameters: ArenaParameters, stepmul: f64) -> ArenaParameters {
+    parameters.set_timing_factor(100.0 / stepmul.max(0.0))
+}
+
+fn mode_arg<'gc>(args: &[Value<'gc>], i: usize) -> Result<Vec<u8>, Error<'gc>> {
+    match args.get(i).cloned().unwrap_or(Value::Nil) {
+        Value::Nil => Ok(b"bt".to_vec()),
+        Value::String(mode) => Ok(mode.as_bytes().to_vec()),
+        value => Err(TypeError {
+            expected: "string",
+            found: value.type_name(),
+        }
+        .into()),
+    }
+}
+
+// Chunks are given the global environment unless another table is given.
+fn env_arg<'gc>(root: Root<'gc>, args: &[Value<'gc>], i: usize) -> Result<Table<'gc>, Error<'gc>> {
+    match args.get(i).cloned().unwrap_or(Value::Nil) {
+        Value::Nil => Ok(root.globals),
+        Value::Table(env) => Ok(env),
+        value => Err(TypeError {
+            expected: "table",
+            found: value.type_name(),
+        }
+        .into()),
+    }
+}
+
+fn optional_file_name<'gc>(args: &[Value<'gc>], i: usize) -> Result<Option<Vec<u8>>, Error<'gc>> {
+    match args.get(i).cloned().unwrap_or(Value::Nil) {
+        Value::Nil => Ok(None),
+        Value::String(file_name) => Ok(Some(file_name.as_bytes().to_vec())),
+        value => Err(TypeError {
+            expected: "string",
+            found: value.type_name(),
+        }
+        .into()),
+    }
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> &Path {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    OsStr::from_bytes(bytes).as_ref()
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    std::string::String::from_utf8_lossy(bytes)
+        .into_owned()
+        .into()
+}
+
+// Converts each value to a string as `tostring` does, calling `__tostring` metamethods in order,
+// and then passes `state` and the strings to `finish`.  `strings` holds the values which have
+// already been converted.
+pub(super) fn to_strings<'gc, S, F>(
+    mc: MutationContext<'gc, '_>,
+    values: Vec<Value<'gc>>,
+    mut strings: Vec<String<'gc>>,
+    state: S,
+    finish: F,
+) -> Result<CallbackResult<'gc>, Error<'gc>>
+where
+    S: 'gc + Collect,
+    F: 'static
+        + for<'a> FnOnce(
+            MutationContext<'gc, 'a>,
+            S,
+            Vec<String<'gc>>,
+        ) -> Result<CallbackResult<'gc>, Error<'gc>>,
+{
+    while strings.len() < values.len() {
+        let value = values[strings.len()];
+        match meta_ops::get_metamethod(value, MetaMethod::ToString) {
+            Value::Nil => strings.push(display_string(mc, value)?),
+            Value::Function(function) => {
+                return Ok(CallbackResult::TailCall {
+                    function,
+                    args: vec![value],
+                    continuation: Continuation::new_sequence_with(
+                        (values, strings, state),
+                        move |(values, strings, state), res| {
+                            let res = res?;
+                            Ok(sequence::from_fn_with(
+                                (values, strings, state, res),
+                                move |mc, (values, mut strings, state, res)| {
+                                    match res.get(0).cloned().unwrap_or(Value::Nil) {
+                                        Value::String(s) => strings.push(s),
+                                        v @ Value::Integer(_) | v @ Value::Number(_) => {
+                                            strings.push(display_string(mc, v)?)
+                                        }
+                                        _ => {
+                                            return Err(RuntimeError(Value::String(
+                                                String::new_static(
+                                                    b"'__tostring' must return a string",
+                                                ),
+                                            ))
+                                            .into());
+                                        }
+                                    }
+                                    to_strings(mc, values, strings, state, finish)
+                                },
+                            ))
+                        },
+                    ),
+                });
+            }
+            handler => {
+                return Err(TypeError {
+                    expected: "function",
+                    found: handler.type_name(),
+                }
+                .into());
+            }
+        }
+    }
+
+    finish(mc, state, strings)
+}
+
+fn display_string<'gc>(
+    mc: MutationContext<'gc, '_>,
+    value: Value<'gc>,
+) -> Result<String<'gc>, Error<'gc>> {
+    match value {
+        Value::String(s) => Ok(s),
+        value => {
+            let mut buf = Vec::new();
+            value.display(&mut buf)?;
+            Ok(String::new(mc, &buf))
+        }
+    }
+}
+
+// Converts a string to a number the way Lua numerals are read, allowing surrounding whitespace.
+// Integers which do not fit in an `i64` are converted to floats, except for hexadecimal integers,
+// which wrap around.
+fn string_to_number<'gc>(s: &[u8]) -> Option<Value<'gc>> {
+    let s = trim(s);
+    let (negative, digits) = match s.first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    // Rust accepts "inf" and "nan" as floats, but Lua does not.
+    if digits.iter().any(|&c| c == b'n' || c == b'N') {
+        return None;
+    }
+
+    let hex = digits.len() >= 2 && digits[0] == b'0' && (digits[1] == b'x' || digits[1] == b'X');
+    if hex {
+        let hex_digits = &digits[2..];
+        if !hex_digits.is_empty() && hex_digits.iter().all(u8::is_ascii_hexdigit) {
+            let i = hex_digits.iter().fold(0i64, |i, &c| {
+                i.wrapping_mul(16)
+                    .wrapping_add((c as char).to_digit(16).unwrap() as i64)
+            });
+            return Some(Value::Integer(if negative { i.wrapping_neg() } else { i }));
+        }
+        read_hex_float(s).map(Value::Number)
+    } else {
+        if !digits.is_empty() && digits.iter().all(u8::is_ascii_digit) {
+            let max = i64::max_value() as u64 + negative as u64;
+            let i = digits.iter().try_fold(0u64, |i, &c| {
+                i.checked_mul(10)?
+                    .checked_add((c - b'0') as u64)
+                    .filter(|&i| i <= max)
+            });
+            if let Some(i) = i {
+                return Some(Value::Integer(if negative {
+                    (i as i64).wrapping_neg()
+                } else {
+                    i as i64
+                }));
+            }
+        }
+        // Other than "inf" and "nan", Rust accepts the same decimal floats as `strtod`.
+        read_float(s).map(Value::Number)
+    }
+}
+
+// Converts a string holding an integer in the given base to an integer, allowing surrounding
+// whitespace and a leading '-'.  Overflow wraps around.
+fn string_to_integer(s: &[u8], base: u32) -> Option<i64> {
+    let s = trim(s);
+    let (negative, digits) = match s.first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+
+    let mut i: i64 = 0;
+    for &c in digits {
+        let d = (c as char).to_digit(base)?;
+        i = i.wrapping_mul(base as i64).wrapping_add(d as i64);
+    }
+    Some(if negative { i.wrapping_neg() } else { i })
+}
+
+fn trim(s: &[u8]) -> &[u8] {
+    let is_space = |c: &u8| c.is_ascii_whitespace() || *c == 0x0b;
+    let start = s.iter().position(|c| !is_space(c)).unwrap_or(s.len());
+    let end = s
+        .iter()
+        .rposition(|c| !is_space(c))
+        .map_or(start, |i| i + 1);
+    &s[start..end]
 }
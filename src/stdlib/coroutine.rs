@@ -2,8 +2,8 @@ use gc_arena::MutationContext;
 use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
 
 use crate::{
-    Callback, CallbackResult, Root, RuntimeError, String, Table, Thread, ThreadMode,
-    ThreadSequence, TypeError, Value,
+    BadThreadMode, Callback, CallbackResult, Error, Function, Root, RuntimeError, String, Table,
+    Thread, ThreadMode, ThreadSequence, TypeError, Value,
 };
 
 pub fn load_coroutine<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<'gc>) {
@@ -13,23 +13,16 @@ pub fn load_coroutine<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: T
         .set(
             mc,
             String::new_static(b"create"),
-            Callback::new_sequence(mc, |args| {
-                let function = match args.get(0).cloned().unwrap_or(Value::Nil) {
-                    Value::Function(function) => function,
-                    value => {
-                        return Err(TypeError {
-                            expected: "function",
-                            found: value.type_name(),
-                        }
-                        .into());
-                    }
-                };
-
-                Ok(sequence::from_fn_with(function, |mc, function| {
-                    let thread = Thread::new(mc, true);
-                    thread.start_suspended(mc, function).unwrap();
-                    Ok(CallbackResult::Return(vec![Value::Thread(thread)]))
-                }))
+            Callback::new_sequence_with(mc, root, |&root, args| {
+                let function = function_arg(&args, 0)?;
+                Ok(sequence::from_fn_with(
+                    (root, function),
+                    |mc, (root, function)| {
+                        let thread = root.new_thread(mc, true);
+                        thread.start_suspended(mc, function).unwrap();
+                        Ok(CallbackResult::Return(vec![Value::Thread(thread)]))
+                    },
+                ))
             }),
         )
         .unwrap();
@@ -39,17 +32,7 @@ pub fn load_coroutine<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: T
             mc,
             String::new_static(b"resume"),
             Callback::new_sequence_with(mc, root.interned_strings, |interned_strings, mut args| {
-                let thread = match args.get(0).cloned().unwrap_or(Value::Nil) {
-                    Value::Thread(closure) => closure,
-                    value => {
-                        return Err(TypeError {
-                            expected: "thread",
-                            found: value.type_name(),
-                        }
-                        .into());
-                    }
-                };
-
+                let thread = thread_arg(&args, 0)?;
                 args.remove(0);
                 Ok(
                     sequence::from_fn_with((thread, args), |mc, (thread, args)| {
@@ -71,6 +54,8 @@ pub fn load_coroutine<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: T
                                     res.insert(0, Value::Boolean(true));
                                     res
                                 }
+                                // `os.exit` unwinds through every running coroutine.
+                                Err(Error::ExitError(err)) => return Err(err.into()),
                                 Err(err) => {
                                     vec![Value::Boolean(false), err.to_value(mc, interned_strings)]
                                 }
@@ -82,35 +67,133 @@ pub fn load_coroutine<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: T
         )
         .unwrap();
 
+    coroutine
+        .set(
+            mc,
+            String::new_static(b"wrap"),
+            Callback::new_sequence_with(mc, root, |&root, args| {
+                let function = function_arg(&args, 0)?;
+                Ok(sequence::from_fn_with(
+                    (root, function),
+                    |mc, (root, function)| {
+                        let thread = root.new_thread(mc, true);
+                        thread.start_suspended(mc, function).unwrap();
+                        Ok(CallbackResult::Return(vec![Callback::new_sequence_with(
+                            mc,
+                            (root, thread),
+                            |&(root, thread), args| {
+                                // Unlike `resume`, errors raised by the coroutine are propagated
+                                // to the caller, with string errors prefixed by the caller's
+                                // position.
+                                Ok(
+                                    sequence::from_fn_with((thread, args), |mc, (thread, args)| {
+                                        match thread.resume(mc, &args) {
+                                            Ok(()) => Ok(ThreadSequence(thread)),
+                                            Err(BadThreadMode { found, .. }) => Err(RuntimeError(
+                                                Value::String(String::new_static(match found {
+                                                    ThreadMode::Stopped | ThreadMode::Results => {
+                                                        b"cannot resume dead coroutine"
+                                                    }
+                                                    _ => b"cannot resume non-suspended coroutine",
+                                                })),
+                                            )
+                                            .into()),
+                                        }
+                                    })
+                                    .flatten_ok()
+                                    .map_with(
+                                        root,
+                                        |root, res| match res {
+                                            Ok(res) => Ok(CallbackResult::Return(res)),
+                                            Err(err) => Err(current_thread(root).locate_error(err)),
+                                        },
+                                    ),
+                                )
+                            },
+                        )
+                        .into()]))
+                    },
+                ))
+            }),
+        )
+        .unwrap();
+
     coroutine
         .set(
             mc,
             String::new_static(b"status"),
             Callback::new_immediate(mc, |args| {
-                let thread = match args.get(0).cloned().unwrap_or(Value::Nil) {
-                    Value::Thread(closure) => closure,
-                    value => {
-                        return Err(TypeError {
-                            expected: "thread",
-                            found: value.type_name(),
-                        }
-                        .into());
-                    }
-                };
-
+                let thread = thread_arg(&args, 0)?;
                 Ok(CallbackResult::Return(vec![Value::String(
-                    // TODO: When the current thread is available again for callbacks, whether or
-                    // not the active thread matches will determine 'normal' from 'running'.
                     String::new_static(match thread.mode() {
                         ThreadMode::Stopped | ThreadMode::Results => b"dead",
                         ThreadMode::Running => b"running",
                         ThreadMode::Suspended => b"suspended",
+                        ThreadMode::Normal => b"normal",
                     }),
                 )]))
             }),
         )
         .unwrap();
 
+    coroutine
+        .set(
+            mc,
+            String::new_static(b"running"),
+            Callback::new_immediate_with(mc, root, |root, _| {
+                let thread = current_thread(*root);
+                Ok(CallbackResult::Return(vec![
+                    Value::Thread(thread),
+                    Value::Boolean(thread == root.main_thread),
+                ]))
+            }),
+        )
+        .unwrap();
+
+    coroutine
+        .set(
+            mc,
+            String::new_static(b"isyieldable"),
+            Callback::new_sequence_with(mc, root, |&root, args| {
+                let thread = match args.get(0).cloned().unwrap_or(Value::Nil) {
+                    Value::Nil => current_thread(root),
+                    _ => thread_arg(&args, 0)?,
+                };
+                // The running thread cannot be read until this callback is stepped.
+                Ok(sequence::from_fn_with(thread, |_, thread| {
+                    Ok(CallbackResult::Return(vec![Value::Boolean(
+                        thread.allow_yield(),
+                    )]))
+                }))
+            }),
+        )
+        .unwrap();
+
+    coroutine
+        .set(
+            mc,
+            String::new_static(b"close"),
+            Callback::new_sequence_with(mc, root, |_, args| {
+                let thread = thread_arg(&args, 0)?;
+                Ok(sequence::from_fn_with(thread, |mc, thread| {
+                    match thread.close(mc) {
+                        Ok(None) => Ok(CallbackResult::Return(vec![Value::Boolean(true)])),
+                        Ok(Some(error)) => {
+                            Ok(CallbackResult::Return(vec![Value::Boolean(false), error]))
+                        }
+                        Err(_) => Err(RuntimeError(Value::String(String::new_static(
+                            match thread.mode() {
+                                ThreadMode::Normal => b"cannot close a normal coroutine",
+                                _ => b"cannot close a running coroutine",
+                            },
+                        )))
+                        .into()),
+                    }
+                }))
+            }),
+        )
+        .unwrap();
+
     coroutine
         .set(
             mc,
@@ -122,3 +205,31 @@ pub fn load_coroutine<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: T
     env.set(mc, String::new_static(b"coroutine"), coroutine)
         .unwrap();
 }
+
+// Threads are only missing from the root's active threads when they are called from outside of any
+// thread step, which is treated as running on the main thread.
+fn current_thread<'gc>(root: Root<'gc>) -> Thread<'gc> {
+    root.active_threads.current().unwrap_or(root.main_thread)
+}
+
+fn function_arg<'gc>(args: &[Value<'gc>], i: usize) -> Result<Function<'gc>, Error<'gc>> {
+    match args.get(i).cloned().unwrap_or(Value::Nil) {
+        Value::Function(function) => Ok(function),
+        value => Err(TypeError {
+            expected: "function",
+            found: value.type_name(),
+        }
+        .into()),
+    }
+}
+
+fn thread_arg<'gc>(args: &[Value<'gc>], i: usize) -> Result<Thread<'gc>, Error<'gc>> {
+    match args.get(i).cloned().unwrap_or(Value::Nil) {
+        Value::Thread(thread) => Ok(thread),
+        value => Err(TypeError {
+            expected: "thread",
+            found: value.type_name(),
+        }
+        .into()),
+    }
+}
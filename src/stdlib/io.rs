@@ -0,0 +1,1081 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use gc_arena::{Collect, GcCell, MutationContext};
+use gc_sequence as sequence;
+
+use crate::{
+    format::FormatSpec,
+    lexer::{read_float, read_hex_float, read_hex_integer, read_integer},
+    Callback, CallbackResult, Error, Root, RuntimeError, String, Table, TypeError, UserData, Value,
+};
+
+use super::args::{integer_arg, string_arg};
+
+const BUFFER_SIZE: usize = 8192;
+// The longest numeral that `read("n")` will accept.
+const MAX_NUMERAL_LEN: usize = 200;
+// The maximum number of formats that may be given to a `lines` iterator.
+const MAX_LINES_FORMATS: usize = 250;
+
+pub fn load_io<'gc>(mc: MutationContext<'gc, '_>, _: Root<'gc>, env: Table<'gc>) {
+    let io = Table::new(mc);
+
+    let methods = Table::new(mc);
+    let metatable = Table::new(mc);
+    metatable
+        .set(mc, String::new_static(b"__index"), methods)
+        .unwrap();
+    metatable
+        .set(
+            mc,
+            String::new_static(b"__name"),
+            String::new_static(b"FILE*"),
+        )
+        .unwrap();
+
+    let stdin = new_file(
+        mc,
+        metatable,
+        BufferedFile::new(Stream::Stdin(io::stdin()), Buffering::Full),
+    );
+    // The standard output streams are left unbuffered so that their output is ordered correctly
+    // with `print`, which writes to the standard output directly.
+    let stdout = new_file(
+        mc,
+        metatable,
+        BufferedFile::new(Stream::Stdout(io::stdout()), Buffering::No),
+    );
+    let stderr = new_file(
+        mc,
+        metatable,
+        BufferedFile::new(Stream::Stderr(io::stderr()), Buffering::No),
+    );
+    io.set(mc, String::new_static(b"stdin"), stdin).unwrap();
+    io.set(mc, String::new_static(b"stdout"), stdout).unwrap();
+    io.set(mc, String::new_static(b"stderr"), stderr).unwrap();
+
+    let state = IoState {
+        metatable,
+        defaults: GcCell::allocate(
+            mc,
+            Defaults {
+                input: stdin,
+                output: stdout,
+            },
+        ),
+    };
+
+    metatable
+        .set(
+            mc,
+            String::new_static(b"__tostring"),
+            callback(mc, state, |mc, _, args| {
+                let file = file_arg(&args, 0)?;
+                let message = if file.read::<FileHandle>().unwrap().0.borrow().is_some() {
+                    format!("file ({:p})", GcCell::as_ptr(file.0))
+                } else {
+                    "file (closed)".to_owned()
+                };
+                Ok(CallbackResult::Return(vec![Value::String(String::new(
+                    mc,
+                    message.as_bytes(),
+                ))]))
+            }),
+        )
+        .unwrap();
+
+    methods
+        .set(
+            mc,
+            String::new_static(b"close"),
+            callback(mc, state, |mc, _, args| file_close(mc, file_arg(&args, 0)?)),
+        )
+        .unwrap();
+
+    methods
+        .set(
+            mc,
+            String::new_static(b"flush"),
+            callback(mc, state, |mc, _, args| {
+                let res = with_file(file_arg(&args, 0)?, BufferedFile::flush)?;
+                Ok(CallbackResult::Return(file_result(mc, res)))
+            }),
+        )
+        .unwrap();
+
+    methods
+        .set(
+            mc,
+            String::new_static(b"lines"),
+            callback(mc, state, |mc, _, args| {
+                let file = file_arg(&args, 0)?;
+                lines_iterator(mc, file, &args[1..], false)
+            }),
+        )
+        .unwrap();
+
+    methods
+        .set(
+            mc,
+            String::new_static(b"read"),
+            callback(mc, state, |mc, _, args| {
+                file_read(mc, file_arg(&args, 0)?, &args[1..])
+            }),
+        )
+        .unwrap();
+
+    methods
+        .set(
+            mc,
+            String::new_static(b"seek"),
+            callback(mc, state, |mc, _, args| {
+                let file = file_arg(&args, 0)?;
+                let whence = match args.get(1).cloned().unwrap_or(Value::Nil) {
+                    Value::Nil => Cow::Borrowed(&b"cur"[..]),
+                    _ => string_arg(&args, 1)?,
+                };
+                let offset = integer_arg(&args, 2)?.unwrap_or(0);
+                let pos = match whence.as_ref() {
+                    b"set" => SeekFrom::Start(offset as u64),
+                    b"cur" => SeekFrom::Current(offset),
+                    b"end" => SeekFrom::End(offset),
+                    _ => return Err(invalid_option(mc, &whence)),
+                };
+
+                Ok(CallbackResult::Return(
+                    match with_file(file, |file| file.seek(pos))? {
+                        Ok(pos) => vec![Value::Integer(pos as i64)],
+                        Err(err) => error_result(mc, None, &err),
+                    },
+                ))
+            }),
+        )
+        .unwrap();
+
+    methods
+        .set(
+            mc,
+            String::new_static(b"setvbuf"),
+            callback(mc, state, |mc, _, args| {
+                let file = file_arg(&args, 0)?;
+                let mode = string_arg(&args, 1)?;
+                let buffering = match mode.as_ref() {
+                    b"no" => Buffering::No,
+                    b"full" => Buffering::Full,
+                    b"line" => Buffering::Line,
+                    _ => return Err(invalid_option(mc, &mode)),
+                };
+                let size = match integer_arg(&args, 2)? {
+                    Some(size) if size > 0 => size as usize,
+                    _ => BUFFER_SIZE,
+                };
+
+                let res = with_file(file, |file| file.set_buffering(buffering, size))?;
+                Ok(CallbackResult::Return(file_result(mc, res)))
+            }),
+        )
+        .unwrap();
+
+    methods
+        .set(
+            mc,
+            String::new_static(b"write"),
+            callback(mc, state, |mc, _, args| {
+                file_write(mc, file_arg(&args, 0)?, &args[1..])
+            }),
+        )
+        .unwrap();
+
+    io.set(
+        mc,
+        String::new_static(b"close"),
+        callback(mc, state, |mc, state, args| {
+            let file = match args.get(0).cloned().unwrap_or(Value::Nil) {
+                Value::Nil => state.defaults.read().output,
+                _ => file_arg(&args, 0)?,
+            };
+            file_close(mc, file)
+        }),
+    )
+    .unwrap();
+
+    io.set(
+        mc,
+        String::new_static(b"flush"),
+        callback(mc, state, |mc, state, _| {
+            let res = with_file(state.defaults.read().output, BufferedFile::flush)?;
+            Ok(CallbackResult::Return(file_result(mc, res)))
+        }),
+    )
+    .unwrap();
+
+    io.set(
+        mc,
+        String::new_static(b"input"),
+        callback(mc, state, |mc, state, args| {
+            set_default(mc, state, &args, false)
+        }),
+    )
+    .unwrap();
+
+    io.set(
+        mc,
+        String::new_static(b"lines"),
+        callback(mc, state, |mc, state, args| {
+            match args.get(0).cloned().unwrap_or(Value::Nil) {
+                Value::Nil => {
+                    let file = state.defaults.read().input;
+                    with_file(file, |_| ())?;
+                    lines_iterator(mc, file, args.get(1..).unwrap_or(&[]), false)
+                }
+                _ => {
+                    let name = string_arg(&args, 0)?;
+                    let file = match open_file(&name, b"r") {
+                        Ok(file) => new_file(mc, state.metatable, file),
+                        Err(err) => return Err(file_error(mc, &name, &err)),
+                    };
+                    lines_iterator(mc, file, &args[1..], true)
+                }
+            }
+        }),
+    )
+    .unwrap();
+
+    io.set(
+        mc,
+        String::new_static(b"open"),
+        callback(mc, state, |mc, state, args| {
+            let name = string_arg(&args, 0)?;
+            let mode = match args.get(1).cloned().unwrap_or(Value::Nil) {
+                Value::Nil => Cow::Borrowed(&b"r"[..]),
+                _ => string_arg(&args, 1)?,
+            };
+            if !is_valid_mode(&mode) {
+                return Err(
+                    RuntimeError(Value::String(String::new_static(b"invalid mode"))).into(),
+                );
+            }
+
+            Ok(CallbackResult::Return(match open_file(&name, &mode) {
+                Ok(file) => vec![new_file(mc, state.metatable, file).into()],
+                Err(err) => error_result(mc, Some(&name), &err),
+            }))
+        }),
+    )
+    .unwrap();
+
+    io.set(
+        mc,
+        String::new_static(b"output"),
+        callback(mc, state, |mc, state, args| {
+            set_default(mc, state, &args, true)
+        }),
+    )
+    .unwrap();
+
+    io.set(
+        mc,
+        String::new_static(b"read"),
+        callback(mc, state, |mc, state, args| {
+            file_read(mc, state.defaults.read().input, &args)
+        }),
+    )
+    .unwrap();
+
+    io.set(
+        mc,
+        String::new_static(b"type"),
+        Callback::new_immediate(mc, |args| {
+            let file_type = match args.get(0).cloned().unwrap_or(Value::Nil) {
+                Value::UserData(u) => match u.read::<FileHandle>() {
+                    Some(handle) if handle.0.borrow().is_some() => {
+                        Value::String(String::new_static(b"file"))
+                    }
+                    Some(_) => Value::String(String::new_static(b"closed file")),
+                    None => Value::Nil,
+                },
+                _ => Value::Nil,
+            };
+            Ok(CallbackResult::Return(vec![file_type]))
+        }),
+    )
+    .unwrap();
+
+    io.set(
+        mc,
+        String::new_static(b"write"),
+        callback(mc, state, |mc, state, args| {
+            file_write(mc, state.defaults.read().output, &args)
+        }),
+    )
+    .unwrap();
+
+    env.set(mc, String::new_static(b"io"), io).unwrap();
+}
+
+/// The result of a failed I/O operation as returned from the `io` and `os` libraries: nil, an error
+/// message optionally prefixed with a file name, and the system error code.
+pub fn error_result<'gc>(
+    mc: MutationContext<'gc, '_>,
+    filename: Option<&[u8]>,
+    err: &io::Error,
+) -> Vec<Value<'gc>> {
+    vec![
+        Value::Nil,
+        Value::String(String::new(mc, &error_message(filename, err))),
+        Value::Integer(err.raw_os_error().unwrap_or(0) as i64),
+    ]
+}
+
+fn error_message(filename: Option<&[u8]>, err: &io::Error) -> Vec<u8> {
+    let mut msg = Vec::new();
+    if let Some(filename) = filename {
+        msg.extend_from_slice(filename);
+        msg.extend_from_slice(b": ");
+    }
+
+    // Remove the " (os error N)" suffix that `std` adds, which matches the output of C's
+    // `strerror`.
+    let description = err.to_string();
+    let description = match err.raw_os_error() {
+        Some(code) => {
+            let suffix = format!(" (os error {})", code);
+            if description.ends_with(&suffix) {
+                &description[..description.len() - suffix.len()]
+            } else {
+                &description
+            }
+        }
+        None => &description,
+    };
+    msg.extend_from_slice(description.as_bytes());
+    msg
+}
+
+fn file_result<'gc>(mc: MutationContext<'gc, '_>, res: io::Result<()>) -> Vec<Value<'gc>> {
+    match res {
+        Ok(()) => vec![Value::Boolean(true)],
+        Err(err) => error_result(mc, None, &err),
+    }
+}
+
+// An error raised for a file that cannot be opened where there is no way to return an error
+// result.
+fn file_error<'gc>(mc: MutationContext<'gc, '_>, filename: &[u8], err: &io::Error) -> Error<'gc> {
+    RuntimeError(Value::String(String::new(
+        mc,
+        &error_message(Some(filename), err),
+    )))
+    .into()
+}
+
+fn invalid_option<'gc>(mc: MutationContext<'gc, '_>, option: &[u8]) -> Error<'gc> {
+    let mut msg = b"invalid option '".to_vec();
+    msg.extend_from_slice(option);
+    msg.push(b'\'');
+    RuntimeError(Value::String(String::new(mc, &msg))).into()
+}
+
+fn closed_file_error<'gc>() -> Error<'gc> {
+    RuntimeError(Value::String(String::new_static(
+        b"attempt to use a closed file",
+    )))
+    .into()
+}
+
+#[derive(Collect, Clone, Copy)]
+#[collect(require_copy)]
+struct IoState<'gc> {
+    // The metatable of all file handles
+    metatable: Table<'gc>,
+    defaults: GcCell<'gc, Defaults<'gc>>,
+}
+
+// The files used by `io.read`, `io.write` and friends.
+#[derive(Collect)]
+#[collect(empty_drop)]
+struct Defaults<'gc> {
+    input: UserData<'gc>,
+    output: UserData<'gc>,
+}
+
+// Creates a callback that runs its body in a single sequence step, so that it can allocate.
+fn callback<'gc, F>(mc: MutationContext<'gc, '_>, state: IoState<'gc>, f: F) -> Callback<'gc>
+where
+    F: 'static
+        + Copy
+        + Fn(
+            MutationContext<'gc, '_>,
+            IoState<'gc>,
+            Vec<Value<'gc>>,
+        ) -> Result<CallbackResult<'gc>, Error<'gc>>,
+{
+    Callback::new_sequence_with(mc, state, move |&state, args| {
+        Ok(sequence::from_fn_with(
+            (state, args),
+            move |mc, (state, args)| f(mc, state, args),
+        ))
+    })
+}
+
+// The payload of file handle userdata, which holds `None` once the file has been closed.  Files are
+// flushed and closed when they are collected.
+struct FileHandle(RefCell<Option<BufferedFile>>);
+
+fn new_file<'gc>(
+    mc: MutationContext<'gc, '_>,
+    metatable: Table<'gc>,
+    file: BufferedFile,
+) -> UserData<'gc> {
+    let file = UserData::new_static(mc, FileHandle(RefCell::new(Some(file))));
+    file.set_metatable(mc, Some(metatable));
+    file
+}
+
+fn file_arg<'gc>(args: &[Value<'gc>], i: usize) -> Result<UserData<'gc>, Error<'gc>> {
+    match args.get(i).cloned().unwrap_or(Value::Nil) {
+        Value::UserData(u) if u.read::<FileHandle>().is_some() => Ok(u),
+        v => Err(TypeError {
+            expected: "FILE*",
+            found: v.type_name(),
+        }
+        .into()),
+    }
+}
+
+// Runs `f` with the file held by the given handle, failing if it has been closed.
+fn with_file<'gc, R>(
+    file: UserData<'gc>,
+    f: impl FnOnce(&mut BufferedFile) -> R,
+) -> Result<R, Error<'gc>> {
+    let handle = file.read::<FileHandle>().unwrap();
+    let mut file = handle.0.borrow_mut();
+    match file.as_mut() {
+        Some(file) => Ok(f(file)),
+        None => Err(closed_file_error()),
+    }
+}
+
+fn file_close<'gc>(
+    mc: MutationContext<'gc, '_>,
+    file: UserData<'gc>,
+) -> Result<CallbackResult<'gc>, Error<'gc>> {
+    if with_file(file, |file| file.is_standard())? {
+        return Ok(CallbackResult::Return(vec![
+            Value::Nil,
+            Value::String(String::new_static(b"cannot close standard file")),
+        ]));
+    }
+
+    let file = file.read::<FileHandle>().unwrap().0.borrow_mut().take();
+    Ok(CallbackResult::Return(file_result(
+        mc,
+        file.unwrap().close(),
+    )))
+}
+
+fn file_read<'gc>(
+    mc: MutationContext<'gc, '_>,
+    file: UserData<'gc>,
+    formats: &[Value<'gc>],
+) -> Result<CallbackResult<'gc>, Error<'gc>> {
+    let formats = read_formats(formats)?;
+    Ok(CallbackResult::Return(
+        match with_file(file, |file| read(mc, file, &formats))? {
+            Ok(res) => res,
+            Err(err) => error_result(mc, None, &err),
+        },
+    ))
+}
+
+fn file_write<'gc>(
+    mc: MutationContext<'gc, '_>,
+    file: UserData<'gc>,
+    args: &[Value<'gc>],
+) -> Result<CallbackResult<'gc>, Error<'gc>> {
+    let mut buf = Vec::new();
+    for (i, arg) in args.iter().enumerate() {
+        match *arg {
+            Value::Integer(n) => write!(buf, "{}", n).unwrap(),
+            Value::Number(n) => FormatSpec {
+                conversion: b'g',
+                precision: Some(14),
+                ..Default::default()
+            }
+            .write_float(&mut buf, n),
+            _ => buf.extend_from_slice(&string_arg(args, i)?),
+        }
+    }
+
+    Ok(CallbackResult::Return(
+        match with_file(file, |file| file.write(&buf))? {
+            Ok(()) => vec![file.into()],
+            Err(err) => error_result(mc, None, &err),
+        },
+    ))
+}
+
+fn set_default<'gc>(
+    mc: MutationContext<'gc, '_>,
+    state: IoState<'gc>,
+    args: &[Value<'gc>],
+    output: bool,
+) -> Result<CallbackResult<'gc>, Error<'gc>> {
+    let file = match args.get(0).cloned().unwrap_or(Value::Nil) {
+        Value::Nil => None,
+        Value::UserData(_) => {
+            let file = file_arg(args, 0)?;
+            with_file(file, |_| ())?;
+            Some(file)
+        }
+        _ => {
+            let name = string_arg(args, 0)?;
+            match open_file(&name, if output { b"w" } else { b"r" }) {
+                Ok(file) => Some(new_file(mc, state.metatable, file)),
+                Err(err) => return Err(file_error(mc, &name, &err)),
+            }
+        }
+    };
+
+    let mut defaults = state.defaults.write(mc);
+    let current = if output {
+        &mut defaults.output
+    } else {
+        &mut defaults.input
+    };
+    if let Some(file) = file {
+        *current = file;
+    }
+    Ok(CallbackResult::Return(vec![(*current).into()]))
+}
+
+fn lines_iterator<'gc>(
+    mc: MutationContext<'gc, '_>,
+    file: UserData<'gc>,
+    formats: &[Value<'gc>],
+    close_at_eof: bool,
+) -> Result<CallbackResult<'gc>, Error<'gc>> {
+    if formats.len() > MAX_LINES_FORMATS {
+        return Err(RuntimeError(Value::String(String::new_static(b"too many arguments"))).into());
+    }
+    let formats = read_formats(formats)?;
+
+    Ok(CallbackResult::Return(vec![Callback::new_sequence_with(
+        mc,
+        file,
+        move |&file, _| {
+            let formats = formats.clone();
+            Ok(sequence::from_fn_with(file, move |mc, file| {
+                let handle = file.read::<FileHandle>().unwrap();
+                let mut handle = handle.0.borrow_mut();
+                let res = match handle.as_mut() {
+                    Some(f) => read(mc, f, &formats),
+                    None => {
+                        return Err(RuntimeError(Value::String(String::new_static(
+                            b"file is already closed",
+                        )))
+                        .into());
+                    }
+                };
+
+                match res {
+                    Ok(res) => {
+                        if close_at_eof && res.first() == Some(&Value::Nil) {
+                            if let Err(err) = handle.take().unwrap().close() {
+                                return Err(RuntimeError(Value::String(String::new(
+                                    mc,
+                                    &error_message(None, &err),
+                                )))
+                                .into());
+                            }
+                        }
+                        Ok(CallbackResult::Return(res))
+                    }
+                    Err(err) => Err(RuntimeError(Value::String(String::new(
+                        mc,
+                        &error_message(None, &err),
+                    )))
+                    .into()),
+                }
+            }))
+        },
+    )
+    .into()]))
+}
+
+// Accepts the same modes as C's `fopen`: one of 'r', 'w' or 'a', optionally followed by '+', and
+// then any number of 'b's.
+fn is_valid_mode(mode: &[u8]) -> bool {
+    let rest = match mode.split_first() {
+        Some((b'r', rest)) | Some((b'w', rest)) | Some((b'a', rest)) => rest,
+        _ => return false,
+    };
+    let rest = match rest.split_first() {
+        Some((b'+', rest)) => rest,
+        _ => rest,
+    };
+    rest.iter().all(|&c| c == b'b')
+}
+
+fn open_file(name: &[u8], mode: &[u8]) -> io::Result<BufferedFile> {
+    let plus = mode.contains(&b'+');
+    let mut options = OpenOptions::new();
+    match mode[0] {
+        b'r' => options.read(true).write(plus),
+        b'w' => options.read(plus).write(true).create(true).truncate(true),
+        _ => options.read(plus).append(true).create(true),
+    };
+
+    #[cfg(unix)]
+    let path = {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        OsStr::from_bytes(name)
+    };
+    #[cfg(not(unix))]
+    let path = std::string::String::from_utf8_lossy(name).into_owned();
+
+    Ok(BufferedFile::new(
+        Stream::File(options.open(path)?),
+        Buffering::Full,
+    ))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadFormat {
+    Number,
+    Line,
+    LineWithNewline,
+    All,
+    Count(usize),
+}
+
+fn read_formats<'gc>(args: &[Value<'gc>]) -> Result<Vec<ReadFormat>, Error<'gc>> {
+    if args.is_empty() {
+        return Ok(vec![ReadFormat::Line]);
+    }
+
+    let mut formats = Vec::new();
+    for (i, arg) in args.iter().enumerate() {
+        let format = match *arg {
+            Value::Integer(_) | Value::Number(_) => match arg.to_integer() {
+                // Like PUC-Rio Lua, negative counts wrap around to huge counts.
+                Some(n) => ReadFormat::Count(n as usize),
+                None => {
+                    return Err(TypeError {
+                        expected: "integer",
+                        found: "number",
+                    }
+                    .into());
+                }
+            },
+            _ => {
+                let format = string_arg(args, i)?;
+                // Formats may be prefixed with '*' for compatibility with Lua 5.2.
+                let format = match format.split_first() {
+                    Some((b'*', rest)) => rest,
+                    _ => &format[..],
+                };
+                match format.first() {
+                    Some(b'n') => ReadFormat::Number,
+                    Some(b'l') => ReadFormat::Line,
+                    Some(b'L') => ReadFormat::LineWithNewline,
+                    Some(b'a') => ReadFormat::All,
+                    _ => {
+                        return Err(RuntimeError(Value::String(String::new_static(
+                            b"invalid format",
+                        )))
+                        .into());
+                    }
+                }
+            }
+        };
+        formats.push(format);
+    }
+    Ok(formats)
+}
+
+// Reads a value for each format, stopping after the first one which fails and returns nil.
+fn read<'gc>(
+    mc: MutationContext<'gc, '_>,
+    file: &mut BufferedFile,
+    formats: &[ReadFormat],
+) -> io::Result<Vec<Value<'gc>>> {
+    let mut res = Vec::new();
+    for &format in formats {
+        let string = |s: Option<Vec<u8>>| match s {
+            Some(s) => Value::String(String::new(mc, &s)),
+            None => Value::Nil,
+        };
+        let value = match format {
+            ReadFormat::Number => file.read_number()?.unwrap_or(Value::Nil),
+            ReadFormat::Line => string(file.read_line(false)?),
+            ReadFormat::LineWithNewline => string(file.read_line(true)?),
+            ReadFormat::All => string(Some(file.read_all()?)),
+            ReadFormat::Count(n) => string(file.read_count(n)?),
+        };
+        res.push(value);
+        if value == Value::Nil {
+            break;
+        }
+    }
+    Ok(res)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Buffering {
+    No,
+    Full,
+    Line,
+}
+
+enum Stream {
+    Stdin(io::Stdin),
+    Stdout(io::Stdout),
+    Stderr(io::Stderr),
+    File(fs::File),
+}
+
+impl Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Stdin(s) => s.read(buf),
+            Stream::File(f) => f.read(buf),
+            Stream::Stdout(_) | Stream::Stderr(_) => {
+                Err(io::Error::new(io::ErrorKind::Other, "Bad file descriptor"))
+            }
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Stream::Stdout(s) => s.write_all(buf),
+            Stream::Stderr(s) => s.write_all(buf),
+            Stream::File(f) => f.write_all(buf),
+            Stream::Stdin(_) => Err(io::Error::new(io::ErrorKind::Other, "Bad file descriptor")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Stdout(s) => s.flush(),
+            Stream::Stderr(s) => s.flush(),
+            Stream::File(f) => f.flush(),
+            Stream::Stdin(_) => Ok(()),
+        }
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Stream::File(f) => f.seek(pos),
+            _ => Err(io::Error::new(io::ErrorKind::Other, "Illegal seek")),
+        }
+    }
+}
+
+// A file with separate read and write buffers, behaving like a C `FILE`.  Switching between
+// reading and writing discards the read buffer and flushes the write buffer, so that the two may be
+// freely mixed.
+struct BufferedFile {
+    stream: Stream,
+    buffering: Buffering,
+    buffer_size: usize,
+    read_buf: Vec<u8>,
+    read_pos: usize,
+    write_buf: Vec<u8>,
+}
+
+impl Drop for BufferedFile {
+    fn drop(&mut self) {
+        let _ = self.flush_write();
+    }
+}
+
+impl BufferedFile {
+    fn new(stream: Stream, buffering: Buffering) -> BufferedFile {
+        BufferedFile {
+            stream,
+            buffering,
+            buffer_size: BUFFER_SIZE,
+            read_buf: Vec::new(),
+            read_pos: 0,
+            write_buf: Vec::new(),
+        }
+    }
+
+    fn is_standard(&self) -> bool {
+        match self.stream {
+            Stream::File(_) => false,
+            _ => true,
+        }
+    }
+
+    fn close(mut self) -> io::Result<()> {
+        self.flush()
+    }
+
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.flush_write()?;
+        if self.read_pos == self.read_buf.len() {
+            self.read_buf.resize(self.buffer_size, 0);
+            self.read_pos = 0;
+            let res = loop {
+                match self.stream.read(&mut self.read_buf) {
+                    Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    res => break res,
+                }
+            };
+            match res {
+                Ok(len) => self.read_buf.truncate(len),
+                Err(err) => {
+                    self.read_buf.clear();
+                    return Err(err);
+                }
+            }
+        }
+        Ok(&self.read_buf[self.read_pos..])
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.read_pos += len;
+    }
+
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.fill_buf()?.first().cloned())
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.discard_read_buf()?;
+        match self.buffering {
+            Buffering::No => {
+                self.flush_write()?;
+                self.stream.write_all(data)
+            }
+            Buffering::Full => {
+                self.write_buf.extend_from_slice(data);
+                if self.write_buf.len() >= self.buffer_size {
+                    self.flush_write()?;
+                }
+                Ok(())
+            }
+            Buffering::Line => {
+                self.write_buf.extend_from_slice(data);
+                if data.contains(&b'\n') || self.write_buf.len() >= self.buffer_size {
+                    self.flush_write()?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_write()?;
+        self.stream.flush()
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.flush_write()?;
+        let unread = (self.read_buf.len() - self.read_pos) as i64;
+        self.read_buf.clear();
+        self.read_pos = 0;
+        self.stream.seek(match pos {
+            SeekFrom::Current(offset) => SeekFrom::Current(offset - unread),
+            pos => pos,
+        })
+    }
+
+    fn set_buffering(&mut self, buffering: Buffering, size: usize) -> io::Result<()> {
+        self.flush_write()?;
+        self.buffering = buffering;
+        self.buffer_size = size;
+        Ok(())
+    }
+
+    fn flush_write(&mut self) -> io::Result<()> {
+        if self.write_buf.is_empty() {
+            return Ok(());
+        }
+        let res = self.stream.write_all(&self.write_buf);
+        self.write_buf.clear();
+        res
+    }
+
+    // Moves the stream position back to account for any data that has been read into the buffer
+    // but not consumed.
+    fn discard_read_buf(&mut self) -> io::Result<()> {
+        let unread = (self.read_buf.len() - self.read_pos) as i64;
+        self.read_buf.clear();
+        self.read_pos = 0;
+        if unread > 0 {
+            if let Stream::File(f) = &mut self.stream {
+                f.seek(SeekFrom::Current(-unread))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads a line, returning `None` at the end of the file.
+    fn read_line(&mut self, keep_newline: bool) -> io::Result<Option<Vec<u8>>> {
+        let mut line = Vec::new();
+        loop {
+            let buf = self.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(if line.is_empty() { None } else { Some(line) });
+            }
+
+            match buf.iter().position(|&c| c == b'\n') {
+                Some(i) => {
+                    line.extend_from_slice(&buf[..if keep_newline { i + 1 } else { i }]);
+                    self.consume(i + 1);
+                    return Ok(Some(line));
+                }
+                None => {
+                    let len = buf.len();
+                    line.extend_from_slice(buf);
+                    self.consume(len);
+                }
+            }
+        }
+    }
+
+    fn read_all(&mut self) -> io::Result<Vec<u8>> {
+        let mut res = Vec::new();
+        loop {
+            let buf = self.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(res);
+            }
+            let len = buf.len();
+            res.extend_from_slice(buf);
+            self.consume(len);
+        }
+    }
+
+    // Reads up to `count` bytes, returning `None` at the end of the file.  A count of zero tests for
+    // the end of the file.
+    fn read_count(&mut self, count: usize) -> io::Result<Option<Vec<u8>>> {
+        if count == 0 {
+            return Ok(if self.fill_buf()?.is_empty() {
+                None
+            } else {
+                Some(Vec::new())
+            });
+        }
+
+        let mut res = Vec::new();
+        while res.len() < count {
+            let buf = self.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            let len = buf.len().min(count - res.len());
+            res.extend_from_slice(&buf[..len]);
+            self.consume(len);
+        }
+        Ok(if res.is_empty() { None } else { Some(res) })
+    }
+
+    // Reads the longest prefix of the input which could be the start of a numeral, following
+    // PUC-Rio Lua, and returns it as a number if it is one.
+    fn read_number<'gc>(&mut self) -> io::Result<Option<Value<'gc>>> {
+        while let Some(c) = self.peek()? {
+            if c == b' ' || (b'\t'..=b'\r').contains(&c) {
+                self.consume(1);
+            } else {
+                break;
+            }
+        }
+
+        let mut numeral = Numeral {
+            file: self,
+            buf: Vec::new(),
+            overflow: false,
+        };
+
+        numeral.accept(b"-+")?;
+        let mut digits = 0;
+        let mut hex = false;
+        if numeral.accept(b"0")? {
+            if numeral.accept(b"xX")? {
+                hex = true;
+            } else {
+                digits = 1;
+            }
+        }
+        digits += numeral.accept_digits(hex)?;
+        if numeral.accept(b".")? {
+            digits += numeral.accept_digits(hex)?;
+        }
+        let mut float = false;
+        if digits > 0 && numeral.accept(if hex { b"pP" } else { b"eE" })? {
+            numeral.accept(b"-+")?;
+            numeral.accept_digits(false)?;
+            float = true;
+        }
+
+        if digits == 0 || numeral.overflow {
+            return Ok(None);
+        }
+
+        let s = &numeral.buf;
+        let float = float || s.contains(&b'.');
+        Ok(if hex {
+            if float {
+                read_hex_float(s).map(Value::Number)
+            } else {
+                read_hex_integer(s)
+                    .map(Value::Integer)
+                    .or_else(|| read_hex_float(s).map(Value::Number))
+            }
+        } else if float {
+            read_float(s).map(Value::Number)
+        } else {
+            read_integer(s)
+                .map(Value::Integer)
+                .or_else(|| read_float(s).map(Value::Number))
+        })
+    }
+}
+
+// A numeral being read by `BufferedFile::read_number`.
+struct Numeral<'a> {
+    file: &'a mut BufferedFile,
+    buf: Vec<u8>,
+    overflow: bool,
+}
+
+impl<'a> Numeral<'a> {
+    // Consumes the next byte if it is in the given set.
+    fn accept(&mut self, set: &[u8]) -> io::Result<bool> {
+        match self.file.peek()? {
+            Some(_) if self.buf.len() >= MAX_NUMERAL_LEN => {
+                self.overflow = true;
+                Ok(false)
+            }
+            Some(c) if set.contains(&c) => {
+                self.buf.push(c);
+                self.file.consume(1);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn accept_digits(&mut self, hex: bool) -> io::Result<usize> {
+        let mut count = 0;
+        while let Some(c) = self.file.peek()? {
+            let is_digit = if hex {
+                c.is_ascii_hexdigit()
+            } else {
+                c.is_ascii_digit()
+            };
+            if !is_digit || !self.accept(&[c])? {
+                break;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+}
@@ -1,7 +1,22 @@
+mod args;
 mod base;
 mod coroutine;
+mod io;
 mod math;
+mod os;
+mod pack;
+mod package;
+mod pattern;
+mod string;
+mod table;
+mod utf8;
 
 pub use base::load_base;
 pub use coroutine::load_coroutine;
+pub use io::load_io;
 pub use math::load_math;
+pub use os::{load_os, OsHost, StdOsHost};
+pub use package::load_package;
+pub use string::load_string;
+pub use table::load_table;
+pub use utf8::load_utf8;
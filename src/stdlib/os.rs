@@ -0,0 +1,634 @@
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use gc_arena::MutationContext;
+use gc_sequence as sequence;
+
+use super::args::{integer_arg, required_integer_arg, string_arg};
+use super::io::error_result;
+
+use crate::{
+    Callback, CallbackResult, Error, ExitError, Root, RuntimeError, String, Table, TypeError, Value,
+};
+
+/// The interface through which the `os` library reaches the outside world.  Embedders may provide
+/// their own implementation to sandbox or virtualize scripts, `StdOsHost` is the default.
+pub trait OsHost {
+    /// The current time, in seconds since the Unix epoch.
+    fn time(&self) -> i64;
+
+    /// An approximation of the processor time used by the program, in seconds.  `StdOsHost`
+    /// returns the wall clock time since it was created instead.
+    fn clock(&self) -> f64;
+
+    /// The offset of local time from UTC in seconds, at the given time.  `StdOsHost` always returns
+    /// 0, so by default `os.date` and `os.time` treat local time as UTC.
+    fn utc_offset(&self, time: i64) -> i64;
+
+    fn getenv(&self, name: &[u8]) -> Option<Vec<u8>>;
+
+    fn remove(&self, path: &[u8]) -> io::Result<()>;
+
+    fn rename(&self, from: &[u8], to: &[u8]) -> io::Result<()>;
+
+    /// Creates a new empty temporary file and returns its name.
+    fn tmpname(&self) -> io::Result<Vec<u8>>;
+
+    /// Called by `os.exit`.  If this returns rather than ending the process, the running Lua code
+    /// is unwound with an `ExitError` carrying the same code.
+    fn exit(&self, code: i32);
+}
+
+/// An `OsHost` that uses the real system through `std`.
+///
+/// The standard library provides no access to the local time zone, so local time is the same as
+/// UTC, and `os.clock` measures wall clock time since the host was created.
+pub struct StdOsHost {
+    start: Instant,
+}
+
+impl StdOsHost {
+    pub fn new() -> StdOsHost {
+        StdOsHost {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for StdOsHost {
+    fn default() -> StdOsHost {
+        StdOsHost::new()
+    }
+}
+
+impl OsHost for StdOsHost {
+    fn time(&self) -> i64 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        }
+    }
+
+    /// Returns the wall clock time elapsed since this host was created, not processor time.
+    fn clock(&self) -> f64 {
+        let elapsed = self.start.elapsed();
+        elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9
+    }
+
+    /// Always 0, local time is UTC.
+    fn utc_offset(&self, _: i64) -> i64 {
+        0
+    }
+
+    fn getenv(&self, name: &[u8]) -> Option<Vec<u8>> {
+        std::env::var_os(bytes_to_path(name)).map(|v| path_to_bytes(v.into()))
+    }
+
+    fn remove(&self, path: &[u8]) -> io::Result<()> {
+        // Like C's `remove`, this also removes empty directories.
+        let path = bytes_to_path(path);
+        match fs::remove_file(&path) {
+            Err(err) if path.is_dir() => fs::remove_dir(&path).map_err(|_| err),
+            res => res,
+        }
+    }
+
+    fn rename(&self, from: &[u8], to: &[u8]) -> io::Result<()> {
+        fs::rename(bytes_to_path(from), bytes_to_path(to))
+    }
+
+    fn tmpname(&self) -> io::Result<Vec<u8>> {
+        let dir = std::env::temp_dir();
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        for i in 0..100u32 {
+            let path = dir.join(format!(
+                "lua_{:x}_{:x}",
+                std::process::id(),
+                seed.wrapping_add(i.wrapping_mul(7919))
+            ));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(path_to_bytes(path)),
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "no unique temporary file name found",
+        ))
+    }
+
+    fn exit(&self, code: i32) {
+        std::process::exit(code)
+    }
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    OsStr::from_bytes(bytes).into()
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    std::string::String::from_utf8_lossy(bytes)
+        .into_owned()
+        .into()
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: PathBuf) -> Vec<u8> {
+    use std::os::unix::ffi::OsStringExt;
+    path.into_os_string().into_vec()
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: PathBuf) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+pub fn load_os<'gc>(
+    mc: MutationContext<'gc, '_>,
+    _: Root<'gc>,
+    env: Table<'gc>,
+    host: Rc<dyn OsHost>,
+) {
+    let os = Table::new(mc);
+
+    let h = host.clone();
+    os.set(
+        mc,
+        String::new_static(b"clock"),
+        Callback::new_immediate(mc, move |_| {
+            Ok(CallbackResult::Return(vec![Value::Number(h.clock())]))
+        }),
+    )
+    .unwrap();
+
+    let h = host.clone();
+    os.set(
+        mc,
+        String::new_static(b"date"),
+        Callback::new_sequence(mc, move |args| {
+            let format = match args.get(0).cloned().unwrap_or(Value::Nil) {
+                Value::Nil => b"%c".to_vec(),
+                _ => string_arg(&args, 0)?.into_owned(),
+            };
+            let time = match integer_arg(&args, 1)? {
+                Some(time) => time,
+                None => h.time(),
+            };
+
+            let (utc, format) = match format.split_first() {
+                Some((b'!', rest)) => (true, rest.to_vec()),
+                _ => (false, format),
+            };
+            let offset = if utc { 0 } else { h.utc_offset(time) };
+            let date = match time.checked_add(offset) {
+                Some(time) => Date::from_time(time),
+                None => {
+                    return Err(RuntimeError(Value::String(String::new_static(
+                        b"time out-of-bounds",
+                    )))
+                    .into());
+                }
+            };
+
+            Ok(sequence::from_fn(move |mc| {
+                if format == b"*t" {
+                    let t = Table::new(mc);
+                    date.set_fields(mc, t);
+                    t.set(mc, String::new_static(b"isdst"), false)?;
+                    Ok(CallbackResult::Return(vec![Value::Table(t)]))
+                } else {
+                    let mut out = Vec::new();
+                    if let Err(conversion) = date.format(&mut out, &format, utc, offset) {
+                        let mut msg = b"invalid conversion specifier '%".to_vec();
+                        msg.extend_from_slice(conversion);
+                        msg.push(b'\'');
+                        return Err(RuntimeError(Value::String(String::new(mc, &msg))).into());
+                    }
+                    Ok(CallbackResult::Return(vec![Value::String(String::new(
+                        mc, &out,
+                    ))]))
+                }
+            }))
+        }),
+    )
+    .unwrap();
+
+    os.set(
+        mc,
+        String::new_static(b"difftime"),
+        Callback::new_immediate(mc, |args| {
+            let t2 = required_integer_arg(&args, 0)?;
+            let t1 = required_integer_arg(&args, 1)?;
+            Ok(CallbackResult::Return(vec![Value::Number(
+                t2.wrapping_sub(t1) as f64,
+            )]))
+        }),
+    )
+    .unwrap();
+
+    let h = host.clone();
+    os.set(
+        mc,
+        String::new_static(b"exit"),
+        Callback::new_immediate(mc, move |args| {
+            let code = match args.get(0).cloned().unwrap_or(Value::Nil) {
+                Value::Nil | Value::Boolean(true) => 0,
+                Value::Boolean(false) => 1,
+                _ => required_integer_arg(&args, 0)? as i32,
+            };
+            h.exit(code);
+            Err(ExitError { code }.into())
+        }),
+    )
+    .unwrap();
+
+    let h = host.clone();
+    os.set(
+        mc,
+        String::new_static(b"getenv"),
+        Callback::new_sequence(mc, move |args| {
+            let value = h.getenv(&string_arg(&args, 0)?);
+            Ok(sequence::from_fn(move |mc| {
+                Ok(CallbackResult::Return(vec![match &value {
+                    Some(value) => Value::String(String::new(mc, value)),
+                    None => Value::Nil,
+                }]))
+            }))
+        }),
+    )
+    .unwrap();
+
+    let h = host.clone();
+    os.set(
+        mc,
+        String::new_static(b"remove"),
+        Callback::new_sequence(mc, move |args| {
+            let path = string_arg(&args, 0)?;
+            let res = h.remove(&path).map_err(|err| (path.into_owned(), err));
+            Ok(sequence::from_fn(move |mc| file_result(mc, &res)))
+        }),
+    )
+    .unwrap();
+
+    let h = host.clone();
+    os.set(
+        mc,
+        String::new_static(b"rename"),
+        Callback::new_sequence(mc, move |args| {
+            let from = string_arg(&args, 0)?;
+            let to = string_arg(&args, 1)?;
+            let res = h.rename(&from, &to).map_err(|err| (from.into_owned(), err));
+            Ok(sequence::from_fn(move |mc| file_result(mc, &res)))
+        }),
+    )
+    .unwrap();
+
+    let h = host.clone();
+    os.set(
+        mc,
+        String::new_static(b"time"),
+        Callback::new_sequence(mc, move |args| {
+            let table = match args.get(0).cloned().unwrap_or(Value::Nil) {
+                Value::Nil => None,
+                Value::Table(t) => Some(t),
+                v => {
+                    return Err(TypeError {
+                        expected: "table",
+                        found: v.type_name(),
+                    }
+                    .into());
+                }
+            };
+
+            let h = h.clone();
+            Ok(sequence::from_fn_with(table, move |mc, table| {
+                let table = match table {
+                    Some(table) => table,
+                    None => return Ok(CallbackResult::Return(vec![Value::Integer(h.time())])),
+                };
+
+                let year = date_field(mc, table, b"year", None)?;
+                let month = date_field(mc, table, b"month", None)?;
+                let day = date_field(mc, table, b"day", None)?;
+                let hour = date_field(mc, table, b"hour", Some(12))?;
+                let min = date_field(mc, table, b"min", Some(0))?;
+                let sec = date_field(mc, table, b"sec", Some(0))?;
+
+                // Out of range fields are normalized like C's `mktime` does, and like PUC-Rio Lua
+                // the table is updated with the normalized fields.
+                let months = year * 12 + month - 1;
+                let days =
+                    days_from_civil(months.div_euclid(12), months.rem_euclid(12) + 1, 1) + day - 1;
+                let local = days * 86400 + hour * 3600 + min * 60 + sec;
+                Date::from_time(local).set_fields(mc, table);
+
+                Ok(CallbackResult::Return(vec![Value::Integer(
+                    local - h.utc_offset(local),
+                )]))
+            }))
+        }),
+    )
+    .unwrap();
+
+    let h = host;
+    os.set(
+        mc,
+        String::new_static(b"tmpname"),
+        Callback::new_sequence(mc, move |_| match h.tmpname() {
+            Ok(name) => Ok(sequence::from_fn(move |mc| {
+                Ok(CallbackResult::Return(vec![Value::String(String::new(
+                    mc, &name,
+                ))]))
+            })),
+            Err(_) => Err(RuntimeError(Value::String(String::new_static(
+                b"unable to generate a unique filename",
+            )))
+            .into()),
+        }),
+    )
+    .unwrap();
+
+    env.set(mc, String::new_static(b"os"), os).unwrap();
+}
+
+// The return values of `os.remove` and `os.rename`: true on success, otherwise nil, a message
+// including the file name, and the system error code.
+fn file_result<'gc>(
+    mc: MutationContext<'gc, '_>,
+    res: &Result<(), (Vec<u8>, io::Error)>,
+) -> Result<CallbackResult<'gc>, Error<'gc>> {
+    Ok(CallbackResult::Return(match res {
+        Ok(()) => vec![Value::Boolean(true)],
+        Err((path, err)) => error_result(mc, Some(path), err),
+    }))
+}
+
+// Reads an integer field of a date table given to `os.time`, returning the default if the field is
+// missing.
+fn date_field<'gc>(
+    mc: MutationContext<'gc, '_>,
+    table: Table<'gc>,
+    name: &'static [u8],
+    default: Option<i64>,
+) -> Result<i64, Error<'gc>> {
+    // Large enough for any reasonable date while guaranteeing the arithmetic cannot overflow.
+    const MAX_FIELD: i64 = i32::max_value() as i64 / 2;
+
+    let error = |msg: &[u8]| {
+        let mut buf = b"field '".to_vec();
+        buf.extend_from_slice(name);
+        buf.extend_from_slice(msg);
+        Err(RuntimeError(Value::String(String::new(mc, &buf))).into())
+    };
+
+    match table.get(String::new_static(name)) {
+        Value::Nil => match default {
+            Some(default) => Ok(default),
+            None => error(b"' missing in date table"),
+        },
+        v => match v.to_integer() {
+            Some(i) if i.abs() <= MAX_FIELD => Ok(i),
+            Some(_) => error(b"' is out-of-bound"),
+            None => error(b"' is not an integer"),
+        },
+    }
+}
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+// The conversions accepted by `os.date`, the same set that C99 `strftime` supports.
+const CONVERSIONS: &[u8] = b"aAbBcCdDeFgGhHIjmMnprRStTuUVwWxXyYzZ%";
+const E_CONVERSIONS: &[u8] = b"cCxXyY";
+const O_CONVERSIONS: &[u8] = b"deHImMSuUVwWy";
+
+// A broken down time in the proleptic Gregorian calendar.
+#[derive(Debug, Clone, Copy)]
+struct Date {
+    year: i64,
+    // 1 to 12
+    month: i64,
+    // 1 to 31
+    day: i64,
+    hour: i64,
+    min: i64,
+    sec: i64,
+    // 0 to 6, starting from Sunday
+    weekday: i64,
+    // 0 to 365
+    yearday: i64,
+}
+
+impl Date {
+    fn from_time(time: i64) -> Date {
+        let days = time.div_euclid(86400);
+        let secs = time.rem_euclid(86400);
+        let (year, month, day) = civil_from_days(days);
+        Date {
+            year,
+            month,
+            day,
+            hour: secs / 3600,
+            min: secs / 60 % 60,
+            sec: secs % 60,
+            // The epoch was a Thursday.
+            weekday: (days + 4).rem_euclid(7),
+            yearday: days - days_from_civil(year, 1, 1),
+        }
+    }
+
+    fn set_fields<'gc>(&self, mc: MutationContext<'gc, '_>, table: Table<'gc>) {
+        let fields = [
+            (&b"year"[..], self.year),
+            (b"month", self.month),
+            (b"day", self.day),
+            (b"hour", self.hour),
+            (b"min", self.min),
+            (b"sec", self.sec),
+            (b"wday", self.weekday + 1),
+            (b"yday", self.yearday + 1),
+        ];
+        for &(name, value) in &fields {
+            table.set(mc, String::new_static(name), value).unwrap();
+        }
+    }
+
+    // Formats the date like C's `strftime` in the "C" locale.  On an invalid conversion, returns
+    // the conversion specifier following the '%'.
+    fn format<'a>(
+        &self,
+        out: &mut Vec<u8>,
+        format: &'a [u8],
+        utc: bool,
+        offset: i64,
+    ) -> Result<(), &'a [u8]> {
+        let mut i = 0;
+        while i < format.len() {
+            if format[i] != b'%' {
+                out.push(format[i]);
+                i += 1;
+                continue;
+            }
+
+            let rest = &format[i + 1..];
+            let (conversion, len) = match rest.first() {
+                Some(&m @ b'E') | Some(&m @ b'O') => {
+                    let allowed = if m == b'E' {
+                        E_CONVERSIONS
+                    } else {
+                        O_CONVERSIONS
+                    };
+                    match rest.get(1) {
+                        Some(c) if allowed.contains(c) => (*c, 2),
+                        _ => return Err(&rest[..rest.len().min(2)]),
+                    }
+                }
+                Some(c) if CONVERSIONS.contains(c) => (*c, 1),
+                _ => return Err(&rest[..rest.len().min(1)]),
+            };
+            self.write_conversion(out, conversion, utc, offset);
+            i += 1 + len;
+        }
+        Ok(())
+    }
+
+    fn write_conversion(&self, out: &mut Vec<u8>, conversion: u8, utc: bool, offset: i64) {
+        let s = match conversion {
+            b'a' => WEEKDAYS[self.weekday as usize][..3].to_owned(),
+            b'A' => WEEKDAYS[self.weekday as usize].to_owned(),
+            b'b' | b'h' => MONTHS[self.month as usize - 1][..3].to_owned(),
+            b'B' => MONTHS[self.month as usize - 1].to_owned(),
+            b'c' => return self.write_all(out, b"%a %b %e %H:%M:%S %Y"),
+            b'C' => format!("{:02}", self.year.div_euclid(100)),
+            b'd' => format!("{:02}", self.day),
+            b'D' | b'x' => return self.write_all(out, b"%m/%d/%y"),
+            b'e' => format!("{:2}", self.day),
+            b'F' => return self.write_all(out, b"%Y-%m-%d"),
+            b'g' => format!("{:02}", self.iso_week().0.rem_euclid(100)),
+            b'G' => format!("{}", self.iso_week().0),
+            b'H' => format!("{:02}", self.hour),
+            b'I' => format!("{:02}", (self.hour + 11) % 12 + 1),
+            b'j' => format!("{:03}", self.yearday + 1),
+            b'm' => format!("{:02}", self.month),
+            b'M' => format!("{:02}", self.min),
+            b'n' => "\n".to_owned(),
+            b'p' => if self.hour < 12 { "AM" } else { "PM" }.to_owned(),
+            b'r' => return self.write_all(out, b"%I:%M:%S %p"),
+            b'R' => return self.write_all(out, b"%H:%M"),
+            b'S' => format!("{:02}", self.sec),
+            b't' => "\t".to_owned(),
+            b'T' | b'X' => return self.write_all(out, b"%H:%M:%S"),
+            b'u' => format!("{}", (self.weekday + 6) % 7 + 1),
+            b'U' => format!("{:02}", (self.yearday + 7 - self.weekday) / 7),
+            b'V' => format!("{:02}", self.iso_week().1),
+            b'w' => format!("{}", self.weekday),
+            b'W' => format!("{:02}", (self.yearday + 7 - (self.weekday + 6) % 7) / 7),
+            b'y' => format!("{:02}", self.year.rem_euclid(100)),
+            b'Y' => format!("{}", self.year),
+            b'z' => format!(
+                "{}{:02}{:02}",
+                if offset < 0 { '-' } else { '+' },
+                offset.abs() / 3600,
+                offset.abs() / 60 % 60
+            ),
+            b'Z' => if utc {
+                "GMT"
+            } else if offset == 0 {
+                "UTC"
+            } else {
+                ""
+            }
+            .to_owned(),
+            _ => "%".to_owned(),
+        };
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    // Formats a composite conversion, which only ever contains valid conversions that do not
+    // depend on the time zone.
+    fn write_all(&self, out: &mut Vec<u8>, format: &[u8]) {
+        self.format(out, format, true, 0).unwrap();
+    }
+
+    // The ISO 8601 week based year and week number, where weeks start on Monday and the first week
+    // of the year is the one containing its first Thursday.
+    fn iso_week(&self) -> (i64, i64) {
+        let weekday = (self.weekday + 6) % 7;
+        let week = (self.yearday - weekday + 10) / 7;
+        if week < 1 {
+            (self.year - 1, iso_weeks_in_year(self.year - 1))
+        } else if week > iso_weeks_in_year(self.year) {
+            (self.year + 1, 1)
+        } else {
+            (self.year, week)
+        }
+    }
+}
+
+fn iso_weeks_in_year(year: i64) -> i64 {
+    // The weekday of December 31st, starting from Sunday.
+    let dec31 =
+        |y: i64| (y + y.div_euclid(4) - y.div_euclid(100) + y.div_euclid(400)).rem_euclid(7);
+    if dec31(year) == 4 || dec31(year - 1) == 3 {
+        53
+    } else {
+        52
+    }
+}
+
+// The number of days since the Unix epoch of the given date, from Howard Hinnant's date
+// algorithms.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -0,0 +1,275 @@
+//! The format language of `string.pack` and `string.unpack`, closely following PUC-Rio Lua's
+//! `lstrlib.c`.
+
+const MAX_INT_SIZE: usize = 16;
+const MAX_ALIGN: usize = 8;
+const INTEGER_SIZE: usize = 8;
+// Formats may not describe data larger than this.
+const MAX_SIZE: usize = i64::max_value() as usize;
+
+/// An error caused by a malformed pack format or by data that does not fit the format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackError(pub &'static str);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackOption {
+    Int,
+    Uint,
+    Float,
+    // Fixed size string
+    Char,
+    // String preceded by its length
+    String,
+    // Zero terminated string
+    ZString,
+    Padding,
+    PadAlign,
+    Nop,
+}
+
+/// A single option from a pack format, along with its size in bytes and the amount of padding
+/// needed before it to satisfy its alignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackItem {
+    pub option: PackOption,
+    pub size: usize,
+    pub align_padding: usize,
+}
+
+pub struct PackFormat<'a> {
+    fmt: &'a [u8],
+    pos: usize,
+    little_endian: bool,
+    max_align: usize,
+}
+
+impl<'a> PackFormat<'a> {
+    pub fn new(fmt: &'a [u8]) -> PackFormat<'a> {
+        PackFormat {
+            fmt,
+            pos: 0,
+            little_endian: cfg!(target_endian = "little"),
+            max_align: 1,
+        }
+    }
+
+    /// The endianness set by the options read so far.
+    pub fn little_endian(&self) -> bool {
+        self.little_endian
+    }
+
+    /// Reads the next option from the format, given the total size of the data so far (which
+    /// determines alignment padding).  Returns `None` at the end of the format.
+    pub fn next_item(&mut self, total_size: usize) -> Option<Result<PackItem, PackError>> {
+        if self.pos == self.fmt.len() {
+            None
+        } else {
+            Some(self.read_item(total_size))
+        }
+    }
+
+    fn read_item(&mut self, total_size: usize) -> Result<PackItem, PackError> {
+        let (option, size) = self.read_option()?;
+
+        let mut align = size;
+        if option == PackOption::PadAlign {
+            // 'X' aligns according to the next option, which is otherwise ignored.
+            if self.pos == self.fmt.len() {
+                return Err(PackError("invalid next option for option 'X'"));
+            }
+            let (next_option, next_size) = self.read_option()?;
+            if next_option == PackOption::Char || next_size == 0 {
+                return Err(PackError("invalid next option for option 'X'"));
+            }
+            align = next_size;
+        }
+
+        let align_padding = if align <= 1 || option == PackOption::Char {
+            0
+        } else {
+            let align = align.min(self.max_align);
+            if !align.is_power_of_two() {
+                return Err(PackError("format asks for alignment not power of 2"));
+            }
+            (align - (total_size & (align - 1))) & (align - 1)
+        };
+
+        Ok(PackItem {
+            option,
+            size,
+            align_padding,
+        })
+    }
+
+    fn read_option(&mut self) -> Result<(PackOption, usize), PackError> {
+        let c = self.fmt[self.pos];
+        self.pos += 1;
+        Ok(match c {
+            b'b' => (PackOption::Int, 1),
+            b'B' => (PackOption::Uint, 1),
+            b'h' => (PackOption::Int, 2),
+            b'H' => (PackOption::Uint, 2),
+            b'l' | b'j' => (PackOption::Int, 8),
+            b'L' | b'J' | b'T' => (PackOption::Uint, 8),
+            b'f' => (PackOption::Float, 4),
+            b'd' | b'n' => (PackOption::Float, 8),
+            b'i' => (PackOption::Int, self.read_size_limit(4)?),
+            b'I' => (PackOption::Uint, self.read_size_limit(4)?),
+            b's' => (PackOption::String, self.read_size_limit(8)?),
+            b'c' => match self.read_size() {
+                Some(size) => (PackOption::Char, size),
+                None => return Err(PackError("missing size for format option 'c'")),
+            },
+            b'z' => (PackOption::ZString, 0),
+            b'x' => (PackOption::Padding, 1),
+            b'X' => (PackOption::PadAlign, 0),
+            b' ' => (PackOption::Nop, 0),
+            b'<' => {
+                self.little_endian = true;
+                (PackOption::Nop, 0)
+            }
+            b'>' => {
+                self.little_endian = false;
+                (PackOption::Nop, 0)
+            }
+            b'=' => {
+                self.little_endian = cfg!(target_endian = "little");
+                (PackOption::Nop, 0)
+            }
+            b'!' => {
+                self.max_align = self.read_size_limit(MAX_ALIGN)?;
+                (PackOption::Nop, 0)
+            }
+            _ => return Err(PackError("invalid format option")),
+        })
+    }
+
+    fn read_size(&mut self) -> Option<usize> {
+        if !self
+            .fmt
+            .get(self.pos)
+            .map(u8::is_ascii_digit)
+            .unwrap_or(false)
+        {
+            return None;
+        }
+
+        let mut size: usize = 0;
+        while let Some(&c) = self.fmt.get(self.pos) {
+            if !c.is_ascii_digit() || size > (MAX_SIZE - 9) / 10 {
+                break;
+            }
+            size = size * 10 + (c - b'0') as usize;
+            self.pos += 1;
+        }
+        Some(size)
+    }
+
+    fn read_size_limit(&mut self, default: usize) -> Result<usize, PackError> {
+        let size = self.read_size().unwrap_or(default);
+        if size == 0 || size > MAX_INT_SIZE {
+            Err(PackError("integral size out of limits [1,16]"))
+        } else {
+            Ok(size)
+        }
+    }
+}
+
+/// Returns true if the given integer fits in a packed integer of `size` bytes.
+pub fn int_fits(n: i64, size: usize, signed: bool) -> bool {
+    if size >= INTEGER_SIZE {
+        true
+    } else if signed {
+        let limit = 1 << (size * 8 - 1);
+        -limit <= n && n < limit
+    } else {
+        (n as u64) < 1 << (size * 8)
+    }
+}
+
+/// Writes an integer of `size` bytes, sign extending it if the size is larger than a Lua integer.
+pub fn pack_int(out: &mut Vec<u8>, n: u64, little_endian: bool, size: usize, negative: bool) {
+    let mut bytes = [0; MAX_INT_SIZE];
+    for (i, b) in bytes.iter_mut().enumerate().take(size) {
+        *b = if i < INTEGER_SIZE {
+            (n >> (i * 8)) as u8
+        } else if negative {
+            0xff
+        } else {
+            0
+        };
+    }
+
+    let bytes = &mut bytes[..size];
+    if !little_endian {
+        bytes.reverse();
+    }
+    out.extend_from_slice(bytes);
+}
+
+/// Reads an integer of `size` bytes, which must be representable as a Lua integer.
+pub fn unpack_int(
+    data: &[u8],
+    little_endian: bool,
+    size: usize,
+    signed: bool,
+) -> Result<i64, PackError> {
+    let byte = |i: usize| {
+        if little_endian {
+            data[i]
+        } else {
+            data[size - 1 - i]
+        }
+    };
+
+    let mut res: u64 = 0;
+    for i in (0..size.min(INTEGER_SIZE)).rev() {
+        res = (res << 8) | byte(i) as u64;
+    }
+
+    if size < INTEGER_SIZE {
+        if signed {
+            let mask = 1 << (size * 8 - 1);
+            res = (res ^ mask).wrapping_sub(mask);
+        }
+    } else if size > INTEGER_SIZE {
+        let extension = if signed && (res as i64) < 0 { 0xff } else { 0 };
+        for i in INTEGER_SIZE..size {
+            if byte(i) != extension {
+                return Err(PackError("integer does not fit into Lua integer"));
+            }
+        }
+    }
+    Ok(res as i64)
+}
+
+/// Writes a float of either 4 or 8 bytes.
+pub fn pack_float(out: &mut Vec<u8>, n: f64, little_endian: bool, size: usize) {
+    match (size, little_endian) {
+        (4, true) => out.extend_from_slice(&(n as f32).to_bits().to_le_bytes()),
+        (4, false) => out.extend_from_slice(&(n as f32).to_bits().to_be_bytes()),
+        (_, true) => out.extend_from_slice(&n.to_bits().to_le_bytes()),
+        (_, false) => out.extend_from_slice(&n.to_bits().to_be_bytes()),
+    }
+}
+
+/// Reads a float of either 4 or 8 bytes.
+pub fn unpack_float(data: &[u8], little_endian: bool, size: usize) -> f64 {
+    if size == 4 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&data[..4]);
+        f32::from_bits(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        }) as f64
+    } else {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&data[..8]);
+        f64::from_bits(if little_endian {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    }
+}
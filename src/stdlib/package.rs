@@ -0,0 +1,514 @@
+use std::fs::File;
+
+use gc_arena::{Collect, MutationContext};
+use gc_sequence::{self as sequence, Sequence};
+
+use crate::{
+    compile_named, io::buffered_read, Callback, CallbackResult, Closure, Error, Function, OsHost,
+    Root, RuntimeError, String, Table, ThreadSequence, TypeError, Value,
+};
+
+use super::args::string_arg;
+
+const DIRECTORY_SEPARATOR: &[u8] = b"/";
+const PATH_SEPARATOR: &[u8] = b";";
+const PATH_MARK: &[u8] = b"?";
+const EXECUTABLE_DIRECTORY: &[u8] = b"!";
+const IGNORE_MARK: &[u8] = b"-";
+
+const DEFAULT_PATH: &[u8] = b"/usr/local/share/lua/5.3/?.lua;/usr/local/share/lua/5.3/?/init.lua;\
+/usr/local/lib/lua/5.3/?.lua;/usr/local/lib/lua/5.3/?/init.lua;./?.lua;./?/init.lua";
+
+// The libraries which are already loaded by the time `package` is, and so are present in
+// `package.loaded` from the start.
+const LOADED_LIBRARIES: &[&[u8]] = &[
+    b"_G",
+    b"coroutine",
+    b"io",
+    b"math",
+    b"os",
+    b"string",
+    b"table",
+    b"utf8",
+];
+
+/// Loads the `package` library and `require`.  Must be loaded after every other library, so that
+/// they can be placed in `package.loaded`.
+///
+/// The initial `package.path` is taken from the `LUA_PATH_5_3` or `LUA_PATH` environment variables
+/// as seen through the given host, with any ";;" replaced by the default path.
+pub fn load_package<'gc>(
+    mc: MutationContext<'gc, '_>,
+    root: Root<'gc>,
+    env: Table<'gc>,
+    host: &dyn OsHost,
+) {
+    let package = Table::new(mc);
+    let loaded = Table::new(mc);
+    let preload = Table::new(mc);
+
+    for &name in LOADED_LIBRARIES {
+        let library = if name == b"_G" {
+            Value::Table(env)
+        } else {
+            env.get(String::new_static(name))
+        };
+        loaded.set(mc, String::new_static(name), library).unwrap();
+    }
+    loaded
+        .set(mc, String::new_static(b"package"), package)
+        .unwrap();
+
+    let mut config = Vec::new();
+    for part in &[
+        DIRECTORY_SEPARATOR,
+        PATH_SEPARATOR,
+        PATH_MARK,
+        EXECUTABLE_DIRECTORY,
+        IGNORE_MARK,
+    ] {
+        config.extend_from_slice(part);
+        config.push(b'\n');
+    }
+    package
+        .set(mc, String::new_static(b"config"), String::new(mc, &config))
+        .unwrap();
+
+    let path = match host
+        .getenv(b"LUA_PATH_5_3")
+        .or_else(|| host.getenv(b"LUA_PATH"))
+    {
+        Some(path) => {
+            replace(&path, b";;", b";\x01;")
+                .into_iter()
+                .fold(Vec::new(), |mut path, b| {
+                    if b == b'\x01' {
+                        path.extend_from_slice(DEFAULT_PATH);
+                    } else {
+                        path.push(b);
+                    }
+                    path
+                })
+        }
+        None => DEFAULT_PATH.to_vec(),
+    };
+    package
+        .set(mc, String::new_static(b"path"), String::new(mc, &path))
+        .unwrap();
+
+    package
+        .set(mc, String::new_static(b"loaded"), loaded)
+        .unwrap();
+    package
+        .set(mc, String::new_static(b"preload"), preload)
+        .unwrap();
+
+    package
+        .set(
+            mc,
+            String::new_static(b"searchpath"),
+            Callback::new_sequence(mc, |args| {
+                let name = string_arg(&args, 0)?.into_owned();
+                let path = string_arg(&args, 1)?.into_owned();
+                let separator = match args.get(2) {
+                    None | Some(Value::Nil) => b".".to_vec(),
+                    _ => string_arg(&args, 2)?.into_owned(),
+                };
+                let replacement = match args.get(3) {
+                    None | Some(Value::Nil) => DIRECTORY_SEPARATOR.to_vec(),
+                    _ => string_arg(&args, 3)?.into_owned(),
+                };
+
+                Ok(sequence::from_fn(move |mc| {
+                    Ok(CallbackResult::Return(
+                        match search_path(&name, &path, &separator, &replacement) {
+                            Ok(file_name) => vec![Value::String(String::new(mc, &file_name))],
+                            Err(message) => {
+                                vec![Value::Nil, Value::String(String::new(mc, &message))]
+                            }
+                        },
+                    ))
+                }))
+            }),
+        )
+        .unwrap();
+
+    let searchers = Table::new(mc);
+
+    searchers
+        .set(
+            mc,
+            1,
+            Callback::new_sequence_with(mc, preload, |preload, args| {
+                let name = module_name_arg(&args)?;
+                Ok(sequence::from_fn_with(
+                    (*preload, name),
+                    |mc, (preload, name)| {
+                        Ok(CallbackResult::Return(match preload.get(name) {
+                            Value::Nil => vec![not_found(
+                                mc,
+                                b"\n\tno field package.preload['",
+                                name,
+                                b"']",
+                            )],
+                            loader => vec![loader],
+                        }))
+                    },
+                ))
+            }),
+        )
+        .unwrap();
+
+    searchers
+        .set(
+            mc,
+            2,
+            Callback::new_sequence_with(mc, root.modules, |modules, args| {
+                let name = module_name_arg(&args)?;
+                Ok(sequence::from_fn_with(
+                    (*modules, name),
+                    |mc, (modules, name)| {
+                        Ok(CallbackResult::Return(match modules.get(name) {
+                            Value::Nil => {
+                                vec![not_found(mc, b"\n\tno native module '", name, b"'")]
+                            }
+                            loader => vec![loader],
+                        }))
+                    },
+                ))
+            }),
+        )
+        .unwrap();
+
+    searchers
+        .set(
+            mc,
+            3,
+            Callback::new_sequence_with(mc, (root, package), |&(root, package), args| {
+                let name = module_name_arg(&args)?;
+                let path = match package.get(String::new_static(b"path")) {
+                    Value::String(path) => path,
+                    _ => {
+                        return Err(RuntimeError(Value::String(String::new_static(
+                            b"'package.path' must be a string",
+                        )))
+                        .into());
+                    }
+                };
+
+                Ok(sequence::from_fn_with(
+                    (root, name, path),
+                    |mc, (root, name, path)| {
+                        let file_name = match search_path(
+                            name.as_bytes(),
+                            path.as_bytes(),
+                            b".",
+                            DIRECTORY_SEPARATOR,
+                        ) {
+                            Ok(file_name) => file_name,
+                            Err(message) => {
+                                return Ok(CallbackResult::Return(vec![Value::String(
+                                    String::new(mc, &message),
+                                )]));
+                            }
+                        };
+
+                        match load_file(mc, root, &file_name) {
+                            Ok(closure) => Ok(CallbackResult::Return(vec![
+                                Value::Function(Function::Closure(closure)),
+                                Value::String(String::new(mc, &file_name)),
+                            ])),
+                            Err(err) => {
+                                let mut message = b"error loading module '".to_vec();
+                                message.extend_from_slice(name.as_bytes());
+                                message.extend_from_slice(b"' from file '");
+                                message.extend_from_slice(&file_name);
+                                message.extend_from_slice(b"':\n\t");
+                                message.extend_from_slice(err.to_string().as_bytes());
+                                Err(RuntimeError(Value::String(String::new(mc, &message))).into())
+                            }
+                        }
+                    },
+                ))
+            }),
+        )
+        .unwrap();
+
+    package
+        .set(mc, String::new_static(b"searchers"), searchers)
+        .unwrap();
+
+    env.set(mc, String::new_static(b"package"), package)
+        .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"require"),
+        Callback::new_sequence_with(
+            mc,
+            (root, package, loaded),
+            |&(root, package, loaded), args| {
+                Ok(Require {
+                    root,
+                    name: module_name_arg(&args)?,
+                    package,
+                    loaded,
+                    state: RequireState::Start,
+                })
+            },
+        ),
+    )
+    .unwrap();
+}
+
+// `require` is written as a state machine which calls each searcher in turn and then the found
+// loader, each as a `ThreadSequence` on a new thread.
+#[derive(Collect)]
+#[collect(empty_drop)]
+struct Require<'gc> {
+    root: Root<'gc>,
+    name: String<'gc>,
+    package: Table<'gc>,
+    loaded: Table<'gc>,
+    state: RequireState<'gc>,
+}
+
+#[derive(Collect)]
+#[collect(empty_drop)]
+enum RequireState<'gc> {
+    Start,
+    Search {
+        searchers: Table<'gc>,
+        // The index of the searcher currently being called
+        index: i64,
+        // The collected messages of every searcher which did not find the module
+        message: Vec<u8>,
+        call: Option<ThreadSequence<'gc>>,
+    },
+    Load(ThreadSequence<'gc>),
+}
+
+impl<'gc> Sequence<'gc> for Require<'gc> {
+    type Output = Result<CallbackResult<'gc>, Error<'gc>>;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
+        match &mut self.state {
+            RequireState::Start => {
+                let module = self.loaded.get(self.name);
+                if module.to_bool() {
+                    return Some(Ok(CallbackResult::Return(vec![module])));
+                }
+
+                match self.package.get(String::new_static(b"searchers")) {
+                    Value::Table(searchers) => {
+                        self.state = RequireState::Search {
+                            searchers,
+                            index: 1,
+                            message: Vec::new(),
+                            call: None,
+                        };
+                        None
+                    }
+                    _ => Some(Err(RuntimeError(Value::String(String::new_static(
+                        b"'package.searchers' must be a table",
+                    )))
+                    .into())),
+                }
+            }
+
+            RequireState::Search {
+                searchers,
+                index,
+                message,
+                call,
+            } => {
+                let res = match call {
+                    None => match searchers.get(*index) {
+                        Value::Nil => {
+                            let mut buf = b"module '".to_vec();
+                            buf.extend_from_slice(self.name.as_bytes());
+                            buf.extend_from_slice(b"' not found:");
+                            buf.extend_from_slice(message);
+                            return Some(Err(
+                                RuntimeError(Value::String(String::new(mc, &buf))).into()
+                            ));
+                        }
+                        Value::Function(searcher) => {
+                            *call = Some(call_function(
+                                mc,
+                                self.root,
+                                searcher,
+                                &[Value::String(self.name)],
+                            ));
+                            return None;
+                        }
+                        searcher => {
+                            return Some(Err(TypeError {
+                                expected: "function",
+                                found: searcher.type_name(),
+                            }
+                            .into()));
+                        }
+                    },
+                    Some(call) => match call.step(mc)? {
+                        Ok(res) => res,
+                        Err(err) => return Some(Err(err)),
+                    },
+                };
+
+                match res.get(0).cloned().unwrap_or(Value::Nil) {
+                    Value::Function(loader) => {
+                        let data = res.get(1).cloned().unwrap_or(Value::Nil);
+                        self.state = RequireState::Load(call_function(
+                            mc,
+                            self.root,
+                            loader,
+                            &[Value::String(self.name), data],
+                        ));
+                        return None;
+                    }
+                    Value::String(s) => message.extend_from_slice(s.as_bytes()),
+                    _ => {}
+                }
+                *index += 1;
+                *call = None;
+                None
+            }
+
+            RequireState::Load(call) => match call.step(mc)? {
+                Err(err) => Some(Err(err)),
+                Ok(res) => Some(self.finish(mc, res.get(0).cloned().unwrap_or(Value::Nil))),
+            },
+        }
+    }
+}
+
+impl<'gc> Require<'gc> {
+    // A non-nil result of the loader becomes the module, otherwise whatever the loader itself placed
+    // in `package.loaded`, or finally `true`.
+    fn finish(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        module: Value<'gc>,
+    ) -> Result<CallbackResult<'gc>, Error<'gc>> {
+        if module != Value::Nil {
+            self.loaded.set(mc, self.name, module)?;
+        }
+        let module = match self.loaded.get(self.name) {
+            Value::Nil => {
+                self.loaded.set(mc, self.name, true)?;
+                Value::Boolean(true)
+            }
+            module => module,
+        };
+        Ok(CallbackResult::Return(vec![module]))
+    }
+}
+
+fn call_function<'gc>(
+    mc: MutationContext<'gc, '_>,
+    root: Root<'gc>,
+    function: Function<'gc>,
+    args: &[Value<'gc>],
+) -> ThreadSequence<'gc> {
+    ThreadSequence::call_function(mc, root.new_thread(mc, false), function, args).unwrap()
+}
+
+// Loads the Lua source file with the given name as a chunk with the global environment.
+fn load_file<'gc>(
+    mc: MutationContext<'gc, '_>,
+    root: Root<'gc>,
+    file_name: &[u8],
+) -> Result<Closure<'gc>, Error<'gc>> {
+    let file = buffered_read(File::open(bytes_to_path(file_name))?)?;
+    let mut chunk_name = b"@".to_vec();
+    chunk_name.extend_from_slice(file_name);
+    Ok(Closure::new(
+        mc,
+        compile_named(mc, root.interned_strings, &chunk_name, file)?,
+        Some(root.globals),
+    )?)
+}
+
+// Finds the first readable file given by a template in `path` with `name` substituted, after
+// replacing every `separator` in `name` with `replacement`.  If there is no such file, returns the
+// message listing every file which was tried.
+fn search_path(
+    name: &[u8],
+    path: &[u8],
+    separator: &[u8],
+    replacement: &[u8],
+) -> Result<Vec<u8>, Vec<u8>> {
+    let name = if separator.is_empty() {
+        name.to_vec()
+    } else {
+        replace(name, separator, replacement)
+    };
+
+    let mut message = Vec::new();
+    for template in path.split(|&b| b == PATH_SEPARATOR[0]) {
+        if template.is_empty() {
+            continue;
+        }
+        let file_name = replace(template, PATH_MARK, &name);
+        if File::open(bytes_to_path(&file_name)).is_ok() {
+            return Ok(file_name);
+        }
+        message.extend_from_slice(b"\n\tno file '");
+        message.extend_from_slice(&file_name);
+        message.push(b'\'');
+    }
+    Err(message)
+}
+
+// Replaces every non-overlapping occurrence of the non-empty `pattern` in `s`.
+fn replace(s: &[u8], pattern: &[u8], replacement: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        if s[i..].starts_with(pattern) {
+            out.extend_from_slice(replacement);
+            i += pattern.len();
+        } else {
+            out.push(s[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+fn not_found<'gc>(
+    mc: MutationContext<'gc, '_>,
+    prefix: &[u8],
+    name: String<'gc>,
+    suffix: &[u8],
+) -> Value<'gc> {
+    let mut message = prefix.to_vec();
+    message.extend_from_slice(name.as_bytes());
+    message.extend_from_slice(suffix);
+    Value::String(String::new(mc, &message))
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> &std::path::Path {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    OsStr::from_bytes(bytes).as_ref()
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> std::path::PathBuf {
+    std::string::String::from_utf8_lossy(bytes)
+        .into_owned()
+        .into()
+}
+
+fn module_name_arg<'gc>(args: &[Value<'gc>]) -> Result<String<'gc>, Error<'gc>> {
+    match args.get(0).cloned().unwrap_or(Value::Nil) {
+        Value::String(name) => Ok(name),
+        v => Err(TypeError {
+            expected: "string",
+            found: v.type_name(),
+        }
+        .into()),
+    }
+}
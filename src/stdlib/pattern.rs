@@ -0,0 +1,427 @@
+//! A byte oriented implementation of Lua 5.3 patterns, closely following the matcher in PUC-Rio
+//! Lua's `lstrlib.c`.
+
+const MAX_CAPTURES: usize = 32;
+const MAX_MATCH_DEPTH: usize = 200;
+const ESCAPE: u8 = b'%';
+const SPECIALS: &[u8] = b"^$*+?.([%-";
+
+/// An error caused by a malformed pattern or replacement string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternError(pub &'static str);
+
+/// A single capture from a successful match, as byte positions into the source string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capture {
+    Position(usize),
+    Slice(usize, usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CaptureLen {
+    Unfinished,
+    Position,
+    Len(usize),
+}
+
+pub struct Matcher<'a> {
+    source: &'a [u8],
+    pattern: &'a [u8],
+    level: usize,
+    captures: [(usize, CaptureLen); MAX_CAPTURES],
+    match_depth: usize,
+}
+
+impl<'a> Matcher<'a> {
+    /// The pattern must already have any leading '^' anchor removed, anchoring is handled by the
+    /// caller.
+    pub fn new(source: &'a [u8], pattern: &'a [u8]) -> Matcher<'a> {
+        Matcher {
+            source,
+            pattern,
+            level: 0,
+            captures: [(0, CaptureLen::Unfinished); MAX_CAPTURES],
+            match_depth: MAX_MATCH_DEPTH,
+        }
+    }
+
+    pub fn source(&self) -> &'a [u8] {
+        self.source
+    }
+
+    /// Attempts to match the whole pattern starting exactly at the given source position, returning
+    /// the end of the match if successful.
+    pub fn match_at(&mut self, start: usize) -> Result<Option<usize>, PatternError> {
+        self.level = 0;
+        self.match_depth = MAX_MATCH_DEPTH;
+        self.do_match(start, 0)
+    }
+
+    /// Returns the captures of the last successful match between `start` and `end`.  If the pattern
+    /// has no explicit captures and `whole_if_none` is true, the entire match is returned as the
+    /// only capture.
+    pub fn captures(
+        &self,
+        start: usize,
+        end: usize,
+        whole_if_none: bool,
+    ) -> Result<Vec<Capture>, PatternError> {
+        let count = if self.level == 0 && whole_if_none {
+            1
+        } else {
+            self.level
+        };
+        (0..count).map(|i| self.capture(i, start, end)).collect()
+    }
+
+    /// Returns a single capture of the last successful match between `start` and `end`, capture 0
+    /// is the entire match if the pattern has no explicit captures.
+    pub fn capture(&self, i: usize, start: usize, end: usize) -> Result<Capture, PatternError> {
+        if i >= self.level {
+            if i == 0 {
+                Ok(Capture::Slice(start, end))
+            } else {
+                Err(PatternError("invalid capture index"))
+            }
+        } else {
+            let (init, len) = self.captures[i];
+            match len {
+                CaptureLen::Unfinished => Err(PatternError("unfinished capture")),
+                CaptureLen::Position => Ok(Capture::Position(init)),
+                CaptureLen::Len(len) => Ok(Capture::Slice(init, init + len)),
+            }
+        }
+    }
+
+    fn do_match(&mut self, s: usize, p: usize) -> Result<Option<usize>, PatternError> {
+        if self.match_depth == 0 {
+            return Err(PatternError("pattern too complex"));
+        }
+        self.match_depth -= 1;
+        let res = self.do_match_inner(s, p);
+        self.match_depth += 1;
+        res
+    }
+
+    fn do_match_inner(
+        &mut self,
+        mut s: usize,
+        mut p: usize,
+    ) -> Result<Option<usize>, PatternError> {
+        loop {
+            if p == self.pattern.len() {
+                return Ok(Some(s));
+            }
+
+            match self.pattern[p] {
+                b'(' => {
+                    return if self.pattern.get(p + 1) == Some(&b')') {
+                        self.start_capture(s, p + 2, CaptureLen::Position)
+                    } else {
+                        self.start_capture(s, p + 1, CaptureLen::Unfinished)
+                    };
+                }
+                b')' => return self.end_capture(s, p + 1),
+                b'$' if p + 1 == self.pattern.len() => {
+                    return Ok(if s == self.source.len() {
+                        Some(s)
+                    } else {
+                        None
+                    });
+                }
+                ESCAPE if self.pattern.get(p + 1) == Some(&b'b') => {
+                    match self.match_balance(s, p + 2)? {
+                        Some(e) => {
+                            s = e;
+                            p += 4;
+                            continue;
+                        }
+                        None => return Ok(None),
+                    }
+                }
+                ESCAPE if self.pattern.get(p + 1) == Some(&b'f') => {
+                    p += 2;
+                    if self.pattern.get(p) != Some(&b'[') {
+                        return Err(PatternError("missing '[' after '%f' in pattern"));
+                    }
+                    let ep = self.class_end(p)?;
+                    let previous = if s == 0 { 0 } else { self.source[s - 1] };
+                    let current = self.source.get(s).cloned().unwrap_or(0);
+                    if !self.match_bracket_class(previous, p, ep - 1)
+                        && self.match_bracket_class(current, p, ep - 1)
+                    {
+                        p = ep;
+                        continue;
+                    }
+                    return Ok(None);
+                }
+                ESCAPE
+                    if self
+                        .pattern
+                        .get(p + 1)
+                        .map(|c| c.is_ascii_digit())
+                        .unwrap_or(false) =>
+                {
+                    match self.match_capture(s, self.pattern[p + 1])? {
+                        Some(e) => {
+                            s = e;
+                            p += 2;
+                            continue;
+                        }
+                        None => return Ok(None),
+                    }
+                }
+                _ => {
+                    let ep = self.class_end(p)?;
+                    let suffix = self.pattern.get(ep).cloned();
+                    if !self.single_match(s, p, ep) {
+                        match suffix {
+                            Some(b'*') | Some(b'?') | Some(b'-') => {
+                                p = ep + 1;
+                                continue;
+                            }
+                            _ => return Ok(None),
+                        }
+                    }
+
+                    match suffix {
+                        Some(b'?') => {
+                            if let Some(res) = self.do_match(s + 1, ep + 1)? {
+                                return Ok(Some(res));
+                            }
+                            p = ep + 1;
+                        }
+                        Some(b'+') => return self.max_expand(s + 1, p, ep),
+                        Some(b'*') => return self.max_expand(s, p, ep),
+                        Some(b'-') => return self.min_expand(s, p, ep),
+                        _ => {
+                            s += 1;
+                            p = ep;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Returns the pattern position just past the single character class starting at `p`.
+    fn class_end(&self, mut p: usize) -> Result<usize, PatternError> {
+        let c = self.pattern[p];
+        p += 1;
+        if c == ESCAPE {
+            if p == self.pattern.len() {
+                return Err(PatternError("malformed pattern (ends with '%')"));
+            }
+            Ok(p + 1)
+        } else if c == b'[' {
+            if self.pattern.get(p) == Some(&b'^') {
+                p += 1;
+            }
+            // The first character of a set is never treated as its closing ']'.
+            loop {
+                if p >= self.pattern.len() {
+                    return Err(PatternError("malformed pattern (missing ']')"));
+                }
+                let c = self.pattern[p];
+                p += 1;
+                if c == ESCAPE && p < self.pattern.len() {
+                    p += 1;
+                }
+                if self.pattern.get(p) == Some(&b']') {
+                    return Ok(p + 1);
+                }
+            }
+        } else {
+            Ok(p)
+        }
+    }
+
+    fn single_match(&self, s: usize, p: usize, ep: usize) -> bool {
+        match self.source.get(s) {
+            None => false,
+            Some(&c) => match self.pattern[p] {
+                b'.' => true,
+                ESCAPE => match_class(c, self.pattern[p + 1]),
+                b'[' => self.match_bracket_class(c, p, ep - 1),
+                pc => pc == c,
+            },
+        }
+    }
+
+    // Matches a character against the set starting with the '[' at `p` and ending with the ']' at
+    // `ec`.
+    fn match_bracket_class(&self, c: u8, mut p: usize, ec: usize) -> bool {
+        let mut negate = false;
+        if self.pattern[p + 1] == b'^' {
+            negate = true;
+            p += 1;
+        }
+
+        p += 1;
+        while p < ec {
+            if self.pattern[p] == ESCAPE {
+                p += 1;
+                if match_class(c, self.pattern[p]) {
+                    return !negate;
+                }
+            } else if self.pattern[p + 1] == b'-' && p + 2 < ec {
+                if self.pattern[p] <= c && c <= self.pattern[p + 2] {
+                    return !negate;
+                }
+                p += 2;
+            } else if self.pattern[p] == c {
+                return !negate;
+            }
+            p += 1;
+        }
+        negate
+    }
+
+    fn max_expand(&mut self, s: usize, p: usize, ep: usize) -> Result<Option<usize>, PatternError> {
+        let mut i = 0;
+        while self.single_match(s + i, p, ep) {
+            i += 1;
+        }
+        loop {
+            if let Some(res) = self.do_match(s + i, ep + 1)? {
+                return Ok(Some(res));
+            }
+            if i == 0 {
+                return Ok(None);
+            }
+            i -= 1;
+        }
+    }
+
+    fn min_expand(
+        &mut self,
+        mut s: usize,
+        p: usize,
+        ep: usize,
+    ) -> Result<Option<usize>, PatternError> {
+        loop {
+            if let Some(res) = self.do_match(s, ep + 1)? {
+                return Ok(Some(res));
+            } else if self.single_match(s, p, ep) {
+                s += 1;
+            } else {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn start_capture(
+        &mut self,
+        s: usize,
+        p: usize,
+        len: CaptureLen,
+    ) -> Result<Option<usize>, PatternError> {
+        if self.level >= MAX_CAPTURES {
+            return Err(PatternError("too many captures"));
+        }
+        self.captures[self.level] = (s, len);
+        self.level += 1;
+        let res = self.do_match(s, p)?;
+        if res.is_none() {
+            self.level -= 1;
+        }
+        Ok(res)
+    }
+
+    fn end_capture(&mut self, s: usize, p: usize) -> Result<Option<usize>, PatternError> {
+        let l = (0..self.level)
+            .rev()
+            .find(|&l| self.captures[l].1 == CaptureLen::Unfinished)
+            .ok_or(PatternError("invalid pattern capture"))?;
+        self.captures[l].1 = CaptureLen::Len(s - self.captures[l].0);
+        let res = self.do_match(s, p)?;
+        if res.is_none() {
+            self.captures[l].1 = CaptureLen::Unfinished;
+        }
+        Ok(res)
+    }
+
+    fn match_balance(&self, s: usize, p: usize) -> Result<Option<usize>, PatternError> {
+        if p + 1 >= self.pattern.len() {
+            return Err(PatternError(
+                "malformed pattern (missing arguments to '%b')",
+            ));
+        }
+
+        let (open, close) = (self.pattern[p], self.pattern[p + 1]);
+        if self.source.get(s) != Some(&open) {
+            return Ok(None);
+        }
+
+        let mut depth = 1;
+        for i in s + 1..self.source.len() {
+            let c = self.source[i];
+            if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(Some(i + 1));
+                }
+            } else if c == open {
+                depth += 1;
+            }
+        }
+        Ok(None)
+    }
+
+    // Matches a back reference to a previous capture, `l` is the ASCII digit following the '%'.
+    fn match_capture(&self, s: usize, l: u8) -> Result<Option<usize>, PatternError> {
+        let l = (l as usize).wrapping_sub(b'1' as usize);
+        if l >= self.level || self.captures[l].1 == CaptureLen::Unfinished {
+            return Err(PatternError("invalid capture index"));
+        }
+
+        match self.captures[l] {
+            (init, CaptureLen::Len(len)) => {
+                if self.source.len() - s >= len
+                    && self.source[init..init + len] == self.source[s..s + len]
+                {
+                    Ok(Some(s + len))
+                } else {
+                    Ok(None)
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Returns true if the pattern contains no special characters and can be matched as a plain
+/// substring.
+pub fn is_plain(pattern: &[u8]) -> bool {
+    !pattern.iter().any(|c| SPECIALS.contains(c))
+}
+
+/// Finds the first occurrence of `needle` in `haystack`.
+pub fn find_plain(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        Some(0)
+    } else {
+        haystack.windows(needle.len()).position(|w| w == needle)
+    }
+}
+
+fn match_class(c: u8, class: u8) -> bool {
+    let res = match class.to_ascii_lowercase() {
+        b'a' => c.is_ascii_alphabetic(),
+        b'c' => c.is_ascii_control(),
+        b'd' => c.is_ascii_digit(),
+        b'g' => c.is_ascii_graphic(),
+        b'l' => c.is_ascii_lowercase(),
+        b'p' => c.is_ascii_punctuation(),
+        b's' => c == b' ' || (c >= b'\t' && c <= b'\r'),
+        b'u' => c.is_ascii_uppercase(),
+        b'w' => c.is_ascii_alphanumeric(),
+        b'x' => c.is_ascii_hexdigit(),
+        _ => return class == c,
+    };
+    if class.is_ascii_uppercase() {
+        !res
+    } else {
+        res
+    }
+}
@@ -0,0 +1,1071 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+
+use gc_arena::{Collect, MutationContext};
+use gc_sequence::{self as sequence, Sequence};
+
+use crate::{
+    format::{FormatError, FormatSpec},
+    meta_ops::{self, MetaResult},
+    Callback, CallbackResult, Continuation, Error, Function, InternedStringSet, Root, RuntimeError,
+    String, Table, TypeError, Value,
+};
+
+use super::args::{integer_arg, string_arg};
+use super::base::to_strings;
+use super::pack::{self, PackError, PackFormat, PackOption};
+use super::pattern::{self, Capture, Matcher, PatternError};
+
+pub fn load_string<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<'gc>) {
+    let string = Table::new(mc);
+
+    string
+        .set(
+            mc,
+            String::new_static(b"byte"),
+            Callback::new_immediate(mc, |args| {
+                let s = string_arg(&args, 0)?;
+                let len = s.len() as i64;
+                let i = integer_arg(&args, 1)?.unwrap_or(1);
+                let j = integer_arg(&args, 2)?.unwrap_or(i);
+
+                let start = relative_start(i, len);
+                let end = relative_end(j, len);
+                if start > end {
+                    return Ok(CallbackResult::Return(vec![]));
+                }
+
+                Ok(CallbackResult::Return(
+                    s[start as usize - 1..end as usize]
+                        .iter()
+                        .map(|&b| Value::Integer(b as i64))
+                        .collect(),
+                ))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"char"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                let mut res = Vec::with_capacity(args.len());
+                for i in 0..args.len() {
+                    match integer_arg(&args, i)? {
+                        Some(c) if c >= 0 && c <= 255 => res.push(c as u8),
+                        _ => {
+                            return Err(RuntimeError(Value::String(String::new_static(
+                                b"Bad argument to char",
+                            )))
+                            .into());
+                        }
+                    }
+                }
+                Ok(return_string(interned_strings, res))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"find"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                Ok(return_values(interned_strings, string_find(&args, true)?))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"format"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                // Check every directive first, collecting the arguments of `%s` directives, so
+                // that bad arguments are reported before any `__tostring` metamethod is called.
+                let mut string_args = Vec::new();
+                string_format(&args, |_, _, arg| {
+                    string_args.push(arg);
+                    Ok(())
+                })?;
+
+                Ok(sequence::from_fn_with(
+                    (interned_strings, args, string_args),
+                    |mc, (interned_strings, args, string_args)| {
+                        to_strings(
+                            mc,
+                            string_args,
+                            Vec::new(),
+                            (interned_strings, args),
+                            |mc, (interned_strings, args), strings| {
+                                let mut strings = strings.into_iter();
+                                let out = string_format(&args, |out, spec, _| {
+                                    let s = strings.next().expect("missing converted string");
+                                    spec.write_string(out, s.as_bytes());
+                                    Ok(())
+                                })?;
+                                Ok(CallbackResult::Return(vec![Value::String(
+                                    interned_strings.new_string(mc, &out),
+                                )]))
+                            },
+                        )
+                    },
+                ))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"gmatch"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                string_arg(&args, 0)?;
+                string_arg(&args, 1)?;
+                Ok(sequence::from_fn_with(
+                    (interned_strings, args),
+                    |mc, (interned_strings, args)| {
+                        let source = string_value(mc, &args, 0)?;
+                        let pattern = string_value(mc, &args, 1)?;
+                        // The position to start the next search from, and the end of the last
+                        // match.
+                        let state = Cell::new((0, None));
+
+                        Ok(CallbackResult::Return(vec![Value::Function(
+                            Function::Callback(Callback::new_sequence_with(
+                                mc,
+                                (interned_strings, source, pattern),
+                                move |&(interned_strings, source, pattern), _| {
+                                    let source = source.as_bytes();
+                                    let mut matcher = Matcher::new(source, pattern.as_bytes());
+                                    let (mut start, last_match) = state.get();
+                                    while start <= source.len() {
+                                        if let Some(end) =
+                                            matcher.match_at(start).map_err(pattern_error)?
+                                        {
+                                            if Some(end) != last_match {
+                                                state.set((end, Some(end)));
+                                                let captures = matcher
+                                                    .captures(start, end, true)
+                                                    .map_err(pattern_error)?;
+                                                return Ok(return_values(
+                                                    interned_strings,
+                                                    capture_return_values(source, &captures),
+                                                ));
+                                            }
+                                        }
+                                        start += 1;
+                                    }
+                                    state.set((start, last_match));
+                                    Ok(return_values(interned_strings, vec![]))
+                                },
+                            )),
+                        )]))
+                    },
+                ))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"gsub"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                string_arg(&args, 0)?;
+                string_arg(&args, 1)?;
+                match args.get(2).cloned().unwrap_or(Value::Nil) {
+                    Value::String(_)
+                    | Value::Integer(_)
+                    | Value::Number(_)
+                    | Value::Table(_)
+                    | Value::Function(_) => {}
+                    v => {
+                        return Err(TypeError {
+                            expected: "string/function/table",
+                            found: v.type_name(),
+                        }
+                        .into());
+                    }
+                }
+                let max_n = integer_arg(&args, 3)?;
+
+                Ok(sequence::from_fn_with(
+                    (interned_strings, args),
+                    move |mc, (interned_strings, args)| {
+                        let source = string_value(mc, &args, 0)?;
+                        let pattern = string_value(mc, &args, 1)?;
+                        let replacement = match args[2] {
+                            Value::Integer(_) | Value::Number(_) => {
+                                Value::String(string_value(mc, &args, 2)?)
+                            }
+                            v => v,
+                        };
+                        gsub_step(
+                            mc,
+                            GSubState {
+                                interned_strings,
+                                source,
+                                pattern,
+                                anchor: pattern.as_bytes().first() == Some(&b'^'),
+                                replacement,
+                                max_n: max_n.unwrap_or(source.as_bytes().len() as i64 + 1),
+                                n: 0,
+                                position: 0,
+                                match_start: 0,
+                                last_match: None,
+                                result: Vec::new(),
+                            },
+                        )
+                    },
+                ))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"len"),
+            Callback::new_immediate(mc, |args| {
+                let s = string_arg(&args, 0)?;
+                Ok(CallbackResult::Return(vec![Value::Integer(s.len() as i64)]))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"lower"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                let s = string_arg(&args, 0)?;
+                Ok(return_string(interned_strings, s.to_ascii_lowercase()))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"match"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                Ok(return_values(interned_strings, string_find(&args, false)?))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"pack"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                let fmt = string_arg(&args, 0)?;
+                let mut format = PackFormat::new(&fmt);
+                let mut out = Vec::new();
+                let mut next_arg = 1;
+
+                while let Some(item) = format.next_item(out.len()) {
+                    let item = item.map_err(pack_error)?;
+                    out.extend((0..item.align_padding).map(|_| 0));
+
+                    let little_endian = format.little_endian();
+                    match item.option {
+                        PackOption::Int | PackOption::Uint => {
+                            let signed = item.option == PackOption::Int;
+                            let n = check_integer(
+                                args.get(next_arg).cloned().unwrap_or(Value::Nil),
+                            )?;
+                            if !pack::int_fits(n, item.size, signed) {
+                                return Err(pack_error(PackError(if signed {
+                                    "integer overflow"
+                                } else {
+                                    "unsigned overflow"
+                                })));
+                            }
+                            pack::pack_int(
+                                &mut out,
+                                n as u64,
+                                little_endian,
+                                item.size,
+                                signed && n < 0,
+                            );
+                        }
+                        PackOption::Float => {
+                            let arg = args.get(next_arg).cloned().unwrap_or(Value::Nil);
+                            match arg.to_number() {
+                                Some(n) => pack::pack_float(&mut out, n, little_endian, item.size),
+                                None => {
+                                    return Err(TypeError {
+                                        expected: "number",
+                                        found: arg.type_name(),
+                                    }
+                                    .into());
+                                }
+                            }
+                        }
+                        PackOption::Char => {
+                            let s = string_arg(&args, next_arg)?;
+                            if s.len() > item.size {
+                                return Err(pack_error(PackError("string longer than given size")));
+                            }
+                            out.extend_from_slice(&s);
+                            out.extend((s.len()..item.size).map(|_| 0));
+                        }
+                        PackOption::String => {
+                            let s = string_arg(&args, next_arg)?;
+                            if !pack::int_fits(s.len() as i64, item.size, false) {
+                                return Err(pack_error(PackError(
+                                    "string length does not fit in given size",
+                                )));
+                            }
+                            pack::pack_int(
+                                &mut out,
+                                s.len() as u64,
+                                little_endian,
+                                item.size,
+                                false,
+                            );
+                            out.extend_from_slice(&s);
+                        }
+                        PackOption::ZString => {
+                            let s = string_arg(&args, next_arg)?;
+                            if s.contains(&0) {
+                                return Err(pack_error(PackError("string contains zeros")));
+                            }
+                            out.extend_from_slice(&s);
+                            out.push(0);
+                        }
+                        PackOption::Padding => {
+                            out.push(0);
+                            continue;
+                        }
+                        PackOption::PadAlign | PackOption::Nop => continue,
+                    }
+                    next_arg += 1;
+                }
+
+                Ok(return_string(interned_strings, out))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"packsize"),
+            Callback::new_immediate(mc, |args| {
+                let fmt = string_arg(&args, 0)?;
+                let mut format = PackFormat::new(&fmt);
+                let mut total_size: usize = 0;
+
+                while let Some(item) = format.next_item(total_size) {
+                    let item = item.map_err(pack_error)?;
+                    match item.option {
+                        PackOption::String | PackOption::ZString => {
+                            return Err(pack_error(PackError("variable-length format")));
+                        }
+                        _ => {}
+                    }
+                    total_size = total_size
+                        .checked_add(item.align_padding + item.size)
+                        .filter(|&s| s <= i64::max_value() as usize)
+                        .ok_or_else(|| pack_error(PackError("format result too large")))?;
+                }
+
+                Ok(CallbackResult::Return(vec![Value::Integer(
+                    total_size as i64,
+                )]))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"rep"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                let s = string_arg(&args, 0)?;
+                let n = match integer_arg(&args, 1)? {
+                    Some(n) => n,
+                    None => {
+                        return Err(TypeError {
+                            expected: "integer",
+                            found: "nil",
+                        }
+                        .into());
+                    }
+                };
+                let sep = match args.get(2).cloned().unwrap_or(Value::Nil) {
+                    Value::Nil => Cow::Borrowed(&b""[..]),
+                    _ => string_arg(&args, 2)?,
+                };
+
+                if n <= 0 {
+                    return Ok(return_string(interned_strings, Vec::new()));
+                }
+
+                let n = n as usize;
+                let total_len = s
+                    .len()
+                    .checked_add(sep.len())
+                    .and_then(|l| l.checked_mul(n))
+                    .ok_or_else(|| {
+                        RuntimeError(Value::String(String::new_static(
+                            b"resulting string too large",
+                        )))
+                    })?;
+
+                let mut res = Vec::with_capacity(total_len);
+                for i in 0..n {
+                    if i != 0 {
+                        res.extend_from_slice(&sep);
+                    }
+                    res.extend_from_slice(&s);
+                }
+                Ok(return_string(interned_strings, res))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"reverse"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                let mut res = string_arg(&args, 0)?.into_owned();
+                res.reverse();
+                Ok(return_string(interned_strings, res))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"sub"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                let s = string_arg(&args, 0)?;
+                let len = s.len() as i64;
+                let i = integer_arg(&args, 1)?.unwrap_or(1);
+                let j = integer_arg(&args, 2)?.unwrap_or(-1);
+
+                let start = relative_start(i, len);
+                let end = relative_end(j, len);
+                Ok(return_string(
+                    interned_strings,
+                    if start > end {
+                        Vec::new()
+                    } else {
+                        s[start as usize - 1..end as usize].to_vec()
+                    },
+                ))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"unpack"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                string_arg(&args, 0)?;
+                string_arg(&args, 1)?;
+                integer_arg(&args, 2)?;
+                Ok(sequence::from_fn_with(
+                    (interned_strings, args),
+                    |mc, (interned_strings, args)| {
+                        let fmt = string_arg(&args, 0)?;
+                        let data = string_arg(&args, 1)?;
+                        let len = data.len() as i64;
+                        let mut pos = match integer_arg(&args, 2)?.unwrap_or(1) {
+                            i if i >= 0 => i,
+                            i if i < -len => 0,
+                            i => len + i + 1,
+                        } - 1;
+                        if pos < 0 || pos > len {
+                            return Err(pack_error(PackError("initial position out of string")));
+                        }
+
+                        let mut format = PackFormat::new(&fmt);
+                        let mut res = Vec::new();
+                        while let Some(item) = format.next_item(pos as usize) {
+                            let item = item.map_err(pack_error)?;
+                            let mut start = pos as usize + item.align_padding;
+                            if item.align_padding + item.size > data.len() - pos as usize {
+                                return Err(pack_error(PackError("data string too short")));
+                            }
+
+                            let little_endian = format.little_endian();
+                            match item.option {
+                                PackOption::Int | PackOption::Uint => {
+                                    res.push(Value::Integer(
+                                        pack::unpack_int(
+                                            &data[start..],
+                                            little_endian,
+                                            item.size,
+                                            item.option == PackOption::Int,
+                                        )
+                                        .map_err(pack_error)?,
+                                    ));
+                                }
+                                PackOption::Float => {
+                                    res.push(Value::Number(pack::unpack_float(
+                                        &data[start..],
+                                        little_endian,
+                                        item.size,
+                                    )));
+                                }
+                                PackOption::Char => {
+                                    res.push(Value::String(
+                                        interned_strings
+                                            .new_string(mc, &data[start..start + item.size]),
+                                    ));
+                                }
+                                PackOption::String => {
+                                    let len = pack::unpack_int(
+                                        &data[start..],
+                                        little_endian,
+                                        item.size,
+                                        false,
+                                    )
+                                    .map_err(pack_error)?
+                                        as u64;
+                                    let data_start = start + item.size;
+                                    if len > (data.len() - data_start) as u64 {
+                                        return Err(pack_error(PackError("data string too short")));
+                                    }
+                                    let len = len as usize;
+                                    res.push(Value::String(
+                                        interned_strings
+                                            .new_string(mc, &data[data_start..data_start + len]),
+                                    ));
+                                    start += len;
+                                }
+                                PackOption::ZString => {
+                                    let len = match data[start..].iter().position(|&c| c == 0) {
+                                        Some(len) => len,
+                                        None => {
+                                            return Err(pack_error(PackError(
+                                                "unfinished string for format 'z'",
+                                            )));
+                                        }
+                                    };
+                                    res.push(Value::String(
+                                        interned_strings.new_string(mc, &data[start..start + len]),
+                                    ));
+                                    start += len + 1;
+                                }
+                                PackOption::Padding | PackOption::PadAlign | PackOption::Nop => {}
+                            }
+                            pos = (start + item.size) as i64;
+                        }
+
+                        res.push(Value::Integer(pos + 1));
+                        Ok(CallbackResult::Return(res))
+                    },
+                ))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"upper"),
+            Callback::new_sequence_with(mc, root.interned_strings, |&interned_strings, args| {
+                let s = string_arg(&args, 0)?;
+                Ok(return_string(interned_strings, s.to_ascii_uppercase()))
+            }),
+        )
+        .unwrap();
+
+    root.string_metatable
+        .set(mc, String::new_static(b"__index"), string)
+        .unwrap();
+
+    env.set(mc, String::new_static(b"string"), string).unwrap();
+}
+
+// Translates a possibly negative Lua string index into a position in the range [1, len + 1].
+fn relative_start(i: i64, len: i64) -> i64 {
+    if i > 0 {
+        i.min(len + 1)
+    } else if i == 0 || i < -len {
+        1
+    } else {
+        len + i + 1
+    }
+}
+
+// Translates a possibly negative Lua string index into a position in the range [0, len].
+fn relative_end(j: i64, len: i64) -> i64 {
+    if j > len {
+        len
+    } else if j >= 0 {
+        j
+    } else if j < -len {
+        0
+    } else {
+        len + j + 1
+    }
+}
+
+fn return_string<'gc>(
+    interned_strings: InternedStringSet<'gc>,
+    bytes: Vec<u8>,
+) -> impl Sequence<'gc, Output = Result<CallbackResult<'gc>, Error<'gc>>> {
+    sequence::from_fn_with(interned_strings, move |mc, interned_strings| {
+        Ok(CallbackResult::Return(vec![Value::String(
+            interned_strings.new_string(mc, &bytes),
+        )]))
+    })
+}
+
+// Like `string_arg`, but produces a `String` value, allocating a new one only if the argument is a
+// number.
+fn string_value<'gc>(
+    mc: MutationContext<'gc, '_>,
+    args: &[Value<'gc>],
+    i: usize,
+) -> Result<String<'gc>, Error<'gc>> {
+    match args.get(i) {
+        Some(Value::String(s)) => Ok(*s),
+        _ => Ok(String::new(mc, &string_arg(args, i)?)),
+    }
+}
+
+fn pattern_error<'gc>(err: PatternError) -> Error<'gc> {
+    RuntimeError(Value::String(String::new_static(err.0.as_bytes()))).into()
+}
+
+// Formats the arguments to `string.format`, passing the argument of each `%s` directive to
+// `write_string` to be converted and written.
+fn string_format<'gc, F>(args: &[Value<'gc>], mut write_string: F) -> Result<Vec<u8>, Error<'gc>>
+where
+    F: FnMut(&mut Vec<u8>, &FormatSpec, Value<'gc>) -> Result<(), Error<'gc>>,
+{
+    let fmt = string_arg(args, 0)?;
+    let mut out = Vec::new();
+    let mut next_arg = 1;
+
+    let mut i = 0;
+    while i < fmt.len() {
+        let c = fmt[i];
+        i += 1;
+        if c != b'%' {
+            out.push(c);
+            continue;
+        } else if fmt.get(i) == Some(&b'%') {
+            out.push(b'%');
+            i += 1;
+            continue;
+        }
+
+        let (spec, len) = FormatSpec::parse(&fmt[i..]).map_err(format_error)?;
+        i += len;
+
+        let arg = match args.get(next_arg) {
+            Some(&arg) => arg,
+            None => {
+                return Err(RuntimeError(Value::String(String::new_static(
+                    b"bad argument to format (no value)",
+                )))
+                .into());
+            }
+        };
+        next_arg += 1;
+
+        match spec.conversion {
+            b'd' | b'i' | b'u' | b'o' | b'x' | b'X' => {
+                spec.write_integer(&mut out, check_integer(arg)?);
+            }
+            b'c' => spec.write_char(&mut out, check_integer(arg)? as u8),
+            b'a' | b'A' | b'e' | b'E' | b'f' | b'g' | b'G' => match arg.to_number() {
+                Some(n) => spec.write_float(&mut out, n),
+                None => {
+                    return Err(TypeError {
+                        expected: "number",
+                        found: arg.type_name(),
+                    }
+                    .into());
+                }
+            },
+            b's' => write_string(&mut out, &spec, arg)?,
+            b'q' => write_quoted(&mut out, arg)?,
+            _ => {
+                return Err(format_error(FormatError("invalid conversion to format")));
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn format_error<'gc>(err: FormatError) -> Error<'gc> {
+    RuntimeError(Value::String(String::new_static(err.0.as_bytes()))).into()
+}
+
+fn pack_error<'gc>(err: PackError) -> Error<'gc> {
+    RuntimeError(Value::String(String::new_static(err.0.as_bytes()))).into()
+}
+
+fn check_integer<'gc>(arg: Value<'gc>) -> Result<i64, Error<'gc>> {
+    match arg.to_integer() {
+        Some(i) => Ok(i),
+        None if arg.to_number().is_some() => Err(RuntimeError(Value::String(String::new_static(
+            b"number has no integer representation",
+        )))
+        .into()),
+        None => Err(TypeError {
+            expected: "number",
+            found: arg.type_name(),
+        }
+        .into()),
+    }
+}
+
+// Writes a value for the `%q` format directive as a Lua literal that reads back as the same value.
+fn write_quoted<'gc>(out: &mut Vec<u8>, arg: Value<'gc>) -> Result<(), Error<'gc>> {
+    match arg {
+        Value::String(s) => {
+            let s = s.as_bytes();
+            out.push(b'"');
+            for (i, &c) in s.iter().enumerate() {
+                if c == b'"' || c == b'\\' || c == b'\n' {
+                    out.push(b'\\');
+                    out.push(c);
+                } else if c.is_ascii_control() {
+                    // Decimal escapes must be padded if a digit follows them.
+                    if s.get(i + 1).map(|c| c.is_ascii_digit()).unwrap_or(false) {
+                        out.extend_from_slice(format!("\\{:03}", c).as_bytes());
+                    } else {
+                        out.extend_from_slice(format!("\\{}", c).as_bytes());
+                    }
+                } else {
+                    out.push(c);
+                }
+            }
+            out.push(b'"');
+        }
+        // The minimum integer cannot be written as a negated decimal literal, but a hex literal
+        // wraps around to it.
+        Value::Integer(i) if i == i64::min_value() => out.extend_from_slice(b"0x8000000000000000"),
+        Value::Integer(i) => out.extend_from_slice(i.to_string().as_bytes()),
+        Value::Number(n) => {
+            if n == f64::INFINITY {
+                out.extend_from_slice(b"1e9999");
+            } else if n == f64::NEG_INFINITY {
+                out.extend_from_slice(b"-1e9999");
+            } else if n.is_nan() {
+                out.extend_from_slice(b"(0/0)");
+            } else {
+                // Hex floats are exact and always read back as floats.
+                let spec = FormatSpec {
+                    conversion: b'a',
+                    ..Default::default()
+                };
+                spec.write_float(out, n);
+            }
+        }
+        Value::Nil | Value::Boolean(_) => arg.display(out)?,
+        _ => {
+            return Err(RuntimeError(Value::String(String::new_static(
+                b"value has no literal form",
+            )))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+// Values returned from the string library that are computed before a `MutationContext` is
+// available to allocate strings.
+enum ReturnValue {
+    Nil,
+    Integer(i64),
+    String(Vec<u8>),
+}
+
+fn return_values<'gc>(
+    interned_strings: InternedStringSet<'gc>,
+    values: Vec<ReturnValue>,
+) -> impl Sequence<'gc, Output = Result<CallbackResult<'gc>, Error<'gc>>> {
+    sequence::from_fn_with(interned_strings, move |mc, interned_strings| {
+        Ok(CallbackResult::Return(
+            values
+                .into_iter()
+                .map(|v| match v {
+                    ReturnValue::Nil => Value::Nil,
+                    ReturnValue::Integer(i) => Value::Integer(i),
+                    ReturnValue::String(s) => Value::String(interned_strings.new_string(mc, &s)),
+                })
+                .collect(),
+        ))
+    })
+}
+
+fn capture_return_values(source: &[u8], captures: &[Capture]) -> Vec<ReturnValue> {
+    captures
+        .iter()
+        .map(|c| match *c {
+            Capture::Position(p) => ReturnValue::Integer(p as i64 + 1),
+            Capture::Slice(start, end) => ReturnValue::String(source[start..end].to_vec()),
+        })
+        .collect()
+}
+
+fn capture_value<'gc>(
+    mc: MutationContext<'gc, '_>,
+    interned_strings: InternedStringSet<'gc>,
+    source: &[u8],
+    capture: Capture,
+) -> Value<'gc> {
+    match capture {
+        Capture::Position(p) => Value::Integer(p as i64 + 1),
+        Capture::Slice(start, end) => {
+            Value::String(interned_strings.new_string(mc, &source[start..end]))
+        }
+    }
+}
+
+// Shared implementation of `string.find` and `string.match`.
+fn string_find<'gc>(args: &[Value<'gc>], find: bool) -> Result<Vec<ReturnValue>, Error<'gc>> {
+    let s = string_arg(args, 0)?;
+    let pattern = string_arg(args, 1)?;
+    let len = s.len() as i64;
+    let init = match integer_arg(args, 2)?.unwrap_or(1) {
+        i if i >= 0 => i,
+        i if i < -len => 0,
+        i => len + i + 1,
+    }
+    .max(1);
+    if init > len + 1 {
+        return Ok(vec![ReturnValue::Nil]);
+    }
+    let init = init as usize - 1;
+
+    if find && (args.get(3).cloned().unwrap_or(Value::Nil).to_bool() || pattern::is_plain(&pattern))
+    {
+        if let Some(i) = pattern::find_plain(&s[init..], &pattern) {
+            let start = init + i;
+            return Ok(vec![
+                ReturnValue::Integer(start as i64 + 1),
+                ReturnValue::Integer((start + pattern.len()) as i64),
+            ]);
+        }
+    } else {
+        let (anchor, pattern) = match pattern.split_first() {
+            Some((b'^', rest)) => (true, rest),
+            _ => (false, &pattern[..]),
+        };
+        let mut matcher = Matcher::new(&s, pattern);
+        for start in init..=s.len() {
+            if let Some(end) = matcher.match_at(start).map_err(pattern_error)? {
+                let captures = matcher.captures(start, end, !find).map_err(pattern_error)?;
+                let mut values = if find {
+                    vec![
+                        ReturnValue::Integer(start as i64 + 1),
+                        ReturnValue::Integer(end as i64),
+                    ]
+                } else {
+                    Vec::new()
+                };
+                values.extend(capture_return_values(&s, &captures));
+                return Ok(values);
+            }
+            if anchor {
+                break;
+            }
+        }
+    }
+
+    Ok(vec![ReturnValue::Nil])
+}
+
+// The state of an in progress `string.gsub`, which must be suspended whenever the replacement is a
+// function (or a table with an `__index` function) so that the function can be called without
+// blocking the sequencer.
+#[derive(Collect)]
+#[collect(empty_drop)]
+struct GSubState<'gc> {
+    interned_strings: InternedStringSet<'gc>,
+    source: String<'gc>,
+    pattern: String<'gc>,
+    anchor: bool,
+    replacement: Value<'gc>,
+    max_n: i64,
+    n: i64,
+    position: usize,
+    match_start: usize,
+    last_match: Option<usize>,
+    result: Vec<u8>,
+}
+
+fn gsub_step<'gc>(
+    mc: MutationContext<'gc, '_>,
+    mut state: GSubState<'gc>,
+) -> Result<CallbackResult<'gc>, Error<'gc>> {
+    let source = state.source.as_bytes();
+    let pattern = &state.pattern.as_bytes()[if state.anchor { 1 } else { 0 }..];
+    let mut matcher = Matcher::new(source, pattern);
+
+    while state.n < state.max_n {
+        let start = state.position;
+        match matcher.match_at(start).map_err(pattern_error)? {
+            Some(end) if Some(end) != state.last_match => {
+                state.n += 1;
+                state.position = end;
+                state.match_start = start;
+                state.last_match = Some(end);
+
+                match state.replacement {
+                    Value::String(repl) => {
+                        expand_replacement(
+                            &mut state.result,
+                            repl.as_bytes(),
+                            &matcher,
+                            start,
+                            end,
+                        )
+                        .map_err(pattern_error)?;
+                    }
+                    Value::Table(table) => {
+                        let key = matcher.capture(0, start, end).map_err(pattern_error)?;
+                        let key = capture_value(mc, state.interned_strings, source, key);
+                        match meta_ops::index(None, table.into(), key)? {
+                            MetaResult::Value(value) => {
+                                append_replacement(&mut state.result, value, &source[start..end])?;
+                            }
+                            MetaResult::Call(call) => {
+                                return Ok(CallbackResult::TailCall {
+                                    function: call.function,
+                                    args: call.args,
+                                    continuation: gsub_continuation(state),
+                                });
+                            }
+                        }
+                    }
+                    Value::Function(function) => {
+                        let args = matcher
+                            .captures(start, end, true)
+                            .map_err(pattern_error)?
+                            .into_iter()
+                            .map(|c| capture_value(mc, state.interned_strings, source, c))
+                            .collect();
+                        return Ok(CallbackResult::TailCall {
+                            function,
+                            args,
+                            continuation: gsub_continuation(state),
+                        });
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            _ if start < source.len() => {
+                state.result.push(source[start]);
+                state.position += 1;
+            }
+            _ => break,
+        }
+
+        if state.anchor {
+            break;
+        }
+    }
+
+    Ok(gsub_finish(mc, state))
+}
+
+fn gsub_finish<'gc>(
+    mc: MutationContext<'gc, '_>,
+    mut state: GSubState<'gc>,
+) -> CallbackResult<'gc> {
+    state
+        .result
+        .extend_from_slice(&state.source.as_bytes()[state.position..]);
+    CallbackResult::Return(vec![
+        Value::String(state.interned_strings.new_string(mc, &state.result)),
+        Value::Integer(state.n),
+    ])
+}
+
+// Resumes a suspended `string.gsub` once a replacement function or `__index` metamethod returns.
+fn gsub_continuation<'gc>(state: GSubState<'gc>) -> Continuation<'gc> {
+    Continuation::new_sequence_with(state, |mut state, res| {
+        let value = res?.get(0).cloned().unwrap_or(Value::Nil);
+        let source = state.source.as_bytes();
+        append_replacement(
+            &mut state.result,
+            value,
+            &source[state.match_start..state.position],
+        )?;
+        Ok(sequence::from_fn_with(state, |mc, state| {
+            if state.anchor {
+                Ok(gsub_finish(mc, state))
+            } else {
+                gsub_step(mc, state)
+            }
+        }))
+    })
+}
+
+// Appends a replacement string to `result`, expanding any `%0`-`%9` capture references and `%%`
+// escapes.
+fn expand_replacement(
+    result: &mut Vec<u8>,
+    replacement: &[u8],
+    matcher: &Matcher,
+    start: usize,
+    end: usize,
+) -> Result<(), PatternError> {
+    let source = matcher.source();
+    let mut i = 0;
+    while i < replacement.len() {
+        let c = replacement[i];
+        i += 1;
+        if c != b'%' {
+            result.push(c);
+            continue;
+        }
+
+        match replacement.get(i) {
+            Some(b'%') => result.push(b'%'),
+            Some(b'0') => result.extend_from_slice(&source[start..end]),
+            Some(&d) if d.is_ascii_digit() => {
+                match matcher.capture((d - b'1') as usize, start, end)? {
+                    Capture::Position(p) => {
+                        result.extend_from_slice((p + 1).to_string().as_bytes())
+                    }
+                    Capture::Slice(s, e) => result.extend_from_slice(&source[s..e]),
+                }
+            }
+            _ => return Err(PatternError("invalid use of '%' in replacement string")),
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+// Appends the result of a table or function replacement, where false or nil keep the original
+// match.
+fn append_replacement<'gc>(
+    result: &mut Vec<u8>,
+    value: Value<'gc>,
+    matched: &[u8],
+) -> Result<(), Error<'gc>> {
+    match value {
+        Value::Nil | Value::Boolean(false) => result.extend_from_slice(matched),
+        Value::String(s) => result.extend_from_slice(s.as_bytes()),
+        v @ Value::Integer(_) | v @ Value::Number(_) => v.display(result)?,
+        v => {
+            return Err(TypeError {
+                expected: "string",
+                found: v.type_name(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
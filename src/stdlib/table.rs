@@ -0,0 +1,421 @@
+use gc_arena::{Collect, MutationContext};
+use gc_sequence::{self as sequence, Sequence};
+
+use crate::{
+    meta_ops::{self, CompareResult},
+    Callback, CallbackResult, Continuation, Error, Function, Root, RuntimeError, String, Table,
+    TypeError, Value,
+};
+
+use super::args::{integer_arg, required_integer_arg, table_arg};
+
+// The maximum number of values that `table.unpack` will return.
+const MAX_UNPACK: i64 = 1_000_000;
+
+pub fn load_table<'gc>(mc: MutationContext<'gc, '_>, _: Root<'gc>, env: Table<'gc>) {
+    let table = Table::new(mc);
+
+    table
+        .set(
+            mc,
+            String::new_static(b"concat"),
+            Callback::new_sequence(mc, |args| {
+                let t = table_arg(&args, 0)?;
+                let sep = match args.get(1).cloned().unwrap_or(Value::Nil) {
+                    Value::Nil => Vec::new(),
+                    v @ Value::String(_) | v @ Value::Integer(_) | v @ Value::Number(_) => {
+                        let mut buf = Vec::new();
+                        v.display(&mut buf)?;
+                        buf
+                    }
+                    v => {
+                        return Err(TypeError {
+                            expected: "string",
+                            found: v.type_name(),
+                        }
+                        .into());
+                    }
+                };
+                let i = integer_arg(&args, 2)?.unwrap_or(1);
+                let j = match integer_arg(&args, 3)? {
+                    Some(j) => j,
+                    None => t.length(),
+                };
+
+                let mut res = Vec::new();
+                let mut k = i;
+                while k <= j {
+                    match t.get(k) {
+                        v @ Value::String(_) | v @ Value::Integer(_) | v @ Value::Number(_) => {
+                            v.display(&mut res)?;
+                        }
+                        _ => {
+                            return Err(RuntimeError(Value::String(String::new_static(
+                                b"invalid value in table for 'concat'",
+                            )))
+                            .into());
+                        }
+                    }
+                    if k == j {
+                        break;
+                    }
+                    res.extend_from_slice(&sep);
+                    k += 1;
+                }
+
+                Ok(sequence::from_fn(move |mc| {
+                    Ok(CallbackResult::Return(vec![Value::String(String::new(
+                        mc, &res,
+                    ))]))
+                }))
+            }),
+        )
+        .unwrap();
+
+    table
+        .set(
+            mc,
+            String::new_static(b"insert"),
+            Callback::new_sequence(mc, |args| {
+                let t = table_arg(&args, 0)?;
+                let end = t.length() + 1;
+                let (pos, value) = match args.len() {
+                    2 => (end, args[1]),
+                    3 => match integer_arg(&args, 1)? {
+                        Some(pos) if pos >= 1 && pos <= end => (pos, args[2]),
+                        _ => {
+                            return Err(RuntimeError(Value::String(String::new_static(
+                                b"position out of bounds",
+                            )))
+                            .into());
+                        }
+                    },
+                    _ => {
+                        return Err(RuntimeError(Value::String(String::new_static(
+                            b"wrong number of arguments to 'insert'",
+                        )))
+                        .into());
+                    }
+                };
+
+                Ok(sequence::from_fn_with((t, value), move |mc, (t, value)| {
+                    let mut i = end;
+                    while i > pos {
+                        t.set(mc, i, t.get(i - 1))?;
+                        i -= 1;
+                    }
+                    t.set(mc, pos, value)?;
+                    Ok(CallbackResult::Return(vec![]))
+                }))
+            }),
+        )
+        .unwrap();
+
+    table
+        .set(
+            mc,
+            String::new_static(b"move"),
+            Callback::new_sequence(mc, |args| {
+                let a1 = table_arg(&args, 0)?;
+                let f = required_integer_arg(&args, 1)?;
+                let e = required_integer_arg(&args, 2)?;
+                let t = required_integer_arg(&args, 3)?;
+                let a2 = match args.get(4).cloned().unwrap_or(Value::Nil) {
+                    Value::Nil => a1,
+                    _ => table_arg(&args, 4)?,
+                };
+
+                if e >= f {
+                    if f <= 0 && e >= i64::max_value() + f {
+                        return Err(RuntimeError(Value::String(String::new_static(
+                            b"too many elements to move",
+                        )))
+                        .into());
+                    }
+                    if t > i64::max_value() - (e - f) {
+                        return Err(RuntimeError(Value::String(String::new_static(
+                            b"destination wrap around",
+                        )))
+                        .into());
+                    }
+                }
+
+                Ok(sequence::from_fn_with((a1, a2), move |mc, (a1, a2)| {
+                    if e >= f {
+                        let n = e - f + 1;
+                        // Copy backwards if the destination overlaps the end of the source range.
+                        if t > e || t <= f || a1 != a2 {
+                            for i in 0..n {
+                                a2.set(mc, t + i, a1.get(f + i))?;
+                            }
+                        } else {
+                            for i in (0..n).rev() {
+                                a2.set(mc, t + i, a1.get(f + i))?;
+                            }
+                        }
+                    }
+                    Ok(CallbackResult::Return(vec![Value::Table(a2)]))
+                }))
+            }),
+        )
+        .unwrap();
+
+    table
+        .set(
+            mc,
+            String::new_static(b"pack"),
+            Callback::new_sequence(mc, |args| {
+                Ok(sequence::from_fn_with(args, |mc, args| {
+                    let t = Table::new(mc);
+                    for (i, &v) in args.iter().enumerate() {
+                        t.set(mc, i as i64 + 1, v)?;
+                    }
+                    t.set(mc, String::new_static(b"n"), args.len() as i64)?;
+                    Ok(CallbackResult::Return(vec![Value::Table(t)]))
+                }))
+            }),
+        )
+        .unwrap();
+
+    table
+        .set(
+            mc,
+            String::new_static(b"remove"),
+            Callback::new_sequence(mc, |args| {
+                let t = table_arg(&args, 0)?;
+                let size = t.length();
+                let pos = integer_arg(&args, 1)?.unwrap_or(size);
+                if pos != size && (pos < 1 || pos > size + 1) {
+                    return Err(RuntimeError(Value::String(String::new_static(
+                        b"position out of bounds",
+                    )))
+                    .into());
+                }
+
+                Ok(sequence::from_fn_with(t, move |mc, t| {
+                    let removed = t.get(pos);
+                    let mut i = pos;
+                    while i < size {
+                        t.set(mc, i, t.get(i + 1))?;
+                        i += 1;
+                    }
+                    t.set(mc, i, Value::Nil)?;
+                    Ok(CallbackResult::Return(vec![removed]))
+                }))
+            }),
+        )
+        .unwrap();
+
+    table
+        .set(
+            mc,
+            String::new_static(b"sort"),
+            Callback::new_sequence(mc, |args| {
+                let t = table_arg(&args, 0)?;
+                let comparator = match args.get(1).cloned().unwrap_or(Value::Nil) {
+                    Value::Nil => None,
+                    Value::Function(f) => Some(f),
+                    v => {
+                        return Err(TypeError {
+                            expected: "function",
+                            found: v.type_name(),
+                        }
+                        .into());
+                    }
+                };
+
+                let len = t.length();
+                if len >= i32::max_value() as i64 {
+                    return Err(
+                        RuntimeError(Value::String(String::new_static(b"array too big"))).into(),
+                    );
+                }
+                let items: Vec<Value<'gc>> = (1..=len).map(|i| t.get(i)).collect();
+                Ok(SortSequence(Some(SortState::new(t, comparator, items))))
+            }),
+        )
+        .unwrap();
+
+    table
+        .set(
+            mc,
+            String::new_static(b"unpack"),
+            Callback::new_immediate(mc, |args| {
+                let t = table_arg(&args, 0)?;
+                let i = integer_arg(&args, 1)?.unwrap_or(1);
+                let j = match integer_arg(&args, 2)? {
+                    Some(j) => j,
+                    None => t.length(),
+                };
+
+                if i > j {
+                    return Ok(CallbackResult::Return(vec![]));
+                }
+                match j.checked_sub(i) {
+                    Some(n) if n < MAX_UNPACK => {}
+                    _ => {
+                        return Err(RuntimeError(Value::String(String::new_static(
+                            b"too many results to unpack",
+                        )))
+                        .into());
+                    }
+                }
+                Ok(CallbackResult::Return((i..=j).map(|k| t.get(k)).collect()))
+            }),
+        )
+        .unwrap();
+
+    env.set(mc, String::new_static(b"table"), table).unwrap();
+}
+
+// The number of comparisons `table.sort` will make in a single sequence step, when it does not need
+// to call a Lua comparator.
+const SORT_GRANULARITY: u32 = 256;
+
+// `table.sort` is a bottom-up merge sort written as a state machine, so that comparisons may call
+// into Lua (and yield) through continuations, and so that garbage collection may happen in between
+// steps of a large sort.
+#[derive(Collect)]
+#[collect(empty_drop)]
+struct SortState<'gc> {
+    table: Table<'gc>,
+    comparator: Option<Function<'gc>>,
+    items: Vec<Value<'gc>>,
+    buffer: Vec<Value<'gc>>,
+    // The width of the sorted runs currently being merged in pairs
+    width: usize,
+    // The start of the current pair of runs being merged
+    start: usize,
+    // The next unmerged index in the left and right runs
+    left: usize,
+    right: usize,
+}
+
+enum SortStatus<'gc> {
+    Done,
+    Compare(Value<'gc>, Value<'gc>),
+}
+
+impl<'gc> SortState<'gc> {
+    fn new(
+        table: Table<'gc>,
+        comparator: Option<Function<'gc>>,
+        items: Vec<Value<'gc>>,
+    ) -> SortState<'gc> {
+        SortState {
+            table,
+            comparator,
+            buffer: items.clone(),
+            right: items.len().min(1),
+            items,
+            width: 1,
+            start: 0,
+            left: 0,
+        }
+    }
+
+    fn mid(&self) -> usize {
+        (self.start + self.width).min(self.items.len())
+    }
+
+    fn end(&self) -> usize {
+        (self.start + 2 * self.width).min(self.items.len())
+    }
+
+    // Merges until the sort is finished or the next comparison is needed.  The comparison returned
+    // is whether the next right item is less than the next left item.
+    fn advance(&mut self) -> SortStatus<'gc> {
+        let len = self.items.len();
+        loop {
+            if self.width >= len {
+                return SortStatus::Done;
+            } else if self.start >= len {
+                std::mem::swap(&mut self.items, &mut self.buffer);
+                self.width *= 2;
+                self.start = 0;
+                self.left = 0;
+                self.right = self.mid();
+                continue;
+            }
+
+            let (mid, end) = (self.mid(), self.end());
+            if self.left == mid && self.right == end {
+                self.start = end;
+                self.left = end;
+                self.right = self.mid();
+            } else if self.left == mid {
+                self.take(true);
+            } else if self.right == end {
+                self.take(false);
+            } else {
+                return SortStatus::Compare(self.items[self.right], self.items[self.left]);
+            }
+        }
+    }
+
+    // Moves the next item from either the right or left run into the merged output.
+    fn take(&mut self, right: bool) {
+        let out = self.left + self.right - self.mid();
+        if right {
+            self.buffer[out] = self.items[self.right];
+            self.right += 1;
+        } else {
+            self.buffer[out] = self.items[self.left];
+            self.left += 1;
+        }
+    }
+
+    fn finish(self, mc: MutationContext<'gc, '_>) -> Result<CallbackResult<'gc>, Error<'gc>> {
+        for (i, &v) in self.items.iter().enumerate() {
+            self.table.set(mc, i as i64 + 1, v)?;
+        }
+        Ok(CallbackResult::Return(vec![]))
+    }
+}
+
+#[derive(Collect)]
+#[collect(empty_drop)]
+struct SortSequence<'gc>(Option<SortState<'gc>>);
+
+impl<'gc> Sequence<'gc> for SortSequence<'gc> {
+    type Output = Result<CallbackResult<'gc>, Error<'gc>>;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
+        let state = self.0.as_mut().expect("cannot step a finished sort");
+
+        for _ in 0..SORT_GRANULARITY {
+            match state.advance() {
+                SortStatus::Done => return Some(self.0.take().unwrap().finish(mc)),
+                SortStatus::Compare(a, b) => {
+                    let (function, args, invert) = match state.comparator {
+                        None => match meta_ops::less_than(a, b) {
+                            Ok(CompareResult::Value(less)) => {
+                                state.take(less);
+                                continue;
+                            }
+                            Ok(CompareResult::Call { call, invert }) => {
+                                (call.function, call.args, invert)
+                            }
+                            Err(err) => return Some(Err(err)),
+                        },
+                        Some(function) => (function, vec![a, b], false),
+                    };
+                    return Some(Ok(CallbackResult::TailCall {
+                        function,
+                        args,
+                        continuation: Continuation::new_sequence_with(
+                            self.0.take().unwrap(),
+                            move |mut state, res| {
+                                let less = res?.get(0).cloned().unwrap_or(Value::Nil);
+                                state.take(less.to_bool() != invert);
+                                Ok(SortSequence(Some(state)))
+                            },
+                        ),
+                    }));
+                }
+            }
+        }
+
+        None
+    }
+}
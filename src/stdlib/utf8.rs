@@ -0,0 +1,308 @@
+use gc_arena::MutationContext;
+use gc_sequence as sequence;
+
+use crate::{Callback, CallbackResult, Error, Root, RuntimeError, String, Table, TypeError, Value};
+
+use super::args::{integer_arg, string_arg};
+
+const MAX_UNICODE: u32 = 0x10ffff;
+const MAX_UTF: u32 = 0x7fffffff;
+
+pub fn load_utf8<'gc>(mc: MutationContext<'gc, '_>, _: Root<'gc>, env: Table<'gc>) {
+    let utf8 = Table::new(mc);
+
+    utf8.set(
+        mc,
+        String::new_static(b"char"),
+        Callback::new_sequence(mc, |args| {
+            let mut res = Vec::new();
+            for i in 0..args.len() {
+                match integer_arg(&args, i)? {
+                    Some(c) if c >= 0 && c <= MAX_UTF as i64 => encode(&mut res, c as u32),
+                    _ => return Err(runtime_error(b"value out of range")),
+                }
+            }
+            Ok(sequence::from_fn(move |mc| {
+                Ok(CallbackResult::Return(vec![Value::String(String::new(
+                    mc, &res,
+                ))]))
+            }))
+        }),
+    )
+    .unwrap();
+
+    utf8.set(
+        mc,
+        String::new_static(b"charpattern"),
+        String::new_static(b"[\0-\x7F\xC2-\xFD][\x80-\xBF]*"),
+    )
+    .unwrap();
+
+    let codes_strict = Callback::new_immediate(mc, |args| codes_next(&args, true));
+    let codes_lax = Callback::new_immediate(mc, |args| codes_next(&args, false));
+    utf8.set(
+        mc,
+        String::new_static(b"codes"),
+        Callback::new_immediate_with(
+            mc,
+            (codes_strict, codes_lax),
+            |&(codes_strict, codes_lax), args| {
+                let s = string_arg(&args, 0)?;
+                if s.first().cloned().map(is_continuation).unwrap_or(false) {
+                    return Err(runtime_error(b"invalid UTF-8 code"));
+                }
+                let lax = args.get(1).cloned().unwrap_or(Value::Nil).to_bool();
+                Ok(CallbackResult::Return(vec![
+                    if lax { codes_lax } else { codes_strict }.into(),
+                    args[0],
+                    Value::Integer(0),
+                ]))
+            },
+        ),
+    )
+    .unwrap();
+
+    utf8.set(
+        mc,
+        String::new_static(b"codepoint"),
+        Callback::new_immediate(mc, |args| {
+            let s = string_arg(&args, 0)?;
+            let len = s.len() as i64;
+            let i = relative_position(integer_arg(&args, 1)?.unwrap_or(1), len);
+            let j = relative_position(integer_arg(&args, 2)?.unwrap_or(i), len);
+            let strict = !args.get(3).cloned().unwrap_or(Value::Nil).to_bool();
+            if i < 1 || j > len {
+                return Err(runtime_error(b"out of bounds"));
+            }
+
+            let mut res = Vec::new();
+            let mut pos = i as usize - 1;
+            while pos < j as usize {
+                match decode(&s[pos..], strict) {
+                    Some((c, len)) => {
+                        res.push(Value::Integer(c as i64));
+                        pos += len;
+                    }
+                    None => return Err(runtime_error(b"invalid UTF-8 code")),
+                }
+            }
+            Ok(CallbackResult::Return(res))
+        }),
+    )
+    .unwrap();
+
+    utf8.set(
+        mc,
+        String::new_static(b"len"),
+        Callback::new_immediate(mc, |args| {
+            let s = string_arg(&args, 0)?;
+            let len = s.len() as i64;
+            let i = relative_position(integer_arg(&args, 1)?.unwrap_or(1), len);
+            let j = relative_position(integer_arg(&args, 2)?.unwrap_or(-1), len);
+            let strict = !args.get(3).cloned().unwrap_or(Value::Nil).to_bool();
+            if i < 1 || i - 1 > len {
+                return Err(runtime_error(b"initial position out of bounds"));
+            }
+            if j - 1 >= len {
+                return Err(runtime_error(b"final position out of bounds"));
+            }
+
+            let mut n = 0;
+            let mut pos = i - 1;
+            while pos < j {
+                match decode(&s[pos as usize..], strict) {
+                    Some((_, len)) => {
+                        pos += len as i64;
+                        n += 1;
+                    }
+                    None => {
+                        return Ok(CallbackResult::Return(vec![
+                            Value::Nil,
+                            Value::Integer(pos + 1),
+                        ]));
+                    }
+                }
+            }
+            Ok(CallbackResult::Return(vec![Value::Integer(n)]))
+        }),
+    )
+    .unwrap();
+
+    utf8.set(
+        mc,
+        String::new_static(b"offset"),
+        Callback::new_immediate(mc, |args| {
+            let s = string_arg(&args, 0)?;
+            let len = s.len() as i64;
+            let mut n = match integer_arg(&args, 1)? {
+                Some(n) => n,
+                None => {
+                    return Err(TypeError {
+                        expected: "integer",
+                        found: "nil",
+                    }
+                    .into());
+                }
+            };
+            let default_i = if n >= 0 { 1 } else { len + 1 };
+            let i = relative_position(integer_arg(&args, 2)?.unwrap_or(default_i), len);
+            if i < 1 || i - 1 > len {
+                return Err(runtime_error(b"position out of bounds"));
+            }
+
+            // Bytes past the end of the string are never continuation bytes.
+            let is_cont =
+                |pos: i64| s.get(pos as usize).cloned().map(is_continuation) == Some(true);
+
+            let mut pos = i - 1;
+            if n == 0 {
+                // Find the start of the character containing byte `i`.
+                while pos > 0 && is_cont(pos) {
+                    pos -= 1;
+                }
+            } else {
+                if is_cont(pos) {
+                    return Err(runtime_error(b"initial position is a continuation byte"));
+                }
+
+                if n < 0 {
+                    while n < 0 && pos > 0 {
+                        pos -= 1;
+                        while pos > 0 && is_cont(pos) {
+                            pos -= 1;
+                        }
+                        n += 1;
+                    }
+                } else {
+                    n -= 1;
+                    while n > 0 && pos < len {
+                        pos += 1;
+                        while is_cont(pos) {
+                            pos += 1;
+                        }
+                        n -= 1;
+                    }
+                }
+            }
+
+            Ok(CallbackResult::Return(vec![if n == 0 {
+                Value::Integer(pos + 1)
+            } else {
+                Value::Nil
+            }]))
+        }),
+    )
+    .unwrap();
+
+    env.set(mc, String::new_static(b"utf8"), utf8).unwrap();
+}
+
+// The iterator function returned by `utf8.codes`, called with the string and the position of the
+// previous character.
+fn codes_next<'gc>(args: &[Value<'gc>], strict: bool) -> Result<CallbackResult<'gc>, Error<'gc>> {
+    let s = string_arg(args, 0)?;
+    let mut pos = match integer_arg(args, 1)?.unwrap_or(0) {
+        pos if pos < 0 => return Ok(CallbackResult::Return(vec![])),
+        pos => pos as usize,
+    };
+    while s.get(pos).cloned().map(is_continuation).unwrap_or(false) {
+        pos += 1;
+    }
+
+    if pos >= s.len() {
+        return Ok(CallbackResult::Return(vec![]));
+    }
+
+    match decode(&s[pos..], strict) {
+        Some((c, len))
+            if !s
+                .get(pos + len)
+                .cloned()
+                .map(is_continuation)
+                .unwrap_or(false) =>
+        {
+            Ok(CallbackResult::Return(vec![
+                Value::Integer(pos as i64 + 1),
+                Value::Integer(c as i64),
+            ]))
+        }
+        _ => Err(runtime_error(b"invalid UTF-8 code")),
+    }
+}
+
+fn runtime_error<'gc>(msg: &'static [u8]) -> Error<'gc> {
+    RuntimeError(Value::String(String::new_static(msg))).into()
+}
+
+// Translates a negative position relative to the end of the string into an absolute one, unlike the
+// `string` library this does not clamp the result.
+fn relative_position(pos: i64, len: i64) -> i64 {
+    if pos >= 0 {
+        pos
+    } else if pos < -len {
+        0
+    } else {
+        len + pos + 1
+    }
+}
+
+fn is_continuation(b: u8) -> bool {
+    b & 0xc0 == 0x80
+}
+
+// Decodes a single UTF-8 sequence, returning the code point and its length in bytes.  Lax decoding
+// accepts the original UTF-8 encoding of values up to 2^31, strict decoding rejects values past
+// the Unicode range and surrogates.
+fn decode(s: &[u8], strict: bool) -> Option<(u32, usize)> {
+    const LIMITS: [u32; 6] = [0, 0x80, 0x800, 0x10000, 0x200000, 0x4000000];
+
+    let mut c = *s.first()? as u32;
+    let mut res = 0;
+    let mut count = 0;
+    if c < 0x80 {
+        res = c;
+    } else {
+        while c & 0x40 != 0 {
+            count += 1;
+            let cc = *s.get(count)?;
+            if !is_continuation(cc) {
+                return None;
+            }
+            res = (res << 6) | (cc & 0x3f) as u32;
+            c <<= 1;
+        }
+        if count == 0 || count > 5 {
+            return None;
+        }
+        res |= (c & 0x7f) << (count * 5);
+        if res > MAX_UTF || res < LIMITS[count] {
+            return None;
+        }
+    }
+
+    if strict && (res > MAX_UNICODE || (res >= 0xd800 && res <= 0xdfff)) {
+        return None;
+    }
+    Some((res, count + 1))
+}
+
+// Encodes a value up to 2^31 using the original UTF-8 scheme of up to 6 bytes.
+fn encode(out: &mut Vec<u8>, mut c: u32) {
+    if c < 0x80 {
+        out.push(c as u8);
+        return;
+    }
+
+    let mut buf = [0; 6];
+    let mut n = 0;
+    // The maximum value that fits in the first byte
+    let mut first_max = 0x3f;
+    while c > first_max {
+        buf[5 - n] = 0x80 | (c & 0x3f) as u8;
+        n += 1;
+        c >>= 6;
+        first_max >>= 1;
+    }
+    buf[5 - n] = ((!first_max << 1) | c) as u8;
+    out.extend_from_slice(&buf[5 - n..]);
+}
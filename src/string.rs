@@ -8,9 +8,9 @@ use std::str;
 
 use rustc_hash::FxHashSet;
 
-use gc_arena::{Collect, Gc, GcCell, MutationContext};
+use gc_arena::{Collect, CollectionContext, Gc, GcCell, GcPointer, MutationContext, WeakCollect};
 
-use crate::Value;
+use crate::{format::write_number, Value};
 
 #[derive(Debug, Clone, Copy, Collect)]
 #[collect(require_static)]
@@ -63,7 +63,7 @@ impl<'gc> String<'gc> {
             let mut b = [0; 8];
             b[..len].copy_from_slice(s);
             String::Short8(len as u8, Gc::allocate(mc, b))
-        } else if len <= 32 {
+        } else if len <= MAX_SHORT_LEN {
             let mut b = [0; 32];
             b[..len].copy_from_slice(s);
             String::Short32(len as u8, Gc::allocate(mc, b))
@@ -80,26 +80,7 @@ impl<'gc> String<'gc> {
         mc: MutationContext<'gc, '_>,
         values: &[Value<'gc>],
     ) -> Result<String<'gc>, StringError> {
-        let mut bytes = Vec::new();
-        for value in values {
-            match value {
-                Value::Nil => write!(&mut bytes, "nil").unwrap(),
-                Value::Boolean(b) => write!(&mut bytes, "{}", b).unwrap(),
-                Value::Integer(i) => write!(&mut bytes, "{}", i).unwrap(),
-                Value::Number(n) => write!(&mut bytes, "{}", n).unwrap(),
-                Value::String(s) => bytes.extend(s.as_bytes()),
-                Value::Table(_) => return Err(StringError::Concat { bad_type: "table" }),
-                Value::Function(_) => {
-                    return Err(StringError::Concat {
-                        bad_type: "function",
-                    });
-                }
-                Value::Thread(_) => {
-                    return Err(StringError::Concat { bad_type: "thread" });
-                }
-            }
-        }
-        Ok(String::Long(Gc::allocate(mc, bytes.into_boxed_slice())))
+        Ok(String::new(mc, &concat_bytes(values)?))
     }
 
     pub fn as_bytes(&self) -> &[u8] {
@@ -112,6 +93,34 @@ impl<'gc> String<'gc> {
     }
 }
 
+fn concat_bytes(values: &[Value]) -> Result<Vec<u8>, StringError> {
+    let mut bytes = Vec::new();
+    for value in values {
+        match value {
+            Value::Nil => write!(&mut bytes, "nil").unwrap(),
+            Value::Boolean(b) => write!(&mut bytes, "{}", b).unwrap(),
+            Value::Integer(i) => write!(&mut bytes, "{}", i).unwrap(),
+            Value::Number(n) => write_number(&mut bytes, *n),
+            Value::String(s) => bytes.extend(s.as_bytes()),
+            Value::Table(_) => return Err(StringError::Concat { bad_type: "table" }),
+            Value::Function(_) => {
+                return Err(StringError::Concat {
+                    bad_type: "function",
+                });
+            }
+            Value::Thread(_) => {
+                return Err(StringError::Concat { bad_type: "thread" });
+            }
+            Value::UserData(_) => {
+                return Err(StringError::Concat {
+                    bad_type: "userdata",
+                });
+            }
+        }
+    }
+    Ok(bytes)
+}
+
 impl<'gc> Deref for String<'gc> {
     type Target = [u8];
 
@@ -137,7 +146,10 @@ where
     T: AsRef<[u8]>,
 {
     fn eq(&self, other: &T) -> bool {
-        self.as_bytes() == other.as_ref()
+        let (a, b) = (self.as_bytes(), other.as_ref());
+        // Interned strings with the same contents share an allocation, so this is usually enough
+        // to tell that two strings are equal without comparing their bytes.
+        a.len() == b.len() && (a.as_ptr() == b.as_ptr() || a == b)
     }
 }
 
@@ -149,22 +161,73 @@ impl<'gc> Hash for String<'gc> {
     }
 }
 
+/// Strings at most this long are stored inline in a small allocation, and are interned by
+/// `InternedStringSet`.
+pub const MAX_SHORT_LEN: usize = 32;
+
+/// A set of short strings, used so that equal short strings created through it share a single
+/// allocation.
+///
+/// The set holds its strings weakly, so interned strings which are no longer reachable from
+/// anywhere else are removed from it when they are collected.
 #[derive(Collect, Clone, Copy)]
 #[collect(require_copy)]
-pub struct InternedStringSet<'gc>(GcCell<'gc, FxHashSet<String<'gc>>>);
+pub struct InternedStringSet<'gc>(GcCell<'gc, InternedStrings<'gc>>);
 
 impl<'gc> InternedStringSet<'gc> {
     pub fn new(mc: MutationContext<'gc, '_>) -> InternedStringSet<'gc> {
-        InternedStringSet(GcCell::allocate(mc, FxHashSet::default()))
+        let set = GcCell::allocate(mc, InternedStrings(FxHashSet::default()));
+        GcCell::register_weak(mc, set);
+        InternedStringSet(set)
     }
 
+    /// Returns the interned string with the given contents if there is one, otherwise creates a
+    /// new string, interning it if it is no longer than `MAX_SHORT_LEN`.
     pub fn new_string(&self, mc: MutationContext<'gc, '_>, s: &[u8]) -> String<'gc> {
-        if let Some(found) = self.0.read().get(s) {
+        if s.len() > MAX_SHORT_LEN {
+            return String::new(mc, s);
+        }
+
+        if let Some(found) = self.0.read().0.get(s) {
             return *found;
         }
 
         let s = String::new(mc, s);
-        self.0.write(mc).insert(s);
+        self.0.write(mc).0.insert(s);
         s
     }
+
+    /// Like `String::concat`, but interns the result as `new_string` does.
+    pub fn concat(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        values: &[Value<'gc>],
+    ) -> Result<String<'gc>, StringError> {
+        Ok(self.new_string(mc, &concat_bytes(values)?))
+    }
+
+    /// The number of strings currently interned.
+    pub fn len(&self) -> usize {
+        self.0.read().0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.read().0.is_empty()
+    }
+}
+
+struct InternedStrings<'gc>(FxHashSet<String<'gc>>);
+
+// Interned strings are not traced, they are only kept alive by other references to them.
+unsafe impl<'gc> Collect for InternedStrings<'gc> {}
+
+unsafe impl<'gc> WeakCollect for InternedStrings<'gc> {
+    fn clear_unmarked(&mut self, cc: CollectionContext) {
+        self.0.retain(|s| match s {
+            String::Short8(_, b) => cc.is_marked(b.gc_ptr()),
+            String::Short32(_, b) => cc.is_marked(b.gc_ptr()),
+            String::Long(b) => cc.is_marked(b.gc_ptr()),
+            String::Static(_) => true,
+        });
+    }
 }
@@ -5,13 +5,16 @@ use std::{fmt, i64, mem};
 use num_traits::cast;
 use rustc_hash::FxHashMap;
 
-use gc_arena::{Collect, GcCell, MutationContext};
+use gc_arena::{
+    Collect, CollectionContext, GcCell, GcPointer, GcPtr, MutationContext, WeakCollect,
+};
 
-use crate::Value;
+use crate::meta_ops::MetaMethod;
+use crate::{Function, String, Value};
 
 #[derive(Debug, Copy, Clone, Collect)]
 #[collect(require_copy)]
-pub struct Table<'gc>(pub GcCell<'gc, TableState<'gc>>);
+pub struct Table<'gc>(pub(crate) GcCell<'gc, TableState<'gc>>);
 
 #[derive(Debug, Clone, Copy, Collect)]
 #[collect(require_static)]
@@ -22,6 +25,19 @@ pub enum InvalidTableKey {
 
 impl StdError for InvalidTableKey {}
 
+/// Returned by `next` when given a key which is not present in the table.
+#[derive(Debug, Clone, Copy, Collect)]
+#[collect(require_static)]
+pub struct InvalidNextKey;
+
+impl StdError for InvalidNextKey {}
+
+impl fmt::Display for InvalidNextKey {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "invalid key to 'next'")
+    }
+}
+
 impl fmt::Display for InvalidTableKey {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -60,19 +76,187 @@ impl<'gc> Table<'gc> {
         key: K,
         value: V,
     ) -> Result<Value<'gc>, InvalidTableKey> {
-        self.0.write(mc).set(key.into(), value.into())
+        let (key, value) = (key.into(), value.into());
+        let old = self.0.write(mc).set(key, value)?;
+        // A removed entry does not keep its key alive, so the table must be told when the key is
+        // collected to drop the entry.
+        if value == Value::Nil && old != Value::Nil && gc_ptr(key).is_some() {
+            GcCell::register_weak(mc, self.0);
+        }
+        Ok(old)
     }
 
     pub fn length(&self) -> i64 {
         self.0.read().length()
     }
+
+    pub fn metatable(&self) -> Option<Table<'gc>> {
+        self.0.read().metatable
+    }
+
+    /// Sets the metatable of this table, returning the previous one.  The table becomes weak if the
+    /// new metatable has a `__mode` field containing 'k' or 'v' at the time it is set.
+    pub fn set_metatable(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        metatable: Option<Table<'gc>>,
+    ) -> Option<Table<'gc>> {
+        let (weak_keys, weak_values) =
+            match metatable.map(|mt| mt.get(String::new_static(MetaMethod::Mode.name()))) {
+                Some(Value::String(mode)) => (
+                    mode.as_bytes().contains(&b'k'),
+                    mode.as_bytes().contains(&b'v'),
+                ),
+                _ => (false, false),
+            };
+
+        let mut state = self.0.write(mc);
+        state.weak_keys = weak_keys;
+        state.weak_values = weak_values;
+        let old = mem::replace(&mut state.metatable, metatable);
+        drop(state);
+
+        if weak_keys || weak_values {
+            GcCell::register_weak(mc, self.0);
+        }
+        old
+    }
+
+    /// Returns the key and value following the given key in the table's iteration order, or the
+    /// first entry if the key is nil.  Returns `Ok(None)` once there are no more entries.
+    pub fn next<K: Into<Value<'gc>>>(
+        &self,
+        key: K,
+    ) -> Result<Option<(Value<'gc>, Value<'gc>)>, InvalidNextKey> {
+        self.0.read().next(key.into())
+    }
+
+    /// Iterates over every entry in the table.  The table is not borrowed in between calls to
+    /// `Iterator::next`, so like Lua's `next` it is valid to clear or change the value of existing
+    /// fields during iteration, but not to add new fields.
+    pub fn iter(&self) -> TableIter<'gc> {
+        TableIter {
+            table: *self,
+            key: Some(Value::Nil),
+        }
+    }
 }
 
-#[derive(Debug, Collect, Default)]
-#[collect(empty_drop)]
+pub struct TableIter<'gc> {
+    table: Table<'gc>,
+    // The last key returned, or `None` once iteration has finished.
+    key: Option<Value<'gc>>,
+}
+
+impl<'gc> Iterator for TableIter<'gc> {
+    type Item = (Value<'gc>, Value<'gc>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = self.key?;
+        // If the last key has disappeared because new fields were added, iteration simply ends.
+        let entry = self.table.next(key).ok().and_then(|e| e);
+        self.key = entry.map(|(k, _)| k);
+        entry
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct TableState<'gc> {
     array: Vec<Value<'gc>>,
-    map: FxHashMap<TableKey<'gc>, Value<'gc>>,
+    map: MapPart<'gc>,
+    metatable: Option<Table<'gc>>,
+    // Set from the metatable's `__mode` field when the metatable is set.
+    weak_keys: bool,
+    weak_values: bool,
+}
+
+// Weak tables only trace the keys and values which they hold strongly.  A table with weak keys but
+// strong values is an ephemeron table, where a value is only traced once its key is known to be
+// reachable some other way, so that a value which refers to its own key does not keep the key
+// alive.
+unsafe impl<'gc> Collect for TableState<'gc> {
+    fn trace(&self, cc: CollectionContext) {
+        self.metatable.trace(cc);
+
+        for &value in &self.array {
+            if !self.weak_values || weak_ptr(value).is_none() {
+                value.trace(cc);
+            }
+        }
+
+        // The keys of removed entries are not traced, like PUC-Rio Lua's dead keys, and their
+        // entries are dropped once the keys are collected.
+        for (key, value) in &self.map.entries {
+            let key_ptr = weak_ptr(key.0);
+            if *value != Value::Nil && (!self.weak_keys || key_ptr.is_none()) {
+                key.trace(cc);
+            }
+
+            if weak_ptr(*value).is_none()
+                || (!self.weak_values
+                    && (!self.weak_keys || key_ptr.map(|k| cc.is_marked(k)).unwrap_or(true)))
+            {
+                value.trace(cc);
+            }
+        }
+    }
+}
+
+unsafe impl<'gc> WeakCollect for TableState<'gc> {
+    fn trace_ephemerons(&self, cc: CollectionContext) -> bool {
+        if !self.weak_keys || self.weak_values {
+            return false;
+        }
+
+        let mut traced = false;
+        for (key, value) in &self.map.entries {
+            if let (Some(key_ptr), Some(value_ptr)) = (weak_ptr(key.0), weak_ptr(*value)) {
+                if cc.is_marked(key_ptr) && !cc.is_marked(value_ptr) {
+                    value.trace(cc);
+                    traced = true;
+                }
+            }
+        }
+        traced
+    }
+
+    fn clear_unmarked(&mut self, cc: CollectionContext) {
+        let is_dead =
+            |value: Value<'gc>| weak_ptr(value).map(|p| !cc.is_marked(p)).unwrap_or(false);
+
+        // Any key which was not traced and is not otherwise reachable is dead, whether it is a weak
+        // key or the key of a removed entry.
+        self.map
+            .remove_keys(|key| gc_ptr(key.0).map(|p| !cc.is_marked(p)).unwrap_or(false));
+
+        if self.weak_values {
+            for value in self.array.iter_mut() {
+                if is_dead(*value) {
+                    *value = Value::Nil;
+                }
+            }
+
+            // Entries with dead values are removed normally, keeping their keys so that iteration
+            // may continue past them.
+            for (_, value) in self.map.entries.iter_mut() {
+                if is_dead(*value) {
+                    *value = Value::Nil;
+                }
+            }
+        }
+    }
+
+    // A table stays registered while it has weak fields or the untraced keys of removed entries,
+    // which are otherwise only dropped when the map part is rehashed.
+    fn is_weak(&self) -> bool {
+        self.weak_keys
+            || self.weak_values
+            || self
+                .map
+                .entries
+                .iter()
+                .any(|(key, value)| *value == Value::Nil && gc_ptr(key.0).is_some())
+    }
 }
 
 impl<'gc> TableState<'gc> {
@@ -84,13 +268,15 @@ impl<'gc> TableState<'gc> {
         }
 
         if let Ok(key) = TableKey::new(key) {
-            self.map.get(&key).cloned().unwrap_or(Value::Nil)
+            self.map.get(&key)
         } else {
             Value::Nil
         }
     }
 
-    pub fn set(
+    // Removing an entry with a collectable key leaves a key that is no longer traced, so this must
+    // only be called through `Table::set`, which registers the table as weak.
+    pub(crate) fn set(
         &mut self,
         key: Value<'gc>,
         value: Value<'gc>,
@@ -104,9 +290,9 @@ impl<'gc> TableState<'gc> {
 
         let hash_key = TableKey::new(key)?;
         if value == Value::Nil {
-            Ok(self.map.remove(&hash_key).unwrap_or(Value::Nil))
-        } else if self.map.len() < self.map.capacity() {
-            Ok(self.map.insert(hash_key, value).unwrap_or(Value::Nil))
+            Ok(self.map.remove(&hash_key))
+        } else if self.map.has_slot(&hash_key) || self.map.slots() < self.map.capacity() {
+            Ok(self.map.insert(hash_key, value))
         } else {
             // If a new element does not fit in either the array or map part of the table, we need
             // to grow.  First, we find the total count of array candidate elements across the array
@@ -157,7 +343,6 @@ impl<'gc> TableState<'gc> {
             }
 
             let old_array_size = self.array.len();
-            let old_map_size = self.map.len();
             if optimal_size > old_array_size {
                 // If we're growing the array part, we need to grow the array and take any newly valid
                 // array keys from the map part.
@@ -170,7 +355,7 @@ impl<'gc> TableState<'gc> {
                 self.map.retain(|k, v| {
                     if let Some(i) = to_array_index(k.0) {
                         if i < array.len() {
-                            array[i] = *v;
+                            array[i] = v;
                             return false;
                         }
                     }
@@ -178,12 +363,11 @@ impl<'gc> TableState<'gc> {
                 });
             } else {
                 // If we aren't growing the array, we're adding a new element to the map that won't
-                // fit in the advertised capacity.  The capacity of std::collections::HashMap is
-                // just a lower-bound, so we may actually be able to insert past the capacity
-                // without the advertised capacity growing, so to make sure that we don't try to
-                // grow repeatedly, we need to make sure the capacity actually increases.  We simply
-                // double the capacity here.
-                self.map.reserve(old_map_size);
+                // fit in its capacity.  First we reclaim the slots of any removed entries, then we
+                // make sure the capacity actually increases so that we don't try to grow
+                // repeatedly.  We simply double the capacity here.
+                self.map.retain(|_, _| true);
+                self.map.reserve(self.map.slots().max(1));
             }
 
             // Now we can insert the new key value pair
@@ -192,8 +376,46 @@ impl<'gc> TableState<'gc> {
                     return Ok(mem::replace(&mut self.array[index], value));
                 }
             }
-            Ok(self.map.insert(hash_key, value).unwrap_or(Value::Nil))
+            Ok(self.map.insert(hash_key, value))
+        }
+    }
+
+    /// Returns the key and value following the given key, or the first entry if the key is nil.
+    /// The array part is visited in order first, followed by the map part.
+    pub fn next(
+        &self,
+        key: Value<'gc>,
+    ) -> Result<Option<(Value<'gc>, Value<'gc>)>, InvalidNextKey> {
+        let (array_start, map_start) = if key == Value::Nil {
+            (0, 0)
+        } else {
+            match to_array_index(key) {
+                Some(index) if index < self.array.len() => (index + 1, 0),
+                _ => {
+                    let key = TableKey::new(key).map_err(|_| InvalidNextKey)?;
+                    let slot = self.map.slot(&key).ok_or(InvalidNextKey)?;
+                    (self.array.len(), slot + 1)
+                }
+            }
+        };
+
+        for i in array_start..self.array.len() {
+            if self.array[i] != Value::Nil {
+                return Ok(Some((Value::Integer(i as i64 + 1), self.array[i])));
+            }
         }
+
+        Ok(self.map.next(map_start))
+    }
+
+    /// Iterates over every entry in the table, in the same order as `next`.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (Value<'gc>, Value<'gc>)> + 'a {
+        self.array
+            .iter()
+            .enumerate()
+            .filter(|(_, &v)| v != Value::Nil)
+            .map(|(i, &v)| (Value::Integer(i as i64 + 1), v))
+            .chain(self.map.iter())
     }
 
     /// Returns a 'border' for this table.
@@ -254,9 +476,132 @@ impl<'gc> TableState<'gc> {
     }
 }
 
-// Value which implements Hash and Eq, and cannot contain Nil or NaN values.
-#[derive(Debug, Collect, PartialEq)]
+// The map part of a table.  Entries are kept in insertion order, and removing an entry only clears
+// its value, leaving the key in place.  This way the entry after any key that was present when
+// iteration started can always be found directly, even if fields are cleared during iteration.  The
+// slots of removed entries are reclaimed whenever the map part is rebuilt, or once their keys are
+// collected.
+#[derive(Debug, Collect, Default)]
 #[collect(empty_drop)]
+struct MapPart<'gc> {
+    entries: Vec<(TableKey<'gc>, Value<'gc>)>,
+    slots: FxHashMap<TableKey<'gc>, usize>,
+}
+
+impl<'gc> MapPart<'gc> {
+    fn get(&self, key: &TableKey<'gc>) -> Value<'gc> {
+        match self.slots.get(key) {
+            Some(&slot) => self.entries[slot].1,
+            None => Value::Nil,
+        }
+    }
+
+    fn contains_key(&self, key: &TableKey<'gc>) -> bool {
+        self.get(key) != Value::Nil
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // The slot for the given key, if it has one, even if the entry has been removed.
+    fn slot(&self, key: &TableKey<'gc>) -> Option<usize> {
+        self.slots.get(key).cloned()
+    }
+
+    fn has_slot(&self, key: &TableKey<'gc>) -> bool {
+        self.slots.contains_key(key)
+    }
+
+    // The number of slots in use, including removed entries.
+    fn slots(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.entries.reserve(additional);
+        self.slots.reserve(additional);
+    }
+
+    // Sets the value for a key, which must not be nil, returning the previous value.
+    fn insert(&mut self, key: TableKey<'gc>, value: Value<'gc>) -> Value<'gc> {
+        match self.slots.get(&key) {
+            Some(&slot) => mem::replace(&mut self.entries[slot].1, value),
+            None => {
+                self.slots.insert(key, self.entries.len());
+                self.entries.push((key, value));
+                Value::Nil
+            }
+        }
+    }
+
+    // Removes the entry for a key, returning the previous value.  The key keeps its slot.
+    fn remove(&mut self, key: &TableKey<'gc>) -> Value<'gc> {
+        match self.slots.get(key) {
+            Some(&slot) => mem::replace(&mut self.entries[slot].1, Value::Nil),
+            None => Value::Nil,
+        }
+    }
+
+    // Rebuilds the map with only the present entries for which `f` returns true.
+    fn retain<F: FnMut(&TableKey<'gc>, Value<'gc>) -> bool>(&mut self, mut f: F) {
+        let entries = mem::replace(&mut self.entries, Vec::new());
+        self.slots.clear();
+        for (key, value) in entries {
+            if value != Value::Nil && f(&key, value) {
+                self.slots.insert(key, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    // Removes the entries and slots of every key for which `f` returns true, including keys of
+    // removed entries, keeping the order of the rest.
+    fn remove_keys<F: Fn(&TableKey<'gc>) -> bool>(&mut self, f: F) {
+        if !self.entries.iter().any(|(key, _)| f(key)) {
+            return;
+        }
+
+        let entries = mem::replace(&mut self.entries, Vec::new());
+        self.slots.clear();
+        for (key, value) in entries {
+            if !f(&key) {
+                self.slots.insert(key, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    fn keys<'a>(&'a self) -> impl Iterator<Item = &'a TableKey<'gc>> + 'a {
+        self.entries
+            .iter()
+            .filter(|(_, v)| *v != Value::Nil)
+            .map(|(k, _)| k)
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (Value<'gc>, Value<'gc>)> + 'a {
+        self.entries
+            .iter()
+            .filter(|(_, v)| *v != Value::Nil)
+            .map(|(k, v)| (k.0, *v))
+    }
+
+    // The first present entry at or after the given slot.
+    fn next(&self, start: usize) -> Option<(Value<'gc>, Value<'gc>)> {
+        self.entries[start.min(self.entries.len())..]
+            .iter()
+            .find(|(_, v)| *v != Value::Nil)
+            .map(|(k, v)| (k.0, *v))
+    }
+}
+
+// Value which implements Hash and Eq, and cannot contain Nil or NaN values.
+#[derive(Debug, Clone, Copy, Collect, PartialEq)]
+#[collect(require_copy)]
 struct TableKey<'gc>(Value<'gc>);
 
 impl<'gc> Eq for TableKey<'gc> {}
@@ -293,6 +638,10 @@ impl<'gc> Hash for TableKey<'gc> {
                 Hash::hash(&7, state);
                 t.hash(state);
             }
+            Value::UserData(u) => {
+                Hash::hash(&8, state);
+                u.hash(state);
+            }
         }
     }
 }
@@ -317,6 +666,29 @@ impl<'gc> TableKey<'gc> {
     }
 }
 
+// The allocation of a value which weak tables hold weakly.  Strings are values rather than objects
+// in Lua, so like numbers they are never removed from weak tables.
+fn weak_ptr<'gc>(value: Value<'gc>) -> Option<GcPtr<'gc>> {
+    match value {
+        Value::Table(t) => Some(t.0.gc_ptr()),
+        Value::Function(Function::Closure(c)) => Some(c.0.gc_ptr()),
+        Value::Function(Function::Callback(c)) => Some(c.0.gc_ptr()),
+        Value::Thread(t) => Some(t.0.gc_ptr()),
+        Value::UserData(u) => Some(u.0.gc_ptr()),
+        _ => None,
+    }
+}
+
+// The allocation of any collectable value, including strings.
+fn gc_ptr<'gc>(value: Value<'gc>) -> Option<GcPtr<'gc>> {
+    match value {
+        Value::String(String::Short8(_, b)) => Some(b.gc_ptr()),
+        Value::String(String::Short32(_, b)) => Some(b.gc_ptr()),
+        Value::String(String::Long(b)) => Some(b.gc_ptr()),
+        value => weak_ptr(value),
+    }
+}
+
 // Returns the closest i64 to a given f64 such that casting the i64 back to an f64 results in an
 // equal value, if such an integer exists.
 fn f64_to_i64(n: f64) -> Option<i64> {
@@ -3,7 +3,7 @@ mod thread;
 mod vm;
 
 pub use error::{BadThreadMode, BinaryOperatorError, ThreadError};
-pub use thread::{Thread, ThreadMode, ThreadSequence};
+pub use thread::{ActiveThreads, Thread, ThreadMode, ThreadSequence};
 
-pub(crate) use thread::LuaFrame;
+pub(crate) use thread::{LuaFrame, MetaReturn};
 pub(crate) use vm::run_vm;
@@ -7,8 +7,11 @@ use gc_arena::{Collect, GcCell, MutationContext};
 use gc_sequence::Sequence;
 
 use crate::{
-    thread::run_vm, BadThreadMode, CallbackResult, CallbackReturn, Closure, Continuation, Error,
-    Function, RegisterIndex, ThreadError, TypeError, UpValue, UpValueState, Value, VarCount,
+    meta_ops::{self, MetaCall},
+    thread::run_vm,
+    BadThreadMode, CallbackResult, CallbackReturn, Closure, Continuation, Error, Function,
+    InternedStringSet, LocatedError, RegisterIndex, RuntimeError, Table, ThreadError, UpValue,
+    UpValueState, Value, VarCount,
 };
 
 #[derive(Clone, Copy, Collect)]
@@ -45,6 +48,37 @@ pub enum ThreadMode {
     Running,
     // Thread has yielded and is waiting on being resumed
     Suspended,
+    // Thread is running, but has resumed another thread and is waiting on it
+    Normal,
+}
+
+/// The stack of threads which are currently being stepped, innermost last.  A thread which resumes
+/// another is stepping it from inside one of its own steps, so it stays below the resumed thread
+/// until that thread yields or returns.
+///
+/// Threads only appear here if they have been given the stack with `Thread::set_active_threads`,
+/// as threads created with `Root::new_thread` are.
+#[derive(Clone, Copy, Collect)]
+#[collect(require_copy)]
+pub struct ActiveThreads<'gc>(GcCell<'gc, Vec<Thread<'gc>>>);
+
+impl<'gc> ActiveThreads<'gc> {
+    pub fn new(mc: MutationContext<'gc, '_>) -> ActiveThreads<'gc> {
+        ActiveThreads(GcCell::allocate(mc, Vec::new()))
+    }
+
+    /// Returns the innermost thread being stepped, which is the thread running the current code.
+    pub fn current(&self) -> Option<Thread<'gc>> {
+        self.0.read().last().copied()
+    }
+
+    /// Returns true if the given thread is being stepped, but is not the innermost thread.
+    pub fn is_resuming(&self, thread: Thread<'gc>) -> bool {
+        match self.0.read().split_last() {
+            Some((_, outer)) => outer.contains(&thread),
+            None => false,
+        }
+    }
 }
 
 #[derive(Collect)]
@@ -58,7 +92,13 @@ pub(crate) struct ThreadState<'gc> {
     frames: Vec<Frame<'gc>>,
     open_upvalues: BTreeMap<usize, UpValue<'gc>>,
     result: Option<Result<Vec<Value<'gc>>, Error<'gc>>>,
+    // The error value this thread died with, kept after its results are taken so that it can be
+    // reported when the thread is closed.
+    error: Option<Value<'gc>>,
     allow_yield: bool,
+    string_metatable: Option<Table<'gc>>,
+    interned_strings: Option<InternedStringSet<'gc>>,
+    active_threads: Option<ActiveThreads<'gc>>,
 }
 
 pub(crate) struct LuaFrame<'gc, 'a> {
@@ -68,6 +108,9 @@ pub(crate) struct LuaFrame<'gc, 'a> {
 
 pub(crate) struct LuaRegisters<'gc, 'a> {
     pub pc: &'a mut usize,
+    // The number of values left to concatenate when a `Concat` instruction is resumed after
+    // calling a `__concat` metamethod
+    pub pending_concat: &'a mut Option<u8>,
     pub stack_frame: &'a mut [Value<'gc>],
     upper_stack: &'a mut [Value<'gc>],
     base: usize,
@@ -116,14 +159,73 @@ impl<'gc> Thread<'gc> {
                 frames: Vec::new(),
                 open_upvalues: BTreeMap::new(),
                 result: None,
+                error: None,
                 allow_yield,
+                string_metatable: None,
+                interned_strings: None,
+                active_threads: None,
             },
         ))
     }
 
+    /// Returns the metatable consulted when indexing strings in code run by this thread.
+    pub fn string_metatable(self) -> Option<Table<'gc>> {
+        self.0.read().string_metatable
+    }
+
+    /// Sets the metatable consulted when indexing strings.  Threads created with
+    /// `Root::new_thread` share the root's string metatable.
+    pub fn set_string_metatable(self, mc: MutationContext<'gc, '_>, metatable: Option<Table<'gc>>) {
+        self.0.write(mc).string_metatable = metatable;
+    }
+
+    /// Returns the set used to intern strings created by code run by this thread.
+    pub fn interned_strings(self) -> Option<InternedStringSet<'gc>> {
+        self.0.read().interned_strings
+    }
+
+    /// Sets the set used to intern strings, such as the results of concatenation.  Threads created
+    /// with `Root::new_thread` share the root's interned strings.
+    pub fn set_interned_strings(
+        self,
+        mc: MutationContext<'gc, '_>,
+        interned_strings: Option<InternedStringSet<'gc>>,
+    ) {
+        self.0.write(mc).interned_strings = interned_strings;
+    }
+
+    /// Returns the stack of threads being stepped which this thread joins while it steps.
+    pub fn active_threads(self) -> Option<ActiveThreads<'gc>> {
+        self.0.read().active_threads
+    }
+
+    /// Sets the stack of threads being stepped.  Threads created with `Root::new_thread` share the
+    /// root's active threads.
+    pub fn set_active_threads(
+        self,
+        mc: MutationContext<'gc, '_>,
+        active_threads: Option<ActiveThreads<'gc>>,
+    ) {
+        self.0.write(mc).active_threads = active_threads;
+    }
+
+    /// Returns whether this thread may yield, which it may if it was created as a coroutine.
+    pub fn allow_yield(self) -> bool {
+        self.0.read().allow_yield
+    }
+
     pub fn mode(self) -> ThreadMode {
         if let Ok(state) = self.0.try_read() {
-            get_mode(&state)
+            match get_mode(&state) {
+                ThreadMode::Running
+                    if state
+                        .active_threads
+                        .map_or(false, |active| active.is_resuming(self)) =>
+                {
+                    ThreadMode::Normal
+                }
+                mode => mode,
+            }
         } else {
             ThreadMode::Running
         }
@@ -138,6 +240,7 @@ impl<'gc> Thread<'gc> {
     ) -> Result<(), BadThreadMode> {
         let mut state = self.0.write(mc);
         check_mode(&state, ThreadMode::Stopped)?;
+        state.error = None;
         ext_call_function(self, &mut state, mc, function, args);
         Ok(())
     }
@@ -150,6 +253,7 @@ impl<'gc> Thread<'gc> {
     ) -> Result<(), BadThreadMode> {
         let mut state = self.0.write(mc);
         check_mode(&state, ThreadMode::Stopped)?;
+        state.error = None;
         state.frames.push(Frame::StartCoroutine(function));
         Ok(())
     }
@@ -201,9 +305,50 @@ impl<'gc> Thread<'gc> {
         Ok(())
     }
 
+    /// If the thread is not `Running`, close any upvalues still open on its stack and reset it to
+    /// `Stopped`, discarding any suspended function and any results.  Returns the error value the
+    /// thread died with, if it died with an error.
+    ///
+    /// To-be-closed variables are not supported, the parser accepts neither `<close>` nor
+    /// `<const>` attributes, so closing a thread never calls any `__close` metamethods.
+    pub fn close(self, mc: MutationContext<'gc, '_>) -> Result<Option<Value<'gc>>, BadThreadMode> {
+        let mut state = self.0.write(mc);
+        let found = get_mode(&state);
+        if found == ThreadMode::Running {
+            return Err(BadThreadMode {
+                expected: None,
+                found,
+            });
+        }
+        close_upvalues(self, &mut state, mc, 0);
+        state.values.clear();
+        state.frames.clear();
+        state.result = None;
+        Ok(state.error.take())
+    }
+
+    /// Attaches the chunk name and current line of the function that called the running callback
+    /// to an error, as `luaL_where(L, 1)` does.  Errors with non-string values, and errors where
+    /// the caller is not a Lua function, are left as they are.
+    pub fn locate_error(self, error: Error<'gc>) -> Error<'gc> {
+        locate_error(&self.0.read(), 1, error)
+    }
+
     /// If the thread is in `Running` mode, either run the Lua VM for a while or step any callback
     /// that we are waiting on.
     pub fn step(self, mc: MutationContext<'gc, '_>) -> Result<(), BadThreadMode> {
+        let active_threads = self.0.read().active_threads;
+        if let Some(active_threads) = active_threads {
+            active_threads.0.write(mc).push(self);
+        }
+        let res = self.step_frames(mc);
+        if let Some(active_threads) = active_threads {
+            active_threads.0.write(mc).pop();
+        }
+        res
+    }
+
+    fn step_frames(self, mc: MutationContext<'gc, '_>) -> Result<(), BadThreadMode> {
         let mut state = self.0.write(mc);
         check_mode(&state, ThreadMode::Running)?;
         match state.frames.last_mut() {
@@ -238,6 +383,7 @@ impl<'gc> Thread<'gc> {
                     };
                     match run_vm(mc, lua_frame, instructions) {
                         Err(err) => {
+                            let err = locate_error(&state, 0, err);
                             unwind(self, &mut state, mc, err);
                             break;
                         }
@@ -273,13 +419,29 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
         }
     }
 
+    // Returns the metatable of string values
+    pub(crate) fn string_metatable(&self) -> Option<Table<'gc>> {
+        self.state.string_metatable
+    }
+
+    // Returns the set used to intern new strings
+    pub(crate) fn interned_strings(&self) -> Option<InternedStringSet<'gc>> {
+        self.state.interned_strings
+    }
+
     // returns a view of the Lua frame's registers
     pub(crate) fn registers<'b>(&'b mut self) -> LuaRegisters<'gc, 'b> {
         match self.state.frames.last_mut() {
-            Some(Frame::Lua { base, pc, .. }) => {
+            Some(Frame::Lua {
+                base,
+                pc,
+                pending_concat,
+                ..
+            }) => {
                 let (upper_stack, stack_frame) = self.state.values.split_at_mut(*base);
                 LuaRegisters {
                     pc,
+                    pending_concat,
                     stack_frame,
                     upper_stack,
                     base: *base,
@@ -343,7 +505,7 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
     ) -> Result<(), ThreadError> {
         match self.state.frames.last_mut() {
             Some(Frame::Lua {
-                expected_returns,
+                expected_return,
                 is_variable,
                 base,
                 ..
@@ -352,20 +514,20 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                     return Err(ThreadError::ExpectedVariable(*is_variable));
                 }
 
-                *expected_returns = Some(returns);
+                *expected_return = Some(LuaReturn::Normal(returns));
                 let function_index = *base + func.0 as usize;
-                let arg_count = args
+                let mut arg_count = args
                     .to_constant()
                     .map(|c| c as usize)
                     .unwrap_or(self.state.values.len() - function_index - 1);
 
-                match self.state.values[function_index] {
-                    Value::Function(Function::Closure(closure)) => {
+                match get_function(&mut self.state.values, function_index, &mut arg_count)? {
+                    Function::Closure(closure) => {
                         let fixed_params = closure.0.proto.fixed_params as usize;
                         let stack_size = closure.0.proto.stack_size as usize;
 
+                        self.state.values.truncate(function_index + 1 + arg_count);
                         let base = if arg_count > fixed_params {
-                            self.state.values.truncate(function_index + 1 + arg_count);
                             self.state.values[function_index + 1..].rotate_left(fixed_params);
                             function_index + 1 + (arg_count - fixed_params)
                         } else {
@@ -380,11 +542,12 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                             is_variable: false,
                             pc: 0,
                             stack_size,
-                            expected_returns: None,
+                            expected_return: None,
+                            pending_concat: None,
                         });
                         Ok(())
                     }
-                    Value::Function(Function::Callback(callback)) => {
+                    Function::Callback(callback) => {
                         let ret = callback.call(
                             self.state.values[function_index + 1..function_index + 1 + arg_count]
                                 .to_vec(),
@@ -393,10 +556,6 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                         callback_return(self.thread, &mut self.state, mc, ret);
                         Ok(())
                     }
-                    val => Err(ThreadError::BadCall(TypeError {
-                        expected: "function",
-                        found: val.type_name(),
-                    })),
                 }
             }
             _ => panic!("top frame is not lua frame"),
@@ -415,7 +574,7 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
     ) -> Result<(), ThreadError> {
         match self.state.frames.last_mut() {
             Some(Frame::Lua {
-                expected_returns,
+                expected_return,
                 is_variable,
                 base,
                 ..
@@ -424,8 +583,8 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                     return Err(ThreadError::ExpectedVariable(false));
                 }
 
-                let arg_count = arg_count as usize;
-                *expected_returns = Some(returns);
+                let mut arg_count = arg_count as usize;
+                *expected_return = Some(LuaReturn::Normal(returns));
                 let given_function_index = *base + func.0 as usize;
                 let function_index = given_function_index + 1 + arg_count;
                 self.state
@@ -436,8 +595,8 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                         self.state.values[given_function_index + i];
                 }
 
-                match self.state.values[function_index] {
-                    Value::Function(Function::Closure(closure)) => {
+                match get_function(&mut self.state.values, function_index, &mut arg_count)? {
+                    Function::Closure(closure) => {
                         let fixed_params = closure.0.proto.fixed_params as usize;
                         let stack_size = closure.0.proto.stack_size as usize;
 
@@ -456,11 +615,12 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                             is_variable: false,
                             pc: 0,
                             stack_size,
-                            expected_returns: None,
+                            expected_return: None,
+                            pending_concat: None,
                         });
                         Ok(())
                     }
-                    Value::Function(Function::Callback(callback)) => {
+                    Function::Callback(callback) => {
                         let ret = callback.call(
                             self.state.values[function_index + 1..function_index + 1 + arg_count]
                                 .to_vec(),
@@ -469,10 +629,74 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                         callback_return(self.thread, &mut self.state, mc, ret);
                         Ok(())
                     }
-                    val => Err(ThreadError::BadCall(TypeError {
-                        expected: "function",
-                        found: val.type_name(),
-                    })),
+                }
+            }
+            _ => panic!("top frame is not lua frame"),
+        }
+    }
+
+    // Calls a metamethod with the given arguments, placing the function and its arguments above all
+    // of the current frame's registers.  On return, the metamethod's first result is handled
+    // according to the given `MetaReturn`.
+    pub(crate) fn call_meta(
+        mut self,
+        mc: MutationContext<'gc, '_>,
+        call: MetaCall<'gc>,
+        meta_return: MetaReturn,
+    ) -> Result<(), ThreadError> {
+        match self.state.frames.last_mut() {
+            Some(Frame::Lua {
+                expected_return,
+                is_variable,
+                base,
+                stack_size,
+                ..
+            }) => {
+                if *is_variable {
+                    return Err(ThreadError::ExpectedVariable(false));
+                }
+
+                *expected_return = Some(LuaReturn::Meta(meta_return));
+                let function_index = *base + *stack_size;
+                let arg_count = call.args.len();
+
+                match call.function {
+                    Function::Closure(closure) => {
+                        self.state.values.truncate(function_index);
+                        self.state
+                            .values
+                            .push(Value::Function(Function::Closure(closure)));
+                        self.state.values.extend(call.args);
+
+                        let fixed_params = closure.0.proto.fixed_params as usize;
+                        let stack_size = closure.0.proto.stack_size as usize;
+
+                        let base = if arg_count > fixed_params {
+                            self.state.values[function_index + 1..].rotate_left(fixed_params);
+                            function_index + 1 + (arg_count - fixed_params)
+                        } else {
+                            function_index + 1
+                        };
+
+                        self.state.values.resize(base + stack_size, Value::Nil);
+
+                        self.state.frames.push(Frame::Lua {
+                            bottom: function_index,
+                            base,
+                            is_variable: false,
+                            pc: 0,
+                            stack_size,
+                            expected_return: None,
+                            pending_concat: None,
+                        });
+                        Ok(())
+                    }
+                    Function::Callback(callback) => {
+                        let ret = callback.call(call.args);
+                        self.state.values.resize(function_index, Value::Nil);
+                        callback_return(self.thread, &mut self.state, mc, ret);
+                        Ok(())
+                    }
                 }
             }
             _ => panic!("top frame is not lua frame"),
@@ -501,13 +725,13 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                 close_upvalues(self.thread, self.state, mc, bottom);
 
                 let function_index = base + func.0 as usize;
-                let arg_count = args
+                let mut arg_count = args
                     .to_constant()
                     .map(|c| c as usize)
                     .unwrap_or(self.state.values.len() - function_index - 1);
 
-                match self.state.values[function_index] {
-                    Value::Function(Function::Closure(closure)) => {
+                match get_function(&mut self.state.values, function_index, &mut arg_count)? {
+                    Function::Closure(closure) => {
                         self.state.values[bottom] = self.state.values[function_index];
                         for i in 0..arg_count {
                             self.state.values[bottom + 1 + i] =
@@ -517,8 +741,8 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                         let fixed_params = closure.0.proto.fixed_params as usize;
                         let stack_size = closure.0.proto.stack_size as usize;
 
+                        self.state.values.truncate(bottom + 1 + arg_count);
                         let base = if arg_count > fixed_params {
-                            self.state.values.truncate(bottom + 1 + arg_count);
                             self.state.values[bottom + 1..].rotate_left(fixed_params);
                             bottom + 1 + (arg_count - fixed_params)
                         } else {
@@ -533,11 +757,12 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                             is_variable: false,
                             pc: 0,
                             stack_size,
-                            expected_returns: None,
+                            expected_return: None,
+                            pending_concat: None,
                         });
                         Ok(())
                     }
-                    Value::Function(Function::Callback(callback)) => {
+                    Function::Callback(callback) => {
                         let ret = callback.call(
                             self.state.values[function_index + 1..function_index + 1 + arg_count]
                                 .to_vec(),
@@ -546,10 +771,6 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                         callback_return(self.thread, &mut self.state, mc, ret);
                         Ok(())
                     }
-                    val => Err(ThreadError::BadCall(TypeError {
-                        expected: "function",
-                        found: val.type_name(),
-                    })),
                 }
             }
             _ => panic!("top frame is not lua frame"),
@@ -591,35 +812,46 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                         callback_return(self.thread, &mut self.state, mc, ret);
                     }
                     Some(Frame::Lua {
-                        expected_returns,
+                        expected_return,
                         is_variable,
                         base,
                         stack_size,
+                        pc,
                         ..
-                    }) => {
-                        let expected_returns =
-                            expected_returns.expect("no expected returns for upper lua frame");
-                        let returning = expected_returns
-                            .to_constant()
-                            .map(|c| c as usize)
-                            .unwrap_or(count);
-
-                        for i in 0..returning.min(count) {
-                            self.state.values[bottom + i] = self.state.values[start + i]
-                        }
+                    }) => match expected_return.expect("no expected returns for upper lua frame") {
+                        LuaReturn::Normal(expected_returns) => {
+                            let returning = expected_returns
+                                .to_constant()
+                                .map(|c| c as usize)
+                                .unwrap_or(count);
+
+                            for i in 0..returning.min(count) {
+                                self.state.values[bottom + i] = self.state.values[start + i]
+                            }
 
-                        for i in count..returning {
-                            self.state.values[bottom + i] = Value::Nil;
-                        }
+                            for i in count..returning {
+                                self.state.values[bottom + i] = Value::Nil;
+                            }
 
-                        if expected_returns.is_variable() {
-                            self.state.values.truncate(bottom + returning);
-                            *is_variable = true;
-                        } else {
+                            if expected_returns.is_variable() {
+                                self.state.values.truncate(bottom + returning);
+                                *is_variable = true;
+                            } else {
+                                self.state.values.resize(*base + *stack_size, Value::Nil);
+                                *is_variable = false;
+                            }
+                        }
+                        LuaReturn::Meta(meta_return) => {
+                            let value = if count > 0 {
+                                self.state.values[start]
+                            } else {
+                                Value::Nil
+                            };
                             self.state.values.resize(*base + *stack_size, Value::Nil);
                             *is_variable = false;
+                            meta_return.apply(&mut self.state.values[*base..], pc, value);
                         }
-                    }
+                    },
                     None => {
                         let ret_vals = self.state.values[start..start + count].to_vec();
                         self.state.result = Some(Ok(ret_vals));
@@ -709,6 +941,40 @@ impl<'gc, 'a> LuaRegisters<'gc, 'a> {
     }
 }
 
+/// What to do with the first result of a metamethod called from a Lua frame.
+#[derive(Debug, Copy, Clone, Collect)]
+#[collect(require_static)]
+pub(crate) enum MetaReturn {
+    /// Discard the result
+    None,
+    /// Place the result in the given register
+    Register(RegisterIndex),
+    /// Skip the next instruction if the result's truthiness matches the given value
+    SkipIf(bool),
+}
+
+impl MetaReturn {
+    fn apply<'gc>(self, registers: &mut [Value<'gc>], pc: &mut usize, value: Value<'gc>) {
+        match self {
+            MetaReturn::None => {}
+            MetaReturn::Register(reg) => registers[reg.0 as usize] = value,
+            MetaReturn::SkipIf(skip_if) => {
+                if value.to_bool() == skip_if {
+                    *pc += 1;
+                }
+            }
+        }
+    }
+}
+
+// How the results of a call made from a Lua frame should be returned to it
+#[derive(Debug, Copy, Clone, Collect)]
+#[collect(require_static)]
+enum LuaReturn {
+    Normal(VarCount),
+    Meta(MetaReturn),
+}
+
 #[derive(Collect)]
 #[collect(empty_drop)]
 enum Frame<'gc> {
@@ -718,11 +984,14 @@ enum Frame<'gc> {
         is_variable: bool,
         pc: usize,
         stack_size: usize,
-        expected_returns: Option<VarCount>,
+        expected_return: Option<LuaReturn>,
+        pending_concat: Option<u8>,
     },
     Continuation {
         bottom: usize,
         continuation: Option<Continuation<'gc>>,
+        // Called with any error raised above this frame, before it is unwound
+        handler: Option<Function<'gc>>,
     },
     StartCoroutine(Function<'gc>),
     ResumeCoroutine,
@@ -793,7 +1062,7 @@ fn ext_call_function<'gc>(
                 state.values[base + i] = args.get(i).cloned().unwrap_or(Value::Nil);
             }
             for i in 0..var_params {
-                state.values[1 + i] = args[fixed_params + i]
+                state.values[bottom + 1 + i] = args[fixed_params + i]
             }
 
             state.frames.push(Frame::Lua {
@@ -802,7 +1071,8 @@ fn ext_call_function<'gc>(
                 is_variable: false,
                 pc: 0,
                 stack_size,
-                expected_returns: None,
+                expected_return: None,
+                pending_concat: None,
             });
         }
         Function::Callback(callback) => {
@@ -812,19 +1082,49 @@ fn ext_call_function<'gc>(
     }
 }
 
+// Returns the function to call for the value at `function_index`.  Values which are not functions
+// are called through their `__call` metamethod, which is inserted at `function_index` so that the
+// called value becomes its first argument.
+fn get_function<'gc>(
+    values: &mut Vec<Value<'gc>>,
+    function_index: usize,
+    arg_count: &mut usize,
+) -> Result<Function<'gc>, ThreadError> {
+    match values[function_index] {
+        Value::Function(function) => Ok(function),
+        value => {
+            let function = meta_ops::call(value).map_err(ThreadError::BadCall)?;
+            values.insert(function_index, Value::Function(function));
+            *arg_count += 1;
+            Ok(function)
+        }
+    }
+}
+
 // Return to the top Lua frame from an external call
 fn return_to_lua<'gc>(state: &mut ThreadState<'gc>, rets: &[Value<'gc>]) {
     match state.frames.last_mut() {
         Some(Frame::Lua {
-            expected_returns,
+            expected_return,
             is_variable,
             base,
             stack_size,
+            pc,
             ..
         }) => {
-            let ret_count = expected_returns
+            let ret_count = match expected_return
                 .take()
-                .expect("no expected returns for lua frame");
+                .expect("no expected returns for lua frame")
+            {
+                LuaReturn::Normal(ret_count) => ret_count,
+                LuaReturn::Meta(meta_return) => {
+                    let value = rets.get(0).copied().unwrap_or(Value::Nil);
+                    state.values.resize(*base + *stack_size, Value::Nil);
+                    *is_variable = false;
+                    meta_return.apply(&mut state.values[*base..], pc, value);
+                    return;
+                }
+            };
             let return_len = ret_count
                 .to_constant()
                 .map(|c| c as usize)
@@ -846,6 +1146,35 @@ fn return_to_lua<'gc>(state: &mut ThreadState<'gc>, rets: &[Value<'gc>]) {
     };
 }
 
+// Attaches the chunk name and line of the current opcode in the frame `level` frames below the top
+// to an error, as PUC-Rio Lua does.  Errors with non-string values, and errors where that frame is
+// not a Lua frame, are left as they are.
+fn locate_error<'gc>(state: &ThreadState<'gc>, level: usize, error: Error<'gc>) -> Error<'gc> {
+    match error {
+        Error::RuntimeError(RuntimeError(Value::String(_))) => {}
+        Error::RuntimeError(_) | Error::ExitError(_) => return error,
+        _ => {}
+    }
+
+    let (closure, pc) = match state.frames.iter().rev().nth(level) {
+        Some(Frame::Lua { bottom, pc, .. }) => match state.values[*bottom] {
+            Value::Function(Function::Closure(closure)) => (closure, *pc),
+            _ => return error,
+        },
+        _ => return error,
+    };
+    let proto = &closure.0.proto;
+    match proto.line_number(pc.saturating_sub(1)) {
+        Some(line_number) => LocatedError {
+            chunk_name: proto.chunk_name,
+            line_number,
+            error: Box::new(error),
+        }
+        .into(),
+        None => error,
+    }
+}
+
 // TODO: `unwind`, `return_ext`, and `callback_return` have to be merged somehow, because otherwise
 // they are a stack overflow risk in pathalogical or malicious cases.
 
@@ -855,10 +1184,32 @@ fn unwind<'gc>(
     mc: MutationContext<'gc, '_>,
     error: Error<'gc>,
 ) {
+    // If the error will be caught by a frame with a message handler, the handler is called on top
+    // of the erroring frames, and the frames are unwound once it returns.  `os.exit` unwinds
+    // without calling any handlers.
+    let handler = match error {
+        Error::ExitError(_) => None,
+        _ => take_handler(state),
+    };
+    if let Some(handler) = handler {
+        let error = error.as_value(mc, state.interned_strings);
+        let bottom = state.values.len();
+        state.frames.push(Frame::Continuation {
+            continuation: Some(Continuation::new_immediate(|res| {
+                Err(RuntimeError(res?.get(0).cloned().unwrap_or(Value::Nil)).into())
+            })),
+            bottom,
+            handler: None,
+        });
+        ext_call_function(thread, state, mc, handler, &[error]);
+        return;
+    }
+
     while let Some(mut top_frame) = state.frames.pop() {
         if let Frame::Continuation {
             continuation,
             bottom,
+            ..
         } = &mut top_frame
         {
             close_upvalues(thread, state, mc, *bottom);
@@ -871,9 +1222,24 @@ fn unwind<'gc>(
     }
     close_upvalues(thread, state, mc, 0);
     state.values.clear();
+    state.error = match error {
+        Error::ExitError(_) => None,
+        _ => Some(error.as_value(mc, state.interned_strings)),
+    };
     state.result = Some(Err(error));
 }
 
+// Takes the message handler of the innermost continuation frame, which is the frame that an error
+// raised now would unwind to.
+fn take_handler<'gc>(state: &mut ThreadState<'gc>) -> Option<Function<'gc>> {
+    for frame in state.frames.iter_mut().rev() {
+        if let Frame::Continuation { handler, .. } = frame {
+            return handler.take();
+        }
+    }
+    None
+}
+
 fn return_ext<'gc>(
     thread: Thread<'gc>,
     state: &mut ThreadState<'gc>,
@@ -916,6 +1282,21 @@ fn return_ext<'gc>(
             state.frames.push(Frame::Continuation {
                 continuation: Some(continuation),
                 bottom,
+                handler: None,
+            });
+            ext_call_function(thread, state, mc, function, &args);
+        }
+        Ok(CallbackResult::TailCallWithHandler {
+            function,
+            args,
+            handler,
+            continuation,
+        }) => {
+            let bottom = state.values.len();
+            state.frames.push(Frame::Continuation {
+                continuation: Some(continuation),
+                bottom,
+                handler: Some(handler),
             });
             ext_call_function(thread, state, mc, function, &args);
         }
@@ -1,8 +1,10 @@
 use gc_arena::{Gc, MutationContext};
 
 use crate::{
-    thread::LuaFrame, BinaryOperatorError, Closure, ClosureState, Error, Function, OpCode,
-    RegisterIndex, String, Table, TypeError, UpValueDescriptor, Value, VarCount,
+    meta_ops::{self, CompareResult, ConcatResult, MetaMethod, MetaResult},
+    thread::{LuaFrame, MetaReturn},
+    BinaryOperatorError, Closure, ClosureState, Error, Function, OpCode, RegisterIndex, Table,
+    UpValueDescriptor, Value, VarCount,
 };
 
 // Runs the VM for the given number of instructions or until the current LuaFrame may have been
@@ -16,6 +18,8 @@ pub(crate) fn run_vm<'gc>(
     assert_ne!(instructions, 0);
 
     let current_function = lua_frame.closure();
+    let string_metatable = lua_frame.string_metatable();
+    let interned_strings = lua_frame.interned_strings();
     let mut registers = lua_frame.registers();
 
     loop {
@@ -54,96 +58,155 @@ pub(crate) fn run_vm<'gc>(
             }
 
             OpCode::GetTableR { dest, table, key } => {
-                registers.stack_frame[dest.0 as usize] =
-                    get_table(registers.stack_frame[table.0 as usize])?
-                        .get(registers.stack_frame[key.0 as usize]);
+                match meta_ops::index(
+                    string_metatable,
+                    registers.stack_frame[table.0 as usize],
+                    registers.stack_frame[key.0 as usize],
+                )? {
+                    MetaResult::Value(v) => registers.stack_frame[dest.0 as usize] = v,
+                    MetaResult::Call(call) => {
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::GetTableC { dest, table, key } => {
-                registers.stack_frame[dest.0 as usize] =
-                    get_table(registers.stack_frame[table.0 as usize])?
-                        .get(current_function.0.proto.constants[key.0 as usize].to_value())
+                match meta_ops::index(
+                    string_metatable,
+                    registers.stack_frame[table.0 as usize],
+                    current_function.0.proto.constants[key.0 as usize].to_value(),
+                )? {
+                    MetaResult::Value(v) => registers.stack_frame[dest.0 as usize] = v,
+                    MetaResult::Call(call) => {
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::SetTableRR { table, key, value } => {
-                get_table(registers.stack_frame[table.0 as usize])?.set(
+                if let Some(call) = meta_ops::new_index(
                     mc,
+                    registers.stack_frame[table.0 as usize],
                     registers.stack_frame[key.0 as usize],
                     registers.stack_frame[value.0 as usize],
-                )?;
+                )? {
+                    lua_frame.call_meta(mc, call, MetaReturn::None)?;
+                    break;
+                }
             }
 
             OpCode::SetTableRC { table, key, value } => {
-                get_table(registers.stack_frame[table.0 as usize])?.set(
+                if let Some(call) = meta_ops::new_index(
                     mc,
+                    registers.stack_frame[table.0 as usize],
                     registers.stack_frame[key.0 as usize],
                     current_function.0.proto.constants[value.0 as usize].to_value(),
-                )?;
+                )? {
+                    lua_frame.call_meta(mc, call, MetaReturn::None)?;
+                    break;
+                }
             }
 
             OpCode::SetTableCR { table, key, value } => {
-                get_table(registers.stack_frame[table.0 as usize])?.set(
+                if let Some(call) = meta_ops::new_index(
                     mc,
+                    registers.stack_frame[table.0 as usize],
                     current_function.0.proto.constants[key.0 as usize].to_value(),
                     registers.stack_frame[value.0 as usize],
-                )?;
+                )? {
+                    lua_frame.call_meta(mc, call, MetaReturn::None)?;
+                    break;
+                }
             }
 
             OpCode::SetTableCC { table, key, value } => {
-                get_table(registers.stack_frame[table.0 as usize])?.set(
+                if let Some(call) = meta_ops::new_index(
                     mc,
+                    registers.stack_frame[table.0 as usize],
                     current_function.0.proto.constants[key.0 as usize].to_value(),
                     current_function.0.proto.constants[value.0 as usize].to_value(),
-                )?;
+                )? {
+                    lua_frame.call_meta(mc, call, MetaReturn::None)?;
+                    break;
+                }
             }
 
             OpCode::GetUpTableR { dest, table, key } => {
-                registers.stack_frame[dest.0 as usize] = get_table(
+                match meta_ops::index(
+                    string_metatable,
                     registers.get_upvalue(current_function.0.upvalues[table.0 as usize]),
-                )?
-                .get(registers.stack_frame[key.0 as usize]);
+                    registers.stack_frame[key.0 as usize],
+                )? {
+                    MetaResult::Value(v) => registers.stack_frame[dest.0 as usize] = v,
+                    MetaResult::Call(call) => {
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::GetUpTableC { dest, table, key } => {
-                registers.stack_frame[dest.0 as usize] =
-                    get_table(registers.get_upvalue(current_function.0.upvalues[table.0 as usize]))?
-                        .get(current_function.0.proto.constants[key.0 as usize].to_value())
+                match meta_ops::index(
+                    string_metatable,
+                    registers.get_upvalue(current_function.0.upvalues[table.0 as usize]),
+                    current_function.0.proto.constants[key.0 as usize].to_value(),
+                )? {
+                    MetaResult::Value(v) => registers.stack_frame[dest.0 as usize] = v,
+                    MetaResult::Call(call) => {
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::SetUpTableRR { table, key, value } => {
-                get_table(registers.get_upvalue(current_function.0.upvalues[table.0 as usize]))?
-                    .set(
-                        mc,
-                        registers.stack_frame[key.0 as usize],
-                        registers.stack_frame[value.0 as usize],
-                    )?;
+                if let Some(call) = meta_ops::new_index(
+                    mc,
+                    registers.get_upvalue(current_function.0.upvalues[table.0 as usize]),
+                    registers.stack_frame[key.0 as usize],
+                    registers.stack_frame[value.0 as usize],
+                )? {
+                    lua_frame.call_meta(mc, call, MetaReturn::None)?;
+                    break;
+                }
             }
 
             OpCode::SetUpTableRC { table, key, value } => {
-                get_table(registers.get_upvalue(current_function.0.upvalues[table.0 as usize]))?
-                    .set(
-                        mc,
-                        registers.stack_frame[key.0 as usize],
-                        current_function.0.proto.constants[value.0 as usize].to_value(),
-                    )?;
+                if let Some(call) = meta_ops::new_index(
+                    mc,
+                    registers.get_upvalue(current_function.0.upvalues[table.0 as usize]),
+                    registers.stack_frame[key.0 as usize],
+                    current_function.0.proto.constants[value.0 as usize].to_value(),
+                )? {
+                    lua_frame.call_meta(mc, call, MetaReturn::None)?;
+                    break;
+                }
             }
 
             OpCode::SetUpTableCR { table, key, value } => {
-                get_table(registers.get_upvalue(current_function.0.upvalues[table.0 as usize]))?
-                    .set(
-                        mc,
-                        current_function.0.proto.constants[key.0 as usize].to_value(),
-                        registers.stack_frame[value.0 as usize],
-                    )?;
+                if let Some(call) = meta_ops::new_index(
+                    mc,
+                    registers.get_upvalue(current_function.0.upvalues[table.0 as usize]),
+                    current_function.0.proto.constants[key.0 as usize].to_value(),
+                    registers.stack_frame[value.0 as usize],
+                )? {
+                    lua_frame.call_meta(mc, call, MetaReturn::None)?;
+                    break;
+                }
             }
 
             OpCode::SetUpTableCC { table, key, value } => {
-                get_table(registers.get_upvalue(current_function.0.upvalues[table.0 as usize]))?
-                    .set(
-                        mc,
-                        current_function.0.proto.constants[key.0 as usize].to_value(),
-                        current_function.0.proto.constants[value.0 as usize].to_value(),
-                    )?;
+                if let Some(call) = meta_ops::new_index(
+                    mc,
+                    registers.get_upvalue(current_function.0.upvalues[table.0 as usize]),
+                    current_function.0.proto.constants[key.0 as usize].to_value(),
+                    current_function.0.proto.constants[value.0 as usize].to_value(),
+                )? {
+                    lua_frame.call_meta(mc, call, MetaReturn::None)?;
+                    break;
+                }
             }
 
             OpCode::Call {
@@ -283,7 +346,7 @@ pub(crate) fn run_vm<'gc>(
             }
 
             OpCode::GenericForLoop { base, jump } => {
-                if registers.stack_frame[base.0 as usize + 1].to_bool() {
+                if registers.stack_frame[base.0 as usize + 1] != Value::Nil {
                     registers.stack_frame[base.0 as usize] =
                         registers.stack_frame[base.0 as usize + 1];
                     *registers.pc = add_offset(*registers.pc, jump);
@@ -292,16 +355,28 @@ pub(crate) fn run_vm<'gc>(
 
             OpCode::SelfR { base, table, key } => {
                 let table = registers.stack_frame[table.0 as usize];
-                let key = current_function.0.proto.constants[key.0 as usize].to_value();
+                let key = registers.stack_frame[key.0 as usize];
                 registers.stack_frame[base.0 as usize + 1] = table;
-                registers.stack_frame[base.0 as usize] = get_table(table)?.get(key);
+                match meta_ops::index(string_metatable, table, key)? {
+                    MetaResult::Value(v) => registers.stack_frame[base.0 as usize] = v,
+                    MetaResult::Call(call) => {
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(base))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::SelfC { base, table, key } => {
                 let table = registers.stack_frame[table.0 as usize];
                 let key = current_function.0.proto.constants[key.0 as usize].to_value();
                 registers.stack_frame[base.0 as usize + 1] = table;
-                registers.stack_frame[base.0 as usize] = get_table(table)?.get(key);
+                match meta_ops::index(string_metatable, table, key)? {
+                    MetaResult::Value(v) => registers.stack_frame[base.0 as usize] = v,
+                    MetaResult::Call(call) => {
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(base))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::Concat {
@@ -309,14 +384,28 @@ pub(crate) fn run_vm<'gc>(
                 source,
                 count,
             } => {
-                registers.stack_frame[dest.0 as usize] = Value::String(
-                    String::concat(
-                        mc,
-                        &registers.stack_frame
-                            [source.0 as usize..source.0 as usize + count as usize],
-                    )
-                    .unwrap(),
-                );
+                let count = registers.pending_concat.take().unwrap_or(count);
+                match meta_ops::concat(
+                    mc,
+                    interned_strings,
+                    &mut registers.stack_frame
+                        [source.0 as usize..source.0 as usize + count as usize],
+                )? {
+                    ConcatResult::Value(v) => registers.stack_frame[dest.0 as usize] = v,
+                    ConcatResult::Call(call, remaining) => {
+                        let result = if remaining > 1 {
+                            // Run this instruction again once the metamethod returns, only
+                            // concatenating the values which remain.
+                            *registers.pending_concat = Some(remaining as u8);
+                            *registers.pc -= 1;
+                            RegisterIndex(source.0 + remaining as u8 - 1)
+                        } else {
+                            dest
+                        };
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(result))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::GetUpValue { source, dest } => {
@@ -333,8 +422,13 @@ pub(crate) fn run_vm<'gc>(
             }
 
             OpCode::Length { dest, source } => {
-                registers.stack_frame[dest.0 as usize] =
-                    Value::Integer(get_table(registers.stack_frame[source.0 as usize])?.length());
+                match meta_ops::length(registers.stack_frame[source.0 as usize])? {
+                    MetaResult::Value(v) => registers.stack_frame[dest.0 as usize] = v,
+                    MetaResult::Call(call) => {
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::EqRR {
@@ -344,8 +438,16 @@ pub(crate) fn run_vm<'gc>(
             } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                if (left == right) == skip_if {
-                    *registers.pc += 1;
+                match meta_ops::equal(left, right)? {
+                    CompareResult::Value(result) => {
+                        if result == skip_if {
+                            *registers.pc += 1;
+                        }
+                    }
+                    CompareResult::Call { call, invert } => {
+                        lua_frame.call_meta(mc, call, MetaReturn::SkipIf(skip_if != invert))?;
+                        break;
+                    }
                 }
             }
 
@@ -356,8 +458,16 @@ pub(crate) fn run_vm<'gc>(
             } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                if (left == right) == skip_if {
-                    *registers.pc += 1;
+                match meta_ops::equal(left, right)? {
+                    CompareResult::Value(result) => {
+                        if result == skip_if {
+                            *registers.pc += 1;
+                        }
+                    }
+                    CompareResult::Call { call, invert } => {
+                        lua_frame.call_meta(mc, call, MetaReturn::SkipIf(skip_if != invert))?;
+                        break;
+                    }
                 }
             }
 
@@ -368,8 +478,16 @@ pub(crate) fn run_vm<'gc>(
             } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                if (left == right) == skip_if {
-                    *registers.pc += 1;
+                match meta_ops::equal(left, right)? {
+                    CompareResult::Value(result) => {
+                        if result == skip_if {
+                            *registers.pc += 1;
+                        }
+                    }
+                    CompareResult::Call { call, invert } => {
+                        lua_frame.call_meta(mc, call, MetaReturn::SkipIf(skip_if != invert))?;
+                        break;
+                    }
                 }
             }
 
@@ -380,8 +498,16 @@ pub(crate) fn run_vm<'gc>(
             } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                if (left == right) == skip_if {
-                    *registers.pc += 1;
+                match meta_ops::equal(left, right)? {
+                    CompareResult::Value(result) => {
+                        if result == skip_if {
+                            *registers.pc += 1;
+                        }
+                    }
+                    CompareResult::Call { call, invert } => {
+                        lua_frame.call_meta(mc, call, MetaReturn::SkipIf(skip_if != invert))?;
+                        break;
+                    }
                 }
             }
 
@@ -392,8 +518,16 @@ pub(crate) fn run_vm<'gc>(
             } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                if (left.less_than(right).ok_or(BinaryOperatorError::LessThan)?) == skip_if {
-                    *registers.pc += 1;
+                match meta_ops::less_than(left, right)? {
+                    CompareResult::Value(result) => {
+                        if result == skip_if {
+                            *registers.pc += 1;
+                        }
+                    }
+                    CompareResult::Call { call, invert } => {
+                        lua_frame.call_meta(mc, call, MetaReturn::SkipIf(skip_if != invert))?;
+                        break;
+                    }
                 }
             }
 
@@ -404,8 +538,16 @@ pub(crate) fn run_vm<'gc>(
             } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                if (left.less_than(right).ok_or(BinaryOperatorError::LessThan)?) == skip_if {
-                    *registers.pc += 1;
+                match meta_ops::less_than(left, right)? {
+                    CompareResult::Value(result) => {
+                        if result == skip_if {
+                            *registers.pc += 1;
+                        }
+                    }
+                    CompareResult::Call { call, invert } => {
+                        lua_frame.call_meta(mc, call, MetaReturn::SkipIf(skip_if != invert))?;
+                        break;
+                    }
                 }
             }
 
@@ -416,8 +558,16 @@ pub(crate) fn run_vm<'gc>(
             } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                if (left.less_than(right).ok_or(BinaryOperatorError::LessThan)?) == skip_if {
-                    *registers.pc += 1;
+                match meta_ops::less_than(left, right)? {
+                    CompareResult::Value(result) => {
+                        if result == skip_if {
+                            *registers.pc += 1;
+                        }
+                    }
+                    CompareResult::Call { call, invert } => {
+                        lua_frame.call_meta(mc, call, MetaReturn::SkipIf(skip_if != invert))?;
+                        break;
+                    }
                 }
             }
 
@@ -428,8 +578,16 @@ pub(crate) fn run_vm<'gc>(
             } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                if (left.less_than(right).ok_or(BinaryOperatorError::LessThan)?) == skip_if {
-                    *registers.pc += 1;
+                match meta_ops::less_than(left, right)? {
+                    CompareResult::Value(result) => {
+                        if result == skip_if {
+                            *registers.pc += 1;
+                        }
+                    }
+                    CompareResult::Call { call, invert } => {
+                        lua_frame.call_meta(mc, call, MetaReturn::SkipIf(skip_if != invert))?;
+                        break;
+                    }
                 }
             }
 
@@ -440,12 +598,16 @@ pub(crate) fn run_vm<'gc>(
             } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                if (left
-                    .less_equal(right)
-                    .ok_or(BinaryOperatorError::LessEqual)?)
-                    == skip_if
-                {
-                    *registers.pc += 1;
+                match meta_ops::less_equal(left, right)? {
+                    CompareResult::Value(result) => {
+                        if result == skip_if {
+                            *registers.pc += 1;
+                        }
+                    }
+                    CompareResult::Call { call, invert } => {
+                        lua_frame.call_meta(mc, call, MetaReturn::SkipIf(skip_if != invert))?;
+                        break;
+                    }
                 }
             }
 
@@ -456,12 +618,16 @@ pub(crate) fn run_vm<'gc>(
             } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                if (left
-                    .less_equal(right)
-                    .ok_or(BinaryOperatorError::LessEqual)?)
-                    == skip_if
-                {
-                    *registers.pc += 1;
+                match meta_ops::less_equal(left, right)? {
+                    CompareResult::Value(result) => {
+                        if result == skip_if {
+                            *registers.pc += 1;
+                        }
+                    }
+                    CompareResult::Call { call, invert } => {
+                        lua_frame.call_meta(mc, call, MetaReturn::SkipIf(skip_if != invert))?;
+                        break;
+                    }
                 }
             }
 
@@ -472,12 +638,16 @@ pub(crate) fn run_vm<'gc>(
             } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                if (left
-                    .less_equal(right)
-                    .ok_or(BinaryOperatorError::LessEqual)?)
-                    == skip_if
-                {
-                    *registers.pc += 1;
+                match meta_ops::less_equal(left, right)? {
+                    CompareResult::Value(result) => {
+                        if result == skip_if {
+                            *registers.pc += 1;
+                        }
+                    }
+                    CompareResult::Call { call, invert } => {
+                        lua_frame.call_meta(mc, call, MetaReturn::SkipIf(skip_if != invert))?;
+                        break;
+                    }
                 }
             }
 
@@ -488,12 +658,16 @@ pub(crate) fn run_vm<'gc>(
             } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                if (left
-                    .less_equal(right)
-                    .ok_or(BinaryOperatorError::LessEqual)?)
-                    == skip_if
-                {
-                    *registers.pc += 1;
+                match meta_ops::less_equal(left, right)? {
+                    CompareResult::Value(result) => {
+                        if result == skip_if {
+                            *registers.pc += 1;
+                        }
+                    }
+                    CompareResult::Call { call, invert } => {
+                        lua_frame.call_meta(mc, call, MetaReturn::SkipIf(skip_if != invert))?;
+                        break;
+                    }
                 }
             }
 
@@ -504,371 +678,650 @@ pub(crate) fn run_vm<'gc>(
 
             OpCode::Minus { dest, source } => {
                 let value = registers.stack_frame[source.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    value.negate().ok_or(BinaryOperatorError::UnaryNegate)?;
+                match value.negate() {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Unm, value, value)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitNot { dest, source } => {
                 let value = registers.stack_frame[source.0 as usize];
-                registers.stack_frame[dest.0 as usize] = value
-                    .bitwise_not()
-                    .ok_or(BinaryOperatorError::BitNot)?;
+                match value.bitwise_not() {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BNot, value, value)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::AddRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.add(right).ok_or(BinaryOperatorError::Add)?;
+                match left.add(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Add, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::AddRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.add(right).ok_or(BinaryOperatorError::Add)?;
+                match left.add(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Add, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::AddCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.add(right).ok_or(BinaryOperatorError::Add)?;
+                match left.add(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Add, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::AddCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.add(right).ok_or(BinaryOperatorError::Add)?;
+                match left.add(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Add, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::SubRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.subtract(right).ok_or(BinaryOperatorError::Add)?;
+                match left.subtract(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Sub, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::SubRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.subtract(right).ok_or(BinaryOperatorError::Subtract)?;
+                match left.subtract(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Sub, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::SubCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.subtract(right).ok_or(BinaryOperatorError::Subtract)?;
+                match left.subtract(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Sub, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::SubCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.subtract(right).ok_or(BinaryOperatorError::Subtract)?;
+                match left.subtract(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Sub, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::MulRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.multiply(right).ok_or(BinaryOperatorError::Multiply)?;
+                match left.multiply(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Mul, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::MulRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.multiply(right).ok_or(BinaryOperatorError::Multiply)?;
+                match left.multiply(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Mul, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::MulCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.multiply(right).ok_or(BinaryOperatorError::Multiply)?;
+                match left.multiply(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Mul, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::MulCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.multiply(right).ok_or(BinaryOperatorError::Multiply)?;
+                match left.multiply(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Mul, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::DivRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .float_divide(right)
-                    .ok_or(BinaryOperatorError::FloatDivide)?;
+                match left.float_divide(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Div, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::DivRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .float_divide(right)
-                    .ok_or(BinaryOperatorError::FloatDivide)?;
+                match left.float_divide(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Div, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::DivCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .float_divide(right)
-                    .ok_or(BinaryOperatorError::FloatDivide)?;
+                match left.float_divide(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Div, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::DivCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .float_divide(right)
-                    .ok_or(BinaryOperatorError::FloatDivide)?;
+                match left.float_divide(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Div, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::IDivRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .floor_divide(right)
-                    .ok_or(BinaryOperatorError::FloorDivide)?;
+                match left.floor_divide(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::IDiv, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::IDivRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .floor_divide(right)
-                    .ok_or(BinaryOperatorError::FloorDivide)?;
+                match left.floor_divide(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::IDiv, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::IDivCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .floor_divide(right)
-                    .ok_or(BinaryOperatorError::FloorDivide)?;
+                match left.floor_divide(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::IDiv, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::IDivCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .floor_divide(right)
-                    .ok_or(BinaryOperatorError::FloorDivide)?;
+                match left.floor_divide(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::IDiv, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::ModRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.modulo(right).ok_or(BinaryOperatorError::Modulo)?;
+                match left.modulo(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Mod, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::ModRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.modulo(right).ok_or(BinaryOperatorError::Modulo)?;
+                match left.modulo(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Mod, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::ModCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.modulo(right).ok_or(BinaryOperatorError::Modulo)?;
+                match left.modulo(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Mod, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::ModCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.modulo(right).ok_or(BinaryOperatorError::Modulo)?;
+                match left.modulo(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Mod, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::PowRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .exponentiate(right)
-                    .ok_or(BinaryOperatorError::Exponentiate)?;
+                match left.exponentiate(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Pow, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::PowRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .exponentiate(right)
-                    .ok_or(BinaryOperatorError::Exponentiate)?;
+                match left.exponentiate(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Pow, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::PowCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .exponentiate(right)
-                    .ok_or(BinaryOperatorError::Exponentiate)?;
+                match left.exponentiate(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Pow, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::PowCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .exponentiate(right)
-                    .ok_or(BinaryOperatorError::Exponentiate)?;
+                match left.exponentiate(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Pow, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitAndRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_and(right).ok_or(BinaryOperatorError::BitAnd)?;
+                match left.bitwise_and(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BAnd, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitAndRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_and(right).ok_or(BinaryOperatorError::BitAnd)?;
+                match left.bitwise_and(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BAnd, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitAndCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_and(right).ok_or(BinaryOperatorError::BitAnd)?;
+                match left.bitwise_and(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BAnd, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitAndCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_and(right).ok_or(BinaryOperatorError::BitAnd)?;
+                match left.bitwise_and(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BAnd, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitOrRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_or(right).ok_or(BinaryOperatorError::BitOr)?;
+                match left.bitwise_or(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BOr, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitOrRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_or(right).ok_or(BinaryOperatorError::BitOr)?;
+                match left.bitwise_or(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BOr, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitOrCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_or(right).ok_or(BinaryOperatorError::BitOr)?;
+                match left.bitwise_or(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BOr, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitOrCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_or(right).ok_or(BinaryOperatorError::BitOr)?;
+                match left.bitwise_or(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BOr, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitXorRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_xor(right).ok_or(BinaryOperatorError::BitXor)?;
+                match left.bitwise_xor(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BXor, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitXorRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_xor(right).ok_or(BinaryOperatorError::BitXor)?;
+                match left.bitwise_xor(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BXor, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitXorCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_xor(right).ok_or(BinaryOperatorError::BitXor)?;
+                match left.bitwise_xor(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BXor, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::BitXorCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_xor(right).ok_or(BinaryOperatorError::BitXor)?;
+                match left.bitwise_xor(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::BXor, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::ShiftLeftRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_left(right)
-                    .ok_or(BinaryOperatorError::ShiftLeft)?;
+                match left.shift_left(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Shl, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::ShiftLeftRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_left(right)
-                    .ok_or(BinaryOperatorError::ShiftLeft)?;
+                match left.shift_left(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Shl, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::ShiftLeftCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_left(right)
-                    .ok_or(BinaryOperatorError::ShiftLeft)?;
+                match left.shift_left(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Shl, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::ShiftLeftCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_left(right)
-                    .ok_or(BinaryOperatorError::ShiftLeft)?;
+                match left.shift_left(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Shl, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::ShiftRightRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_right(right)
-                    .ok_or(BinaryOperatorError::ShiftRight)?;
+                match left.shift_right(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Shr, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::ShiftRightRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_right(right)
-                    .ok_or(BinaryOperatorError::ShiftRight)?;
+                match left.shift_right(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Shr, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::ShiftRightCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_right(right)
-                    .ok_or(BinaryOperatorError::ShiftRight)?;
+                match left.shift_right(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Shr, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::ShiftRightCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_right(right)
-                    .ok_or(BinaryOperatorError::ShiftRight)?;
+                match left.shift_right(right) {
+                    Some(v) => registers.stack_frame[dest.0 as usize] = v,
+                    None => {
+                        let call = meta_ops::arithmetic(MetaMethod::Shr, left, right)?;
+                        lua_frame.call_meta(mc, call, MetaReturn::Register(dest))?;
+                        break;
+                    }
+                }
             }
         }
 
@@ -882,16 +1335,6 @@ pub(crate) fn run_vm<'gc>(
     Ok(instructions)
 }
 
-fn get_table<'gc>(value: Value<'gc>) -> Result<Table<'gc>, TypeError> {
-    match value {
-        Value::Table(t) => Ok(t),
-        val => Err(TypeError {
-            expected: "table",
-            found: val.type_name(),
-        }),
-    }
-}
-
 fn add_offset(pc: usize, offset: i16) -> usize {
     if offset > 0 {
         pc.checked_add(offset as usize).unwrap()
@@ -0,0 +1,213 @@
+use std::any::{Any, TypeId};
+use std::cell::{Ref, RefMut};
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+
+use gc_arena::{Collect, CollectionContext, GcCell, MutationContext};
+
+use crate::{Table, Value};
+
+/// A Lua userdata value, holding an arbitrary Rust value, an optional metatable and a user value.
+#[derive(Clone, Copy, Collect)]
+#[collect(require_copy)]
+pub struct UserData<'gc>(pub GcCell<'gc, UserDataState<'gc>>);
+
+#[derive(Collect)]
+#[collect(empty_drop)]
+pub struct UserDataState<'gc> {
+    data: Data<'gc>,
+    metatable: Option<Table<'gc>>,
+    user_value: Value<'gc>,
+}
+
+/// Names the type of a userdata payload which may hold garbage collected pointers.
+///
+/// A payload containing `Gc` pointers cannot be `'static`, so it cannot be downcast with `Any`
+/// directly.  Instead, a `'static` marker type implements this trait for every `'gc` lifetime, and
+/// the `TypeId` of the marker identifies the payload type.
+pub trait UserDataType<'gc> {
+    type Data: Collect + 'gc;
+}
+
+enum Data<'gc> {
+    Static(Box<dyn Any>),
+    Collect(TypeId, Box<dyn Collect + 'gc>),
+}
+
+unsafe impl<'gc> Collect for Data<'gc> {
+    fn trace(&self, cc: CollectionContext) {
+        match self {
+            Data::Static(_) => {}
+            Data::Collect(_, data) => data.trace(cc),
+        }
+    }
+}
+
+impl<'gc> Data<'gc> {
+    fn downcast_static<T: 'static>(&self) -> Option<&T> {
+        match self {
+            Data::Static(data) => data.downcast_ref(),
+            Data::Collect(_, _) => None,
+        }
+    }
+
+    fn downcast_static_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        match self {
+            Data::Static(data) => data.downcast_mut(),
+            Data::Collect(_, _) => None,
+        }
+    }
+
+    fn downcast<K>(&self) -> Option<&K::Data>
+    where
+        K: UserDataType<'gc> + 'static,
+    {
+        match self {
+            Data::Collect(type_id, data) if *type_id == TypeId::of::<K>() => {
+                // The payload was created from `K::Data` for this same invariant `'gc`, and `K` is
+                // the only thing its `TypeId` depends on.
+                Some(unsafe { &*(&**data as *const dyn Collect as *const K::Data) })
+            }
+            _ => None,
+        }
+    }
+
+    fn downcast_mut<K>(&mut self) -> Option<&mut K::Data>
+    where
+        K: UserDataType<'gc> + 'static,
+    {
+        match self {
+            Data::Collect(type_id, data) if *type_id == TypeId::of::<K>() => {
+                Some(unsafe { &mut *(&mut **data as *mut dyn Collect as *mut K::Data) })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'gc> Debug for UserData<'gc> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("UserData")
+            .field(&GcCell::as_ptr(self.0))
+            .finish()
+    }
+}
+
+impl<'gc> PartialEq for UserData<'gc> {
+    fn eq(&self, other: &UserData<'gc>) -> bool {
+        GcCell::ptr_eq(self.0, other.0)
+    }
+}
+
+impl<'gc> Eq for UserData<'gc> {}
+
+impl<'gc> Hash for UserData<'gc> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        GcCell::as_ptr(self.0).hash(state)
+    }
+}
+
+impl<'gc> UserData<'gc> {
+    /// Creates a userdata holding a value which may contain garbage collected pointers, identified
+    /// by the marker type `K`.  The value is traced along with the userdata.
+    pub fn new<K>(mc: MutationContext<'gc, '_>, data: K::Data) -> UserData<'gc>
+    where
+        K: UserDataType<'gc> + 'static,
+    {
+        UserData::allocate(mc, Data::Collect(TypeId::of::<K>(), Box::new(data)))
+    }
+
+    /// Creates a userdata holding a value which contains no garbage collected pointers.  The value
+    /// is dropped when the userdata is collected.
+    pub fn new_static<T: 'static>(mc: MutationContext<'gc, '_>, data: T) -> UserData<'gc> {
+        UserData::allocate(mc, Data::Static(Box::new(data)))
+    }
+
+    /// Returns a reference to the held value if it was created with `new_static` and is of type
+    /// `T`.
+    pub fn read<'a, T: 'static>(&'a self) -> Option<Ref<'a, T>> {
+        let state = self.0.read();
+        if state.data.downcast_static::<T>().is_some() {
+            Some(Ref::map(state, |state| {
+                state.data.downcast_static::<T>().unwrap()
+            }))
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the held value if it was created with `new_static` and is of
+    /// type `T`.
+    pub fn write<'a, T: 'static>(&'a self, mc: MutationContext<'gc, '_>) -> Option<RefMut<'a, T>> {
+        let mut state = self.0.write(mc);
+        if state.data.downcast_static_mut::<T>().is_some() {
+            Some(RefMut::map(state, |state| {
+                state.data.downcast_static_mut::<T>().unwrap()
+            }))
+        } else {
+            None
+        }
+    }
+
+    /// Returns a reference to the held value if it was created with `new` using the marker type
+    /// `K`.
+    pub fn read_gc<'a, K>(&'a self) -> Option<Ref<'a, K::Data>>
+    where
+        K: UserDataType<'gc> + 'static,
+    {
+        let state = self.0.read();
+        if state.data.downcast::<K>().is_some() {
+            Some(Ref::map(state, |state| state.data.downcast::<K>().unwrap()))
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the held value if it was created with `new` using the marker
+    /// type `K`.
+    pub fn write_gc<'a, K>(&'a self, mc: MutationContext<'gc, '_>) -> Option<RefMut<'a, K::Data>>
+    where
+        K: UserDataType<'gc> + 'static,
+    {
+        let mut state = self.0.write(mc);
+        if state.data.downcast_mut::<K>().is_some() {
+            Some(RefMut::map(state, |state| {
+                state.data.downcast_mut::<K>().unwrap()
+            }))
+        } else {
+            None
+        }
+    }
+
+    pub fn metatable(&self) -> Option<Table<'gc>> {
+        self.0.read().metatable
+    }
+
+    pub fn set_metatable(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        metatable: Option<Table<'gc>>,
+    ) -> Option<Table<'gc>> {
+        std::mem::replace(&mut self.0.write(mc).metatable, metatable)
+    }
+
+    /// Returns the Lua value associated with this userdata, nil by default.
+    pub fn user_value(&self) -> Value<'gc> {
+        self.0.read().user_value
+    }
+
+    pub fn set_user_value(&self, mc: MutationContext<'gc, '_>, value: Value<'gc>) -> Value<'gc> {
+        std::mem::replace(&mut self.0.write(mc).user_value, value)
+    }
+
+    fn allocate(mc: MutationContext<'gc, '_>, data: Data<'gc>) -> UserData<'gc> {
+        UserData(GcCell::allocate(
+            mc,
+            UserDataState {
+                data,
+                metatable: None,
+                user_value: Value::Nil,
+            },
+        ))
+    }
+}
@@ -3,8 +3,10 @@ use std::{f64, i64, io};
 use gc_arena::{Collect, Gc, GcCell};
 
 use crate::{
+    format::write_number,
     lexer::{read_float, read_hex_float},
-    Callback, Closure, String, Table, Thread,
+    meta_ops::{self, MetaMethod},
+    Callback, Closure, String, Table, Thread, UserData,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Collect)]
@@ -25,6 +27,7 @@ pub enum Value<'gc> {
     Table(Table<'gc>),
     Function(Function<'gc>),
     Thread(Thread<'gc>),
+    UserData(UserData<'gc>),
 }
 
 impl<'gc> PartialEq for Value<'gc> {
@@ -55,6 +58,9 @@ impl<'gc> PartialEq for Value<'gc> {
 
             (Value::Thread(a), Value::Thread(b)) => a == b,
             (Value::Thread(_), _) => false,
+
+            (Value::UserData(a), Value::UserData(b)) => a == b,
+            (Value::UserData(_), _) => false,
         }
     }
 }
@@ -69,6 +75,7 @@ impl<'gc> Value<'gc> {
             Value::Table(_) => "table",
             Value::Function(_) => "function",
             Value::Thread(_) => "thread",
+            Value::UserData(_) => "userdata",
         }
     }
 
@@ -260,13 +267,28 @@ impl<'gc> Value<'gc> {
             Value::Nil => write!(w, "nil"),
             Value::Boolean(b) => write!(w, "{}", b),
             Value::Integer(i) => write!(w, "{}", i),
-            Value::Number(f) => write!(w, "{}", f),
+            Value::Number(f) => {
+                let mut buf = Vec::new();
+                write_number(&mut buf, f);
+                w.write_all(&buf)
+            }
             Value::String(s) => w.write_all(s.as_bytes()),
-            Value::Table(t) => write!(w, "<table {:?}>", t.0.as_ptr()),
-            Value::Function(Function::Closure(c)) => write!(w, "<function {:?}>", Gc::as_ptr(c.0)),
-            Value::Function(Function::Callback(c)) => write!(w, "<function {:?}>", Gc::as_ptr(c.0)),
-            Value::Thread(t) => write!(w, "<thread {:?}>", GcCell::as_ptr(t.0)),
+            Value::Table(t) => self.display_object(w, t.0.as_ptr()),
+            Value::Function(Function::Closure(c)) => self.display_object(w, Gc::as_ptr(c.0)),
+            Value::Function(Function::Callback(c)) => self.display_object(w, Gc::as_ptr(c.0)),
+            Value::Thread(t) => self.display_object(w, GcCell::as_ptr(t.0)),
+            Value::UserData(u) => self.display_object(w, GcCell::as_ptr(u.0)),
+        }
+    }
+
+    // Writes "name: 0x...", where the name is the `__name` field of the value's metatable if it is
+    // a string, or its type name otherwise.
+    fn display_object<W: io::Write, T>(self, mut w: W, ptr: *const T) -> Result<(), io::Error> {
+        match meta_ops::get_metamethod(self, MetaMethod::Name) {
+            Value::String(name) => w.write_all(name.as_bytes())?,
+            _ => w.write_all(self.type_name().as_bytes())?,
         }
+        write!(w, ": {:?}", ptr)
     }
 }
 
@@ -317,3 +339,9 @@ impl<'gc> From<Callback<'gc>> for Value<'gc> {
         Value::Function(Function::Callback(v))
     }
 }
+
+impl<'gc> From<UserData<'gc>> for Value<'gc> {
+    fn from(v: UserData<'gc>) -> Value<'gc> {
+        Value::UserData(v)
+    }
+}
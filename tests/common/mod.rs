@@ -0,0 +1,29 @@
+use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
+use luster::{compile, Closure, Error, Function, Lua, StaticError, ThreadSequence, Value};
+
+// Runs the given code, returning whether it returned exactly `true`.
+pub fn run(lua: &mut Lua, code: &'static [u8]) -> Result<bool, StaticError> {
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, move |mc, root| {
+            Ok(Closure::new(
+                mc,
+                compile(mc, root.interned_strings, code)?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map_ok(|res| match &res[..] {
+            &[Value::Boolean(true)] => true,
+            _ => false,
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })
+}
@@ -1,5 +1,8 @@
 use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
-use luster::{compile, Closure, Error, Function, Lua, StaticError, ThreadSequence};
+use luster::{
+    compile, compile_named, Closure, Error, Function, LineNumber, LocatedError, Lua, ParserError,
+    StaticError, ThreadSequence,
+};
 
 #[test]
 fn error_unwind() -> Result<(), Box<StaticError>> {
@@ -52,3 +55,73 @@ fn error_unwind() -> Result<(), Box<StaticError>> {
 
     Ok(())
 }
+
+#[test]
+fn error_located() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            Ok(Closure::new(
+                mc,
+                compile_named(
+                    mc,
+                    root.interned_strings,
+                    b"=test",
+                    &br#"
+                        local t = {}
+                        return t + 1
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?
+            .map(|res| match res {
+                Err(Error::LocatedError(LocatedError {
+                    chunk_name,
+                    line_number: LineNumber(2),
+                    error,
+                })) => match *error {
+                    Error::BinaryOperatorError(_) if chunk_name.as_bytes() == b"=test" => Ok(()),
+                    _ => panic!(),
+                },
+                _ => panic!(),
+            }))
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    Ok(())
+}
+
+#[test]
+fn parser_error_located() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, root| {
+        match compile_named(
+            mc,
+            root.interned_strings,
+            b"=test",
+            &b"local a = 1\na = = 2"[..],
+        ) {
+            Err(Error::LocatedError(LocatedError {
+                chunk_name,
+                line_number: LineNumber(1),
+                error,
+            })) => match *error {
+                Error::ParserError(ParserError::Unexpected { .. }) => {
+                    assert_eq!(chunk_name.as_bytes(), b"=test")
+                }
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+    });
+}
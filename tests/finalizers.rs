@@ -0,0 +1,60 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
+use luster::{compile, Closure, Error, Function, Lua, StaticError, ThreadSequence, Value};
+
+#[test]
+fn gc_error_warns() -> Result<(), Box<StaticError>> {
+    let warnings = Rc::new(RefCell::new(Vec::new()));
+
+    let mut lua = Lua::new();
+    let warnings_clone = warnings.clone();
+    lua.set_warn_handler(move |message| warnings_clone.borrow_mut().push(message.to_owned()));
+
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local finalized = false
+                        local function make()
+                            setmetatable({}, {__gc = function() error("finalizer error") end})
+                            setmetatable({}, {__gc = function() finalized = true end})
+                        end
+                        make()
+                        for i = 1, 10000000 do
+                            if finalized then
+                                return true
+                            end
+                            local t = {i}
+                        end
+                        return false
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map_ok(|b| assert_eq!(b, vec![Value::Boolean(true)]))
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    let warnings = warnings.borrow();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("error in __gc metamethod"));
+    assert!(warnings[0].contains("finalizer error"));
+
+    Ok(())
+}
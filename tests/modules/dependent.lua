@@ -0,0 +1,2 @@
+local simple = require("simple")
+return simple.add(1, 2)
@@ -0,0 +1 @@
+error("failed to load")
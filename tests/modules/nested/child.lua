@@ -0,0 +1 @@
+return "child of " .. ...
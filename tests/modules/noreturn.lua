@@ -0,0 +1 @@
+noreturn_loaded = true
@@ -0,0 +1 @@
+package.loaded[...] = "self"
@@ -0,0 +1,12 @@
+local name, file_name = ...
+loads = (loads or 0) + 1
+
+local M = {}
+M.name = name
+M.file_name = file_name
+
+function M.add(a, b)
+    return a + b
+end
+
+return M
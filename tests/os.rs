@@ -0,0 +1,140 @@
+use std::cell::Cell;
+use std::io;
+use std::rc::Rc;
+
+use luster::{ExitError, Lua, OsHost, StaticError};
+
+mod common;
+
+use common::run;
+
+struct TestHost {
+    exit_code: Rc<Cell<Option<i32>>>,
+}
+
+impl OsHost for TestHost {
+    fn time(&self) -> i64 {
+        1234567890
+    }
+
+    fn clock(&self) -> f64 {
+        1.5
+    }
+
+    fn utc_offset(&self, _: i64) -> i64 {
+        -5 * 3600
+    }
+
+    fn getenv(&self, name: &[u8]) -> Option<Vec<u8>> {
+        if name == b"HOME" {
+            Some(b"/home/test".to_vec())
+        } else {
+            None
+        }
+    }
+
+    fn remove(&self, _: &[u8]) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "denied"))
+    }
+
+    fn rename(&self, _: &[u8], _: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn tmpname(&self) -> io::Result<Vec<u8>> {
+        Ok(b"/virtual/tmp".to_vec())
+    }
+
+    fn exit(&self, code: i32) {
+        self.exit_code.set(Some(code));
+    }
+}
+
+#[test]
+fn custom_host() -> Result<(), StaticError> {
+    let exit_code = Rc::new(Cell::new(None));
+    let mut lua = Lua::with_os_host(TestHost {
+        exit_code: exit_code.clone(),
+    });
+
+    assert!(run(
+        &mut lua,
+        &br#"
+            return
+                os.time() == 1234567890 and
+                os.clock() == 1.5 and
+                os.date("%Y-%m-%d %H:%M %z") == "2009-02-13 18:31 -0500" and
+                os.date("!%H:%M") == "23:31" and
+                os.time({year = 2009, month = 2, day = 13, hour = 18, min = 31, sec = 30}) ==
+                    1234567890 and
+                os.getenv("HOME") == "/home/test" and
+                os.getenv("PATH") == nil and
+                os.remove("anything") == nil and
+                os.rename("a", "b") == true and
+                os.tmpname() == "/virtual/tmp"
+        "#[..],
+    )?);
+    assert_eq!(exit_code.get(), None);
+
+    Ok(())
+}
+
+#[test]
+fn exit_unwinds() {
+    let exit_code = Rc::new(Cell::new(None));
+    let mut lua = Lua::with_os_host(TestHost {
+        exit_code: exit_code.clone(),
+    });
+
+    let res = run(
+        &mut lua,
+        &br#"
+            local co = coroutine.create(function()
+                pcall(os.exit, 3)
+            end)
+            pcall(coroutine.resume, co)
+            return true
+        "#[..],
+    );
+    match res {
+        Err(StaticError::ExitError(ExitError { code: 3 })) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    assert_eq!(exit_code.get(), Some(3));
+
+    let res = run(&mut lua, &b"os.exit(false)"[..]);
+    match res {
+        Err(StaticError::ExitError(ExitError { code: 1 })) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    assert_eq!(exit_code.get(), Some(1));
+
+    // The state remains usable after unwinding.
+    assert!(run(&mut lua, &b"return true"[..]).unwrap());
+}
+
+#[test]
+fn exit_from_finalizer() {
+    let exit_code = Rc::new(Cell::new(None));
+    let mut lua = Lua::with_os_host(TestHost {
+        exit_code: exit_code.clone(),
+    });
+    let warned = Rc::new(Cell::new(false));
+    let warned_clone = warned.clone();
+    lua.set_warn_handler(move |_| warned_clone.set(true));
+
+    let res = run(
+        &mut lua,
+        &br#"
+            setmetatable({}, {__gc = function() os.exit(5) end})
+            collectgarbage()
+            return true
+        "#[..],
+    );
+    match res {
+        Err(StaticError::ExitError(ExitError { code: 5 })) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    assert_eq!(exit_code.get(), Some(5));
+    assert!(!warned.get());
+}
@@ -0,0 +1,115 @@
+use std::io;
+
+use gc_sequence as sequence;
+use luster::{Callback, CallbackResult, Lua, OsHost, StaticError, StdOsHost, String, Table, Value};
+
+mod common;
+
+use common::run;
+
+#[test]
+fn native_modules() -> Result<(), StaticError> {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, root| {
+        root.register_module(
+            mc,
+            b"native",
+            Callback::new_sequence(mc, |args| {
+                Ok(sequence::from_fn_with(args, |mc, args| {
+                    let module = Table::new(mc);
+                    module.set(mc, String::new_static(b"name"), args[0])?;
+                    module.set(
+                        mc,
+                        String::new_static(b"double"),
+                        Callback::new_immediate(mc, |args| {
+                            Ok(CallbackResult::Return(vec![Value::Integer(
+                                args[0].to_integer().unwrap() * 2,
+                            )]))
+                        }),
+                    )?;
+                    Ok(CallbackResult::Return(vec![Value::Table(module)]))
+                }))
+            }),
+        );
+    });
+
+    assert!(run(
+        &mut lua,
+        &br#"
+            local native = require("native")
+            return
+                native.name == "native" and
+                native.double(21) == 42 and
+                require("native") == native and
+                package.loaded.native == native
+        "#[..],
+    )?);
+
+    // Modules in `package.preload` take precedence.
+    assert!(run(
+        &mut lua,
+        &br#"
+            package.loaded.native = nil
+            package.preload.native = function() return "preloaded" end
+            return require("native") == "preloaded"
+        "#[..],
+    )?);
+
+    Ok(())
+}
+
+struct PathHost(StdOsHost);
+
+impl OsHost for PathHost {
+    fn time(&self) -> i64 {
+        self.0.time()
+    }
+
+    fn clock(&self) -> f64 {
+        self.0.clock()
+    }
+
+    fn utc_offset(&self, time: i64) -> i64 {
+        self.0.utc_offset(time)
+    }
+
+    fn getenv(&self, name: &[u8]) -> Option<Vec<u8>> {
+        if name == b"LUA_PATH" {
+            Some(b"./tests/modules/?.lua;;".to_vec())
+        } else {
+            None
+        }
+    }
+
+    fn remove(&self, path: &[u8]) -> io::Result<()> {
+        self.0.remove(path)
+    }
+
+    fn rename(&self, from: &[u8], to: &[u8]) -> io::Result<()> {
+        self.0.rename(from, to)
+    }
+
+    fn tmpname(&self) -> io::Result<Vec<u8>> {
+        self.0.tmpname()
+    }
+
+    fn exit(&self, code: i32) {
+        self.0.exit(code)
+    }
+}
+
+#[test]
+fn path_from_environment() -> Result<(), StaticError> {
+    let mut lua = Lua::with_os_host(PathHost(StdOsHost::new()));
+    assert!(run(
+        &mut lua,
+        &br#"
+            return
+                string.sub(package.path, 1, 24) == "./tests/modules/?.lua;/u" and
+                string.sub(package.path, -13) == "./?/init.lua;" and
+                require("simple").add(1, 2) == 3
+        "#[..],
+    )?);
+
+    Ok(())
+}
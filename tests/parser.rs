@@ -1,8 +1,9 @@
 use luster::parser::{
-    parse_chunk, Block, CallSuffix, Chunk, ConstructorField, Expression, FunctionCallStatement,
-    HeadExpression, PrimaryExpression, SimpleExpression, Statement, SuffixedExpression,
-    TableConstructor,
+    parse_chunk, BinaryOperator, Block, CallSuffix, Chunk, ConstructorField, Expression,
+    FunctionCallStatement, HeadExpression, PrimaryExpression, SimpleExpression, Statement,
+    SuffixedExpression, TableConstructor, UnaryOperator,
 };
+use luster::LineNumber;
 
 #[test]
 fn test_function_call() {
@@ -14,66 +15,113 @@ fn test_function_call() {
         Chunk {
             block: Block {
                 statements: vec![
-                    Statement::FunctionCall(FunctionCallStatement {
-                        head: SuffixedExpression {
-                            primary: PrimaryExpression::Name(
-                                "print".as_bytes().to_vec().into_boxed_slice(),
-                            ),
-                            suffixes: vec![],
-                        },
-                        call: CallSuffix::Function(vec![
-                            Expression {
-                                head: Box::new(HeadExpression::Simple(SimpleExpression::Integer(
-                                    10,
-                                ))),
-                                tail: vec![],
+                    (
+                        LineNumber(0),
+                        Statement::FunctionCall(FunctionCallStatement {
+                            head: SuffixedExpression {
+                                line_number: LineNumber(0),
+                                primary: PrimaryExpression::Name(
+                                    "print".as_bytes().to_vec().into_boxed_slice(),
+                                ),
+                                suffixes: vec![],
+                            },
+                            call: CallSuffix::Function(vec![
+                                Expression {
+                                    head: Box::new(HeadExpression::Simple(
+                                        SimpleExpression::Integer(10,)
+                                    )),
+                                    tail: vec![],
+                                },
+                                Expression {
+                                    head: Box::new(HeadExpression::Simple(
+                                        SimpleExpression::Integer(20,)
+                                    )),
+                                    tail: vec![],
+                                },
+                            ]),
+                        })
+                    ),
+                    (
+                        LineNumber(0),
+                        Statement::FunctionCall(FunctionCallStatement {
+                            head: SuffixedExpression {
+                                line_number: LineNumber(0),
+                                primary: PrimaryExpression::Name(
+                                    "print".as_bytes().to_vec().into_boxed_slice(),
+                                ),
+                                suffixes: vec![],
                             },
-                            Expression {
-                                head: Box::new(HeadExpression::Simple(SimpleExpression::Integer(
-                                    20,
+                            call: CallSuffix::Function(vec![Expression {
+                                head: Box::new(HeadExpression::Simple(SimpleExpression::String(
+                                    "foo".as_bytes().to_vec().into_boxed_slice(),
                                 ))),
                                 tail: vec![],
+                            },]),
+                        })
+                    ),
+                    (
+                        LineNumber(0),
+                        Statement::FunctionCall(FunctionCallStatement {
+                            head: SuffixedExpression {
+                                line_number: LineNumber(0),
+                                primary: PrimaryExpression::Name(
+                                    "print".as_bytes().to_vec().into_boxed_slice(),
+                                ),
+                                suffixes: vec![],
                             },
-                        ]),
-                    }),
-                    Statement::FunctionCall(FunctionCallStatement {
-                        head: SuffixedExpression {
-                            primary: PrimaryExpression::Name(
-                                "print".as_bytes().to_vec().into_boxed_slice(),
-                            ),
-                            suffixes: vec![],
-                        },
-                        call: CallSuffix::Function(vec![Expression {
-                            head: Box::new(HeadExpression::Simple(SimpleExpression::String(
-                                "foo".as_bytes().to_vec().into_boxed_slice(),
-                            ))),
-                            tail: vec![],
-                        },]),
-                    }),
-                    Statement::FunctionCall(FunctionCallStatement {
-                        head: SuffixedExpression {
-                            primary: PrimaryExpression::Name(
-                                "print".as_bytes().to_vec().into_boxed_slice(),
-                            ),
-                            suffixes: vec![],
-                        },
-                        call: CallSuffix::Function(vec![Expression {
-                            head: Box::new(HeadExpression::Simple(
-                                SimpleExpression::TableConstructor(TableConstructor {
-                                    fields: vec![ConstructorField::Array(Expression {
-                                        head: Box::new(HeadExpression::Simple(
-                                            SimpleExpression::Float(30.0),
-                                        )),
-                                        tail: vec![],
-                                    }),],
-                                }),
-                            )),
-                            tail: vec![],
-                        },]),
-                    }),
+                            call: CallSuffix::Function(vec![Expression {
+                                head: Box::new(HeadExpression::Simple(
+                                    SimpleExpression::TableConstructor(TableConstructor {
+                                        fields: vec![ConstructorField::Array(Expression {
+                                            head: Box::new(HeadExpression::Simple(
+                                                SimpleExpression::Float(30.0),
+                                            )),
+                                            tail: vec![],
+                                        }),],
+                                    }),
+                                )),
+                                tail: vec![],
+                            },]),
+                        })
+                    ),
                 ],
                 return_statement: None,
             },
         }
     );
 }
+
+#[test]
+fn test_statement_lines() {
+    let chunk = parse_chunk(
+        "local a = 1\n\nlocal b = {\n  2,\n}\n-- comment\nreturn a".as_bytes(),
+        |s| s.to_vec().into_boxed_slice(),
+    )
+    .unwrap();
+    let lines: Vec<_> = chunk.block.statements.iter().map(|(l, _)| *l).collect();
+    assert_eq!(lines, vec![LineNumber(0), LineNumber(2)]);
+    assert_eq!(chunk.block.return_statement.unwrap().0, LineNumber(6));
+}
+
+#[test]
+fn test_expression_lines() {
+    let chunk = parse_chunk("return 1 +\n-\nx\n,\nf\n()".as_bytes(), |s| {
+        s.to_vec().into_boxed_slice()
+    })
+    .unwrap();
+    let returns = chunk.block.return_statement.unwrap().1.returns;
+    let (op_line, op, right) = &returns[0].tail[0];
+    assert_eq!((*op_line, *op), (LineNumber(0), BinaryOperator::Add));
+    match &*right.head {
+        HeadExpression::UnaryOperator(line, UnaryOperator::Minus, _) => {
+            assert_eq!(*line, LineNumber(1))
+        }
+        head => panic!("unexpected head {:?}", head),
+    }
+    match &*returns[1].head {
+        HeadExpression::Simple(SimpleExpression::Suffixed(suffixed)) => {
+            assert_eq!(suffixed.line_number, LineNumber(4))
+        }
+        head => panic!("unexpected head {:?}", head),
+    }
+}
@@ -0,0 +1,93 @@
+local function add_garbage(t)
+    t[{}] = true
+end
+
+local function test_collect()
+    local t = setmetatable({}, {__mode = "k"})
+    add_garbage(t)
+    local finalized = false
+    local function finalize()
+        setmetatable({}, {__gc = function() finalized = true end})
+    end
+    finalize()
+
+    local r = collectgarbage()
+    collectgarbage("collect")
+    return r == 0 and next(t) == nil and finalized
+end
+
+local function test_count()
+    local before = collectgarbage("count")
+    local t = {}
+    for i = 1, 1000 do
+        t[i] = {}
+    end
+    return math.type(before) == "float" and before > 0 and collectgarbage("count") > before
+end
+
+local function test_count_after_collect()
+    collectgarbage()
+    local t = {}
+    for i = 1, 10000 do
+        t[i] = {}
+    end
+    local before = collectgarbage("count")
+    t = nil
+    collectgarbage()
+    return collectgarbage("count") < before
+end
+
+local function test_step()
+    local finished = false
+    for i = 1, 1000 do
+        if collectgarbage("step", 1) then
+            finished = true
+            break
+        end
+    end
+    return finished and type(collectgarbage("step", 0)) == "boolean"
+end
+
+local function test_stop_restart()
+    local running = collectgarbage("isrunning")
+    local stopped = collectgarbage("stop")
+    local not_running = collectgarbage("isrunning")
+    local restarted = collectgarbage("restart")
+    return running == true and stopped == 0 and not_running == false and
+        restarted == 0 and collectgarbage("isrunning") == true
+end
+
+local function test_stopped_collect()
+    collectgarbage("stop")
+    local t = setmetatable({}, {__mode = "k"})
+    add_garbage(t)
+    collectgarbage()
+    collectgarbage("restart")
+    return next(t) == nil
+end
+
+local function test_parameters()
+    local pause = collectgarbage("setpause", 200)
+    local stepmul = collectgarbage("setstepmul", 400)
+    local mode = collectgarbage("incremental", 300, 0)
+    local ok = math.type(pause) == "integer" and math.type(stepmul) == "integer" and
+        collectgarbage("setpause", pause) == 300 and
+        collectgarbage("setstepmul", stepmul) == 400 and
+        mode == "incremental"
+    return ok and collectgarbage("setpause", pause) == pause
+end
+
+local function test_invalid()
+    local ok, err = pcall(collectgarbage, "invalid")
+    return not ok and string.find(err, "invalid option 'invalid'", 1, true) ~= nil
+end
+
+return
+    test_collect() and
+    test_count() and
+    test_count_after_collect() and
+    test_step() and
+    test_stop_restart() and
+    test_stopped_collect() and
+    test_parameters() and
+    test_invalid()
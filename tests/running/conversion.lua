@@ -0,0 +1,78 @@
+local function test_tostring()
+    return
+        tostring(1) == "1" and
+        tostring(-0) == "0" and
+        tostring(1.0) == "1.0" and
+        tostring(-0.0) == "-0.0" and
+        tostring(0.1) == "0.1" and
+        tostring(1 / 3) == "0.33333333333333" and
+        tostring(1e15) == "1e+15" and
+        tostring(2^53) == "9.007199254741e+15" and
+        tostring(123456789012.0) == "123456789012.0" and
+        tostring(-1.5e-10) == "-1.5e-10" and
+        tostring(1 / 0) == "inf" and
+        tostring(-1 / 0) == "-inf" and
+        tostring(nil) == "nil" and
+        tostring(true) == "true" and
+        tostring("str") == "str" and
+        not pcall(tostring)
+end
+
+local function test_concat()
+    return
+        1.0 .. "" == "1.0" and
+        "x" .. 2.5 == "x2.5" and
+        10 // 3.0 .. "" == "3.0" and
+        3 .. "" == "3" and
+        2^63 .. "" == "9.2233720368548e+18"
+end
+
+local function test_tonumber()
+    return
+        math.type(tonumber("10")) == "integer" and tonumber("10") == 10 and
+        tonumber("  0x10  ") == 16 and
+        math.type(tonumber("1e1")) == "float" and tonumber("1e1") == 10 and
+        tonumber("0x1p4") == 16.0 and
+        tonumber(" -7 ") == -7 and
+        tonumber(".5") == 0.5 and
+        tonumber("5.") == 5.0 and
+        tonumber("0xffffffffffffffff") == -1 and
+        tonumber("9223372036854775807") == math.maxinteger and
+        tonumber("-9223372036854775808") == math.mininteger and
+        math.type(tonumber("9223372036854775808")) == "float" and
+        tonumber("inf") == nil and
+        tonumber("nan") == nil and
+        tonumber("") == nil and
+        tonumber("0x") == nil and
+        tonumber("1 2") == nil and
+        tonumber(5) == 5 and
+        tonumber(5.5) == 5.5 and
+        tonumber(nil) == nil and
+        tonumber({}) == nil and
+        not pcall(tonumber)
+end
+
+local function test_tonumber_base()
+    return
+        tonumber("ff", 16) == 255 and
+        tonumber("FF", 16) == 255 and
+        tonumber("zz", 36) == 1295 and
+        tonumber("111", 2) == 7 and
+        tonumber(" -7 ", 10) == -7 and
+        tonumber("+10", 16) == 16 and
+        tonumber("+", 10) == nil and
+        tonumber("+-1", 10) == nil and
+        tonumber("8", 8) == nil and
+        tonumber("1.0", 10) == nil and
+        tonumber("", 10) == nil and
+        tonumber("-", 10) == nil and
+        not pcall(tonumber, 10, 16) and
+        not pcall(tonumber, "10", 1) and
+        not pcall(tonumber, "10", 37)
+end
+
+return
+    test_tostring() and
+    test_concat() and
+    test_tonumber() and
+    test_tonumber_base()
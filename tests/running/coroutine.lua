@@ -41,6 +41,141 @@ function test2()
         e2 == false and r2 == 'test error' and s2 == "dead"
 end
 
+function test_wrap()
+    local gen = coroutine.wrap(function(a)
+        local b = coroutine.yield(a + 1)
+        local c = coroutine.yield(b * 2)
+        return "done", c
+    end)
+
+    local r1 = gen(1)
+    local r2 = gen(10)
+    local r3, r4 = gen("last")
+    local ok, err = pcall(gen)
+
+    return
+        r1 == 2 and r2 == 20 and r3 == "done" and r4 == "last" and
+        not ok and err == "cannot resume dead coroutine"
+end
+
+function test_wrap_error()
+    local failing = coroutine.wrap(function()
+        coroutine.yield()
+        error({code = 1})
+    end)
+    failing()
+    local ok, err = pcall(failing)
+
+    local adding = coroutine.wrap(function()
+        return 1 + {}
+    end)
+    local ok2, err2 = pcall(adding)
+
+    local caller = load("local wrapped = ...\nlocal r = wrapped()\nreturn r", "=caller")
+    local ok3, err3 = pcall(caller, coroutine.wrap(function() error("werr") end))
+    local ok4, err4 = pcall(coroutine.wrap(function() error("werr") end))
+    local ok5, err5 = pcall(caller, coroutine.wrap(function() error({}) end))
+
+    return
+        not ok and type(err) == "table" and err.code == 1 and
+        not ok2 and type(err2) == "string" and
+        not ok3 and err3 == "caller:2: werr" and
+        not ok4 and err4 == "werr" and
+        not ok5 and type(err5) == "table"
+end
+
+function test_running()
+    local main, ismain = coroutine.running()
+    local co = coroutine.create(function()
+        return coroutine.running()
+    end)
+    local _, inner, inner_ismain = coroutine.resume(co)
+
+    return
+        type(main) == "thread" and ismain == true and
+        inner == co and inner_ismain == false
+end
+
+function test_isyieldable()
+    local yieldable
+    local co = coroutine.create(function()
+        yieldable = coroutine.isyieldable()
+    end)
+    coroutine.resume(co)
+
+    return
+        coroutine.isyieldable() == false and yieldable == true and
+        coroutine.isyieldable(co) == true
+end
+
+function test_normal_status()
+    local outer
+    local status_outer, status_inner
+    local inner = coroutine.create(function()
+        status_outer = coroutine.status(outer)
+        status_inner = coroutine.status(coroutine.running())
+    end)
+    outer = coroutine.create(function()
+        coroutine.resume(inner)
+    end)
+    coroutine.resume(outer)
+
+    local main = coroutine.running()
+    local status_main
+    local co = coroutine.create(function()
+        status_main = coroutine.status(main)
+    end)
+    coroutine.resume(co)
+
+    return
+        status_outer == "normal" and status_inner == "running" and status_main == "normal" and
+        coroutine.status(outer) == "dead"
+end
+
+function test_close()
+    local get
+    local co = coroutine.create(function()
+        local x = 1
+        get = function() return x end
+        coroutine.yield()
+        x = 2
+    end)
+    coroutine.resume(co)
+    local closed = coroutine.close(co)
+    local resumed = coroutine.resume(co)
+
+    local ok, err = pcall(coroutine.close, coroutine.running())
+    local main = coroutine.running()
+    local normal_closing = coroutine.create(function()
+        return pcall(coroutine.close, main)
+    end)
+    local _, normal_ok, normal_err = coroutine.resume(normal_closing)
+    local self_closing = coroutine.create(function()
+        return pcall(coroutine.close, coroutine.running())
+    end)
+    local _, running_ok, running_err = coroutine.resume(self_closing)
+    local failed = coroutine.create(function()
+        error("boom", 0)
+    end)
+    coroutine.resume(failed)
+    local failed_ok, failed_err = coroutine.close(failed)
+
+    return
+        closed == true and coroutine.status(co) == "dead" and get() == 1 and
+        not resumed and
+        not ok and err == "cannot close a running coroutine" and
+        not normal_ok and normal_err == "cannot close a normal coroutine" and
+        not running_ok and running_err == "cannot close a running coroutine" and
+        coroutine.close(self_closing) == true and
+        failed_ok == false and failed_err == "boom" and coroutine.close(failed) == true
+end
+
 return
     test1() and
-    test2()
+    test2() and
+    test_wrap() and
+    test_wrap_error() and
+    test_running() and
+    test_isyieldable() and
+    test_normal_status() and
+    test_close()
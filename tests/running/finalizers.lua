@@ -0,0 +1,93 @@
+local function collect_until(f)
+    for i = 1, 10000000 do
+        if f() then
+            return true
+        end
+        local t = {i}
+    end
+    return false
+end
+
+local function test_gc()
+    local finalized = nil
+    local t = setmetatable({value = 42}, {__gc = function(o) finalized = o.value end})
+    t = nil
+    return collect_until(function() return finalized ~= nil end) and finalized == 42
+end
+
+local function test_resurrect()
+    local resurrected = nil
+    local function make()
+        local inner = {value = "inner"}
+        setmetatable({inner = inner}, {__gc = function(o) resurrected = o end})
+    end
+    make()
+    if not collect_until(function() return resurrected ~= nil end) then
+        return false
+    end
+    return resurrected.inner.value == "inner"
+end
+
+local function test_order()
+    local order = {}
+    local mt = {__gc = function(o) order[#order + 1] = o.i end}
+    local function make()
+        for i = 1, 3 do
+            setmetatable({i = i}, mt)
+        end
+    end
+    make()
+    if not collect_until(function() return #order == 3 end) then
+        return false
+    end
+    return order[1] == 3 and order[2] == 2 and order[3] == 1
+end
+
+local function test_unmarked()
+    local late = false
+    local marked = false
+    local function make()
+        local mt = {}
+        setmetatable({}, mt)
+        mt.__gc = function() late = true end
+        setmetatable({}, {__gc = function() marked = true end})
+    end
+    make()
+    return collect_until(function() return marked end) and not late
+end
+
+local function test_callable()
+    local finalized = false
+    local handler = setmetatable({}, {__call = function(self, o) finalized = o.name end})
+    setmetatable({name = "callable"}, {__gc = handler})
+    return collect_until(function() return finalized end) and finalized == "callable"
+end
+
+local function test_removed_key()
+    local removed = false
+    local kept = false
+    local t = {}
+    local function make()
+        local key = setmetatable({}, {__gc = function() removed = true end})
+        t[key] = 1
+        t[key] = nil
+        t[setmetatable({}, {__gc = function() kept = true end})] = 1
+    end
+    make()
+    if not collect_until(function() return removed end) then
+        return false
+    end
+    local count = 0
+    for k, v in pairs(t) do
+        count = count + 1
+    end
+    return not kept and count == 1
+end
+
+return
+    test_gc() and
+    test_resurrect() and
+    test_order() and
+    test_unmarked() and
+    test_callable() and
+    test_removed_key()
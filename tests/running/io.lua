@@ -0,0 +1,164 @@
+local name = os.tmpname()
+
+function test_write_read()
+    local f = io.open(name, "w")
+    local returns_file = f:write("hello\n", 42, " ", 1.5, " ", 2.0, "\n0x1F -3e2 nope\nlast") == f
+    f:close()
+
+    f = io.open(name)
+    local line = f:read()
+    local with_newline = f:read("L")
+    local i, n, x = f:read("n", "n", "n")
+    local rest = f:read("*a")
+    local eof = f:read("l")
+    local eof_all = f:read("a")
+    f:close()
+
+    return
+        returns_file and
+        line == "hello" and
+        with_newline == "42 1.5 2\n" and
+        i == 31 and math.type(i) == "integer" and
+        n == -300.0 and math.type(n) == "float" and
+        x == nil and
+        rest == "nope\nlast" and
+        eof == nil and
+        eof_all == ""
+end
+
+function test_read_counts()
+    local f = io.open(name, "w")
+    f:write("abcdef")
+    f:close()
+
+    f = io.open(name, "r")
+    local a, b, c = f:read(2, 0, 10)
+    local empty = f:read(0)
+    local d = f:read(1)
+    f:close()
+    return a == "ab" and b == "" and c == "cdef" and empty == nil and d == nil
+end
+
+function test_seek()
+    local f = io.open(name, "w+")
+    f:write("0123456789")
+    local size = f:seek("end")
+    local start = f:seek("set", 2)
+    local two = f:read(2)
+    local pos = f:seek()
+    f:seek("cur", -1)
+    local three = f:read(1)
+    f:close()
+    return size == 10 and start == 2 and two == "23" and pos == 4 and three == "3"
+end
+
+function test_append_and_mixed()
+    local f = io.open(name, "w")
+    f:write("first\n")
+    f:close()
+
+    f = io.open(name, "a")
+    f:write("second\n")
+    f:close()
+
+    f = io.open(name, "r+")
+    local first = f:read("l")
+    f:seek("cur", 0)
+    f:write("SECOND")
+    f:seek("set")
+    local all = f:read("a")
+    f:close()
+
+    return first == "first" and all == "first\nSECOND\n"
+end
+
+function test_lines()
+    local f = io.open(name, "w")
+    f:write("a\nb\n\nc")
+    f:close()
+
+    local lines = {}
+    for l in io.lines(name) do
+        lines[#lines + 1] = l
+    end
+
+    local pairs = {}
+    for a, b in io.lines(name, 1, "l") do
+        pairs[#pairs + 1] = a .. ":" .. b
+    end
+
+    f = io.open(name)
+    local count = 0
+    for l in f:lines("L") do
+        count = count + 1
+    end
+    local still_open = io.type(f)
+    f:close()
+
+    return
+        #lines == 4 and lines[1] == "a" and lines[2] == "b" and lines[3] == "" and
+        lines[4] == "c" and
+        #pairs == 3 and pairs[1] == "a:" and pairs[2] == "b:" and pairs[3] == "\n:c" and
+        count == 4 and still_open == "file" and
+        not pcall(io.lines, name .. "_does_not_exist")
+end
+
+function test_default_files()
+    local old_output = io.output()
+    io.output(name)
+    io.write("via default", "\n")
+    io.close()
+    io.output(old_output)
+
+    io.input(name)
+    local line = io.read()
+    io.input(io.stdin)
+
+    return line == "via default" and io.output() == io.stdout and io.input() == io.stdin
+end
+
+function test_misc()
+    local f = io.open(name, "w")
+    f:setvbuf("no")
+    f:write("x")
+    f:setvbuf("line")
+    f:write("y\n")
+    local flushed = f:flush()
+    f:close()
+
+    local missing, msg, code = io.open(name .. "_does_not_exist")
+    local ok_closed = pcall(f.read, f)
+    local ok_std, std_msg = io.stdout:close()
+
+    local open_file = io.open(name)
+    local open_str = tostring(open_file)
+    open_file:close()
+
+    return
+        flushed == true and
+        string.find(open_str, "^file %(0x%x+%)$") ~= nil and
+        tostring(open_file) == "file (closed)" and
+        string.find(tostring(io.stdout), "^file %(0x%x+%)$") ~= nil and
+        io.type(f) == "closed file" and
+        io.type(io.stdout) == "file" and
+        io.type(42) == nil and
+        type(io.stdin) == "userdata" and
+        missing == nil and type(msg) == "string" and math.type(code) == "integer" and
+        not ok_closed and
+        ok_std == nil and std_msg == "cannot close standard file" and
+        not pcall(io.open, name, "rw") and
+        not pcall(f.seek, f, "bad") and
+        not pcall(io.read, "x")
+end
+
+local res =
+    test_write_read() and
+    test_read_counts() and
+    test_seek() and
+    test_append_and_mixed() and
+    test_lines() and
+    test_default_files() and
+    test_misc()
+
+os.remove(name)
+return res
@@ -0,0 +1,55 @@
+function test_vm_errors()
+    local f = load("local a = 1\n\nreturn a + {}", "=arith")
+    local ok, err = pcall(f)
+    local g = load("local t\nlocal x = 1\nreturn t.x", "@file.lua")
+    local ok2, err2 = pcall(g)
+    return
+        not ok and string.sub(err, 1, 8) == "arith:3:" and
+        not ok2 and string.sub(err2, 1, 11) == "file.lua:3:"
+end
+
+function test_nested_functions()
+    local f = load("local function inner()\n  return nil .. 'x'\nend\nreturn inner()", "=nested")
+    local ok, err = pcall(f)
+    return not ok and string.sub(err, 1, 9) == "nested:2:"
+end
+
+function test_string_chunk_names()
+    local ok, err = pcall(load("return #5"))
+    return not ok and string.sub(err, 1, 23) == "[string \"return #5\"]:1:"
+end
+
+function test_multi_line_expressions()
+    local arith = load("local a =\n 1 +\n nil", "=arith")
+    local _, err1 = pcall(arith)
+    local concat = load("local s = 'a'\n  ..\n  {}", "=concat")
+    local _, err2 = pcall(concat)
+    local unary = load("local n = 1 +\n -\n {}", "=unary")
+    local _, err3 = pcall(unary)
+    local call = load("local t = {}\nlocal x = 1 +\n t.missing(\n 2)", "=call")
+    local _, err4 = pcall(call)
+    local compare = load("if 1\n <\n {} then end", "=compare")
+    local _, err5 = pcall(compare)
+    return
+        string.sub(err1, 1, 8) == "arith:2:" and
+        string.sub(err2, 1, 9) == "concat:2:" and
+        string.sub(err3, 1, 8) == "unary:2:" and
+        string.sub(err4, 1, 7) == "call:3:" and
+        string.sub(err5, 1, 10) == "compare:2:"
+end
+
+function test_error_values_unchanged()
+    local t = {}
+    local ok, err = pcall(function() error(t) end)
+    local ok2, err2 = pcall(function() error("plain") end)
+    return
+        not ok and err == t and
+        not ok2 and err2 == "plain"
+end
+
+return
+    test_vm_errors() and
+    test_nested_functions() and
+    test_string_chunk_names() and
+    test_multi_line_expressions() and
+    test_error_values_unchanged()
@@ -0,0 +1,85 @@
+local name = os.tmpname()
+
+function test_load_string()
+    local f = load("local a, b = ... return a + b")
+    local env = {x = 5}
+    local g = load("return x", "chunk", "t", env)
+    return
+        f(1, 2) == 3 and
+        g() == 5 and
+        load("return x")() == nil
+end
+
+function test_load_reader()
+    local pieces = {"return ", "1 ", "+ ", "2"}
+    local i = 0
+    local f = load(function()
+        i = i + 1
+        return pieces[i]
+    end)
+
+    local function failing_reader()
+        error("reader failed")
+    end
+    local g, err = load(failing_reader)
+
+    local h, bad_piece = load(function() return 1 end)
+
+    return
+        f() == 3 and
+        g == nil and string.find(err, "reader failed") ~= nil and
+        h == nil and bad_piece == "reader function must return a string"
+end
+
+function test_load_errors()
+    local f, err = load("return +", "=custom")
+    local g, named_err = load("return +")
+    local h, mode_err = load("return 1", "chunk", "b")
+    local i, line_err = load("local a = 1\nreturn = a", "=custom")
+    return
+        f == nil and string.sub(err, 1, 10) == "custom:1: " and
+        g == nil and string.sub(named_err, 1, 22) == "[string \"return +\"]:1:" and
+        i == nil and string.sub(line_err, 1, 10) == "custom:2: " and
+        h == nil and mode_err == "attempt to load a text chunk (mode is 'b')"
+end
+
+function test_loadfile()
+    local file = io.open(name, "w")
+    file:write("#!/usr/bin/env lua\nreturn y, ...")
+    file:close()
+
+    local f = loadfile(name)
+    local g = loadfile(name, "t", {y = "env"})
+    local h, err = loadfile(name .. ".missing")
+    return
+        f(1) == nil and select(2, f(1)) == 1 and
+        g() == "env" and
+        h == nil and string.sub(err, 1, 12) == "cannot open "
+end
+
+function test_dofile()
+    local file = io.open(name, "w")
+    file:write("loaded_by_dofile = true return 1, 2")
+    file:close()
+
+    local a, b = dofile(name)
+
+    file = io.open(name, "w")
+    file:write("return +")
+    file:close()
+
+    local ok = pcall(dofile, name)
+    return
+        a == 1 and b == 2 and loaded_by_dofile and
+        not ok
+end
+
+local res =
+    test_load_string() and
+    test_load_reader() and
+    test_load_errors() and
+    test_loadfile() and
+    test_dofile()
+
+os.remove(name)
+return res
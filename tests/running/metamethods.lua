@@ -0,0 +1,271 @@
+local V = {}
+V.__index = V
+
+local function vec(x, y)
+    return setmetatable({x = x, y = y}, V)
+end
+
+local function unwrap(a)
+    if type(a) == "table" then
+        return a.x, a.y
+    else
+        return a, a
+    end
+end
+
+local function binary(op)
+    return function(a, b)
+        local ax, ay = unwrap(a)
+        local bx, by = unwrap(b)
+        return vec(op(ax, bx), op(ay, by))
+    end
+end
+
+V.__add = binary(function(a, b) return a + b end)
+V.__sub = binary(function(a, b) return a - b end)
+V.__mul = binary(function(a, b) return a * b end)
+V.__div = binary(function(a, b) return a / b end)
+V.__mod = binary(function(a, b) return a % b end)
+V.__pow = binary(function(a, b) return a ^ b end)
+V.__idiv = binary(function(a, b) return a // b end)
+V.__band = binary(function(a, b) return a & b end)
+V.__bor = binary(function(a, b) return a | b end)
+V.__bxor = binary(function(a, b) return a ~ b end)
+V.__shl = binary(function(a, b) return a << b end)
+V.__shr = binary(function(a, b) return a >> b end)
+V.__unm = function(a) return vec(-a.x, -a.y) end
+V.__bnot = function(a) return vec(~a.x, ~a.y) end
+
+local function is(v, x, y)
+    return getmetatable(v) == V and v.x == x and v.y == y
+end
+
+local function test_arithmetic()
+    local a = vec(6, 8)
+    local b = vec(2, 4)
+    return
+        is(a + b, 8, 12) and is(a - b, 4, 4) and is(a * b, 12, 32) and
+        is(a / b, 3.0, 2.0) and is(a % b, 0, 0) and is(b ^ 2, 4.0, 16.0) and
+        is(a // b, 3, 2) and is(-a, -6, -8) and
+        is(a + 1, 7, 9) and is(1 + a, 7, 9) and is(2 * a * 2, 24, 32)
+end
+
+local function test_bitwise()
+    local a = vec(6, 12)
+    local b = vec(3, 10)
+    return
+        is(a & b, 2, 8) and is(a | b, 7, 14) and is(a ~ b, 5, 6) and
+        is(a << 1, 12, 24) and is(a >> 1, 3, 6) and is(~a, -7, -13)
+end
+
+local function test_operand_order()
+    local seen
+    local left = setmetatable({}, {__add = function(a, b) seen = "left" return 1 end})
+    local right = setmetatable({}, {__add = function(a, b) seen = "right" return 2 end})
+    local first = left + right
+    local first_seen = seen
+    local second = right + left
+    local second_seen = seen
+    local args = {}
+    local t = setmetatable({}, {__sub = function(a, b) args[1] = a args[2] = b return 0 end})
+    local r = 5 - t
+    return
+        first == 1 and first_seen == "left" and second == 2 and second_seen == "right" and
+        args[1] == 5 and args[2] == t
+end
+
+local function test_concat()
+    local S = {}
+    S.__concat = function(a, b)
+        local as = type(a) == "table" and "<" .. a.s .. ">" or a
+        local bs = type(b) == "table" and "<" .. b.s .. ">" or b
+        return as .. bs
+    end
+    local x = setmetatable({s = "x"}, S)
+    local y = setmetatable({s = "y"}, S)
+    local calls = 0
+    local C = {}
+    C.__concat = function(a, b)
+        calls = calls + 1
+        return setmetatable({}, C)
+    end
+    local c = setmetatable({}, C)
+    local chained = "a" .. c .. "b" .. "c" .. c .. 1
+    return
+        x .. "!" == "<x>!" and "!" .. x == "!<x>" and
+        "a" .. x .. "b" .. y .. 1 == "a<x>b<y>1" and
+        getmetatable(chained) == C and calls == 5
+end
+
+local function test_yield()
+    local Y = {
+        __add = function(a, b)
+            return coroutine.yield("add")
+        end,
+        __concat = function(a, b)
+            return coroutine.yield("concat")
+        end
+    }
+    local t = setmetatable({}, Y)
+    local co = coroutine.create(function()
+        local sum = t + 1
+        local str = t .. "a" .. t
+        return sum, str
+    end)
+    local ok1, r1 = coroutine.resume(co)
+    local ok2, r2 = coroutine.resume(co, 10)
+    local ok3, r3 = coroutine.resume(co, "middle")
+    local ok4, sum, str = coroutine.resume(co, "all")
+    return
+        ok1 and r1 == "add" and ok2 and r2 == "concat" and ok3 and r3 == "concat" and
+        ok4 and sum == 10 and str == "all"
+end
+
+local function test_errors()
+    local t = setmetatable({}, {})
+    return
+        not pcall(function() return t + 1 end) and
+        not pcall(function() return -t end) and
+        not pcall(function() return "a" .. t end) and
+        not pcall(function() return "a" .. nil end) and
+        not pcall(function() return setmetatable({}, {__add = 1}) + 1 end)
+end
+
+local function test_eq()
+    local calls = 0
+    local E = {__eq = function(a, b)
+        calls = calls + 1
+        return a.id == b.id
+    end}
+    local a = setmetatable({id = 1}, E)
+    local b = setmetatable({id = 1}, E)
+    local c = setmetatable({id = 2}, E)
+    local plain = {id = 1}
+    return
+        a == b and a ~= c and not (a == c) and a == a and
+        a == plain and plain == a and a ~= 1 and a ~= "a" and calls == 5
+end
+
+local function test_compare()
+    local O = {}
+    O.__lt = function(a, b) return a.v < b.v end
+    O.__le = function(a, b) return a.v <= b.v end
+    local function o(v) return setmetatable({v = v}, O) end
+    local LtOnly = {__lt = function(a, b) return a.v < b.v end}
+    local function l(v) return setmetatable({v = v}, LtOnly) end
+    local ok = pcall(function() return {} < {} end)
+    return
+        o(1) < o(2) and not (o(2) < o(1)) and o(1) <= o(1) and o(2) > o(1) and
+        o(2) >= o(2) and not (o(3) <= o(2)) and
+        l(1) <= l(1) and l(1) <= l(2) and not (l(2) <= l(1)) and
+        not ok and "a" < "b" and 1 <= 2.5
+end
+
+local function test_len()
+    local t = setmetatable({1, 2, 3}, {__len = function(self) return 42 end})
+    local u = setmetatable({1, 2}, {})
+    return #t == 42 and rawlen(t) == 3 and #u == 2 and #"hello" == 5 and #"" == 0 and
+        not pcall(function() return #5 end)
+end
+
+local function test_call()
+    local C = {__call = function(self, a, b)
+        return self.name, a, b
+    end}
+    local t = setmetatable({name = "t"}, C)
+    local name, a, b = t(1, 2)
+    local function tail(...)
+        return t(...)
+    end
+    local tn, ta = tail("x")
+    local nested = setmetatable({}, {__call = t})
+    local results = {}
+    for i, v in setmetatable({}, {__call = function(self, _, i)
+        if i < 3 then
+            return i + 1, i * 10
+        end
+    end}), nil, 0 do
+        results[i] = v
+    end
+    local ok1 = pcall(function() return ({})() end)
+    local ok2 = pcall(function() return nested() end)
+    return
+        name == "t" and a == 1 and b == 2 and tn == "t" and ta == "x" and
+        results[1] == 0 and results[3] == 20 and not ok1 and not ok2
+end
+
+local function test_yield_compare()
+    local Y = {
+        __lt = function(a, b)
+            return coroutine.yield("lt")
+        end,
+        __len = function(a)
+            return coroutine.yield("len")
+        end
+    }
+    local t = setmetatable({}, Y)
+    local co = coroutine.create(function()
+        local less = t < t
+        local len = #t
+        return less, len
+    end)
+    local ok1, r1 = coroutine.resume(co)
+    local ok2, r2 = coroutine.resume(co, 1)
+    local ok3, less, len = coroutine.resume(co, 7)
+    return ok1 and r1 == "lt" and ok2 and r2 == "len" and ok3 and less == true and len == 7
+end
+
+local function test_tostring()
+    local P = {__tostring = function(self) return "P(" .. self.v .. ")" end}
+    local p = setmetatable({v = 1}, P)
+    local n = setmetatable({}, {__tostring = function() return 42 end})
+    local bad = setmetatable({}, {__tostring = function() return {} end})
+    local y = setmetatable({}, {__tostring = function() return coroutine.yield("y") end})
+    local co = coroutine.create(function() return tostring(y) end)
+    local ok1, r1 = coroutine.resume(co)
+    local ok2, r2 = coroutine.resume(co, "resumed")
+    return
+        tostring(p) == "P(1)" and tostring(n) == "42" and not pcall(tostring, bad) and
+        ok1 and r1 == "y" and ok2 and r2 == "resumed"
+end
+
+local function test_name()
+    local named = tostring(setmetatable({}, {__name = "Thing"}))
+    local unnamed = tostring(setmetatable({}, {__name = 1}))
+    return
+        string.find(named, "^Thing: ") ~= nil and
+        string.find(unnamed, "^table: ") ~= nil and
+        string.find(tostring(print), "^function: ") ~= nil
+end
+
+local function test_pairs_meta()
+    local t = setmetatable({}, {__pairs = function(self)
+        return function(state, k)
+            if k < state then
+                return k + 1, k * 2
+            end
+        end, 3, 0
+    end})
+    local keys = {}
+    for k, v in pairs(t) do
+        keys[#keys + 1] = k
+    end
+    local f, s, k = pairs(setmetatable({}, {__pairs = function() return next end}))
+    return #keys == 3 and keys[3] == 3 and f == next and s == nil and k == nil
+end
+
+return
+    test_arithmetic() and
+    test_bitwise() and
+    test_operand_order() and
+    test_concat() and
+    test_yield() and
+    test_errors() and
+    test_eq() and
+    test_compare() and
+    test_len() and
+    test_call() and
+    test_yield_compare() and
+    test_tostring() and
+    test_name() and
+    test_pairs_meta()
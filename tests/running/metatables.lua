@@ -0,0 +1,146 @@
+local function test_index_table()
+    local base = {a = 1}
+    local middle = setmetatable({b = 2}, {__index = base})
+    local t = setmetatable({c = 3}, {__index = middle})
+    return
+        t.a == 1 and t.b == 2 and t.c == 3 and t.d == nil and
+        rawget(t, "a") == nil and rawget(t, "c") == 3
+end
+
+local function test_index_function()
+    local calls = 0
+    local t = setmetatable({present = true}, {
+        __index = function(self, key)
+            calls = calls + 1
+            return key .. "!"
+        end
+    })
+    local v = t.x
+    local w = t[1]
+    return v == "x!" and w == "1!" and t.present == true and calls == 2
+end
+
+local function test_newindex_table()
+    local store = {}
+    local t = setmetatable({existing = 1}, {__newindex = store})
+    t.a = 1
+    t.existing = 2
+    return rawget(t, "a") == nil and store.a == 1 and t.existing == 2 and store.existing == nil
+end
+
+local function test_newindex_function()
+    local log = {}
+    local t = setmetatable({}, {
+        __newindex = function(self, key, value)
+            log[#log + 1] = key
+            rawset(self, key, value * 2)
+        end
+    })
+    t.a = 1
+    t.a = 5
+    t[2] = 3
+    return t.a == 5 and t[2] == 6 and #log == 2 and log[1] == "a" and log[2] == 2
+end
+
+local function test_methods()
+    local Class = {}
+    Class.__index = Class
+    function Class.new(v)
+        return setmetatable({v = v}, Class)
+    end
+    function Class:get()
+        return self.v
+    end
+
+    local dynamic = setmetatable({}, {
+        __index = function(self, key)
+            return function(self, x)
+                return key .. x
+            end
+        end
+    })
+    return Class.new(4):get() == 4 and dynamic:greet("!") == "greet!"
+end
+
+local function test_upvalue_table()
+    local env = setmetatable({}, {__index = {x = 7}})
+    local function get()
+        return env.x
+    end
+    return get() == 7
+end
+
+local function test_yield_in_metamethod()
+    local t = setmetatable({}, {
+        __index = function(self, key)
+            return coroutine.yield(key)
+        end,
+        __newindex = function(self, key, value)
+            rawset(self, key, coroutine.yield(value))
+        end
+    })
+    local co = coroutine.create(function()
+        local v = t.a
+        t.b = v
+        return t.b
+    end)
+    local ok1, k = coroutine.resume(co)
+    local ok2, v = coroutine.resume(co, 10)
+    local ok3, r = coroutine.resume(co, 20)
+    return ok1 and k == "a" and ok2 and v == 10 and ok3 and r == 20
+end
+
+local function test_yield_as_metamethod()
+    local t = setmetatable({}, {__index = coroutine.yield})
+    local co = coroutine.create(function()
+        return t.a
+    end)
+    local ok1, self, k = coroutine.resume(co)
+    local ok2, r = coroutine.resume(co, "value")
+    return ok1 and self == t and k == "a" and ok2 and r == "value"
+end
+
+local function test_loop()
+    local t = {}
+    setmetatable(t, {__index = t, __newindex = t})
+    local ok1 = pcall(function() return t.x end)
+    local ok2 = pcall(function() t.x = 1 end)
+    return not ok1 and not ok2
+end
+
+local function test_protected()
+    local t = setmetatable({}, {__metatable = "locked"})
+    local ok = pcall(setmetatable, t, {})
+    local mt = {}
+    local u = setmetatable({}, mt)
+    return
+        getmetatable(t) == "locked" and not ok and
+        getmetatable(u) == mt and getmetatable(setmetatable(u, nil)) == nil and
+        getmetatable({}) == nil and getmetatable(1) == nil
+end
+
+local function test_raw()
+    local t = setmetatable({}, {
+        __index = function() return 1 end,
+        __newindex = function() error("no") end
+    })
+    return
+        rawset(t, "a", 2) == t and rawget(t, "a") == 2 and rawget(t, "b") == nil and
+        rawequal(t, t) and not rawequal(t, {}) and rawequal(1, 1.0) and
+        rawlen({1, 2, 3}) == 3 and rawlen("abcd") == 4 and
+        not pcall(rawlen, 1) and not pcall(rawget, 1, 1) and
+        not pcall(setmetatable, {}, 1)
+end
+
+return
+    test_index_table() and
+    test_index_function() and
+    test_newindex_table() and
+    test_newindex_function() and
+    test_methods() and
+    test_upvalue_table() and
+    test_yield_in_metamethod() and
+    test_yield_as_metamethod() and
+    test_loop() and
+    test_protected() and
+    test_raw()
@@ -23,6 +23,29 @@ function test2()
     return t:method(42) == 42
 end
 
+function test3()
+    local t = {}
+    function t:multi(a)
+        return self, a, a + 1
+    end
+
+    local function third(_, _, c)
+        return c
+    end
+
+    local function tail()
+        return t:multi(1)
+    end
+
+    local s, a, b = t:multi(1)
+    local s2, a2, b2 = tail()
+    return
+        s == t and a == 1 and b == 2 and
+        s2 == t and a2 == 1 and b2 == 2 and
+        third(t:multi(1)) == 2
+end
+
 return
     test1() and
-    test2()
+    test2() and
+    test3()
@@ -0,0 +1,81 @@
+local all_conversions =
+    "%a %A %b %B %c %C %d %D %e %F %g %G %h %H %I %j %m %M %p %r %R %S %T %u %U %V %w %W %x %X " ..
+    "%y %Y %z %%"
+
+function test_date()
+    return
+        os.date("!" .. all_conversions, 1234567890) ==
+            "Fri Friday Feb February Fri Feb 13 23:31:30 2009 20 13 02/13/09 13 2009-02-13 09 " ..
+            "2009 Feb 23 11 044 02 31 PM 11:31:30 PM 23:31 30 23:31:30 5 06 07 5 06 02/13/09 " ..
+            "23:31:30 09 2009 +0000 %" and
+        os.date("!" .. all_conversions, -1) ==
+            "Wed Wednesday Dec December Wed Dec 31 23:59:59 1969 19 31 12/31/69 31 1969-12-31 " ..
+            "70 1970 Dec 23 11 365 12 59 PM 11:59:59 PM 23:59 59 23:59:59 3 52 01 3 52 " ..
+            "12/31/69 23:59:59 69 1969 +0000 %" and
+        os.date("!%G %g %V %U %W %j %u %w", 1609459200) == "2020 20 53 00 00 001 5 5" and
+        os.date("!%G %V %U %W", 1230768000) == "2009 01 00 00" and
+        os.date("!%Ey %OH", 0) == "70 00" and
+        type(os.date()) == "string" and
+        not pcall(os.date, "%Q") and
+        not pcall(os.date, "%Ez") and
+        not pcall(os.date, "%")
+end
+
+function test_date_table()
+    local t = os.date("!*t", 1234567890)
+    return
+        t.year == 2009 and t.month == 2 and t.day == 13 and
+        t.hour == 23 and t.min == 31 and t.sec == 30 and
+        t.wday == 6 and t.yday == 44 and t.isdst == false
+end
+
+function test_time()
+    local t = {year = 2009, month = 14, day = 0, hour = 25}
+    local normalized = os.time(t)
+    return
+        math.type(os.time()) == "integer" and
+        os.time({year = 2000, month = 1, day = 1, sec = 1}) -
+            os.time({year = 2000, month = 1, day = 1}) == 1 and
+        os.time(os.date("*t", 1234567890)) == 1234567890 and
+        os.date("%Y-%m-%d %H:%M:%S", normalized) ==
+            "2010-02-01 01:00:00" and
+        t.year == 2010 and t.month == 2 and t.day == 1 and t.hour == 1 and
+        t.wday == 2 and t.yday == 32 and
+        not pcall(os.time, {year = 2000}) and
+        not pcall(os.time, {year = 2000, month = 1, day = 1.5})
+end
+
+function test_clock_difftime()
+    local c = os.clock()
+    return
+        type(c) == "number" and c >= 0 and
+        os.difftime(10, 4) == 6.0 and
+        math.type(os.difftime(10, 4)) == "float"
+end
+
+function test_getenv()
+    return
+        os.getenv("LUSTER_SURELY_NOT_SET_ANYWHERE") == nil and
+        type(os.getenv("PATH")) == "string"
+end
+
+function test_files()
+    local name = os.tmpname()
+    local renamed = name .. "_renamed"
+    local ok, msg, code = os.remove(renamed)
+    return
+        type(name) == "string" and
+        os.rename(name, renamed) == true and
+        os.remove(name) == nil and
+        os.remove(renamed) == true and
+        ok == nil and string.find(msg, renamed, 1, true) == 1 and
+        math.type(code) == "integer"
+end
+
+return
+    test_date() and
+    test_date_table() and
+    test_time() and
+    test_clock_difftime() and
+    test_getenv() and
+    test_files()
@@ -0,0 +1,109 @@
+package.path = "./tests/modules/?.lua;./tests/modules/?/init.lua"
+
+local function test_loaded()
+    return
+        package.loaded._G == _ENV and
+        package.loaded.string == string and
+        package.loaded.table == table and
+        package.loaded.package == package and
+        require("math") == math and
+        type(package.searchers) == "table" and
+        type(package.preload) == "table" and
+        string.sub(package.config, 1, 2) == "/\n"
+end
+
+local function test_lua_modules()
+    local simple = require("simple")
+    if simple.name ~= "simple" or simple.file_name ~= "./tests/modules/simple.lua" or
+            simple.add(2, 3) ~= 5 then
+        return false
+    end
+
+    -- Modules are only loaded once
+    if require("simple") ~= simple or loads ~= 1 or package.loaded.simple ~= simple then
+        return false
+    end
+
+    -- Unless they are removed from `package.loaded`
+    package.loaded.simple = nil
+    local again = require("simple")
+    if again == simple or loads ~= 2 then
+        return false
+    end
+
+    return
+        require("nested.child") == "child of nested.child" and
+        require("nested") == "nested" and
+        require("noreturn") == true and noreturn_loaded and
+        require("selfloaded") == "self" and
+        require("dependent") == 3
+end
+
+local function test_preload()
+    local args
+    package.preload.virtual = function(...)
+        args = {...}
+        return {virtual = true}
+    end
+    local m = require("virtual")
+    return m.virtual and args[1] == "virtual" and package.loaded.virtual == m
+end
+
+local function test_searchers()
+    local calls = 0
+    table.insert(package.searchers, 1, function(name)
+        calls = calls + 1
+        if name == "custom" then
+            return function(name, data)
+                return name .. data
+            end, "!"
+        else
+            return "\n\tnot custom"
+        end
+    end)
+
+    local ok = require("custom") == "custom!" and calls == 1
+    table.remove(package.searchers, 1)
+    return ok
+end
+
+local function test_errors()
+    local ok, err = pcall(require, "missing")
+    if ok or not string.find(err, "module 'missing' not found:", 1, true) or
+            not string.find(err, "no field package.preload['missing']", 1, true) or
+            not string.find(err, "no file './tests/modules/missing.lua'", 1, true) or
+            not string.find(err, "no file './tests/modules/missing/init.lua'", 1, true) then
+        return false
+    end
+
+    local ok, err = pcall(require, "broken")
+    if ok or not string.find(err, "error loading module 'broken' from file './tests/modules/broken.lua'", 1, true) then
+        return false
+    end
+
+    local ok, err = pcall(require, "failing")
+    if ok or err ~= "failed to load" or package.loaded.failing ~= nil then
+        return false
+    end
+
+    return not pcall(require) and not pcall(require, {})
+end
+
+local function test_searchpath()
+    local path = "./tests/modules/?.lua;./tests/modules/?/init.lua"
+    local found = package.searchpath("nested.child", path)
+    local missing, message = package.searchpath("a.b", "./?.x;./?.y")
+    return
+        found == "./tests/modules/nested/child.lua" and
+        package.searchpath("nested", path) == "./tests/modules/nested/init.lua" and
+        package.searchpath("nested_child", path, "_") == "./tests/modules/nested/child.lua" and
+        missing == nil and message == "\n\tno file './a/b.x'\n\tno file './a/b.y'"
+end
+
+return
+    test_loaded() and
+    test_lua_modules() and
+    test_preload() and
+    test_searchers() and
+    test_errors() and
+    test_searchpath()
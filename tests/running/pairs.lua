@@ -0,0 +1,111 @@
+local function count(t)
+    local n = 0
+    for _ in pairs(t) do
+        n = n + 1
+    end
+    return n
+end
+
+local function test_pairs()
+    local t = {1, 2, 3, a = "x", b = "y", [10] = 10, [2.5] = "f", [false] = true}
+    local seen = {}
+    for k, v in pairs(t) do
+        if t[k] ~= v or seen[k] then
+            return false
+        end
+        seen[k] = true
+    end
+    return
+        count(t) == 8 and count(seen) == 8 and count({}) == 0 and
+        seen[1] and seen[3] and seen.a and seen[10] and seen[2.5] and seen[false] and
+        not pcall(pairs) and not pcall(pairs, 1)
+end
+
+local function test_next()
+    local k, v = next({"a"})
+    local ok, err = pcall(next, {}, "missing")
+    return
+        next({}) == nil and
+        k == 1 and v == "a" and
+        next({"a"}, 1) == nil and
+        not ok and err == "invalid key to 'next'" and
+        not pcall(next) and not pcall(next, {}, 0/0)
+end
+
+local function test_ipairs()
+    local t = {5, 6, nil, 8}
+    local s = 0
+    local last
+    for i, v in ipairs(t) do
+        s = s + v
+        last = i
+    end
+    local n = 0
+    for _ in ipairs({}) do
+        n = n + 1
+    end
+    return s == 11 and last == 2 and n == 0 and not pcall(ipairs)
+end
+
+local function test_ipairs_index()
+    local backing = {10, 20, 30}
+    local proxy = setmetatable({}, {__index = function(_, k) return backing[k] end})
+    local s = 0
+    local last
+    for i, v in ipairs(proxy) do
+        s = s + v
+        last = i
+    end
+    local chained = setmetatable({1}, {__index = {nil, 2}})
+    local n = 0
+    for _ in ipairs(chained) do
+        n = n + 1
+    end
+    return s == 60 and last == 3 and n == 2
+end
+
+local function test_clear_during_traversal()
+    local t = {}
+    for i = 1, 1000 do
+        t["k" .. i] = i
+        t[i * 3] = i
+    end
+
+    local n = 0
+    for k in pairs(t) do
+        t[k] = nil
+        n = n + 1
+    end
+    return n == 2000 and next(t) == nil
+end
+
+local function test_assign_during_traversal()
+    local t = {a = 1, b = 2, c = 3, 4, 5}
+    for k, v in pairs(t) do
+        t[k] = v * 10
+    end
+
+    -- Clearing and then resetting a field keeps its place
+    t.b = nil
+    t.b = 200
+    return t.a == 10 and t.b == 200 and t.c == 30 and t[1] == 40 and t[2] == 50 and count(t) == 5
+end
+
+local function test_reuse()
+    local t = {}
+    for i = 1, 100 do
+        t["x" .. i] = i
+        t["x" .. i] = nil
+    end
+    t.y = 1
+    return count(t) == 1 and next(t) == "y"
+end
+
+return
+    test_pairs() and
+    test_next() and
+    test_ipairs() and
+    test_ipairs_index() and
+    test_clear_during_traversal() and
+    test_assign_during_traversal() and
+    test_reuse()
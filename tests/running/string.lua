@@ -5,4 +5,303 @@ function test_concat()
         1 .. 2 .. 3 == "123"
 end
 
-return test_concat()
+function test_len()
+    return
+        string.len("") == 0 and
+        string.len("abc") == 3 and
+        string.len("a\0b") == 3 and
+        string.len(123) == 3
+end
+
+function test_sub()
+    local s = "hello world"
+    return
+        string.sub(s, 1, 5) == "hello" and
+        string.sub(s, 7) == "world" and
+        string.sub(s, -5) == "world" and
+        string.sub(s, -5, -3) == "wor" and
+        string.sub(s, 0) == s and
+        string.sub(s, -100, 2) == "he" and
+        string.sub(s, 5, 2) == "" and
+        string.sub(s, 12) == "" and
+        string.sub(s, 3, 100) == "llo world" and
+        string.sub(s, 2, 0) == ""
+end
+
+function test_case()
+    return
+        string.upper("abc DEF 123") == "ABC DEF 123" and
+        string.lower("abc DEF 123") == "abc def 123" and
+        string.upper("\xe9") == "\xe9"
+end
+
+function test_rep()
+    return
+        string.rep("ab", 3) == "ababab" and
+        string.rep("ab", 3, ",") == "ab,ab,ab" and
+        string.rep("ab", 0) == "" and
+        string.rep("ab", -1) == "" and
+        string.rep("", 5) == "" and
+        string.rep("x", 1, ",") == "x"
+end
+
+function test_reverse()
+    return
+        string.reverse("") == "" and
+        string.reverse("abc") == "cba" and
+        string.reverse("a\0b") == "b\0a"
+end
+
+function test_byte()
+    local a, b, c = string.byte("abc", 1, -1)
+    local x, y = string.byte("abc", 10)
+    return
+        string.byte("abc") == 97 and
+        string.byte("abc", 2) == 98 and
+        string.byte("abc", -1) == 99 and
+        a == 97 and b == 98 and c == 99 and
+        x == nil and y == nil and
+        #{string.byte("abc", 3, 2)} == 0
+end
+
+function test_char()
+    return
+        string.char() == "" and
+        string.char(97, 98, 99) == "abc" and
+        string.char(0, 255) == "\0\255" and
+        not pcall(string.char, 256) and
+        not pcall(string.char, -1)
+end
+
+function test_find()
+    local s, e = string.find("hello world", "wor")
+    local a, b, c = string.find("key = value", "(%w+)%s*=%s*(%w+)")
+    local p, q = string.find("a.b", ".", 1, true)
+    return
+        s == 7 and e == 9 and
+        a == 1 and b == 11 and c == "key" and
+        p == 2 and q == 2 and
+        string.find("abc", "d") == nil and
+        string.find("abc", "b", -1) == nil and
+        string.find("abc", "", 10) == nil and
+        string.find("abc", "", 4) == 4 and
+        string.find("abc", "^b") == nil and
+        string.find("abc", "^a") == 1 and
+        string.find("abc", "c$") == 3 and
+        string.find("a$c", "$c") == 2
+end
+
+function test_match()
+    local k, v = string.match("  name=value  ", "(%a+)=(%a+)")
+    local p1, w, p2 = string.match("hello", "()(ll)()")
+    return
+        k == "name" and v == "value" and
+        p1 == 3 and w == "ll" and p2 == 5 and
+        string.match("hello 123 world", "%d+") == "123" and
+        string.match("f(a(b)c)d", "%b()") == "(a(b)c)" and
+        string.match("THE (quick) fox", "%f[%a]%a+") == "THE" and
+        string.match("the quick", "%f[%l]q%a*") == "quick" and
+        string.match("abcabc", "(abc)%1") == "abc" and
+        string.match("aaab", "a-b") == "aaab" and
+        string.match("aaab", "^a-") == "" and
+        string.match("xyz", "[^x][%l]") == "yz" and
+        string.match("a-b", "[a%-]+") == "a-" and
+        string.match("0x1F", "0x(%x+)") == "1F" and
+        string.match("color", "colou?r") == "color" and
+        string.match("  trim  ", "^%s*(.-)%s*$") == "trim" and
+        string.match("abc", "z") == nil and
+        not pcall(string.match, "abc", "%") and
+        not pcall(string.match, "abc", "[a") and
+        not pcall(string.match, "abc", "(a")
+end
+
+function test_gmatch()
+    local words = {}
+    for w in string.gmatch("one two  three", "%a+") do
+        words[#words + 1] = w
+    end
+    local keys, values = {}, {}
+    for k, v in string.gmatch("a=1, b=2", "(%w+)=(%w+)") do
+        keys[#keys + 1] = k
+        values[#values + 1] = v
+    end
+    local empties = 0
+    for _ in string.gmatch("abc", "x*") do
+        empties = empties + 1
+    end
+    return
+        #words == 3 and words[1] == "one" and words[3] == "three" and
+        keys[2] == "b" and values[2] == "2" and
+        empties == 4
+end
+
+function test_gsub()
+    local r1, n1 = string.gsub("hello world", "o", "0")
+    local r2, n2 = string.gsub("hello world", "o", "0", 1)
+    local r3 = string.gsub("hello world", "(%w+)", "<%1>")
+    local r4 = string.gsub("hello world", "%w+", "%0 %0")
+    local r5 = string.gsub("$name is $age", "%$(%w+)", {name = "bob", age = 42})
+    local r6 = string.gsub("abc", "%w", function(c) return string.upper(c) .. "." end)
+    local r7 = string.gsub("abc", "%w", function(c) if c == "b" then return false end return "x" end)
+    local r8, n8 = string.gsub("abc", "", "-")
+    local r9 = string.gsub("abc", "^a", "x")
+    local r10 = string.gsub("a b c", "%s", "%%")
+    return
+        r1 == "hell0 w0rld" and n1 == 2 and
+        r2 == "hell0 world" and n2 == 1 and
+        r3 == "<hello> <world>" and
+        r4 == "hello hello world world" and
+        r5 == "bob is 42" and
+        r6 == "A.B.C." and
+        r7 == "xbx" and
+        r8 == "-a-b-c-" and n8 == 4 and
+        r9 == "xbc" and
+        r10 == "a%b%c" and
+        not pcall(string.gsub, "abc", "a", "%2") and
+        not pcall(string.gsub, "abc", "a", true)
+end
+
+function test_gsub_yield()
+    local co = coroutine.create(function()
+        return string.gsub("abc", "%w", function(c)
+            return coroutine.yield(c)
+        end)
+    end)
+    local _, a = coroutine.resume(co)
+    local _, b = coroutine.resume(co, "1")
+    local _, c = coroutine.resume(co, "2")
+    local _, r, n = coroutine.resume(co, "3")
+    return a == "a" and b == "b" and c == "c" and r == "123" and n == 3
+end
+
+function test_gsub_index()
+    local proxy = setmetatable({}, {__index = function(_, k) return string.upper(k) end})
+    local r1, n1 = string.gsub("a b", "%w", proxy)
+    local r2 = string.gsub("a b", "%w", setmetatable({}, {__index = {a = "x"}}))
+    local co = coroutine.create(function()
+        return string.gsub("ab", "%w", setmetatable({}, {__index = function(_, k)
+            return coroutine.yield(k)
+        end}))
+    end)
+    local _, a = coroutine.resume(co)
+    local _, b = coroutine.resume(co, "1")
+    local _, r3 = coroutine.resume(co, "2")
+    return r1 == "A B" and n1 == 2 and r2 == "x b" and a == "a" and b == "b" and r3 == "12"
+end
+
+function test_format()
+    return
+        string.format("%d %i %u", 42, -7, 3) == "42 -7 3" and
+        string.format("%5d|%-5d|%05d", 42, 42, 42) == "   42|42   |00042" and
+        string.format("%+d % d %.3d", 5, 5, 5) == "+5  5 005" and
+        string.format("%x %X %#x %o %#o", 255, 255, 255, 8, 8) == "ff FF 0xff 10 010" and
+        string.format("%x", -1) == "ffffffffffffffff" and
+        string.format("%d", 3.0) == "3" and
+        string.format("%c%c%c", 76, 117, 97) == "Lua" and
+        string.format("%5.2f|%-8.3e|%g", 3.14159, 1234.5, 0.0001) == " 3.14|1.234e+03|0.0001" and
+        string.format("%g %g %g", 1e20, 100000, 1e-5) == "1e+20 100000 1e-05" and
+        string.format("%.14g", 0.1) == "0.1" and
+        string.format("%E %G", 1.5, 1e-10) == "1.500000E+00 1E-10" and
+        string.format("%a %A", 1.0, 0.5) == "0x1p+0 0X1P-1" and
+        string.format("%.3a", 1 / 3) == "0x1.555p-2" and
+        string.format("%f %e", 1 / 0, -1 / 0) == "inf -inf" and
+        string.format("%s %s %s", "a", 1, true) == "a 1 true" and
+        string.format("%10s|%-10s|%.2s", "right", "left", "truncate") == "     right|left      |tr" and
+        string.format("100%%") == "100%" and
+        not pcall(string.format, "%d", 1.5) and
+        not pcall(string.format, "%d", "x") and
+        not pcall(string.format, "%d") and
+        not pcall(string.format, "%y", 1) and
+        not pcall(string.format, "%100d", 1) and
+        not pcall(string.format, "%------d", 1)
+end
+
+function test_format_tostring()
+    local calls = 0
+    local obj = setmetatable({}, {__tostring = function()
+        calls = calls + 1
+        return "obj"
+    end})
+    local bad = setmetatable({}, {__tostring = function() return {} end})
+    local before = calls
+    local ok = pcall(string.format, "%s %d", obj, "x")
+    return
+        string.format("[%s|%5s|%.1s]", obj, obj, obj) == "[obj|  obj|o]" and
+        string.format("%s", setmetatable({}, {__name = "Named"})):sub(1, 7) == "Named: " and
+        not ok and calls == before + 3 and
+        not pcall(string.format, "%s", bad)
+end
+
+function test_format_quoted()
+    return
+        string.format("%q", 'he said "hi"\n') == '"he said \\"hi\\"\\\n"' and
+        string.format("%q", "a\0b\0001\r\\") == '"a\\0b\\0001\\13\\\\"' and
+        string.format("%q", 42) == "42" and
+        string.format("%q", math.mininteger) == "0x8000000000000000" and
+        string.format("%q", 1.0) == "0x1p+0" and
+        string.format("%q", -0.5) == "-0x1p-1" and
+        string.format("%q", 1 / 0) == "1e9999" and
+        string.format("%q", -1 / 0) == "-1e9999" and
+        string.format("%q", 0 / 0) == "(0/0)" and
+        string.format("%q %q", nil, false) == "nil false" and
+        not pcall(string.format, "%q", {})
+end
+
+function test_pack()
+    local i1, i2, i3, next = string.unpack("<i4 >h B", string.pack("<i4 >h B", -2, 258, 255))
+    local s1, s2, s3 = string.unpack("s1 z c3", string.pack("s1 z c3", "abc", "def", "gh"))
+    local f, d = string.unpack("f d", string.pack("f d", 0.5, 1 / 3))
+    local aligned = string.pack("!4 b i4", 1, 2)
+    local x, y = string.unpack("!4 b i4", aligned)
+    return
+        string.pack(">I2", 0x1234) == "\x12\x34" and
+        string.pack("<I2", 0x1234) == "\x34\x12" and
+        string.pack("<i3", -1) == "\xff\xff\xff" and
+        string.pack("<i16", -2) == "\xfe" .. string.rep("\xff", 15) and
+        string.unpack("<i16", string.pack("<i16", -2)) == -2 and
+        string.unpack("<j", string.pack("<j", math.mininteger)) == math.mininteger and
+        i1 == -2 and i2 == 258 and i3 == 255 and next == 8 and
+        s1 == "abc" and s2 == "def" and s3 == "gh\0" and
+        f == 0.5 and d == 1 / 3 and
+        string.len(aligned) == 8 and x == 1 and y == 2 and
+        string.pack(">!8 b Xi8 b", 1, 2) == "\1\0\0\0\0\0\0\0\2" and
+        string.pack("b x b", 1, 2) == "\1\0\2" and
+        string.unpack("b", "\1\2\3", 3) == 3 and
+        string.unpack("b", "\1\2\3", -1) == 3 and
+        string.packsize("i4 i8 !8 b d") == 24 and
+        string.packsize("c10") == 10 and
+        not pcall(string.pack, "b", 128) and
+        not pcall(string.pack, "B", -1) and
+        not pcall(string.pack, "i17", 1) and
+        not pcall(string.pack, "y", 1) and
+        not pcall(string.pack, "c2", "abc") and
+        not pcall(string.pack, "z", "a\0b") and
+        not pcall(string.pack, "s1", string.rep("x", 256)) and
+        not pcall(string.pack, "!3 i4", 1) and
+        not pcall(string.pack, "c", "a") and
+        not pcall(string.packsize, "s") and
+        not pcall(string.unpack, "i4", "abc") and
+        not pcall(string.unpack, "z", "abc") and
+        not pcall(string.unpack, "b", "a", 3) and
+        not pcall(string.unpack, "<i9", "\0\0\0\0\0\0\0\0\1")
+end
+
+return
+    test_concat() and
+    test_len() and
+    test_sub() and
+    test_case() and
+    test_rep() and
+    test_reverse() and
+    test_byte() and
+    test_char() and
+    test_find() and
+    test_match() and
+    test_gmatch() and
+    test_gsub() and
+    test_gsub_yield() and
+    test_gsub_index() and
+    test_format() and
+    test_format_tostring() and
+    test_format_quoted() and
+    test_pack()
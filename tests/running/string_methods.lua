@@ -0,0 +1,46 @@
+local function test_methods()
+    local s = "hello"
+    return
+        ("abc"):upper() == "ABC" and
+        s:len() == 5 and
+        s:sub(2, 3) == "el" and
+        ("%d-%s"):format(1, "x") == "1-x" and
+        s:rep(2, ",") == "hello,hello" and
+        ("a,b"):find(",", 1, true) == 2
+end
+
+local function test_index()
+    local s = "hello"
+    return s.upper == string.upper and s.missing == nil and ("x").len("abc") == 3
+end
+
+local function test_metatable()
+    local mt = getmetatable("")
+    return
+        mt ~= nil and mt == getmetatable("other") and mt.__index == string and
+        not pcall(setmetatable, "", {})
+end
+
+local function test_extend()
+    function string.shout(s)
+        return s:upper() .. "!"
+    end
+    local ok = ("hey"):shout() == "HEY!"
+    string.shout = nil
+    return ok
+end
+
+local function test_coroutine()
+    local co = coroutine.create(function(s)
+        return s:upper()
+    end)
+    local ok, r = coroutine.resume(co, "co")
+    return ok and r == "CO"
+end
+
+return
+    test_methods() and
+    test_index() and
+    test_metatable() and
+    test_extend() and
+    test_coroutine()
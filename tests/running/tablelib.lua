@@ -0,0 +1,142 @@
+local function equal(a, b)
+    if #a ~= #b then
+        return false
+    end
+    for i = 1, #a do
+        if a[i] ~= b[i] then
+            return false
+        end
+    end
+    return true
+end
+
+function test_insert_remove()
+    local t = {1, 2, 3}
+    table.insert(t, 4)
+    table.insert(t, 1, 0)
+    table.insert(t, 3, 1.5)
+    local ok1 = equal(t, {0, 1, 1.5, 2, 3, 4})
+
+    local r1 = table.remove(t)
+    local r2 = table.remove(t, 1)
+    local r3 = table.remove({})
+    local ok2 = equal(t, {1, 1.5, 2, 3})
+
+    return
+        ok1 and ok2 and
+        r1 == 4 and r2 == 0 and r3 == nil and
+        not pcall(table.insert, t, 10, 1) and
+        not pcall(table.insert, t, 1, 2, 3) and
+        not pcall(table.remove, t, 10)
+end
+
+function test_concat()
+    return
+        table.concat({}) == "" and
+        table.concat({1, 2, 3}) == "123" and
+        table.concat({"a", "b", "c"}, ", ") == "a, b, c" and
+        table.concat({"a", "b", "c"}, "-", 2) == "b-c" and
+        table.concat({"a", "b", "c"}, "-", 2, 2) == "b" and
+        table.concat({"a", "b", "c"}, "-", 3, 2) == "" and
+        not pcall(table.concat, {1, {}, 3})
+end
+
+function test_pack_unpack()
+    local t = table.pack(1, nil, 3)
+    local a, b, c = table.unpack({1, 2, 3})
+    local x, y = table.unpack({1, 2, 3}, 2)
+    local p, q = table.unpack({1, 2, 3}, 2, 3)
+    return
+        t.n == 3 and t[1] == 1 and t[2] == nil and t[3] == 3 and
+        a == 1 and b == 2 and c == 3 and
+        x == 2 and y == 3 and
+        p == 2 and q == 3 and
+        table.unpack({}, 1, 0) == nil and
+        not pcall(table.unpack, {}, 1, 1e8)
+end
+
+function test_move()
+    local t = {1, 2, 3, 4, 5}
+    table.move(t, 1, 3, 3)
+    local u = table.move({1, 2, 3}, 1, 3, 2, {})
+    local v = {1, 2, 3, 4, 5}
+    table.move(v, 3, 5, 1)
+    return
+        equal(t, {1, 2, 1, 2, 3}) and
+        u[1] == nil and u[2] == 1 and u[4] == 3 and
+        equal(v, {3, 4, 5, 4, 5})
+end
+
+function test_sort()
+    local t = {5, 2, 8, 1, 9, 3, 7, 4, 6, 0}
+    table.sort(t)
+    local ok1 = equal(t, {0, 1, 2, 3, 4, 5, 6, 7, 8, 9})
+
+    table.sort(t, function(a, b) return a > b end)
+    local ok2 = equal(t, {9, 8, 7, 6, 5, 4, 3, 2, 1, 0})
+
+    local s = {"pear", "apple", "fig"}
+    table.sort(s)
+    local ok3 = equal(s, {"apple", "fig", "pear"})
+
+    local big = {}
+    for i = 1, 2000 do
+        big[i] = (i * 7919) % 2003
+    end
+    table.sort(big)
+    local ok4 = true
+    for i = 2, #big do
+        if big[i - 1] > big[i] then
+            ok4 = false
+        end
+    end
+
+    local empty = {}
+    table.sort(empty)
+
+    return
+        ok1 and ok2 and ok3 and ok4 and #empty == 0 and
+        not pcall(table.sort, {1, "a", 2})
+end
+
+function test_sort_yield()
+    local co = coroutine.create(function(t)
+        table.sort(t, function(a, b)
+            coroutine.yield()
+            return a < b
+        end)
+        return t
+    end)
+
+    local t = {3, 1, 2, 5, 4}
+    local yields = 0
+    coroutine.resume(co, t)
+    while coroutine.status(co) ~= "dead" do
+        yields = yields + 1
+        coroutine.resume(co)
+    end
+    return yields > 0 and equal(t, {1, 2, 3, 4, 5})
+end
+
+function test_sort_lt()
+    local mt = {__lt = function(a, b) return a.v < b.v end}
+    local function box(v) return setmetatable({v = v}, mt) end
+    local t = {box(3), box(1), box(2), box(5), box(4)}
+    table.sort(t)
+    local ok = true
+    for i = 1, 5 do
+        if t[i].v ~= i then
+            ok = false
+        end
+    end
+    return ok and not pcall(table.sort, {{}, {}})
+end
+
+return
+    test_insert_remove() and
+    test_concat() and
+    test_pack_unpack() and
+    test_move() and
+    test_sort() and
+    test_sort_yield() and
+    test_sort_lt()
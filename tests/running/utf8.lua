@@ -0,0 +1,103 @@
+function test_char()
+    return
+        utf8.char() == "" and
+        utf8.char(72, 105) == "Hi" and
+        utf8.char(0xe9) == "\xc3\xa9" and
+        utf8.char(0x20ac) == "\xe2\x82\xac" and
+        utf8.char(0x1f600) == "\xf0\x9f\x98\x80" and
+        utf8.char(0x7fffffff) == "\xfd\xbf\xbf\xbf\xbf\xbf" and
+        not pcall(utf8.char, -1) and
+        not pcall(utf8.char, 0x80000000)
+end
+
+function test_charpattern()
+    local count = 0
+    for c in string.gmatch("h\xc3\xa9\xe2\x82\xac", utf8.charpattern) do
+        count = count + 1
+    end
+    return count == 3
+end
+
+function test_codepoint()
+    local a, b, c = utf8.codepoint("h\xc3\xa9\xe2\x82\xac", 1, -1)
+    return
+        utf8.codepoint("abc") == 97 and
+        utf8.codepoint("abc", 3) == 99 and
+        a == 104 and b == 0xe9 and c == 0x20ac and
+        utf8.codepoint("abc", 3, 2) == nil and
+        utf8.codepoint("\xf4\x90\x80\x80", 1, 1, true) == 0x110000 and
+        not pcall(utf8.codepoint, "\xf4\x90\x80\x80") and
+        not pcall(utf8.codepoint, "\xed\xa0\x80") and
+        not pcall(utf8.codepoint, "\xff") and
+        not pcall(utf8.codepoint, "\xc0\x80") and
+        not pcall(utf8.codepoint, "abc", 4) and
+        not pcall(utf8.codepoint, "abc", 0)
+end
+
+function test_len()
+    local n, pos = utf8.len("ab\xffcd")
+    local m, pos2 = utf8.len("\xed\xa0\x80")
+    return
+        utf8.len("") == 0 and
+        utf8.len("abc") == 3 and
+        utf8.len("h\xc3\xa9\xe2\x82\xac") == 3 and
+        utf8.len("h\xc3\xa9\xe2\x82\xac", 2) == 2 and
+        utf8.len("h\xc3\xa9\xe2\x82\xac", -3) == 1 and
+        utf8.len("abc", 4) == 0 and
+        n == nil and pos == 3 and
+        m == nil and pos2 == 1 and
+        utf8.len("\xed\xa0\x80", 1, -1, true) == 1 and
+        not pcall(utf8.len, "abc", 5) and
+        not pcall(utf8.len, "abc", 1, 4)
+end
+
+function test_offset()
+    local s = "h\xc3\xa9\xe2\x82\xacx"
+    return
+        utf8.offset(s, 1) == 1 and
+        utf8.offset(s, 2) == 2 and
+        utf8.offset(s, 3) == 4 and
+        utf8.offset(s, 4) == 7 and
+        utf8.offset(s, 5) == 8 and
+        utf8.offset(s, 6) == nil and
+        utf8.offset(s, -1) == 7 and
+        utf8.offset(s, -2) == 4 and
+        utf8.offset(s, -4) == 1 and
+        utf8.offset(s, -5) == nil and
+        utf8.offset(s, 0, 3) == 2 and
+        utf8.offset(s, 0, 6) == 4 and
+        utf8.offset(s, 2, 2) == 4 and
+        not pcall(utf8.offset, s, 1, 3) and
+        not pcall(utf8.offset, s, 1, 10)
+end
+
+function test_codes()
+    local positions, codes = {}, {}
+    for p, c in utf8.codes("h\xc3\xa9\xe2\x82\xac") do
+        positions[#positions + 1] = p
+        codes[#codes + 1] = c
+    end
+    local lax = 0
+    for _, c in utf8.codes("\xf4\x90\x80\x80", true) do
+        lax = c
+    end
+    return
+        #positions == 3 and
+        positions[1] == 1 and positions[2] == 2 and positions[3] == 4 and
+        codes[1] == 104 and codes[2] == 0xe9 and codes[3] == 0x20ac and
+        lax == 0x110000 and
+        not pcall(function()
+            for _ in utf8.codes("a\xffb") do end
+        end) and
+        not pcall(function()
+            for _ in utf8.codes("\xf4\x90\x80\x80") do end
+        end)
+end
+
+return
+    test_char() and
+    test_charpattern() and
+    test_codepoint() and
+    test_len() and
+    test_offset() and
+    test_codes()
@@ -20,6 +20,29 @@ local function test2()
         varargs(0, 1, 1, 2, 3, 5) == 4
 end
 
+local function test3()
+    local function second(a, b)
+        return b
+    end
+    local function tail(a, b)
+        return second(a)
+    end
+
+    do local w, x, y, z = 1, 2, 3, 4 end
+    return second(1) == nil and tail(1, 2) == nil
+end
+
+local function test4()
+    local function collect(...)
+        return ...
+    end
+
+    local ok, a, b = pcall(collect, 1, 2)
+    return ok and a == 1 and b == 2
+end
+
 return
     test1() and
-    test2()
+    test2() and
+    test3() and
+    test4()
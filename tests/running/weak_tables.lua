@@ -0,0 +1,127 @@
+local function collect_until(f)
+    for i = 1, 10000000 do
+        if f() then
+            return true
+        end
+        local t = {i}
+    end
+    return false
+end
+
+-- Waits until at least one full collection has finished
+local function full_collection()
+    local sentinel = setmetatable({}, {__mode = "k"})
+    sentinel[{}] = true
+    return collect_until(function() return next(sentinel) == nil end)
+end
+
+local function test_weak_keys()
+    local t = setmetatable({}, {__mode = "k"})
+    local kept = {}
+    t[kept] = 1
+    t[{}] = 2
+    t["string"] = 3
+    t[4] = {}
+
+    if not full_collection() then
+        return false
+    end
+
+    local count = 0
+    for _ in pairs(t) do
+        count = count + 1
+    end
+    return count == 3 and t[kept] == 1 and t["string"] == 3 and type(t[4]) == "table"
+end
+
+local function test_weak_values()
+    local t = setmetatable({}, {__mode = "v"})
+    local kept = {}
+    t[1] = kept
+    t[2] = {}
+    t.a = {}
+    t.b = "string"
+    t.c = function() end
+
+    if not collect_until(function() return t[2] == nil and t.a == nil end) then
+        return false
+    end
+
+    return t[1] == kept and t.b == "string"
+end
+
+local function test_weak_keys_and_values()
+    local t = setmetatable({}, {__mode = "kv"})
+    local key = {}
+    local value = {}
+    t[key] = {}
+    t[{}] = value
+    t[key] = value
+
+    if not full_collection() then
+        return false
+    end
+
+    local count = 0
+    for k, v in pairs(t) do
+        count = count + 1
+    end
+    return count == 1 and t[key] == value
+end
+
+local function test_ephemeron()
+    local t = setmetatable({}, {__mode = "k"})
+
+    -- A value which refers only to its own key does not keep the key alive
+    do
+        local key = {}
+        t[key] = {key}
+    end
+
+    -- A value whose key is alive is kept alive, along with the keys it refers to
+    local first = {}
+    do
+        local second = {}
+        t[first] = {second}
+        t[second] = {"second"}
+    end
+
+    if not full_collection() then
+        return false
+    end
+    if not full_collection() then
+        return false
+    end
+
+    local count = 0
+    for _ in pairs(t) do
+        count = count + 1
+    end
+    return count == 2 and t[t[first][1]][1] == "second"
+end
+
+local function test_iterate()
+    local t = setmetatable({}, {__mode = "k"})
+    local keys = {}
+    for i = 1, 10 do
+        keys[i] = {}
+        t[keys[i]] = i
+    end
+
+    local sum = 0
+    for k, v in pairs(t) do
+        t[k] = nil
+        for i = 1, 1000 do
+            local garbage = {}
+        end
+        sum = sum + v
+    end
+    return sum == 55 and next(t) == nil
+end
+
+return
+    test_weak_keys() and
+    test_weak_values() and
+    test_weak_keys_and_values() and
+    test_ephemeron() and
+    test_iterate()
@@ -0,0 +1,75 @@
+function test_xpcall()
+    local function fail(e)
+        error(e)
+    end
+    local function add(a, b)
+        return a + b
+    end
+    local function handler(e)
+        return "handled: " .. e
+    end
+
+    local r1, e1 = xpcall(fail, handler, "oops")
+    local r2, e2 = xpcall(add, handler, 1, 2)
+    local r3, e3 = xpcall(fail, function(e) return e end, {})
+    local r4, e4 = xpcall(fail, function(e) error("handler failed") end, "oops")
+
+    return
+        r1 == false and e1 == "handled: oops" and
+        r2 == true and e2 == 3 and
+        r3 == false and type(e3) == "table" and
+        r4 == false and e4 == "handler failed"
+end
+
+function test_handler_scope()
+    local calls = 0
+    local function handler(e)
+        calls = calls + 1
+        return e
+    end
+
+    -- Errors caught by an inner `pcall` never reach the handler.
+    local ok, inner_ok, inner_err = xpcall(function()
+        return pcall(error, "inner")
+    end, handler)
+    local calls_after_inner = calls
+
+    -- The handler is called once, with the original error value, before the error unwinds.
+    local r, e = xpcall(function()
+        local nested
+        nested = function(n)
+            if n == 0 then
+                error("deep")
+            end
+            nested(n - 1)
+        end
+        nested(10)
+    end, function(e)
+        calls = calls + 1
+        return e .. "!"
+    end)
+
+    return
+        ok and inner_ok == false and inner_err == "inner" and
+        calls_after_inner == 0 and
+        r == false and e == "deep!" and calls == 1
+end
+
+function test_assert()
+    local a, b, c = assert(1, "message", 3)
+    local r1, e1 = pcall(assert, false)
+    local r2, e2 = pcall(assert, nil, "custom")
+    local t = {}
+    local r3, e3 = pcall(assert, false, t)
+
+    return
+        a == 1 and b == "message" and c == 3 and
+        r1 == false and e1 == "assertion failed!" and
+        r2 == false and e2 == "custom" and
+        r3 == false and e3 == t
+end
+
+return
+    test_xpcall() and
+    test_handler_scope() and
+    test_assert()
@@ -0,0 +1,62 @@
+use gc_arena::{make_arena, ArenaParameters, Collect, GcCell};
+use luster::{InternedStringSet, String};
+
+#[derive(Collect)]
+#[collect(empty_drop)]
+struct TestRoot<'gc> {
+    interned_strings: InternedStringSet<'gc>,
+    kept: GcCell<'gc, Vec<String<'gc>>>,
+}
+
+make_arena!(TestArena, TestRoot);
+
+#[test]
+fn short_strings_are_shared() {
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        interned_strings: InternedStringSet::new(mc),
+        kept: GcCell::allocate(mc, Vec::new()),
+    });
+
+    arena.mutate(|mc, root| {
+        let a = root.interned_strings.new_string(mc, b"short");
+        let b = root.interned_strings.new_string(mc, b"short");
+        assert_eq!(a.as_bytes().as_ptr(), b.as_bytes().as_ptr());
+        assert_eq!(a, String::new(mc, b"short"));
+
+        let long = [b'a'; 64];
+        let a = root.interned_strings.new_string(mc, &long);
+        let b = root.interned_strings.new_string(mc, &long);
+        assert_ne!(a.as_bytes().as_ptr(), b.as_bytes().as_ptr());
+        assert_eq!(a, b);
+        assert_eq!(root.interned_strings.len(), 1);
+    });
+}
+
+#[test]
+fn unreachable_strings_are_removed() {
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        interned_strings: InternedStringSet::new(mc),
+        kept: GcCell::allocate(mc, Vec::new()),
+    });
+
+    arena.mutate(|mc, root| {
+        for i in 0..100 {
+            let s = root
+                .interned_strings
+                .new_string(mc, format!("string {}", i).as_bytes());
+            if i % 10 == 0 {
+                root.kept.write(mc).push(s);
+            }
+        }
+        assert_eq!(root.interned_strings.len(), 100);
+    });
+
+    arena.collect_all();
+
+    arena.mutate(|mc, root| {
+        assert_eq!(root.interned_strings.len(), 10);
+        let kept = root.kept.read()[3];
+        let s = root.interned_strings.new_string(mc, b"string 30");
+        assert_eq!(kept.as_bytes().as_ptr(), s.as_bytes().as_ptr());
+    });
+}
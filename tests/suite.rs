@@ -2,7 +2,9 @@ use std::fs::{read_dir, File};
 use std::io::{stdout, Write};
 
 use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
-use luster::{compile, io, parse_chunk, Closure, Error, Function, Lua, ThreadSequence, Value};
+use luster::{
+    compile_named, io, parse_chunk, Closure, Error, Function, Lua, ThreadSequence, Value,
+};
 
 fn test_dir(dir: &str, run_code: bool) {
     let mut file_failed = false;
@@ -13,6 +15,7 @@ fn test_dir(dir: &str, run_code: bool) {
     for dir in read_dir(dir).expect("could not list dir contents") {
         let path = dir.expect("could not read dir entry").path();
         let file = io::buffered_read(File::open(&path).unwrap()).unwrap();
+        let chunk_name = format!("@{}", path.display());
         if let Some(ext) = path.extension() {
             if ext == "lua" {
                 let _ = writeln!(stdout(), "{} file {:?}", op, path);
@@ -22,7 +25,12 @@ fn test_dir(dir: &str, run_code: bool) {
                         sequence::from_fn_with(root, move |mc, root| {
                             Ok(Closure::new(
                                 mc,
-                                compile(mc, root.interned_strings, file)?,
+                                compile_named(
+                                    mc,
+                                    root.interned_strings,
+                                    chunk_name.as_bytes(),
+                                    file,
+                                )?,
                                 Some(root.globals),
                             )?)
                         })
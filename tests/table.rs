@@ -0,0 +1,59 @@
+use luster::{InvalidNextKey, Lua, String, Table, Value};
+
+#[test]
+fn next_and_iter() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let table = Table::new(mc);
+        for i in 1..=4 {
+            table.set(mc, i, i * 10).unwrap();
+        }
+        table.set(mc, String::new_static(b"a"), 1).unwrap();
+        table.set(mc, String::new_static(b"b"), 2).unwrap();
+        table.set(mc, true, 3).unwrap();
+
+        let entries = table.iter().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 7);
+        let mut key = Value::Nil;
+        let mut next_entries = Vec::new();
+        while let Some(entry) = table.next(key).unwrap() {
+            next_entries.push(entry);
+            key = entry.0;
+        }
+        assert_eq!(&entries, &next_entries);
+        for &(key, value) in &entries {
+            assert_eq!(table.get(key), value);
+        }
+        // The array part comes first and in order.
+        assert_eq!(entries[0], (Value::Integer(1), Value::Integer(10)));
+        assert_eq!(entries[3], (Value::Integer(4), Value::Integer(40)));
+
+        assert_eq!(table.next(Value::Nil).unwrap(), Some(entries[0]));
+        assert_eq!(table.next(entries[6].0).unwrap(), None);
+        assert!(match table.next(String::new_static(b"missing")) {
+            Err(InvalidNextKey) => true,
+            _ => false,
+        });
+    });
+}
+
+#[test]
+fn clear_during_iteration() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let table = Table::new(mc);
+        for i in 0..100 {
+            table.set(mc, i * 7, i).unwrap();
+            table.set(mc, Value::Number(i as f64 + 0.5), i).unwrap();
+        }
+
+        let mut count = 0;
+        for (key, _) in table.iter() {
+            table.set(mc, key, Value::Nil).unwrap();
+            count += 1;
+        }
+        assert_eq!(count, 200);
+        assert_eq!(table.next(Value::Nil).unwrap(), None);
+        assert_eq!(table.iter().count(), 0);
+    });
+}
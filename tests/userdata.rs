@@ -0,0 +1,117 @@
+use std::rc::Rc;
+
+use gc_arena::{make_arena, unsafe_empty_collect, ArenaParameters, Collect, Gc, GcCell};
+use luster::{String, Table, UserData, UserDataType, Value};
+
+#[derive(Clone)]
+struct RefCounter(Rc<()>);
+unsafe_empty_collect!(RefCounter);
+
+#[derive(Collect)]
+#[collect(empty_drop)]
+struct Payload<'gc> {
+    counter: Gc<'gc, RefCounter>,
+    table: Table<'gc>,
+}
+
+struct PayloadType;
+
+impl<'gc> UserDataType<'gc> for PayloadType {
+    type Data = Payload<'gc>;
+}
+
+#[derive(Collect)]
+#[collect(empty_drop)]
+struct TestRoot<'gc>(GcCell<'gc, Option<UserData<'gc>>>);
+
+make_arena!(TestArena, TestRoot);
+
+#[test]
+fn userdata_traces_payload() {
+    let counter = RefCounter(Rc::new(()));
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        let table = Table::new(mc);
+        table.set(mc, 1i64, 42i64).unwrap();
+        let payload = Payload {
+            counter: Gc::allocate(mc, counter.clone()),
+            table,
+        };
+        TestRoot(GcCell::allocate(
+            mc,
+            Some(UserData::new::<PayloadType>(mc, payload)),
+        ))
+    });
+
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&counter.0), 2);
+
+    arena.mutate(|mc, root| {
+        let userdata = root.0.read().unwrap();
+        assert!(userdata.read::<i32>().is_none());
+        let payload = userdata.read_gc::<PayloadType>().unwrap();
+        assert_eq!(payload.table.get(1i64), Value::Integer(42));
+        drop(payload);
+
+        userdata.write_gc::<PayloadType>(mc).unwrap().table = Table::new(mc);
+        assert_eq!(
+            userdata.read_gc::<PayloadType>().unwrap().table.get(1i64),
+            Value::Nil
+        );
+    });
+
+    arena.mutate(|mc, root| {
+        *root.0.write(mc) = None;
+    });
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&counter.0), 1);
+}
+
+#[test]
+fn userdata_static_and_user_value() {
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(GcCell::allocate(mc, Some(UserData::new_static(mc, 1i32))))
+    });
+
+    arena.mutate(|mc, root| {
+        let table = Table::new(mc);
+        table.set(mc, String::new_static(b"a"), 1i64).unwrap();
+        let userdata = root.0.read().unwrap();
+        assert_eq!(userdata.user_value(), Value::Nil);
+        userdata.set_user_value(mc, Value::Table(table));
+    });
+
+    arena.collect_all();
+
+    arena.mutate(|mc, root| {
+        let userdata = root.0.read().unwrap();
+        assert!(userdata.read_gc::<PayloadType>().is_none());
+        *userdata.write::<i32>(mc).unwrap() += 1;
+        assert_eq!(*userdata.read::<i32>().unwrap(), 2);
+        match userdata.user_value() {
+            Value::Table(t) => assert_eq!(t.get(String::new_static(b"a")), Value::Integer(1)),
+            v => panic!("unexpected user value {:?}", v),
+        }
+    });
+}
+
+#[test]
+fn userdata_table_keys() {
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(GcCell::allocate(mc, None))
+    });
+
+    arena.mutate(|mc, _| {
+        let a = UserData::new_static(mc, ());
+        let b = UserData::new_static(mc, ());
+        assert_eq!(a, a);
+        assert_ne!(a, b);
+
+        let table = Table::new(mc);
+        table.set(mc, a, 1i64).unwrap();
+        table.set(mc, b, 2i64).unwrap();
+        assert_eq!(table.get(a), Value::Integer(1));
+        assert_eq!(table.get(b), Value::Integer(2));
+        assert_eq!(table.length(), 0);
+    });
+}